@@ -1,17 +1,119 @@
-use anyhow::{Context, Result};
 use notify::{Config as NotifyConfig, Event, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 use tokio::sync::{mpsc, RwLock};
+use tokio::task::JoinHandle;
 
-use super::schema::Config;
+use super::migrate::{self, Migration};
+use super::schema::{Config, ConfigWatchMode, Folder, Snippet, SnippetNode};
+
+/// Errors from reading and writing the config file and import/export files
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    /// The platform has no config directory (e.g. `$HOME` is unset)
+    #[error("could not determine config directory")]
+    NoConfigDir,
+    #[error("failed to read {}: {source}", path.display())]
+    Read {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    /// The file isn't valid YAML or doesn't match the config schema
+    #[error("failed to parse {}: {source}", path.display())]
+    Parse {
+        path: PathBuf,
+        #[source]
+        source: serde_yaml::Error,
+    },
+    #[error("failed to serialize config: {0}")]
+    Serialize(#[source] serde_yaml::Error),
+    #[error("failed to write {}: {source}", path.display())]
+    Write {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("failed to watch config file: {0}")]
+    Watch(#[from] notify::Error),
+}
+
+type Result<T, E = ConfigError> = std::result::Result<T, E>;
+
+/// Read a file, for the config or an import
+fn read_file(path: &Path) -> Result<String> {
+    std::fs::read_to_string(path).map_err(|source| ConfigError::Read {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// Write a file, for the config, its backup or an export
+fn write_file(path: &Path, content: &str) -> Result<()> {
+    std::fs::write(path, content).map_err(|source| ConfigError::Write {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// Whether `content` has comments or formatting that saving would lose, i.e. it
+/// isn't what `save_config` writes for the config it contains
+pub fn is_hand_edited(content: &str) -> bool {
+    reformats::<Config>(content)
+}
+
+/// Whether parsing `content` as a `T` and serializing it again gives different text
+fn reformats<T: Serialize + DeserializeOwned>(content: &str) -> bool {
+    match serde_yaml::from_str::<T>(content) {
+        Ok(value) => serde_yaml::to_string(&value).map_or(true, |saved| saved != content),
+        Err(_) => true,
+    }
+}
+
+/// Write `content` to `path`, first copying a hand-edited `T` file to its backup
+/// path if `preserve_formatting` is on
+fn write_preserving<T: Serialize + DeserializeOwned>(path: &Path, content: &str, preserve_formatting: bool) -> Result<()> {
+    if preserve_formatting {
+        if let Ok(existing) = std::fs::read_to_string(path) {
+            if existing != content && reformats::<T>(&existing) {
+                let backup = backup_path(path);
+                write_file(&backup, &existing)?;
+                log::warn!(
+                    "Saving drops the comments and formatting of {}; the previous version was kept in {}",
+                    path.display(),
+                    backup.display()
+                );
+            }
+        }
+    }
+
+    write_file(path, content)
+}
+
+/// Where `save_config` keeps the previous version of a hand-edited config
+pub fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".bak");
+    PathBuf::from(name)
+}
 
 /// Configuration manager with hot-reload support
 pub struct ConfigManager {
     config: Arc<RwLock<Config>>,
     config_path: PathBuf,
-    _watcher: Option<RecommendedWatcher>,
+    watcher: Option<RecommendedWatcher>,
+    poller: Option<JoinHandle<()>>,
+}
+
+impl Drop for ConfigManager {
+    fn drop(&mut self) {
+        if let Some(poller) = self.poller.take() {
+            poller.abort();
+        }
+    }
 }
 
 impl ConfigManager {
@@ -21,30 +123,47 @@ impl ConfigManager {
 
         // Create config directory if it doesn't exist
         if let Some(parent) = config_path.parent() {
-            std::fs::create_dir_all(parent)
-                .context("Failed to create config directory")?;
+            std::fs::create_dir_all(parent).map_err(|source| ConfigError::Write {
+                path: parent.to_path_buf(),
+                source,
+            })?;
         }
 
         // Load or create initial config
         let config = if config_path.exists() {
-            Self::load_config(&config_path)?
+            Self::load_and_upgrade_config(&config_path)?
         } else {
             let default_config = Config::default();
             Self::save_config(&config_path, &default_config)?;
             default_config
         };
 
+        let snippets_dir = config.snippets_dir.as_ref().map(|dir| snippets_dir_path(&config_path, dir));
+        let watch_mode = config.settings.config_watch_mode;
         let config = Arc::new(RwLock::new(config));
         let (tx, rx) = mpsc::channel(16);
 
         // Set up file watcher
-        let watcher = Self::setup_watcher(&config_path, config.clone(), tx)?;
+        let (watcher, poller) = match watch_mode {
+            ConfigWatchMode::Inotify => {
+                (Some(Self::setup_watcher(&config_path, snippets_dir, config.clone(), tx)?), None)
+            }
+            ConfigWatchMode::Poll(interval_ms) => {
+                let interval = Duration::from_millis(interval_ms.max(100));
+                (None, Some(Self::setup_poller(&config_path, snippets_dir, interval, config.clone(), tx)))
+            }
+            ConfigWatchMode::Off => {
+                log::info!("Config file watching is off, reload from the tray menu after editing");
+                (None, None)
+            }
+        };
 
         Ok((
             Self {
                 config,
                 config_path,
-                _watcher: Some(watcher),
+                watcher,
+                poller,
             },
             rx,
         ))
@@ -52,38 +171,91 @@ impl ConfigManager {
 
     /// Get the default config file path
     pub fn get_config_path() -> Result<PathBuf> {
-        let config_dir = dirs::config_dir()
-            .context("Could not determine config directory")?;
+        let config_dir = dirs::config_dir().ok_or(ConfigError::NoConfigDir)?;
         Ok(config_dir.join("xpander").join("config.yaml"))
     }
 
-    /// Load configuration from a file
+    /// Load configuration from a file, along with the snippet files in its `snippets_dir`.
+    /// A config from an older version is upgraded in memory only, so commands that
+    /// just read the config never rewrite it; see `load_and_upgrade_config`.
     pub fn load_config(path: &Path) -> Result<Config> {
-        let content = std::fs::read_to_string(path)
-            .with_context(|| format!("Failed to read config file: {}", path.display()))?;
+        let (config, migration, _) = Self::read_config(path)?;
+        if let Migration::Upgraded { from, .. } = migration {
+            log::debug!("Read {} as config version {}, upgraded in memory", path.display(), from);
+        }
+        Ok(config)
+    }
 
-        let config: Config = serde_yaml::from_str(&content)
-            .with_context(|| format!("Failed to parse config file: {}", path.display()))?;
+    /// `load_config` for the daemon and the config window: a config from an older
+    /// version is also written back upgraded, once. Files in `snippets_dir` have no
+    /// version and aren't upgraded.
+    pub fn load_and_upgrade_config(path: &Path) -> Result<Config> {
+        let (config, migration, content) = Self::read_config(path)?;
+        if let Migration::Upgraded { from, changed } = migration {
+            log::info!("Upgrading {} from config version {} to {}", path.display(), from, migrate::CONFIG_VERSION);
+            let saved = if changed {
+                Self::save_config(path, &config)
+            } else {
+                write_file(path, &migrate::set_version_line(&content))
+            };
+            if let Err(e) = saved {
+                log::warn!("Could not write the upgraded config, it will be upgraded again next time: {}", e);
+            }
+        }
+        Ok(config)
+    }
+
+    /// Read and migrate the config file and add its `snippets_dir`, returning the
+    /// config, what migrating did and the file's text
+    fn read_config(path: &Path) -> Result<(Config, Migration, String)> {
+        let content = read_file(path)?;
+        let parse_error = |source| ConfigError::Parse {
+            path: path.to_path_buf(),
+            source,
+        };
+        let mut value: serde_yaml::Value = serde_yaml::from_str(&content).map_err(parse_error)?;
+        let migration = migrate::migrate(&mut value);
+        // Parsing the text again keeps line numbers in schema errors
+        let mut config: Config = match migration {
+            Migration::Upgraded { .. } => serde_yaml::from_value(value),
+            _ => serde_yaml::from_str(&content),
+        }
+        .map_err(parse_error)?;
+        load_snippets_dir(&mut config, path)?;
+
+        if let Migration::Newer(version) = migration {
+            log::warn!(
+                "{} is config version {}, newer than this xpander understands ({}); settings it doesn't know are ignored",
+                path.display(),
+                version,
+                migrate::CONFIG_VERSION
+            );
+        }
 
         log::info!("Loaded configuration from {}", path.display());
-        Ok(config)
+        Ok((config, migration, content))
     }
 
-    /// Save configuration to a file
+    /// Save configuration to a file. Folders loaded from `snippets_dir` are written
+    /// back to their own files, if they changed. With `preserve_formatting`, a
+    /// hand-edited file is copied to its backup path first.
     pub fn save_config(path: &Path, config: &Config) -> Result<()> {
-        let content = serde_yaml::to_string(config)
-            .context("Failed to serialize config")?;
+        let (main, files) = split_snippets_dir(config);
+        let content = serde_yaml::to_string(&main).map_err(ConfigError::Serialize)?;
+        write_preserving::<Config>(path, &content, config.settings.preserve_formatting)?;
 
-        std::fs::write(path, content)
-            .with_context(|| format!("Failed to write config file: {}", path.display()))?;
+        for (file, items) in files {
+            save_snippet_file(&file, items, config.settings.preserve_formatting)?;
+        }
 
         log::info!("Saved configuration to {}", path.display());
         Ok(())
     }
 
-    /// Set up file watcher for hot-reload
+    /// Set up file watcher for hot-reload, of the config file and its `snippets_dir`
     fn setup_watcher(
         config_path: &Path,
+        snippets_dir: Option<PathBuf>,
         config: Arc<RwLock<Config>>,
         tx: mpsc::Sender<Config>,
     ) -> Result<RecommendedWatcher> {
@@ -93,29 +265,9 @@ impl ConfigManager {
         let mut watcher = RecommendedWatcher::new(
             move |res: Result<Event, notify::Error>| {
                 if let Ok(event) = res {
-                    if event.kind.is_modify() || event.kind.is_create() {
+                    if event.kind.is_modify() || event.kind.is_create() || event.kind.is_remove() {
                         log::debug!("Config file changed, reloading...");
-
-                        match Self::load_config(&path) {
-                            Ok(new_config) => {
-                                let config = config.clone();
-                                let tx = tx.clone();
-                                let new_config_clone = new_config.clone();
-
-                                // Update config in a blocking way since we're in the notify callback
-                                handle.spawn(async move {
-                                    let mut cfg = config.write().await;
-                                    *cfg = new_config_clone.clone();
-                                    if tx.send(new_config_clone).await.is_err() {
-                                        log::warn!("Failed to send config update notification");
-                                    }
-                                    log::info!("Configuration reloaded successfully");
-                                });
-                            }
-                            Err(e) => {
-                                log::error!("Failed to reload config: {}", e);
-                            }
-                        }
+                        Self::reload(&path, &config, &tx, &handle);
                     }
                 }
             },
@@ -126,11 +278,69 @@ impl ConfigManager {
         if let Some(parent) = config_path.parent() {
             watcher.watch(parent, RecursiveMode::NonRecursive)?;
         }
+        if let Some(dir) = snippets_dir.filter(|dir| dir.is_dir()) {
+            watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+            log::info!("Watching snippet files in {}", dir.display());
+        }
 
         log::info!("Watching config file for changes: {}", config_path.display());
         Ok(watcher)
     }
 
+    /// Poll the config file and its `snippets_dir` for hot-reload, for filesystems
+    /// where change notifications are unreliable
+    fn setup_poller(
+        config_path: &Path,
+        snippets_dir: Option<PathBuf>,
+        interval: Duration,
+        config: Arc<RwLock<Config>>,
+        tx: mpsc::Sender<Config>,
+    ) -> JoinHandle<()> {
+        let path = config_path.to_path_buf();
+        let handle = tokio::runtime::Handle::current();
+        log::info!(
+            "Polling config file for changes every {} ms: {}",
+            interval.as_millis(),
+            config_path.display()
+        );
+
+        tokio::spawn(async move {
+            let mut last = modification_times(&path, snippets_dir.as_deref());
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                if files_changed(&path, snippets_dir.as_deref(), &mut last) {
+                    log::debug!("Config file changed, reloading...");
+                    Self::reload(&path, &config, &tx, &handle);
+                }
+            }
+        })
+    }
+
+    /// Load the config file again and hand the new config to the running app
+    fn reload(path: &Path, config: &Arc<RwLock<Config>>, tx: &mpsc::Sender<Config>, handle: &tokio::runtime::Handle) {
+        match Self::load_and_upgrade_config(path) {
+            Ok(new_config) => {
+                let config = config.clone();
+                let tx = tx.clone();
+
+                // Update config in a blocking way since we may be in the notify callback
+                handle.spawn(async move {
+                    let mut cfg = config.write().await;
+                    *cfg = new_config.clone();
+                    if tx.send(new_config).await.is_err() {
+                        log::warn!("Failed to send config update notification");
+                    }
+                    log::info!("Configuration reloaded successfully");
+                });
+            }
+            Err(e) => {
+                log::error!("Failed to reload config: {}", e);
+            }
+        }
+    }
+
     /// Get a read lock on the current configuration
     pub async fn get_config(&self) -> tokio::sync::RwLockReadGuard<'_, Config> {
         self.config.read().await
@@ -149,6 +359,12 @@ impl ConfigManager {
         &self.config_path
     }
 
+    /// Whether changes to the config file are picked up and reloaded
+    /// (`config_watch_mode` isn't `off`)
+    pub fn watches_file(&self) -> bool {
+        self.watcher.is_some() || self.poller.is_some()
+    }
+
     /// Add a new snippet to the configuration (at the top level)
     pub async fn add_snippet(&self, snippet: super::schema::Snippet) -> Result<()> {
         let mut config = self.config.write().await;
@@ -188,22 +404,51 @@ impl ConfigManager {
 
     /// Flatten snippets from the hierarchy into a single list
     pub fn flatten_snippets(nodes: &[super::schema::SnippetNode]) -> Vec<super::schema::Snippet> {
+        Self::flatten_snippets_in_profile(nodes, None)
+    }
+
+    /// Flatten the enabled snippets, honoring the active profile in the settings
+    pub fn flatten_active_snippets(config: &Config) -> Vec<super::schema::Snippet> {
+        Self::flatten_snippets_in_profile(&config.snippets, config.settings.active_profile_folders())
+    }
+
+    /// Flatten enabled snippets, keeping only top-level snippets and those inside
+    /// `profile_folders` (or nested below one of them) when a profile is given
+    pub fn flatten_snippets_in_profile(
+        nodes: &[super::schema::SnippetNode],
+        profile_folders: Option<&[String]>,
+    ) -> Vec<super::schema::Snippet> {
         let mut result = Vec::new();
-        Self::flatten_recursive(nodes, &mut result);
+        Self::flatten_recursive(nodes, profile_folders, true, true, &mut result);
         result
     }
 
-    fn flatten_recursive(nodes: &[super::schema::SnippetNode], result: &mut Vec<super::schema::Snippet>) {
+    fn flatten_recursive(
+        nodes: &[super::schema::SnippetNode],
+        profile_folders: Option<&[String]>,
+        at_root: bool,
+        included: bool,
+        result: &mut Vec<super::schema::Snippet>,
+    ) {
         for node in nodes {
             match node {
                 super::schema::SnippetNode::Snippet(s) => {
-                    if s.enabled {
+                    if s.enabled && included {
                         result.push(s.clone());
                     }
                 }
                 super::schema::SnippetNode::Folder(f) => {
                     if f.enabled {
-                        Self::flatten_recursive(&f.items, result);
+                        let in_profile = match profile_folders {
+                            None => true,
+                            Some(folders) => folders.contains(&f.folder) || (included && !at_root),
+                        };
+                        let start = result.len();
+                        Self::flatten_recursive(&f.items, profile_folders, false, in_profile, result);
+                        // Inner folders already applied their defaults, so they win
+                        for snippet in &mut result[start..] {
+                            f.apply_defaults(snippet);
+                        }
                     }
                 }
             }
@@ -211,20 +456,188 @@ impl ConfigManager {
     }
 }
 
+/// Where `snippets_dir` points, relative to the config file's directory
+pub fn snippets_dir_path(config_path: &Path, snippets_dir: &Path) -> PathBuf {
+    match config_path.parent() {
+        Some(parent) => parent.join(snippets_dir),
+        None => snippets_dir.to_path_buf(),
+    }
+}
+
+/// The `*.yaml`/`*.yml` files in `dir`, sorted by name. A missing directory has none.
+fn snippet_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            log::warn!("Snippets directory {} does not exist", dir.display());
+            return Ok(Vec::new());
+        }
+        Err(source) => {
+            return Err(ConfigError::Read {
+                path: dir.to_path_buf(),
+                source,
+            })
+        }
+    };
+
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "yaml" || ext == "yml"))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Modification times of the config file and of every file in `snippets_dir`, sorted
+/// by path. A missing file has no time, so deleting one counts as a change.
+fn modification_times(config_path: &Path, snippets_dir: Option<&Path>) -> Vec<(PathBuf, Option<SystemTime>)> {
+    let mut paths = vec![config_path.to_path_buf()];
+    if let Some(entries) = snippets_dir.and_then(|dir| std::fs::read_dir(dir).ok()) {
+        paths.extend(entries.filter_map(|entry| entry.ok().map(|entry| entry.path())));
+    }
+    paths.sort();
+    paths
+        .into_iter()
+        .map(|path| {
+            let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+            (path, modified)
+        })
+        .collect()
+}
+
+/// Whether the config or snippet files changed since `last`, which is updated
+fn files_changed(
+    config_path: &Path,
+    snippets_dir: Option<&Path>,
+    last: &mut Vec<(PathBuf, Option<SystemTime>)>,
+) -> bool {
+    let current = modification_times(config_path, snippets_dir);
+    if current == *last {
+        return false;
+    }
+    *last = current;
+    true
+}
+
+/// Parse a snippet file: either a list of snippets and folders, or an export
+/// (`snippets` and `variables`)
+fn parse_snippet_file(path: &Path, content: &str) -> Result<ExportData> {
+    let parse_error = |source| ConfigError::Parse {
+        path: path.to_path_buf(),
+        source,
+    };
+    let value: serde_yaml::Value = serde_yaml::from_str(content).map_err(parse_error)?;
+    if value.is_sequence() || value.is_null() {
+        let snippets: Option<Vec<SnippetNode>> = serde_yaml::from_str(content).map_err(parse_error)?;
+        Ok(ExportData {
+            snippets: snippets.unwrap_or_default(),
+            variables: serde_yaml::Value::Null,
+        })
+    } else {
+        serde_yaml::from_str(content).map_err(parse_error)
+    }
+}
+
+/// Add the files in `config.snippets_dir` to `config`: each file's snippets as a
+/// folder named after the file, and its variables unless the config file already
+/// defines them
+fn load_snippets_dir(config: &mut Config, config_path: &Path) -> Result<()> {
+    let Some(dir) = &config.snippets_dir else {
+        return Ok(());
+    };
+
+    for path in snippet_files(&snippets_dir_path(config_path, dir))? {
+        let data = parse_snippet_file(&path, &read_file(&path)?)?;
+
+        if let serde_yaml::Value::Mapping(variables) = data.variables {
+            if !config.variables.is_mapping() {
+                config.variables = serde_yaml::Value::Mapping(Default::default());
+            }
+            if let serde_yaml::Value::Mapping(map) = &mut config.variables {
+                for (key, value) in variables {
+                    if !map.contains_key(&key) {
+                        map.insert(key.clone(), value.clone());
+                        config.snippets_dir_variables.insert(key, value);
+                    }
+                }
+            }
+        }
+
+        let name = path.file_stem().map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+        config.snippets.push(SnippetNode::Folder(Folder {
+            items: data.snippets,
+            source: Some(path),
+            ..Folder::new(name)
+        }));
+    }
+
+    Ok(())
+}
+
+/// Separate what `load_snippets_dir` added: the config as it belongs in the config
+/// file, and the items of each snippet file
+fn split_snippets_dir(config: &Config) -> (Config, Vec<(PathBuf, Vec<SnippetNode>)>) {
+    let mut main = config.clone();
+    let mut files = Vec::new();
+
+    main.snippets.retain(|node| match node {
+        SnippetNode::Folder(Folder { source: Some(path), items, .. }) => {
+            files.push((path.clone(), items.clone()));
+            false
+        }
+        _ => true,
+    });
+
+    // Variables from snippet files stay there, unless they were changed
+    if let serde_yaml::Value::Mapping(map) = &mut main.variables {
+        for (key, value) in &config.snippets_dir_variables {
+            if map.get(key) == Some(value) {
+                map.remove(key);
+            }
+        }
+    }
+
+    (main, files)
+}
+
+/// Write a folder loaded from `snippets_dir` back to its file, keeping the file's
+/// format and variables. Unchanged files are left alone.
+fn save_snippet_file(path: &Path, items: Vec<SnippetNode>, preserve_formatting: bool) -> Result<()> {
+    let existing = match std::fs::read_to_string(path) {
+        Ok(content) => Some(parse_snippet_file(path, &content)?),
+        Err(_) => None,
+    };
+
+    if existing.as_ref().is_some_and(|data| data.snippets == items) {
+        return Ok(());
+    }
+
+    match existing.map(|data| data.variables).filter(|variables| !variables.is_null()) {
+        Some(variables) => {
+            let data = ExportData { snippets: items, variables };
+            let content = serde_yaml::to_string(&data).map_err(ConfigError::Serialize)?;
+            write_preserving::<ExportData>(path, &content, preserve_formatting)
+        }
+        None => {
+            let content = serde_yaml::to_string(&items).map_err(ConfigError::Serialize)?;
+            write_preserving::<Vec<SnippetNode>>(path, &content, preserve_formatting)
+        }
+    }
+}
+
 /// Export snippets to a YAML file
 pub fn export_snippets(snippets: &[super::schema::SnippetNode], path: &Path) -> Result<()> {
-    let content = serde_yaml::to_string(snippets)
-        .context("Failed to serialize snippets")?;
-    std::fs::write(path, content)
-        .with_context(|| format!("Failed to write export file: {}", path.display()))?;
-    Ok(())
+    let content = serde_yaml::to_string(snippets).map_err(ConfigError::Serialize)?;
+    write_file(path, &content)
 }
 
 
 /// Structure for exporting custom entries (snippets and variables)
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExportData {
     pub snippets: Vec<super::schema::SnippetNode>,
+    #[serde(default)]
     pub variables: serde_yaml::Value,
 }
 
@@ -234,20 +647,177 @@ pub fn export_custom_entries(snippets: &[super::schema::SnippetNode], variables:
         snippets: snippets.to_vec(),
         variables: variables.clone(),
     };
-    let content = serde_yaml::to_string(&data)
-        .context("Failed to serialize custom entries")?;
-    std::fs::write(path, content)
-        .with_context(|| format!("Failed to write export file: {}", path.display()))?;
-    Ok(())
+    let content = serde_yaml::to_string(&data).map_err(ConfigError::Serialize)?;
+    write_file(path, &content)
 }
 
 /// Import snippets and variables from a YAML file
 pub fn import_custom_entries(path: &Path) -> Result<ExportData> {
-    let content = std::fs::read_to_string(path)
-        .with_context(|| format!("Failed to read import file: {}", path.display()))?;
-    let data: ExportData = serde_yaml::from_str(&content)
-        .with_context(|| format!("Failed to parse import file: {}", path.display()))?;
-    Ok(data)
+    let content = read_file(path)?;
+    serde_yaml::from_str(&content).map_err(|source| ConfigError::Parse {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// How imported entries are combined with the existing config
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportMode {
+    /// Append the imported snippets and add the imported variables, replacing
+    /// variables with the same name, which are reported (what the GUI's Import
+    /// button does)
+    Merge,
+    /// Replace the snippet tree and variables with the imported ones. Snippet
+    /// files in `snippets_dir` are loaded on every reload, so they are kept.
+    Replace,
+}
+
+/// What an import added to the config
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ImportSummary {
+    /// Snippets imported, including those inside folders
+    pub snippets: usize,
+    /// Top-level variables imported
+    pub variables: usize,
+    /// Imported triggers that were already defined. Both are kept; the imported
+    /// snippet comes later, so it's the one that fires.
+    pub duplicates: Vec<String>,
+    /// Triggers of imported snippets that run commands when they expand
+    pub commands: Vec<String>,
+    /// Snippet files in `snippets_dir` that a replace kept, with their variables
+    pub kept_files: Vec<PathBuf>,
+    /// Existing variables a merge gave the imported, different value
+    pub overwritten_variables: Vec<String>,
+}
+
+/// Check a snippet from outside (a snippet pack, a shared string) before it is
+/// imported: it needs a trigger, and a regex trigger must compile as the
+/// matcher compiles it
+pub fn check_imported_snippet(snippet: &Snippet) -> anyhow::Result<()> {
+    if snippet.trigger.is_empty() {
+        anyhow::bail!("A snippet has no trigger");
+    }
+    if snippet.regex {
+        crate::engine::matcher::compile_regex_trigger(&snippet.trigger)
+            .map_err(|e| anyhow::anyhow!("Invalid regex trigger '{}': {}", snippet.trigger, e))?;
+    }
+    Ok(())
+}
+
+/// `check_imported_snippet` for every snippet in `nodes`
+pub fn check_imported_snippets(nodes: &[SnippetNode]) -> anyhow::Result<()> {
+    ConfigManager::flatten_snippets(nodes)
+        .iter()
+        .try_for_each(check_imported_snippet)
+}
+
+/// A variable's name as written in the config
+fn variable_name(key: &serde_yaml::Value) -> String {
+    match key {
+        serde_yaml::Value::String(name) => name.clone(),
+        other => serde_yaml::to_string(other).map_or_else(|_| format!("{:?}", other), |s| s.trim_end().to_string()),
+    }
+}
+
+/// Apply imported snippets and variables to `config`
+pub fn apply_import(config: &mut Config, data: ExportData, mode: ImportMode) -> ImportSummary {
+    let mut summary = ImportSummary {
+        snippets: count_snippets(&data.snippets),
+        variables: match &data.variables {
+            serde_yaml::Value::Mapping(map) => map.len(),
+            serde_yaml::Value::Null => 0,
+            _ => 1,
+        },
+        duplicates: Vec::new(),
+        commands: ConfigManager::flatten_snippets(&data.snippets)
+            .into_iter()
+            .filter(|s| s.runs_commands())
+            .map(|s| s.trigger)
+            .collect(),
+        kept_files: Vec::new(),
+        overwritten_variables: Vec::new(),
+    };
+
+    match mode {
+        ImportMode::Replace => {
+            let (kept, _): (Vec<SnippetNode>, _) = std::mem::take(&mut config.snippets)
+                .into_iter()
+                .partition(|node| matches!(node, SnippetNode::Folder(Folder { source: Some(_), .. })));
+            summary.kept_files = kept
+                .iter()
+                .filter_map(|node| match node {
+                    SnippetNode::Folder(Folder { source, .. }) => source.clone(),
+                    _ => None,
+                })
+                .collect();
+            config.snippets = data.snippets;
+            config.snippets.extend(kept);
+
+            config.variables = data.variables;
+            if !config.snippets_dir_variables.is_empty() && !config.variables.is_mapping() {
+                config.variables = serde_yaml::Value::Mapping(Default::default());
+            }
+            if let serde_yaml::Value::Mapping(map) = &mut config.variables {
+                for (key, value) in &config.snippets_dir_variables {
+                    if !map.contains_key(key) {
+                        map.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        ImportMode::Merge => {
+            let existing: std::collections::HashSet<String> = ConfigManager::flatten_snippets(&config.snippets)
+                .into_iter()
+                .map(|s| s.trigger)
+                .collect();
+            summary.duplicates = ConfigManager::flatten_snippets(&data.snippets)
+                .into_iter()
+                .map(|s| s.trigger)
+                .filter(|trigger| existing.contains(trigger))
+                .collect();
+
+            config.snippets.extend(data.snippets);
+            match (&mut config.variables, data.variables) {
+                (serde_yaml::Value::Mapping(map), serde_yaml::Value::Mapping(new_map)) => {
+                    for (k, v) in new_map {
+                        if map.get(&k).is_some_and(|old| *old != v) {
+                            summary.overwritten_variables.push(variable_name(&k));
+                        }
+                        map.insert(k, v);
+                    }
+                }
+                (_, serde_yaml::Value::Null) => {}
+                (current, new) => *current = new,
+            }
+        }
+    }
+
+    summary
+}
+
+/// Number of snippets in a tree, counting those inside folders
+pub fn count_snippets(nodes: &[super::schema::SnippetNode]) -> usize {
+    nodes
+        .iter()
+        .map(|node| match node {
+            super::schema::SnippetNode::Snippet(_) => 1,
+            super::schema::SnippetNode::Folder(f) => count_snippets(&f.items),
+        })
+        .sum()
+}
+
+/// Find the 1-based line of the config file on which the snippet with `trigger`
+/// is defined. serde doesn't keep line numbers, so this scans the raw text for
+/// a `trigger:` key with that value. Returns the first match.
+pub fn find_snippet_line(config_text: &str, trigger: &str) -> Option<usize> {
+    config_text.lines().position(|line| {
+        let line = line.trim_start();
+        let line = line.strip_prefix("- ").unwrap_or(line).trim_start();
+        line.strip_prefix("trigger:")
+            .and_then(|value| serde_yaml::from_str::<String>(value).ok())
+            .is_some_and(|value| value == trigger)
+    })
+    .map(|index| index + 1)
 }
 
 #[cfg(test)]
@@ -255,6 +825,50 @@ mod tests {
     use super::*;
     use tempfile::tempdir;
 
+    #[test]
+    fn test_find_snippet_line() {
+        let text = r#"settings:
+  layout: qwerty
+snippets:
+  - trigger: ";email"
+    replace: "a@b.c"
+  - folder: Work
+    items:
+      - replace: "by the way"
+        trigger: btw  # comment
+      - trigger: ';sig'
+        replace: ";email"
+"#;
+        assert_eq!(find_snippet_line(text, ";email"), Some(4));
+        assert_eq!(find_snippet_line(text, "btw"), Some(9));
+        assert_eq!(find_snippet_line(text, ";sig"), Some(10));
+        assert_eq!(find_snippet_line(text, ";missing"), None);
+    }
+
+    #[test]
+    fn test_poll_detects_changes() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("config.yaml");
+        let snippets = dir.path().join("snippets");
+        std::fs::create_dir(&snippets).unwrap();
+        std::fs::write(&path, "snippets: []\n").unwrap();
+
+        let mut last = modification_times(&path, Some(&snippets));
+        assert!(!files_changed(&path, Some(&snippets), &mut last));
+
+        // Set the time explicitly, as the filesystem's may be too coarse to see the write
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(5)).unwrap();
+        assert!(files_changed(&path, Some(&snippets), &mut last));
+        assert!(!files_changed(&path, Some(&snippets), &mut last));
+
+        std::fs::write(snippets.join("work.yaml"), "[]\n").unwrap();
+        assert!(files_changed(&path, Some(&snippets), &mut last));
+        std::fs::remove_file(&path).unwrap();
+        assert!(files_changed(&path, Some(&snippets), &mut last));
+        assert!(!files_changed(&path, Some(&snippets), &mut last));
+    }
+
     #[tokio::test]
     async fn test_save_and_load_config() {
         let dir = tempdir().unwrap();
@@ -274,4 +888,300 @@ mod tests {
             _ => panic!("Expected snippet"),
         }
     }
+
+    #[test]
+    fn test_load_config_errors() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("config.yaml");
+
+        let err = ConfigManager::load_config(&path).unwrap_err();
+        assert!(matches!(err, ConfigError::Read { .. }));
+
+        std::fs::write(&path, "snippets: [trigger: ;x").unwrap();
+        let err = ConfigManager::load_config(&path).unwrap_err();
+        assert!(matches!(err, ConfigError::Parse { path: ref p, .. } if *p == path));
+    }
+
+    #[test]
+    fn test_load_upgrades_old_config_once() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("config.yaml");
+
+        // Commands that only read the config upgrade it in memory, leaving the file alone
+        let old = "snippets:\n  - trigger: ';x'\n    replace: 'y'\n    word_boundary: false\n";
+        std::fs::write(&path, old).unwrap();
+        let config = ConfigManager::load_config(&path).unwrap();
+        assert_eq!(config.version, migrate::CONFIG_VERSION);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), old);
+
+        // Only the version is missing: it is added without losing the comments
+        std::fs::write(&path, "# My snippets\nsnippets:\n  - trigger: ';x'\n    replace: 'y'\n").unwrap();
+        let config = ConfigManager::load_and_upgrade_config(&path).unwrap();
+        assert_eq!(config.version, migrate::CONFIG_VERSION);
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(content.starts_with(&format!("version: {}\n# My snippets", migrate::CONFIG_VERSION)));
+
+        // A migration that changes entries saves the upgraded config
+        std::fs::write(&path, old).unwrap();
+        ConfigManager::load_and_upgrade_config(&path).unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(!content.contains("word_boundary"));
+        let written = std::fs::metadata(&path).unwrap().modified().unwrap();
+
+        // Once upgraded, loading leaves the file alone
+        let config = ConfigManager::load_and_upgrade_config(&path).unwrap();
+        assert_eq!(config.version, migrate::CONFIG_VERSION);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), content);
+        assert_eq!(std::fs::metadata(&path).unwrap().modified().unwrap(), written);
+
+        // A config from a newer xpander keeps its version, also when saved again
+        std::fs::write(&path, "version: 99\nsnippets: []\n").unwrap();
+        let config = ConfigManager::load_config(&path).unwrap();
+        assert_eq!(config.version, 99);
+        ConfigManager::save_config(&path, &config).unwrap();
+        assert!(std::fs::read_to_string(&path).unwrap().starts_with("version: 99"));
+    }
+
+    #[test]
+    fn test_save_backs_up_hand_edited_config() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("config.yaml");
+        let backup = backup_path(&path);
+        assert_eq!(backup, dir.path().join("config.yaml.bak"));
+
+        // A file written by xpander is overwritten without a backup
+        let mut config = Config::default();
+        ConfigManager::save_config(&path, &config).unwrap();
+        assert!(!is_hand_edited(&std::fs::read_to_string(&path).unwrap()));
+        ConfigManager::save_config(&path, &config).unwrap();
+        assert!(!backup.exists());
+
+        // Comments are lost on save, but the original is kept
+        let hand_edited = "# My snippets\nsnippets:\n  - trigger: ';x'  # short\n    replace: 'y'\n";
+        std::fs::write(&path, hand_edited).unwrap();
+        assert!(is_hand_edited(hand_edited));
+        ConfigManager::save_config(&path, &config).unwrap();
+        assert_eq!(std::fs::read_to_string(&backup).unwrap(), hand_edited);
+        assert!(!std::fs::read_to_string(&path).unwrap().contains("# My snippets"));
+
+        // Without preserve_formatting there is no backup
+        std::fs::remove_file(&backup).unwrap();
+        std::fs::write(&path, hand_edited).unwrap();
+        config.settings.preserve_formatting = false;
+        ConfigManager::save_config(&path, &config).unwrap();
+        assert!(!backup.exists());
+    }
+
+    #[test]
+    fn test_snippets_dir() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("config.yaml");
+        std::fs::create_dir(dir.path().join("snippets")).unwrap();
+        std::fs::write(
+            &path,
+            "snippets_dir: snippets\nsnippets:\n  - trigger: ';main'\n    replace: main\nvariables:\n  name: Main\n",
+        )
+        .unwrap();
+        let work = "- trigger: ';sig'\n  replace: Work signature\n";
+        std::fs::write(dir.path().join("snippets/work.yaml"), work).unwrap();
+        let shared = "snippets:\n- trigger: ';addr'\n  replace: '{{city}}'\nvariables:\n  city: Paris\n  name: Shared\n";
+        std::fs::write(dir.path().join("snippets/shared.yml"), shared).unwrap();
+        std::fs::write(dir.path().join("snippets/notes.txt"), "not snippets").unwrap();
+
+        // Files are merged in name order, each as a folder; the config file's variables win
+        let mut config = ConfigManager::load_config(&path).unwrap();
+        let folders: Vec<&str> = config
+            .snippets
+            .iter()
+            .filter_map(|node| match node {
+                SnippetNode::Folder(f) => Some(f.folder.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(folders, vec!["shared", "work"]);
+        let triggers: Vec<String> = ConfigManager::flatten_active_snippets(&config)
+            .into_iter()
+            .map(|s| s.trigger)
+            .collect();
+        assert_eq!(triggers, vec![";main", ";addr", ";sig"]);
+        assert_eq!(config.variables["city"].as_str(), Some("Paris"));
+        assert_eq!(config.variables["name"].as_str(), Some("Main"));
+
+        // Saving writes changed folders back to their file and leaves the rest alone
+        if let SnippetNode::Folder(f) = &mut config.snippets[2] {
+            f.items.push(SnippetNode::Snippet(super::super::schema::Snippet::new(";team", "The team")));
+        }
+        ConfigManager::save_config(&path, &config).unwrap();
+
+        let main = std::fs::read_to_string(&path).unwrap();
+        assert!(!main.contains(";sig") && !main.contains(";addr") && !main.contains("city"));
+        assert_eq!(std::fs::read_to_string(dir.path().join("snippets/shared.yml")).unwrap(), shared);
+        assert!(std::fs::read_to_string(dir.path().join("snippets/work.yaml")).unwrap().contains(";team"));
+
+        let reloaded = ConfigManager::load_config(&path).unwrap();
+        assert_eq!(reloaded.snippets, config.snippets);
+    }
+
+    #[test]
+    fn test_flatten_with_profile() {
+        let yaml = r#"
+settings:
+  active_profile: work
+  profiles:
+    work: [Work]
+    personal: [Personal]
+snippets:
+  - trigger: ";top"
+    replace: "always"
+  - folder: Work
+    items:
+      - trigger: ";sig"
+        replace: "Work signature"
+      - folder: Clients
+        items:
+          - trigger: ";acme"
+            replace: "ACME Corp"
+  - folder: Personal
+    items:
+      - trigger: ";home"
+        replace: "Home address"
+      - folder: Work
+        items:
+          - trigger: ";side"
+            replace: "Side project"
+"#;
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        let triggers = |config: &Config| -> Vec<String> {
+            ConfigManager::flatten_active_snippets(config)
+                .into_iter()
+                .map(|s| s.trigger)
+                .collect()
+        };
+
+        // Nested folders inherit, and a listed folder name matches at any depth
+        assert_eq!(triggers(&config), vec![";top", ";sig", ";acme", ";side"]);
+
+        config.settings.active_profile = Some("personal".to_string());
+        assert_eq!(triggers(&config), vec![";top", ";home", ";side"]);
+
+        // No profile or an unknown profile activates everything
+        config.settings.active_profile = Some("missing".to_string());
+        assert_eq!(triggers(&config).len(), 5);
+        config.settings.active_profile = None;
+        assert_eq!(triggers(&config).len(), 5);
+    }
+
+    #[test]
+    fn test_folder_defaults() {
+        let yaml = r#"
+snippets:
+  - folder: Prose
+    default_word_boundary: true
+    default_propagate_case: true
+    items:
+      - trigger: btw
+        replace: by the way
+      - trigger: ";raw"
+        replace: raw
+        word_boundary: false
+      - folder: Code
+        default_propagate_case: false
+        items:
+          - trigger: fn
+            replace: function
+  - trigger: ";top"
+    replace: top
+"#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        let snippets = ConfigManager::flatten_snippets(&config.snippets);
+        let flags: Vec<(&str, bool, bool)> = snippets
+            .iter()
+            .map(|s| (s.trigger.as_str(), s.needs_word_boundary(), s.propagates_case()))
+            .collect();
+
+        assert_eq!(
+            flags,
+            vec![
+                ("btw", true, true),
+                // An explicit value on the snippet wins
+                (";raw", false, true),
+                // The innermost folder's default wins, the rest is inherited
+                ("fn", true, false),
+                (";top", false, false),
+            ]
+        );
+
+        // Unset options aren't written back as explicit values
+        let saved = serde_yaml::to_string(&config).unwrap();
+        assert_eq!(saved.matches("word_boundary").count(), 2);
+    }
+
+    #[test]
+    fn test_export_import_round_trip() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("backup.yaml");
+
+        let yaml = r#"
+variables:
+  name: Rafa
+snippets:
+  - trigger: ";sig"
+    replace: "Best, {{name}}"
+  - folder: Work
+    items:
+      - trigger: ";addr"
+        replace: "123 Main St"
+"#;
+        let original: Config = serde_yaml::from_str(yaml).unwrap();
+        export_custom_entries(&original.snippets, &original.variables, &path).unwrap();
+
+        // Replace restores the exported tree and variables exactly
+        let mut config: Config = serde_yaml::from_str("snippets:\n  - trigger: ;old\n    replace: old\n").unwrap();
+        let summary = apply_import(&mut config, import_custom_entries(&path).unwrap(), ImportMode::Replace);
+        assert_eq!(config.snippets, original.snippets);
+        assert_eq!(config.variables, original.variables);
+        assert_eq!((summary.snippets, summary.variables), (2, 1));
+
+        // Merge appends, reporting triggers that were already defined
+        let summary = apply_import(&mut config, import_custom_entries(&path).unwrap(), ImportMode::Merge);
+        assert_eq!(count_snippets(&config.snippets), 4);
+        assert_eq!(summary.duplicates, vec![";sig".to_string(), ";addr".to_string()]);
+        assert_eq!(config.variables, original.variables);
+        // Variables with the same value aren't reported
+        assert!(summary.overwritten_variables.is_empty());
+
+        // Imported variables replace existing ones with the same name, which are reported
+        let data: ExportData = serde_yaml::from_str("snippets: []\nvariables:\n  name: Sam\n  team: Platform\n").unwrap();
+        let summary = apply_import(&mut config, data, ImportMode::Merge);
+        assert_eq!(summary.overwritten_variables, vec!["name".to_string()]);
+        assert_eq!(config.variables["name"].as_str(), Some("Sam"));
+        assert_eq!(config.variables["team"].as_str(), Some("Platform"));
+    }
+
+    #[test]
+    fn test_replace_keeps_snippets_dir() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("config.yaml");
+        std::fs::create_dir(dir.path().join("snippets")).unwrap();
+        std::fs::write(&path, "snippets_dir: snippets\nsnippets:\n  - trigger: ';old'\n    replace: old\n").unwrap();
+        let work = "snippets:\n- trigger: ';sig'\n  replace: '{{team}}'\nvariables:\n  team: Platform\n";
+        std::fs::write(dir.path().join("snippets/work.yaml"), work).unwrap();
+
+        let mut config = ConfigManager::load_config(&path).unwrap();
+        let data: ExportData = serde_yaml::from_str("snippets:\n  - trigger: ';new'\n    replace: new\n").unwrap();
+        let summary = apply_import(&mut config, data, ImportMode::Replace);
+        assert_eq!(summary.kept_files, vec![dir.path().join("snippets/work.yaml")]);
+
+        // What the replace left is what a reload sees
+        let triggers = |config: &Config| -> Vec<String> {
+            ConfigManager::flatten_active_snippets(config).into_iter().map(|s| s.trigger).collect()
+        };
+        assert_eq!(triggers(&config), vec![";new", ";sig"]);
+        assert_eq!(config.variables["team"].as_str(), Some("Platform"));
+        ConfigManager::save_config(&path, &config).unwrap();
+        let reloaded = ConfigManager::load_config(&path).unwrap();
+        assert_eq!(reloaded.snippets, config.snippets);
+        assert_eq!(reloaded.variables, config.variables);
+        assert_eq!(std::fs::read_to_string(dir.path().join("snippets/work.yaml")).unwrap(), work);
+    }
 }
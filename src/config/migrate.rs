@@ -0,0 +1,159 @@
+use serde_yaml::{Mapping, Value};
+
+/// Version of the config format written by this xpander. Bump it when adding a
+/// step to `MIGRATIONS`.
+pub const CONFIG_VERSION: u32 = 1;
+
+/// Upgrade steps in order: `MIGRATIONS[n]` turns a version `n` config into version `n + 1`
+const MIGRATIONS: [fn(&mut Mapping); CONFIG_VERSION as usize] = [unpin_snippet_flags];
+
+/// What `migrate` did to a config
+#[derive(Debug, PartialEq, Eq)]
+pub enum Migration {
+    /// The config is at the current version
+    Current,
+    /// The config was upgraded from version `from`. `changed` is false if only the
+    /// version number had to be added.
+    Upgraded { from: u32, changed: bool },
+    /// The config was written by a newer xpander and is left as it is
+    Newer(u32),
+}
+
+/// Upgrade a parsed config file to `CONFIG_VERSION`. A config without a `version`
+/// is version 0, from before versions were recorded.
+pub fn migrate(value: &mut Value) -> Migration {
+    let Value::Mapping(root) = value else {
+        return Migration::Current;
+    };
+    let version = match root.get("version") {
+        Some(v) => v.as_u64().map_or(u32::MAX, |v| u32::try_from(v).unwrap_or(u32::MAX)),
+        None => 0,
+    };
+    if version == CONFIG_VERSION {
+        return Migration::Current;
+    }
+    if version > CONFIG_VERSION {
+        return Migration::Newer(version);
+    }
+
+    let before = root.clone();
+    for step in &MIGRATIONS[version as usize..] {
+        step(root);
+    }
+    let changed = *root != before;
+    root.insert("version".into(), CONFIG_VERSION.into());
+    Migration::Upgraded { from: version, changed }
+}
+
+/// `content` with its top-level `version:` line set to the current version, for
+/// upgrades that change nothing else, so a hand-edited file keeps its comments
+pub fn set_version_line(content: &str) -> String {
+    let line = format!("version: {}", CONFIG_VERSION);
+    if content.lines().any(|l| l.starts_with("version:")) {
+        content
+            .lines()
+            .map(|l| if l.starts_with("version:") { line.as_str() } else { l })
+            .collect::<Vec<_>>()
+            .join("\n")
+            + "\n"
+    } else {
+        format!("{}\n{}", line, content)
+    }
+}
+
+/// 0 → 1: before folder defaults existed, every saved snippet had `word_boundary:
+/// false` and `propagate_case: false` written out, which now overrides its folder's
+/// `default_word_boundary` / `default_propagate_case`. Drop those `false`s where no
+/// folder sets a default, which changes nothing today but lets defaults added later apply.
+fn unpin_snippet_flags(root: &mut Mapping) {
+    if let Some(Value::Sequence(nodes)) = root.get_mut("snippets") {
+        unpin_flags_in(nodes, false, false);
+    }
+}
+
+fn unpin_flags_in(nodes: &mut [Value], word_boundary_default: bool, propagate_case_default: bool) {
+    for node in nodes.iter_mut().filter_map(Value::as_mapping_mut) {
+        if node.contains_key("folder") {
+            let word_boundary_default = word_boundary_default || node.contains_key("default_word_boundary");
+            let propagate_case_default = propagate_case_default || node.contains_key("default_propagate_case");
+            if let Some(Value::Sequence(items)) = node.get_mut("items") {
+                unpin_flags_in(items, word_boundary_default, propagate_case_default);
+            }
+            continue;
+        }
+
+        for (key, has_default) in [("word_boundary", word_boundary_default), ("propagate_case", propagate_case_default)] {
+            if !has_default && node.get(key) == Some(&Value::Bool(false)) {
+                node.remove(key);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, SnippetNode};
+
+    #[test]
+    fn test_migrate_v0() {
+        let mut value: Value = serde_yaml::from_str(
+            r#"
+settings:
+  keystroke_delay_ms: 20
+snippets:
+  - trigger: ";a"
+    replace: "a"
+    propagate_case: false
+    word_boundary: false
+  - trigger: ";b"
+    replace: "b"
+    word_boundary: true
+  - folder: Work
+    default_word_boundary: true
+    items:
+      - trigger: ";c"
+        replace: "c"
+        propagate_case: false
+        word_boundary: false
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(migrate(&mut value), Migration::Upgraded { from: 0, changed: true });
+        let config: Config = serde_yaml::from_value(value.clone()).unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.settings.keystroke_delay_ms, 20);
+
+        let SnippetNode::Snippet(a) = &config.snippets[0] else { panic!("expected a snippet") };
+        assert_eq!((a.word_boundary, a.propagate_case), (None, None));
+        let SnippetNode::Snippet(b) = &config.snippets[1] else { panic!("expected a snippet") };
+        assert_eq!(b.word_boundary, Some(true));
+        // The folder sets a default, so the explicit false still overrides it
+        let SnippetNode::Folder(work) = &config.snippets[2] else { panic!("expected a folder") };
+        let SnippetNode::Snippet(c) = &work.items[0] else { panic!("expected a snippet") };
+        assert_eq!((c.word_boundary, c.propagate_case), (Some(false), None));
+
+        // Migrating again does nothing
+        assert_eq!(migrate(&mut value), Migration::Current);
+    }
+
+    #[test]
+    fn test_migrate_only_version() {
+        let mut value: Value = serde_yaml::from_str("snippets:\n  - trigger: ';a'\n    replace: a\n").unwrap();
+        assert_eq!(migrate(&mut value), Migration::Upgraded { from: 0, changed: false });
+
+        let content = "# My config\nsnippets: []\n";
+        assert_eq!(set_version_line(content), format!("version: {}\n# My config\nsnippets: []\n", CONFIG_VERSION));
+        assert_eq!(set_version_line("version: 0\nsnippets: []"), format!("version: {}\nsnippets: []\n", CONFIG_VERSION));
+    }
+
+    #[test]
+    fn test_migrate_newer_version() {
+        let original: Value = serde_yaml::from_str("version: 99\nsnippets:\n  - {trigger: a, replace: b, word_boundary: false}\n").unwrap();
+        let mut value = original.clone();
+
+        assert_eq!(migrate(&mut value), Migration::Newer(99));
+        assert_eq!(value, original);
+    }
+}
@@ -1,5 +1,9 @@
 pub mod loader;
+pub mod migrate;
+pub mod remote;
 pub mod schema;
+pub mod share;
+pub mod variables;
 
 pub use loader::ConfigManager;
-pub use schema::{Config, Snippet, SnippetNode, Folder};
+pub use schema::{BackspaceStrategy, Config, Snippet, SnippetNode, Folder, MacroStep, OutputBackendKind, OutputMethod, OutputMode, PostAction};
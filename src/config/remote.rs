@@ -0,0 +1,118 @@
+use anyhow::{Context, Result};
+use std::io::Read;
+use std::time::Duration;
+
+use super::loader::{check_imported_snippets, count_snippets, ExportData};
+
+/// Largest snippet pack that is downloaded
+const MAX_PACK_SIZE: u64 = 5 * 1024 * 1024;
+
+/// Give up on a download after this long
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Download a snippet pack (a file written by `--export`) from `url` and check it.
+/// Only https URLs are accepted, redirects included. Blocks until the download
+/// is done, so keep it off the async runtime.
+pub fn fetch_snippet_pack(url: &str) -> Result<ExportData> {
+    fetch_snippet_pack_with(url, download)
+}
+
+/// `fetch_snippet_pack`, downloading with `get`
+fn fetch_snippet_pack_with(url: &str, get: fn(&str) -> Result<String>) -> Result<ExportData> {
+    check_url(url)?;
+    let content = get(url)?;
+    parse_snippet_pack(&content)
+}
+
+/// Reject anything but an https URL
+fn check_url(url: &str) -> Result<()> {
+    let parsed = reqwest::Url::parse(url).with_context(|| format!("Not a URL: '{}'", url))?;
+    if parsed.scheme() != "https" {
+        anyhow::bail!("Only https:// URLs can be imported, not '{}'", url);
+    }
+    Ok(())
+}
+
+/// Fetch `url` over https, refusing anything larger than `MAX_PACK_SIZE`
+fn download(url: &str) -> Result<String> {
+    let client = reqwest::blocking::Client::builder()
+        .https_only(true)
+        .timeout(DOWNLOAD_TIMEOUT)
+        .build()
+        .context("Failed to set up the download")?;
+    let response = client
+        .get(url)
+        .send()
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("Download of {} failed", url))?;
+
+    // The size limit also covers servers that don't send a Content-Length
+    let mut body = Vec::new();
+    response
+        .take(MAX_PACK_SIZE + 1)
+        .read_to_end(&mut body)
+        .context("Failed to read the download")?;
+    if body.len() as u64 > MAX_PACK_SIZE {
+        anyhow::bail!("Snippet pack is larger than {} MB", MAX_PACK_SIZE / (1024 * 1024));
+    }
+    String::from_utf8(body).context("Snippet pack is not UTF-8 text")
+}
+
+/// Parse a downloaded snippet pack, rejecting ones that couldn't be used
+fn parse_snippet_pack(content: &str) -> Result<ExportData> {
+    let data: ExportData = serde_yaml::from_str(content).context("Not an xpander snippet pack")?;
+
+    if count_snippets(&data.snippets) == 0 && data.variables.is_null() {
+        anyhow::bail!("Snippet pack contains no snippets or variables");
+    }
+    check_imported_snippets(&data.snippets).context("Snippet pack can't be imported")?;
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_url() {
+        assert!(check_url("https://example.com/pack.yaml").is_ok());
+        assert!(check_url("HTTPS://example.com").is_ok());
+        assert!(check_url("http://example.com/pack.yaml").is_err());
+        assert!(check_url("http://127.0.0.1:8000/pack.yaml").is_err());
+        assert!(check_url("file:///etc/passwd").is_err());
+        assert!(check_url("example.com/pack.yaml").is_err());
+    }
+
+    #[test]
+    fn test_parse_snippet_pack() {
+        assert!(parse_snippet_pack("snippets: []").is_err());
+        assert!(parse_snippet_pack("not: a pack").is_err());
+        assert!(parse_snippet_pack("snippets:\n  - trigger: \"\"\n    replace: x").is_err());
+
+        // A pack with a regex trigger the matcher can't compile is rejected as a
+        // whole, one whose regex triggers compile is kept
+        let invalid = "snippets:\n  - trigger: ';ok'\n    replace: x\n  - trigger: \"(\"\n    replace: x\n    regex: true";
+        let err = parse_snippet_pack(invalid).unwrap_err();
+        assert!(format!("{:#}", err).contains("Invalid regex trigger '('"));
+        assert!(parse_snippet_pack("snippets:\n  - trigger: \"(?x)a # note\"\n    replace: x\n    regex: true").is_err());
+        let data = parse_snippet_pack("snippets:\n  - trigger: \"(\\\\d+)kg\"\n    replace: x\n    regex: true").unwrap();
+        assert_eq!(count_snippets(&data.snippets), 1);
+    }
+
+    #[test]
+    fn test_fetch_snippet_pack() {
+        fn example_pack(_: &str) -> Result<String> {
+            Ok(include_str!("../../resources/snippet-pack.example.yaml").to_string())
+        }
+        let data = fetch_snippet_pack_with("https://example.com/pack.yaml", example_pack).unwrap();
+        assert_eq!(count_snippets(&data.snippets), 3);
+        assert_eq!(data.variables["name"].as_str(), Some("there"));
+
+        // The URL is checked before anything is fetched
+        fn unreachable(_: &str) -> Result<String> {
+            panic!("fetched a URL that should have been rejected")
+        }
+        assert!(fetch_snippet_pack_with("http://example.com/pack.yaml", unreachable).is_err());
+    }
+}
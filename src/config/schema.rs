@@ -1,19 +1,51 @@
+use chrono::{Datelike, NaiveDateTime, NaiveTime};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
 
 /// Main configuration structure for xpander
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Config {
+    /// Version of the config format, upgraded on load (missing: version 0)
+    #[serde(default)]
+    pub version: u32,
+
     #[serde(default)]
     pub settings: Settings,
     #[serde(default)]
     pub snippets: Vec<SnippetNode>,
     
+    /// Custom variables, referenced in replacements with dot notation (e.g. `{{user.email}}`)
     #[serde(default)]
+    #[schemars(schema_with = "any_value_schema")]
     pub variables: serde_yaml::Value,
+
+    /// Directory of extra snippet files (`*.yaml`), relative to the config file.
+    /// Each file's snippets are loaded as a folder named after the file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snippets_dir: Option<PathBuf>,
+
+    /// Variables that came from files in `snippets_dir`, so saving leaves them there
+    #[serde(skip)]
+    pub snippets_dir_variables: serde_yaml::Mapping,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            version: super::migrate::CONFIG_VERSION,
+            settings: Settings::default(),
+            snippets: Vec::new(),
+            variables: serde_yaml::Value::Null,
+            snippets_dir: None,
+            snippets_dir_variables: serde_yaml::Mapping::new(),
+        }
+    }
 }
 
 /// Global application settings
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Settings {
     /// Play sound on expansion
     #[serde(default)]
@@ -23,6 +55,11 @@ pub struct Settings {
     #[serde(default)]
     pub notify_on_expand: bool,
 
+    /// Sound and notification are given at most once per this many milliseconds,
+    /// so a burst of expansions gets one (0 gives them for every expansion)
+    #[serde(default = "default_feedback_cooldown")]
+    pub feedback_cooldown_ms: u64,
+
     /// Enable/disable the expander globally
     #[serde(default = "default_true")]
     pub enabled: bool,
@@ -35,13 +72,153 @@ pub struct Settings {
     #[serde(default = "default_keystroke_delay")]
     pub keystroke_delay_ms: u64,
 
-    /// Path to ydotool socket (optional, uses default if not specified)
+    /// Path to ydotool socket (optional; if not specified, `$YDOTOOL_SOCKET`,
+    /// `$XDG_RUNTIME_DIR/.ydotool_socket` and `/tmp/.ydotool_socket` are tried)
     #[serde(default)]
     pub ydotool_socket: Option<String>,
 
+    /// How text is passed to `ydotool type` (`primary_selection` also applies to xdotool)
+    #[serde(default)]
+    pub output_method: OutputMethod,
+
+    /// Put the previous clipboard contents back after pasting with `output_method: paste`
+    #[serde(default = "default_true")]
+    pub preserve_clipboard: bool,
+
+    /// How long to wait after pasting before restoring the clipboard, giving the
+    /// target application time to read it
+    #[serde(default = "default_clipboard_restore_delay")]
+    pub clipboard_restore_delay_ms: u64,
+
+    /// How long `{{clipboard}}` waits for the clipboard before the expansion fails
+    #[serde(default = "default_clipboard_timeout")]
+    pub clipboard_timeout_ms: u64,
+
+    /// File `{{secret:name}}` reads from (optional, defaults to `secrets.yaml` in the
+    /// config directory)
+    #[serde(default)]
+    pub secrets_file: Option<String>,
+
+    /// Tool used to send keystrokes (ydotool, wtype, xdotool)
+    #[serde(default)]
+    pub output_backend: OutputBackendKind,
+
+    /// How the trigger is deleted before typing the replacement
+    #[serde(default)]
+    pub backspace_strategy: BackspaceStrategy,
+
+    /// When the backend fails to type a replacement with characters outside
+    /// ASCII, retry with them transliterated (`é` → `e`, `…` → `...`)
+    #[serde(default)]
+    pub transliterate_fallback: bool,
+
+    /// Input devices to read, by name (e.g. "AT Translated Set 2 keyboard") or path
+    /// (e.g. "/dev/input/by-id/usb-...-event-kbd"). Unset or empty reads every keyboard.
+    #[serde(default)]
+    pub keyboard_devices: Option<Vec<String>>,
+
+    /// Capitalize the first letter typed after `.`, `!` or `?` and a space
+    #[serde(default)]
+    pub auto_capitalize: bool,
+
+    /// Turn two spaces typed after a word into ". "
+    #[serde(default)]
+    pub double_space_period: bool,
+
     /// Keyboard layout (qwerty, azerty, qwertz)
     #[serde(default = "default_layout")]
     pub layout: String,
+
+    /// Locale for month and weekday names in dates, e.g. "de_DE" (defaults to `$LANG`)
+    #[serde(default)]
+    pub locale: Option<String>,
+
+    /// Suppress expansion when the active window belongs to a password manager
+    /// or other sensitive app (see `password_apps`). Off by default, as it looks up
+    /// the focused window for every match.
+    #[serde(default)]
+    pub disable_in_password_fields: bool,
+
+    /// Window classes (case-insensitive substring) in which expansion is suppressed
+    #[serde(default = "default_password_apps")]
+    pub password_apps: Vec<String>,
+
+    /// Suppress expansion while the focused window is fullscreen (videos, presentations)
+    #[serde(default)]
+    pub suspend_when_fullscreen: bool,
+
+    /// Hotkey that toggles "secure input" mode, e.g. "ctrl+alt+s"
+    #[serde(default)]
+    pub secure_input_hotkey: Option<String>,
+
+    /// Hotkey that opens a searchable list of snippets to insert, e.g. "ctrl+alt+i"
+    #[serde(default)]
+    pub palette_hotkey: Option<String>,
+
+    /// Key that starts a leader code, e.g. "F13" or "Menu": the word typed after it
+    /// expands the snippet `leader_codes` maps it to, instead of matching triggers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub leader_key: Option<String>,
+
+    /// Short codes typed after `leader_key`, mapped to the trigger of the snippet
+    /// they expand, e.g. `e: ";email"`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub leader_codes: BTreeMap<String, String>,
+
+    /// Triggers shorter than this many characters only fire at the start of a word,
+    /// unless the snippet sets `word_boundary` itself (0 turns this off)
+    #[serde(default)]
+    pub min_trigger_chars: usize,
+
+    /// Expansions allowed per rolling second before further expansions are briefly
+    /// suppressed, protecting against feedback loops (0 disables the limit)
+    #[serde(default = "default_max_expansions_per_second")]
+    pub max_expansions_per_second: u32,
+
+    /// Wait for the key that completes a trigger to be released before expanding,
+    /// so the app has shown the whole trigger before it is deleted
+    #[serde(default)]
+    pub fire_on_release: bool,
+
+    /// Only expand a matched trigger once `activation_key` is typed right after it
+    #[serde(default)]
+    pub explicit_trigger: bool,
+
+    /// Text that commits a pending match when `explicit_trigger` is on
+    #[serde(default = "default_activation_key")]
+    pub activation_key: String,
+
+    /// Before rewriting a config file with comments or custom formatting (which
+    /// saving discards), keep a copy next to it as `config.yaml.bak`, and warn
+    /// when the configuration window opens
+    #[serde(default = "default_true")]
+    pub preserve_formatting: bool,
+
+    /// How changes to the config file and snippet files are noticed (read at startup)
+    #[serde(default, with = "serde_yaml::with::singleton_map")]
+    #[schemars(with = "ConfigWatchMode")]
+    pub config_watch_mode: ConfigWatchMode,
+
+    /// Name of the active entry in `profiles` (all folders are active if unset)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active_profile: Option<String>,
+
+    /// Named sets of folders, e.g. `work: [Work, Shared]`. While a profile is active
+    /// only its folders expand; top-level snippets are always active.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Vec<String>>,
+}
+
+impl Settings {
+    /// Folder names of the active profile, or `None` if no (known) profile is active
+    pub fn active_profile_folders(&self) -> Option<&[String]> {
+        let name = self.active_profile.as_ref()?;
+        let folders = self.profiles.get(name);
+        if folders.is_none() {
+            log::warn!("Active profile '{}' is not defined in profiles, using all folders", name);
+        }
+        folders.map(Vec::as_slice)
+    }
 }
 
 impl Default for Settings {
@@ -49,37 +226,169 @@ impl Default for Settings {
         Self {
             enable_sound: false,
             notify_on_expand: false,
+            feedback_cooldown_ms: default_feedback_cooldown(),
             enabled: true,
             delete_trigger: true,
             keystroke_delay_ms: default_keystroke_delay(),
             ydotool_socket: None,
+            output_method: OutputMethod::default(),
+            preserve_clipboard: true,
+            clipboard_restore_delay_ms: default_clipboard_restore_delay(),
+            clipboard_timeout_ms: default_clipboard_timeout(),
+            secrets_file: None,
+            output_backend: OutputBackendKind::default(),
+            backspace_strategy: BackspaceStrategy::default(),
+            transliterate_fallback: false,
+            keyboard_devices: None,
+            auto_capitalize: false,
+            double_space_period: false,
             layout: default_layout(),
+            locale: None,
+            disable_in_password_fields: false,
+            password_apps: default_password_apps(),
+            suspend_when_fullscreen: false,
+            secure_input_hotkey: None,
+            palette_hotkey: None,
+            leader_key: None,
+            leader_codes: BTreeMap::new(),
+            min_trigger_chars: 0,
+            max_expansions_per_second: default_max_expansions_per_second(),
+            fire_on_release: false,
+            explicit_trigger: false,
+            activation_key: default_activation_key(),
+            preserve_formatting: true,
+            config_watch_mode: ConfigWatchMode::default(),
+            active_profile: None,
+            profiles: BTreeMap::new(),
         }
     }
 }
 
+/// Schema for free-form YAML values (accepts anything)
+fn any_value_schema(_gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+    schemars::schema::Schema::Bool(true)
+}
+
+/// Generate the JSON Schema describing the config file
+pub fn config_json_schema() -> schemars::schema::RootSchema {
+    schemars::schema_for!(Config)
+}
+
+/// How text is handed to `ydotool type`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputMethod {
+    /// Pipe the text to `ydotool type --file -` (handles special characters reliably, ydotool 1.x)
+    #[default]
+    Stdin,
+    /// Pass the text as a command-line argument (compatible with ydotool 0.1.x)
+    Args,
+    /// Put the text on the clipboard and press Ctrl+V (fast for long replacements)
+    Paste,
+    /// Put the text on the primary selection and press Shift+Insert (terminals),
+    /// typing it instead if the session has no primary selection
+    PrimarySelection,
+}
+
+/// How typed trigger characters are deleted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BackspaceStrategy {
+    /// Press Backspace N times in a single command (fastest)
+    #[default]
+    Repeat,
+    /// Press Backspace N times as separate commands, waiting the keystroke delay
+    /// in between, for apps that drop fast repeats
+    Individual,
+    /// Select the trigger with Shift+Left N times, then press Backspace once
+    SelectDelete,
+}
+
+/// Tool used to send keystrokes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputBackendKind {
+    /// ydotool (uinput, works on any Wayland compositor and X11)
+    #[default]
+    Ydotool,
+    /// wtype (Wayland virtual keyboard protocol, wlroots compositors)
+    Wtype,
+    /// xdotool (X11 only)
+    Xdotool,
+    /// The compositor's virtual keyboard protocol, falling back to ydotool on
+    /// compositors that don't offer it
+    VirtualKeyboard,
+}
+
+/// How the config file is watched for changes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigWatchMode {
+    /// File system notifications (inotify on Linux)
+    #[default]
+    Inotify,
+    /// Check the files' modification times every this many milliseconds, for
+    /// network and overlay filesystems where notifications are missed or repeated
+    Poll(u64),
+    /// Only reload from the tray menu or on SIGHUP
+    Off,
+}
+
 fn default_true() -> bool {
     true
 }
 
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+fn default_max_expansions_per_second() -> u32 {
+    10
+}
+
+fn default_activation_key() -> String {
+    ";;".to_string()
+}
+
 fn default_keystroke_delay() -> u64 {
     12
 }
 
+fn default_clipboard_restore_delay() -> u64 {
+    300
+}
+
+fn default_clipboard_timeout() -> u64 {
+    1000
+}
+
+fn default_feedback_cooldown() -> u64 {
+    1000
+}
+
 fn default_layout() -> String {
     "qwerty".to_string()
 }
 
+fn default_password_apps() -> Vec<String> {
+    ["keepassxc", "bitwarden", "1password", "pinentry", "gcr-prompter", "polkit"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect()
+}
+
 /// A node in the snippet hierarchy (either a snippet or a folder)
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(untagged)]
+// Most nodes are snippets, so boxing them wouldn't save memory
+#[allow(clippy::large_enum_variant)]
 pub enum SnippetNode {
     Folder(Folder),
     Snippet(Snippet),
 }
 
 /// A folder containing snippets or other folders
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct Folder {
     /// Name of the folder
     pub folder: String,
@@ -91,67 +400,383 @@ pub struct Folder {
     /// Whether this folder is enabled
     #[serde(default = "default_true")]
     pub enabled: bool,
+
+    /// `word_boundary` for snippets in this folder that don't set it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_word_boundary: Option<bool>,
+
+    /// `propagate_case` for snippets in this folder that don't set it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_propagate_case: Option<bool>,
+
+    /// File in `snippets_dir` this folder was loaded from; saving writes its items back there
+    #[serde(skip)]
+    pub source: Option<PathBuf>,
+}
+
+impl Folder {
+    /// Create an empty, enabled folder
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            folder: name.into(),
+            items: Vec::new(),
+            enabled: true,
+            default_word_boundary: None,
+            default_propagate_case: None,
+            source: None,
+        }
+    }
+
+    /// Fill in the options `snippet` leaves unset with this folder's defaults
+    pub fn apply_defaults(&self, snippet: &mut Snippet) {
+        snippet.word_boundary = snippet.word_boundary.or(self.default_word_boundary);
+        snippet.propagate_case = snippet.propagate_case.or(self.default_propagate_case);
+    }
 }
 
 /// A single text expansion snippet
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct Snippet {
     /// The trigger text that activates this snippet
     pub trigger: String,
 
-    /// The replacement text
+    /// The replacement text (may be left out if `cycle` is set)
+    #[serde(default)]
     pub replace: String,
 
+    /// Alternative replacements: the first is typed on a match, and typing the
+    /// trigger again right away replaces it with the next one, wrapping around.
+    /// Takes the place of `replace` when set.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cycle: Vec<String>,
+
     /// Optional label/description for the snippet
     #[serde(default)]
     pub label: Option<String>,
 
-    /// Whether to propagate case from trigger to replacement
-    #[serde(default)]
-    pub propagate_case: bool,
+    /// Free-form documentation, e.g. usage examples. Shown in the editor only,
+    /// never typed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+
+    /// Whether to propagate case from trigger to replacement (unset: the folder's
+    /// `default_propagate_case`, otherwise off)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub propagate_case: Option<bool>,
 
     /// Whether to position cursor at $|$ marker after expansion
     #[serde(default)]
     pub cursor_position: bool,
 
-    /// Only trigger on word boundaries (after space, punctuation, etc.)
+    /// Only trigger on word boundaries (after space, punctuation, etc.). Unset: the
+    /// folder's `default_word_boundary`, otherwise off.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub word_boundary: Option<bool>,
+
+    /// Only trigger on a whole word: like `word_boundary`, and the match also waits
+    /// until a space, punctuation or Enter is typed after the trigger, so `ok`
+    /// doesn't fire while typing `okay`
+    #[serde(default)]
+    pub whole_word: bool,
+
+    /// Only trigger at the start of a line (after Enter or at the start of input)
     #[serde(default)]
-    pub word_boundary: bool,
+    pub line_start: bool,
+
+    /// Match the trigger with or without accents, so `cafe` also fires `café` and
+    /// vice versa
+    #[serde(default)]
+    pub ignore_diacritics: bool,
 
     /// Use regex matching for trigger
     #[serde(default)]
     pub regex: bool,
 
-    /// Only expand in specific applications (by window class)
+    /// Only expand in specific applications: a window class substring,
+    /// `class:name`, or `title:/regex/` to match the window title
     #[serde(default)]
     pub applications: Option<Vec<String>>,
 
-    /// Exclude expansion in specific applications
+    /// Exclude expansion in specific applications (same forms as `applications`)
     #[serde(default)]
     pub exclude_applications: Option<Vec<String>>,
 
+    /// Replacements used instead of `replace` in some applications; the first
+    /// entry matching the active window wins
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub per_app_replace: Vec<AppReplace>,
+
+    /// Number of leading trigger characters left in place instead of deleted,
+    /// e.g. 1 keeps the `#` of a `#tag` trigger
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub keep_prefix_len: usize,
+
+    /// Key to press after the replacement has been typed (e.g. to send a chat message)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_action: Option<PostAction>,
+
+    /// Delay between keystrokes for this snippet, overriding `settings.keystroke_delay_ms`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keystroke_delay_ms: Option<u64>,
+
+    /// Type or paste this snippet regardless of `settings.output_method`, for apps
+    /// that block one of them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_mode: Option<OutputMode>,
+
+    /// Steps typed instead of `replace`, e.g. text, Tab, more text, Enter, to fill
+    /// in a form. Written as `- text: ...` / `- key: tab` / `- delay: 200`.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "serde_yaml::with::singleton_map_recursive"
+    )]
+    #[schemars(with = "Option<Vec<MacroStep>>")]
+    pub macro_steps: Option<Vec<MacroStep>>,
+
+    /// Only expand on these days / during these hours (local time)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active_schedule: Option<Schedule>,
+
+    /// Variables local to this snippet, e.g. `d: "{{date}}"` for `{{d}}`. Each is
+    /// expanded once per expansion, so all references to it get the same value.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub vars: BTreeMap<String, String>,
+
+    /// Type the replacement as written, without expanding `{{...}}` variables
+    #[serde(default)]
+    pub raw: bool,
+
+    /// Shell command run in the background after each expansion, with the typed
+    /// trigger in `$XPANDER_TRIGGER` and the expanded text in `$XPANDER_TEXT`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_expand_command: Option<String>,
+
     /// Whether this snippet is enabled
     #[serde(default = "default_true")]
     pub enabled: bool,
 }
 
+/// A replacement for a snippet that only applies in some applications
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct AppReplace {
+    /// Applications this replacement is for (same forms as `applications`)
+    pub applications: Vec<String>,
+    pub replace: String,
+}
+
+/// Action performed after a snippet has been expanded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PostAction {
+    /// Press Enter (e.g. to send a message)
+    PressEnter,
+    /// Press Tab (e.g. to move to the next form field)
+    PressTab,
+}
+
+/// One step of a macro snippet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MacroStep {
+    /// Type text, with variables expanded like in `replace`
+    Text(String),
+    /// Press a key by name: enter, tab, escape, backspace, delete, space, up,
+    /// down, left, right, home, end, page_up or page_down
+    Key(String),
+    /// Wait this many milliseconds, e.g. for a form field to appear
+    Delay(u64),
+}
+
+/// Per-snippet choice between typing and pasting the replacement
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputMode {
+    /// Simulate keystrokes, for apps that block paste
+    Type,
+    /// Paste through the clipboard, for apps that drop or mangle typed input
+    Paste,
+}
+
+impl OutputMode {
+    /// The output method to use instead of the global `method`. Typing keeps a
+    /// typing method (`args` for old ydotool), so only the paste methods change.
+    pub fn method(self, method: OutputMethod) -> OutputMethod {
+        match (self, method) {
+            (Self::Paste, _) => OutputMethod::Paste,
+            (Self::Type, OutputMethod::Paste | OutputMethod::PrimarySelection) => OutputMethod::Stdin,
+            (Self::Type, method) => method,
+        }
+    }
+}
+
+/// Days and hours during which a snippet is active
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+pub struct Schedule {
+    /// Days of the week the snippet is active on (all days if empty)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub days: Vec<Weekday>,
+
+    /// Start of the active period, "HH:MM" (inclusive)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start: Option<String>,
+
+    /// End of the active period, "HH:MM" (exclusive). May be earlier than `start`
+    /// for periods that span midnight.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end: Option<String>,
+}
+
+/// Day of the week
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Weekday {
+    #[serde(alias = "monday")]
+    Mon,
+    #[serde(alias = "tuesday")]
+    Tue,
+    #[serde(alias = "wednesday")]
+    Wed,
+    #[serde(alias = "thursday")]
+    Thu,
+    #[serde(alias = "friday")]
+    Fri,
+    #[serde(alias = "saturday")]
+    Sat,
+    #[serde(alias = "sunday")]
+    Sun,
+}
+
+impl From<chrono::Weekday> for Weekday {
+    fn from(day: chrono::Weekday) -> Self {
+        match day {
+            chrono::Weekday::Mon => Weekday::Mon,
+            chrono::Weekday::Tue => Weekday::Tue,
+            chrono::Weekday::Wed => Weekday::Wed,
+            chrono::Weekday::Thu => Weekday::Thu,
+            chrono::Weekday::Fri => Weekday::Fri,
+            chrono::Weekday::Sat => Weekday::Sat,
+            chrono::Weekday::Sun => Weekday::Sun,
+        }
+    }
+}
+
+impl Schedule {
+    /// Whether the schedule allows expansion at `now`.
+    /// An unparseable start/end time is ignored (with a warning) rather than
+    /// silently disabling the snippet.
+    pub fn is_active_at(&self, now: NaiveDateTime) -> bool {
+        if !self.days.is_empty() && !self.days.contains(&now.weekday().into()) {
+            return false;
+        }
+
+        let start = self.start.as_deref().and_then(parse_time);
+        let end = self.end.as_deref().and_then(parse_time);
+        let time = now.time();
+
+        match (start, end) {
+            (Some(start), Some(end)) if start <= end => time >= start && time < end,
+            // Period spans midnight, e.g. 22:00-06:00
+            (Some(start), Some(end)) => time >= start || time < end,
+            (Some(start), None) => time >= start,
+            (None, Some(end)) => time < end,
+            (None, None) => true,
+        }
+    }
+}
+
+fn parse_time(text: &str) -> Option<NaiveTime> {
+    let parsed = NaiveTime::parse_from_str(text.trim(), "%H:%M").ok();
+    if parsed.is_none() {
+        log::warn!("Invalid schedule time '{}', expected HH:MM", text);
+    }
+    parsed
+}
+
+/// Whether `text` places the cursor: a literal `$|$` or the `{{cursor}}` / `{{|}}` variable
+pub fn contains_cursor_marker(text: &str) -> bool {
+    ["$|$", "{{cursor}}", "{{|}}"].iter().any(|marker| text.contains(marker))
+}
+
 impl Snippet {
     /// Create a new simple snippet
     pub fn new(trigger: impl Into<String>, replace: impl Into<String>) -> Self {
         Self {
             trigger: trigger.into(),
             replace: replace.into(),
+            cycle: Vec::new(),
             label: None,
-            propagate_case: false,
+            notes: None,
+            propagate_case: None,
             cursor_position: false,
-            word_boundary: false,
+            word_boundary: None,
+            whole_word: false,
+            line_start: false,
+            ignore_diacritics: false,
             regex: false,
             applications: None,
             exclude_applications: None,
+            per_app_replace: Vec::new(),
+            keep_prefix_len: 0,
+            post_action: None,
+            keystroke_delay_ms: None,
+            output_mode: None,
+            macro_steps: None,
+            active_schedule: None,
+            vars: BTreeMap::new(),
+            raw: false,
+            on_expand_command: None,
             enabled: true,
         }
     }
 
+    /// Whether case is propagated from the trigger to the replacement
+    pub fn propagates_case(&self) -> bool {
+        self.propagate_case.unwrap_or(false)
+    }
+
+    /// Whether the snippet only fires at a word boundary (whole-word snippets always do)
+    pub fn needs_word_boundary(&self) -> bool {
+        self.word_boundary.unwrap_or(false) || self.whole_word
+    }
+
+    /// Whether the replacement has a `$|$` cursor marker that does nothing because
+    /// `cursor_position` is off (the marker is dropped and the cursor stays at the end)
+    pub fn has_unused_cursor_marker(&self) -> bool {
+        !self.cursor_position && std::iter::once(&self.replace).chain(&self.cycle).any(|r| contains_cursor_marker(r))
+    }
+
+    /// The texts whose variables are expanded when the snippet fires: `replace`,
+    /// `cycle`, `per_app_replace`, `vars` and macro text steps. None for raw snippets.
+    fn expanded_texts(&self) -> impl Iterator<Item = &String> {
+        let macro_texts = self.macro_steps.iter().flatten().filter_map(|step| match step {
+            MacroStep::Text(text) => Some(text),
+            _ => None,
+        });
+        std::iter::once(&self.replace)
+            .chain(&self.cycle)
+            .chain(self.per_app_replace.iter().map(|r| &r.replace))
+            .chain(self.vars.values())
+            .chain(macro_texts)
+            .filter(|_| !self.raw)
+    }
+
+    /// Whether expanding the snippet runs commands, through `on_expand_command` or
+    /// a `{{shell:...}}` variable in any of its texts
+    pub fn runs_commands(&self) -> bool {
+        self.on_expand_command.is_some() || self.expanded_texts().any(|text| crate::variables::runs_shell(text))
+    }
+
+    /// Whether the snippet inserts the focused window's class or title (`{{window:...}}`)
+    pub fn uses_window(&self) -> bool {
+        self.expanded_texts().any(|text| crate::variables::uses_window(text))
+    }
+
+    /// The text typed on a match: the first `cycle` alternative, if any, otherwise `replace`
+    pub fn replacement(&self) -> &str {
+        self.cycle.first().unwrap_or(&self.replace)
+    }
+
     /// Builder method to set label
     pub fn with_label(mut self, label: impl Into<String>) -> Self {
         self.label = Some(label.into());
@@ -160,7 +785,7 @@ impl Snippet {
 
     /// Builder method to enable case propagation
     pub fn with_case_propagation(mut self) -> Self {
-        self.propagate_case = true;
+        self.propagate_case = Some(true);
         self
     }
 
@@ -172,7 +797,7 @@ impl Snippet {
 
     /// Builder method to enable word boundary matching
     pub fn with_word_boundary(mut self) -> Self {
-        self.word_boundary = true;
+        self.word_boundary = Some(true);
         self
     }
 }
@@ -198,7 +823,7 @@ mod tests {
         assert_eq!(snippet.trigger, ";email");
         assert_eq!(snippet.replace, "test@example.com");
         assert_eq!(snippet.label, Some("Email".to_string()));
-        assert!(snippet.propagate_case);
+        assert!(snippet.propagates_case());
     }
 
     #[test]
@@ -215,17 +840,29 @@ snippets:
         let config: Config = serde_yaml::from_str(yaml).unwrap();
         assert!(config.settings.enable_sound);
         assert!(!config.settings.notify_on_expand);
+        assert_eq!(config.settings.config_watch_mode, ConfigWatchMode::Inotify);
         assert_eq!(config.snippets.len(), 1);
         
         match &config.snippets[0] {
             SnippetNode::Snippet(s) => {
                 assert_eq!(s.trigger, ";test");
-                assert!(s.propagate_case);
+                assert!(s.propagates_case());
             }
             _ => panic!("Expected snippet"),
         }
     }
     
+    #[test]
+    fn test_config_watch_mode() {
+        let settings: Settings = serde_yaml::from_str("config_watch_mode:\n  poll: 2000\n").unwrap();
+        assert_eq!(settings.config_watch_mode, ConfigWatchMode::Poll(2000));
+        let yaml = serde_yaml::to_string(&settings).unwrap();
+        assert!(yaml.contains("config_watch_mode:\n  poll: 2000"));
+
+        let settings: Settings = serde_yaml::from_str("config_watch_mode: off\n").unwrap();
+        assert_eq!(settings.config_watch_mode, ConfigWatchMode::Off);
+    }
+
     #[test]
     fn test_deserialize_nested_config() {
         let yaml = r#"
@@ -259,4 +896,112 @@ snippets:
             _ => panic!("Expected snippet"),
         }
     }
+
+    #[test]
+    fn test_post_action_round_trip() {
+        let yaml = r#"
+snippets:
+  - trigger: ";ty"
+    replace: "Thanks!"
+    post_action: press_enter
+  - trigger: ";plain"
+    replace: "no action"
+"#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        let snippets = crate::config::ConfigManager::flatten_snippets(&config.snippets);
+        assert_eq!(snippets[0].post_action, Some(PostAction::PressEnter));
+        assert_eq!(snippets[1].post_action, None);
+
+        let serialized = serde_yaml::to_string(&config).unwrap();
+        assert!(serialized.contains("post_action: press_enter"));
+
+        let reloaded: Config = serde_yaml::from_str(&serialized).unwrap();
+        let snippets = crate::config::ConfigManager::flatten_snippets(&reloaded.snippets);
+        assert_eq!(snippets[0].post_action, Some(PostAction::PressEnter));
+    }
+
+    #[test]
+    fn test_notes_round_trip() {
+        let yaml = r#"
+snippets:
+  - trigger: ";sig"
+    replace: "Best"
+    notes: |
+      Signature for emails.
+      Example: ;sig at the end of a reply
+"#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        let snippets = crate::config::ConfigManager::flatten_snippets(&config.snippets);
+        assert_eq!(
+            snippets[0].notes.as_deref(),
+            Some("Signature for emails.\nExample: ;sig at the end of a reply\n")
+        );
+
+        let reloaded: Config = serde_yaml::from_str(&serde_yaml::to_string(&config).unwrap()).unwrap();
+        assert_eq!(reloaded.snippets, config.snippets);
+
+        // Snippets without notes don't grow a `notes` key
+        let plain = serde_yaml::to_string(&Snippet::new(";x", "y")).unwrap();
+        assert!(!plain.contains("notes"));
+    }
+
+    #[test]
+    fn test_runs_commands() {
+        assert!(!Snippet::new(";d", "{{date}}").runs_commands());
+        assert!(Snippet::new(";ip", "{{ shell:hostname -I }}").runs_commands());
+
+        let mut snippet = Snippet::new(";x", "y");
+        snippet.on_expand_command = Some("notify-send hi".to_string());
+        assert!(snippet.runs_commands());
+
+        let mut snippet = Snippet::new(";x", "{{who}}");
+        snippet.vars.insert("who".to_string(), "{{shell:whoami}}".to_string());
+        assert!(snippet.runs_commands());
+
+        // Raw snippets are typed as written
+        let mut snippet = Snippet::new(";x", "{{shell:whoami}}");
+        snippet.raw = true;
+        assert!(!snippet.runs_commands());
+    }
+
+    #[test]
+    fn test_schedule() {
+        let yaml = r#"
+days: [mon, tue, wednesday, thu, fri]
+start: "09:00"
+end: "17:30"
+"#;
+        let schedule: Schedule = serde_yaml::from_str(yaml).unwrap();
+        let at = |s: &str| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap();
+
+        // 2024-01-03 is a Wednesday, 2024-01-06 a Saturday
+        assert!(schedule.is_active_at(at("2024-01-03 09:00")));
+        assert!(schedule.is_active_at(at("2024-01-03 17:29")));
+        assert!(!schedule.is_active_at(at("2024-01-03 17:30")));
+        assert!(!schedule.is_active_at(at("2024-01-03 08:59")));
+        assert!(!schedule.is_active_at(at("2024-01-06 12:00")));
+
+        let night = Schedule {
+            start: Some("22:00".to_string()),
+            end: Some("06:00".to_string()),
+            ..Default::default()
+        };
+        assert!(night.is_active_at(at("2024-01-06 23:00")));
+        assert!(night.is_active_at(at("2024-01-07 05:59")));
+        assert!(!night.is_active_at(at("2024-01-07 12:00")));
+
+        // No schedule fields means always active
+        assert!(Schedule::default().is_active_at(at("2024-01-06 12:00")));
+    }
+
+    #[test]
+    fn test_json_schema() {
+        let schema = serde_json::to_value(config_json_schema()).unwrap();
+        let definitions = &schema["definitions"];
+
+        assert!(schema["properties"]["snippets"].is_object());
+        assert!(definitions["Settings"]["properties"]["keystroke_delay_ms"].is_object());
+        assert!(definitions["Snippet"]["properties"]["trigger"]["description"].is_string());
+        assert!(definitions["Folder"]["properties"]["items"].is_object());
+    }
 }
@@ -0,0 +1,83 @@
+use anyhow::{Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+use super::loader::check_imported_snippet;
+use super::schema::{Snippet, SnippetNode};
+
+/// The snippet `--share` encodes for `trigger`: the last one defined, which is the one
+/// that fires, with its folder's defaults applied. Disabled snippets can be shared too.
+pub fn find_shared_snippet(nodes: &[SnippetNode], trigger: &str) -> Option<Snippet> {
+    nodes.iter().rev().find_map(|node| match node {
+        SnippetNode::Snippet(s) => (s.trigger == trigger).then(|| s.clone()),
+        SnippetNode::Folder(f) => find_shared_snippet(&f.items, trigger).map(|mut s| {
+            f.apply_defaults(&mut s);
+            s
+        }),
+    })
+}
+
+/// Encode a snippet as a single line of text (base64 of its YAML) that can be
+/// pasted in a chat and added with `--unshare`
+pub fn share_snippet(snippet: &Snippet) -> Result<String> {
+    let yaml = serde_yaml::to_string(snippet).context("Failed to serialize snippet")?;
+    Ok(STANDARD.encode(yaml))
+}
+
+/// Decode a string made by `share_snippet`, checking that it holds exactly one usable snippet
+pub fn unshare_snippet(encoded: &str) -> Result<Snippet> {
+    // Chat apps like to wrap long lines
+    let encoded: String = encoded.chars().filter(|c| !c.is_whitespace()).collect();
+    let bytes = STANDARD.decode(encoded).context("Not a shared snippet")?;
+    let yaml = String::from_utf8(bytes).context("Not a shared snippet")?;
+    let node: SnippetNode = serde_yaml::from_str(&yaml).context("Shared string does not contain a snippet")?;
+
+    let snippet = match node {
+        SnippetNode::Snippet(snippet) => snippet,
+        SnippetNode::Folder(_) => anyhow::bail!("Shared string contains a folder, not a single snippet"),
+    };
+    check_imported_snippet(&snippet).context("Shared snippet can't be imported")?;
+
+    Ok(snippet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Folder;
+
+    #[test]
+    fn test_share_round_trip() {
+        let mut snippet = Snippet::new(";sig", "Cheers,\n{{name}} – \"Rafa\"");
+        snippet.label = Some("Signature".to_string());
+        snippet.whole_word = true;
+        let nodes = vec![SnippetNode::Folder(Folder {
+            items: vec![SnippetNode::Snippet(snippet.clone())],
+            default_propagate_case: Some(true),
+            ..Folder::new("Mail")
+        })];
+
+        let shared = find_shared_snippet(&nodes, ";sig").unwrap();
+        snippet.propagate_case = Some(true);
+        assert_eq!(shared, snippet);
+
+        let encoded = share_snippet(&shared).unwrap();
+        assert!(!encoded.contains(char::is_whitespace));
+        assert_eq!(unshare_snippet(&encoded).unwrap(), snippet);
+        let (start, end) = encoded.split_at(20);
+        assert_eq!(unshare_snippet(&format!("{}\n{} ", start, end)).unwrap(), snippet);
+        assert!(find_shared_snippet(&nodes, ";nope").is_none());
+    }
+
+    #[test]
+    fn test_unshare_rejects_other_payloads() {
+        assert!(unshare_snippet("not base64!").is_err());
+        assert!(unshare_snippet(&STANDARD.encode("- trigger: ;a\n  replace: a\n")).is_err());
+        assert!(unshare_snippet(&STANDARD.encode("folder: Work\nitems: []\n")).is_err());
+        assert!(unshare_snippet(&STANDARD.encode("trigger: \"\"\nreplace: a\n")).is_err());
+
+        let mut regex = Snippet::new(";n(", "x");
+        regex.regex = true;
+        assert!(unshare_snippet(&share_snippet(&regex).unwrap()).is_err());
+    }
+}
@@ -0,0 +1,189 @@
+use anyhow::Result;
+use serde_yaml::{Mapping, Value};
+
+/// Flatten the scalar leaves of the `variables` mapping into (dotted key, value) pairs,
+/// e.g. `user: {email: a@b.c}` becomes `("user.email", "a@b.c")`.
+/// Sequences and other complex values are not editable as text and are skipped.
+pub fn flatten_variables(variables: &Value) -> Vec<(String, String)> {
+    let mut result = Vec::new();
+    if let Value::Mapping(map) = variables {
+        flatten_recursive(map, "", &mut result);
+    }
+    result
+}
+
+fn flatten_recursive(map: &Mapping, prefix: &str, result: &mut Vec<(String, String)>) {
+    for (key, value) in map {
+        let Some(key) = scalar_to_string(key) else {
+            continue;
+        };
+        let path = if prefix.is_empty() {
+            key
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+
+        match value {
+            Value::Mapping(inner) => flatten_recursive(inner, &path, result),
+            other => {
+                if let Some(text) = scalar_to_string(other) {
+                    result.push((path, text));
+                }
+            }
+        }
+    }
+}
+
+/// Rebuild the `variables` mapping from edited (dotted key, value) pairs.
+///
+/// Non-scalar values from `original` (e.g. lists) are kept, and scalars whose text
+/// didn't change keep their original type so `age: 30` doesn't become `age: '30'`.
+pub fn build_variables(entries: &[(String, String)], original: &Value) -> Result<Value> {
+    let mut root = match original {
+        Value::Mapping(map) => strip_scalars(map),
+        _ => Mapping::new(),
+    };
+
+    for (key, text) in entries {
+        let parts: Vec<&str> = key.split('.').map(str::trim).collect();
+        if parts.iter().any(|p| p.is_empty()) {
+            anyhow::bail!("Invalid variable name '{}': names and nested keys must not be empty", key);
+        }
+
+        let value = match lookup(original, &parts) {
+            Some(existing) if scalar_to_string(existing).as_deref() == Some(text.as_str()) => existing.clone(),
+            _ => Value::String(text.clone()),
+        };
+
+        insert_at_path(&mut root, &parts, value)
+            .map_err(|_| anyhow::anyhow!("Variable '{}' conflicts with another variable", key))?;
+    }
+
+    Ok(Value::Mapping(root))
+}
+
+/// Copy a mapping, keeping only nested mappings and non-scalar values
+fn strip_scalars(map: &Mapping) -> Mapping {
+    let mut result = Mapping::new();
+    for (key, value) in map {
+        match value {
+            Value::Mapping(inner) => {
+                let inner = strip_scalars(inner);
+                if !inner.is_empty() {
+                    result.insert(key.clone(), Value::Mapping(inner));
+                }
+            }
+            Value::Sequence(_) | Value::Tagged(_) => {
+                result.insert(key.clone(), value.clone());
+            }
+            _ => {}
+        }
+    }
+    result
+}
+
+fn insert_at_path(map: &mut Mapping, parts: &[&str], value: Value) -> Result<(), ()> {
+    let (first, rest) = parts.split_first().ok_or(())?;
+    let key = Value::String(first.to_string());
+
+    if rest.is_empty() {
+        if matches!(map.get(&key), Some(Value::Mapping(_))) {
+            return Err(());
+        }
+        map.insert(key, value);
+        return Ok(());
+    }
+
+    let entry = map
+        .entry(key)
+        .or_insert_with(|| Value::Mapping(Mapping::new()));
+    match entry {
+        Value::Mapping(inner) => insert_at_path(inner, rest, value),
+        _ => Err(()),
+    }
+}
+
+fn lookup<'a>(value: &'a Value, parts: &[&str]) -> Option<&'a Value> {
+    parts.iter().try_fold(value, |current, part| current.get(*part))
+}
+
+fn scalar_to_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flatten_variables() {
+        let vars: Value = serde_yaml::from_str(
+            r#"
+name: "Rafa"
+user:
+  email: "test@example.com"
+  age: 30
+tags: [a, b]
+"#,
+        )
+        .unwrap();
+
+        let flat = flatten_variables(&vars);
+        assert_eq!(
+            flat,
+            vec![
+                ("name".to_string(), "Rafa".to_string()),
+                ("user.email".to_string(), "test@example.com".to_string()),
+                ("user.age".to_string(), "30".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_build_variables_round_trip() {
+        let original: Value = serde_yaml::from_str(
+            r#"
+user:
+  email: "old@example.com"
+  age: 30
+tags: [a, b]
+"#,
+        )
+        .unwrap();
+
+        let entries = vec![
+            ("user.email".to_string(), "new@example.com".to_string()),
+            ("user.age".to_string(), "30".to_string()),
+            ("company".to_string(), "ACME".to_string()),
+        ];
+        let built = build_variables(&entries, &original).unwrap();
+
+        assert_eq!(built["user"]["email"], Value::String("new@example.com".to_string()));
+        // Unchanged scalars keep their type
+        assert!(built["user"]["age"].is_number());
+        assert_eq!(built["company"], Value::String("ACME".to_string()));
+        // Lists can't be edited but are preserved
+        assert!(built["tags"].is_sequence());
+    }
+
+    #[test]
+    fn test_build_variables_validation() {
+        let entries = vec![("".to_string(), "x".to_string())];
+        assert!(build_variables(&entries, &Value::Null).is_err());
+
+        let entries = vec![("user.".to_string(), "x".to_string())];
+        assert!(build_variables(&entries, &Value::Null).is_err());
+
+        // "user" can't be both a value and a group
+        let entries = vec![
+            ("user".to_string(), "x".to_string()),
+            ("user.email".to_string(), "y".to_string()),
+        ];
+        assert!(build_variables(&entries, &Value::Null).is_err());
+    }
+}
@@ -0,0 +1,277 @@
+use anyhow::{Context, Result};
+use std::fs::{File, OpenOptions};
+use std::fs::TryLockError;
+use std::io::{ErrorKind, Write};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long `--stop` waits for the daemon to exit after signalling it
+const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The lock file held by the running daemon, until shutdown
+static INSTANCE_LOCK: Mutex<Option<File>> = Mutex::new(None);
+
+/// Where the PID of a daemon started with `--daemonize` is kept
+pub fn pid_file_path() -> PathBuf {
+    dirs::runtime_dir().unwrap_or_else(std::env::temp_dir).join("xpander.pid")
+}
+
+/// Where a daemon started with `--daemonize` writes its log
+pub fn log_file_path() -> PathBuf {
+    pid_file_path().with_file_name("xpander.log")
+}
+
+/// The file the running daemon holds a lock on, so a second one refuses to start
+pub fn lock_file_path() -> PathBuf {
+    pid_file_path().with_file_name("xpander.lock")
+}
+
+/// Take the single-instance lock for this process. Fails if another daemon
+/// holds it, since both would read the keyboard and expand every trigger twice.
+pub fn acquire_instance_lock() -> Result<()> {
+    let path = lock_file_path();
+    match try_lock_file(&path, std::process::id())? {
+        Some(file) => {
+            *INSTANCE_LOCK.lock().unwrap() = Some(file);
+            Ok(())
+        }
+        None => anyhow::bail!("{}", already_running(&path)),
+    }
+}
+
+/// Lock `path` and record `pid` in it, or None if another process holds the
+/// lock. The lock goes with the returned file, and the OS drops it if the
+/// process dies, so it can't go stale like the PID file.
+fn try_lock_file(path: &Path, pid: u32) -> Result<Option<File>> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+
+    match file.try_lock() {
+        Ok(()) => {}
+        Err(TryLockError::WouldBlock) => return Ok(None),
+        Err(TryLockError::Error(e)) => {
+            return Err(e).with_context(|| format!("Failed to lock {}", path.display()))
+        }
+    }
+
+    file.set_len(0)
+        .and_then(|_| writeln!(file, "{}", pid))
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(Some(file))
+}
+
+/// The error for a daemon that finds the lock at `path` taken
+fn already_running(path: &Path) -> String {
+    match read_pid(path) {
+        Some(pid) => format!("xpander is already running (PID {})", pid),
+        None => "xpander is already running".to_string(),
+    }
+}
+
+/// Release the single-instance lock and remove the PID file, when the daemon shuts down
+pub fn release_instance() {
+    INSTANCE_LOCK.lock().unwrap().take();
+    release_pid_file();
+}
+
+/// Whether the process with this PID is running this program. A PID left in
+/// the PID file may since have been reused by an unrelated process, which
+/// must not be mistaken for the daemon, let alone signalled.
+fn is_xpander(pid: u32) -> bool {
+    let Ok(exe) = std::fs::read_link(Path::new("/proc").join(pid.to_string()).join("exe")) else {
+        return false;
+    };
+    let Ok(current) = std::env::current_exe() else {
+        return false;
+    };
+    // The link gains this suffix if the binary was replaced (by an upgrade) while running
+    let exe = exe.to_string_lossy();
+    let current = current.to_string_lossy();
+    exe.strip_suffix(" (deleted)").unwrap_or(&exe) == current.strip_suffix(" (deleted)").unwrap_or(&current)
+}
+
+/// The PID recorded in `path`, if the file exists and holds one
+fn read_pid(path: &Path) -> Option<u32> {
+    std::fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// The PID of the daemon recorded in `path`, if it is still running. A PID file
+/// left behind by a daemon that died is removed.
+fn running_pid(path: &Path, alive: fn(u32) -> bool) -> Option<u32> {
+    match read_pid(path) {
+        Some(pid) if alive(pid) => Some(pid),
+        _ => {
+            if path.exists() {
+                log::info!("Removing stale PID file {}", path.display());
+                let _ = std::fs::remove_file(path);
+            }
+            None
+        }
+    }
+}
+
+/// Create the PID file for a new daemon, recording `pid`, unless one is already running
+fn claim_pid_file(path: &Path, pid: u32, alive: fn(u32) -> bool) -> Result<()> {
+    if let Some(running) = running_pid(path, alive) {
+        anyhow::bail!("xpander is already running (PID {})", running);
+    }
+
+    // create_new, so of two instances starting at once only one gets the file
+    let mut file = match OpenOptions::new().write(true).create_new(true).open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => anyhow::bail!("xpander is already starting"),
+        Err(e) => return Err(e).with_context(|| format!("Failed to create {}", path.display())),
+    };
+    writeln!(file, "{}", pid).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Remove the PID file if it records this process
+fn release_pid_file() {
+    release_pid_file_at(&pid_file_path(), std::process::id());
+}
+
+fn release_pid_file_at(path: &Path, pid: u32) {
+    if read_pid(path) == Some(pid) {
+        if let Err(e) = std::fs::remove_file(path) {
+            log::warn!("Failed to remove {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Start the daemon in the background with the same arguments, minus
+/// `--daemonize`, and record its PID. Returns the PID.
+pub fn daemonize(args: &[String]) -> Result<u32> {
+    // A daemon started some other way (autostart, the tray) holds the lock but
+    // has no PID file; check for it here rather than have the child fail silently
+    let lock_path = lock_file_path();
+    if try_lock_file(&lock_path, std::process::id())?.is_none() {
+        anyhow::bail!("{}", already_running(&lock_path));
+    }
+
+    let path = pid_file_path();
+    claim_pid_file(&path, std::process::id(), is_xpander)?;
+
+    let spawned = File::create(log_file_path())
+        .and_then(|log| Ok((log.try_clone()?, log, std::env::current_exe()?)))
+        .and_then(|(stdout, stderr, exe)| {
+            Command::new(exe)
+                .args(args.iter().skip(1).filter(|a| *a != "--daemonize"))
+                .stdin(Stdio::null())
+                .stdout(stdout)
+                .stderr(stderr)
+                // Out of the terminal's process group, so Ctrl+C there doesn't reach it
+                .process_group(0)
+                .spawn()
+        });
+    let child = match spawned {
+        Ok(child) => child,
+        Err(e) => {
+            let _ = std::fs::remove_file(&path);
+            return Err(e).context("Failed to start xpander in the background");
+        }
+    };
+
+    std::fs::write(&path, format!("{}\n", child.id()))
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(child.id())
+}
+
+/// Ask the daemon started with `--daemonize` to exit, and wait until it has.
+/// Returns its PID.
+pub fn stop_daemon() -> Result<u32> {
+    let pid = running_pid(&pid_file_path(), is_xpander)
+        .context("xpander is not running (or was not started with --daemonize)")?;
+
+    // SAFETY: kill has no memory safety requirements
+    if unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) } != 0 {
+        return Err(std::io::Error::last_os_error())
+            .with_context(|| format!("Failed to signal xpander (PID {})", pid));
+    }
+
+    let start = Instant::now();
+    while is_xpander(pid) {
+        if start.elapsed() > STOP_TIMEOUT {
+            anyhow::bail!("xpander (PID {}) did not exit within {} seconds", pid, STOP_TIMEOUT.as_secs());
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    Ok(pid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_pid_file_single_instance() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("xpander.pid");
+
+        claim_pid_file(&path, 100, |_| true).unwrap();
+        assert_eq!(read_pid(&path), Some(100));
+
+        // A second daemon is refused while the first is alive
+        let err = claim_pid_file(&path, 200, |_| true).unwrap_err();
+        assert!(err.to_string().contains("already running (PID 100)"));
+        assert_eq!(running_pid(&path, |_| true), Some(100));
+
+        // Once it has died, the stale file is replaced
+        claim_pid_file(&path, 200, |pid| pid != 100).unwrap();
+        assert_eq!(read_pid(&path), Some(200));
+    }
+
+    #[test]
+    fn test_pid_file_release() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("xpander.pid");
+
+        // Unreadable contents count as stale
+        std::fs::write(&path, "garbage").unwrap();
+        assert_eq!(running_pid(&path, |_| true), None);
+        assert!(!path.exists());
+
+        claim_pid_file(&path, 100, |_| true).unwrap();
+        // Only the daemon the file records removes it
+        release_pid_file_at(&path, 200);
+        assert!(path.exists());
+        release_pid_file_at(&path, 100);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_is_xpander() {
+        assert!(is_xpander(std::process::id()));
+        // PID 1 runs init, not this program
+        assert!(!is_xpander(1));
+        assert!(!is_xpander(u32::MAX));
+    }
+
+    #[test]
+    fn test_instance_lock() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("xpander.lock");
+
+        let first = try_lock_file(&path, 100).unwrap().expect("lock should be free");
+        assert_eq!(read_pid(&path), Some(100));
+
+        // A second daemon can't take it, and is told who holds it
+        assert!(try_lock_file(&path, 200).unwrap().is_none());
+        assert_eq!(read_pid(&path), Some(100));
+        assert_eq!(already_running(&path), "xpander is already running (PID 100)");
+
+        // Released on shutdown, the next daemon gets it
+        drop(first);
+        let second = try_lock_file(&path, 200).unwrap();
+        assert!(second.is_some());
+        assert_eq!(read_pid(&path), Some(200));
+    }
+}
@@ -0,0 +1,716 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::process::Command;
+
+use super::output::{offer_primary_selection, OutputBackend, OutputError, SpecialKey};
+use super::virtual_keyboard::{keysym_name, VirtualKeyboard};
+use crate::config::BackspaceStrategy;
+
+/// Run a command once, failing with its stderr if it exits unsuccessfully
+async fn run_command(program: &str, args: &[String]) -> Result<()> {
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()
+        .await
+        .map_err(|e| OutputError::spawn(program, e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(OutputError::CommandFailed {
+            program: program.to_string(),
+            stderr: stderr.trim().to_string(),
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Check that `program` is on the PATH
+async fn check_installed(program: &str, hint: &str) -> Result<()> {
+    let output = Command::new("which")
+        .arg(program)
+        .output()
+        .await
+        .map_err(|e| OutputError::spawn("which", e))?;
+
+    if !output.status.success() {
+        return Err(OutputError::BackendMissing {
+            program: program.to_string(),
+            hint: hint.to_string(),
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Output backend using wtype (Wayland virtual keyboard protocol)
+pub struct WtypeBackend {
+    keystroke_delay: u64,
+    backspace_strategy: BackspaceStrategy,
+}
+
+impl WtypeBackend {
+    pub fn new(keystroke_delay: u64) -> Self {
+        Self {
+            keystroke_delay,
+            backspace_strategy: BackspaceStrategy::Repeat,
+        }
+    }
+
+    /// Builder method to set how the trigger is deleted
+    pub fn with_backspace_strategy(mut self, strategy: BackspaceStrategy) -> Self {
+        self.backspace_strategy = strategy;
+        self
+    }
+
+    /// Press `key` `count` times in a single wtype invocation
+    async fn press_repeated(&self, key: &str, count: usize) -> Result<()> {
+        if count == 0 {
+            return Ok(());
+        }
+
+        let mut args = vec!["-d".to_string(), self.keystroke_delay.to_string()];
+        for _ in 0..count {
+            args.push("-k".to_string());
+            args.push(key.to_string());
+        }
+        run_command("wtype", &args).await
+    }
+}
+
+#[async_trait]
+impl OutputBackend for WtypeBackend {
+    async fn type_text(&self, text: &str) -> Result<()> {
+        if text.is_empty() {
+            return Ok(());
+        }
+
+        let args = vec![
+            "-d".to_string(),
+            self.keystroke_delay.to_string(),
+            "--".to_string(),
+            text.to_string(),
+        ];
+        run_command("wtype", &args).await
+    }
+
+    async fn press_backspaces(&self, count: usize) -> Result<()> {
+        self.press_repeated("BackSpace", count).await
+    }
+
+    async fn move_cursor_left(&self, count: usize) -> Result<()> {
+        self.press_repeated("Left", count).await
+    }
+
+    async fn select_left(&self, count: usize) -> Result<()> {
+        if count == 0 {
+            return Ok(());
+        }
+
+        let mut args = vec![
+            "-d".to_string(),
+            self.keystroke_delay.to_string(),
+            "-M".to_string(),
+            "shift".to_string(),
+        ];
+        for _ in 0..count {
+            args.push("-k".to_string());
+            args.push("Left".to_string());
+        }
+        args.push("-m".to_string());
+        args.push("shift".to_string());
+        run_command("wtype", &args).await
+    }
+
+    async fn press_key(&self, key: SpecialKey) -> Result<()> {
+        self.press_repeated(key.keysym(), 1).await
+    }
+
+    async fn check_availability(&self) -> Result<()> {
+        check_installed(
+            "wtype",
+            "Install it with: sudo apt install wtype (requires a wlroots-based compositor)",
+        )
+        .await
+    }
+
+    fn keystroke_delay(&self) -> u64 {
+        self.keystroke_delay
+    }
+
+    fn backspace_strategy(&self) -> BackspaceStrategy {
+        self.backspace_strategy
+    }
+}
+
+/// Output backend typing through the compositor's virtual keyboard protocol
+/// (`zwp_virtual_keyboard_v1`), which needs neither an external tool, a daemon
+/// nor uinput access. If there is no Wayland session or the compositor doesn't
+/// offer the protocol, every call goes to the fallback backend from then on.
+pub struct VirtualKeyboardBackend {
+    keyboard: Arc<Mutex<Option<VirtualKeyboard>>>,
+    fallback: Box<dyn OutputBackend>,
+    /// Set once the virtual keyboard turned out to be unusable
+    use_fallback: AtomicBool,
+    keystroke_delay: u64,
+    backspace_strategy: BackspaceStrategy,
+    /// Connects to the compositor (replaced in tests)
+    connect: fn() -> Result<VirtualKeyboard>,
+}
+
+impl VirtualKeyboardBackend {
+    pub fn new(keystroke_delay: u64, fallback: Box<dyn OutputBackend>) -> Self {
+        Self {
+            keyboard: Arc::new(Mutex::new(None)),
+            fallback,
+            use_fallback: AtomicBool::new(false),
+            keystroke_delay,
+            backspace_strategy: BackspaceStrategy::Repeat,
+            connect: VirtualKeyboard::connect,
+        }
+    }
+
+    /// Builder method to set how the trigger is deleted
+    pub fn with_backspace_strategy(mut self, strategy: BackspaceStrategy) -> Self {
+        self.backspace_strategy = strategy;
+        self
+    }
+
+    /// Whether the virtual keyboard can be used, connecting to the compositor on
+    /// first use (and again after the connection was lost). Switches to the
+    /// fallback backend for good if the connection can't be made.
+    async fn ready(&self) -> bool {
+        if self.use_fallback.load(Ordering::Relaxed) {
+            return false;
+        }
+        if self.keyboard.lock().unwrap().is_some() {
+            return true;
+        }
+
+        let connect = self.connect;
+        match tokio::task::spawn_blocking(connect).await {
+            Ok(Ok(keyboard)) => {
+                *self.keyboard.lock().unwrap() = Some(keyboard);
+                true
+            }
+            Ok(Err(e)) => {
+                log::warn!("Virtual keyboard unavailable ({:#}), falling back to ydotool", e);
+                self.use_fallback.store(true, Ordering::Relaxed);
+                false
+            }
+            Err(e) => {
+                log::warn!("Virtual keyboard unavailable ({}), falling back to ydotool", e);
+                self.use_fallback.store(true, Ordering::Relaxed);
+                false
+            }
+        }
+    }
+
+    /// Press `keys` (xkb keysym names) on the virtual keyboard, off the async
+    /// runtime since it waits between keystrokes
+    async fn press(&self, keys: Vec<String>, shift: bool) -> Result<()> {
+        let keyboard = Arc::clone(&self.keyboard);
+        let delay = std::time::Duration::from_millis(self.keystroke_delay);
+        tokio::task::spawn_blocking(move || {
+            let mut guard = keyboard.lock().unwrap();
+            let Some(keyboard) = guard.as_mut() else {
+                anyhow::bail!("The virtual keyboard is not connected");
+            };
+            let result = keyboard.press_keys(&keys, shift, delay);
+            // Reconnect on the next call rather than reuse a broken connection
+            if result.is_err() {
+                *guard = None;
+            }
+            result
+        })
+        .await?
+    }
+}
+
+#[async_trait]
+impl OutputBackend for VirtualKeyboardBackend {
+    async fn type_text(&self, text: &str) -> Result<()> {
+        if !self.ready().await {
+            return self.fallback.type_text(text).await;
+        }
+        self.press(text.chars().filter_map(keysym_name).collect(), false).await
+    }
+
+    async fn press_backspaces(&self, count: usize) -> Result<()> {
+        if !self.ready().await {
+            return self.fallback.press_backspaces(count).await;
+        }
+        self.press(vec!["BackSpace".to_string(); count], false).await
+    }
+
+    async fn move_cursor_left(&self, count: usize) -> Result<()> {
+        if !self.ready().await {
+            return self.fallback.move_cursor_left(count).await;
+        }
+        self.press(vec!["Left".to_string(); count], false).await
+    }
+
+    async fn select_left(&self, count: usize) -> Result<()> {
+        if !self.ready().await {
+            return self.fallback.select_left(count).await;
+        }
+        self.press(vec!["Left".to_string(); count], true).await
+    }
+
+    async fn press_key(&self, key: SpecialKey) -> Result<()> {
+        if !self.ready().await {
+            return self.fallback.press_key(key).await;
+        }
+        self.press(vec![key.keysym().to_string()], false).await
+    }
+
+    async fn check_availability(&self) -> Result<()> {
+        if self.ready().await {
+            return Ok(());
+        }
+        self.fallback.check_availability().await
+    }
+
+    fn keystroke_delay(&self) -> u64 {
+        if self.use_fallback.load(Ordering::Relaxed) {
+            self.fallback.keystroke_delay()
+        } else {
+            self.keystroke_delay
+        }
+    }
+
+    fn backspace_strategy(&self) -> BackspaceStrategy {
+        if self.use_fallback.load(Ordering::Relaxed) {
+            self.fallback.backspace_strategy()
+        } else {
+            self.backspace_strategy
+        }
+    }
+}
+
+/// Output backend using xdotool (X11 sessions)
+pub struct XdotoolBackend {
+    keystroke_delay: u64,
+    /// Paste through the primary selection instead of typing, when there is one
+    primary_selection: bool,
+    backspace_strategy: BackspaceStrategy,
+}
+
+impl XdotoolBackend {
+    pub fn new(keystroke_delay: u64) -> Self {
+        Self {
+            keystroke_delay,
+            primary_selection: false,
+            backspace_strategy: BackspaceStrategy::Repeat,
+        }
+    }
+
+    /// Builder method to set how the trigger is deleted
+    pub fn with_backspace_strategy(mut self, strategy: BackspaceStrategy) -> Self {
+        self.backspace_strategy = strategy;
+        self
+    }
+
+    /// Builder method to paste replacements through the primary selection
+    pub fn with_primary_selection(mut self, enabled: bool) -> Self {
+        self.primary_selection = enabled;
+        self
+    }
+
+    async fn press_repeated(&self, key: &str, count: usize) -> Result<()> {
+        if count == 0 {
+            return Ok(());
+        }
+
+        let args = vec![
+            "key".to_string(),
+            "--delay".to_string(),
+            self.keystroke_delay.to_string(),
+            "--repeat".to_string(),
+            count.to_string(),
+            key.to_string(),
+        ];
+        run_command("xdotool", &args).await
+    }
+}
+
+#[async_trait]
+impl OutputBackend for XdotoolBackend {
+    async fn type_text(&self, text: &str) -> Result<()> {
+        if text.is_empty() {
+            return Ok(());
+        }
+
+        if self.primary_selection
+            && offer_primary_selection(crate::variables::set_primary_selection, text).await
+        {
+            return run_command("xdotool", &["key".to_string(), "shift+Insert".to_string()]).await;
+        }
+
+        let args = vec![
+            "type".to_string(),
+            "--delay".to_string(),
+            self.keystroke_delay.to_string(),
+            "--".to_string(),
+            text.to_string(),
+        ];
+        run_command("xdotool", &args).await
+    }
+
+    async fn press_backspaces(&self, count: usize) -> Result<()> {
+        self.press_repeated("BackSpace", count).await
+    }
+
+    async fn move_cursor_left(&self, count: usize) -> Result<()> {
+        self.press_repeated("Left", count).await
+    }
+
+    async fn select_left(&self, count: usize) -> Result<()> {
+        self.press_repeated("shift+Left", count).await
+    }
+
+    async fn press_key(&self, key: SpecialKey) -> Result<()> {
+        self.press_repeated(key.keysym(), 1).await
+    }
+
+    async fn check_availability(&self) -> Result<()> {
+        check_installed("xdotool", "Install it with: sudo apt install xdotool (X11 only)").await
+    }
+
+    fn keystroke_delay(&self) -> u64 {
+        self.keystroke_delay
+    }
+
+    fn backspace_strategy(&self) -> BackspaceStrategy {
+        self.backspace_strategy
+    }
+}
+
+/// An operation performed on a [`RecordingBackend`]
+#[cfg(test)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutputOp {
+    Type(String),
+    Backspaces(usize),
+    CursorLeft(usize),
+    SelectLeft(usize),
+    Key(SpecialKey),
+}
+
+/// Backend that records operations instead of sending keystrokes (tests only)
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct RecordingBackend {
+    ops: std::sync::Arc<std::sync::Mutex<Vec<OutputOp>>>,
+    /// Fail every `type_text` call, to exercise error handling
+    fail_typing: bool,
+    /// Fail to type text with non-ASCII characters, like a layout without them
+    ascii_only: bool,
+    backspace_strategy: BackspaceStrategy,
+}
+
+#[cfg(test)]
+impl RecordingBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A backend whose typing always fails, like ydotool with its daemon down
+    pub fn failing() -> Self {
+        Self {
+            fail_typing: true,
+            ..Default::default()
+        }
+    }
+
+    /// A backend that can only type ASCII
+    pub fn ascii_only() -> Self {
+        Self {
+            ascii_only: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_backspace_strategy(mut self, strategy: BackspaceStrategy) -> Self {
+        self.backspace_strategy = strategy;
+        self
+    }
+
+    /// Operations recorded so far, in order
+    pub fn ops(&self) -> Vec<OutputOp> {
+        self.ops.lock().unwrap().clone()
+    }
+
+    fn record(&self, op: OutputOp) {
+        self.ops.lock().unwrap().push(op);
+    }
+}
+
+#[cfg(test)]
+#[async_trait]
+impl OutputBackend for RecordingBackend {
+    async fn type_text(&self, text: &str) -> Result<()> {
+        if self.fail_typing {
+            anyhow::bail!("typing failed");
+        }
+        if self.ascii_only && !text.is_ascii() {
+            // Like a real backend, it gets as far as the first character it can't type
+            let typed = &text[..text.find(|c: char| !c.is_ascii()).unwrap_or_default()];
+            if !typed.is_empty() {
+                self.record(OutputOp::Type(typed.to_string()));
+            }
+            anyhow::bail!("cannot type non-ASCII text");
+        }
+        if !text.is_empty() {
+            self.record(OutputOp::Type(text.to_string()));
+        }
+        Ok(())
+    }
+
+    async fn press_backspaces(&self, count: usize) -> Result<()> {
+        if count > 0 {
+            self.record(OutputOp::Backspaces(count));
+        }
+        Ok(())
+    }
+
+    async fn move_cursor_left(&self, count: usize) -> Result<()> {
+        if count > 0 {
+            self.record(OutputOp::CursorLeft(count));
+        }
+        Ok(())
+    }
+
+    async fn select_left(&self, count: usize) -> Result<()> {
+        if count > 0 {
+            self.record(OutputOp::SelectLeft(count));
+        }
+        Ok(())
+    }
+
+    async fn press_key(&self, key: SpecialKey) -> Result<()> {
+        self.record(OutputOp::Key(key));
+        Ok(())
+    }
+
+    async fn check_availability(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn keystroke_delay(&self) -> u64 {
+        0
+    }
+
+    fn backspace_strategy(&self) -> BackspaceStrategy {
+        self.backspace_strategy
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PostAction;
+    use crate::engine::expander::ExpansionResult;
+
+    fn expansion(text: &str, delete_count: usize) -> ExpansionResult {
+        ExpansionResult {
+            text: text.to_string(),
+            delete_count,
+            cursor_offset: None,
+            selection_len: 0,
+            typed_trigger: ";t".to_string(),
+            post_action: None,
+            clipboard: None,
+            keystroke_delay: None,
+            output_mode: None,
+            macro_actions: Vec::new(),
+            transliterate_fallback: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_output_expansion_sequence() {
+        let backend = RecordingBackend::new();
+        let mut result = expansion("hello", 2);
+        result.post_action = Some(PostAction::PressEnter);
+        backend.output_expansion(&result).await.unwrap();
+
+        assert_eq!(
+            backend.ops(),
+            vec![
+                OutputOp::Backspaces(2),
+                OutputOp::Type("hello".to_string()),
+                OutputOp::Key(SpecialKey::Enter),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_macro_sequence() {
+        use crate::engine::output::MacroAction;
+
+        let backend = RecordingBackend::new();
+        let mut result = expansion("JohnDoe", 5);
+        result.macro_actions = vec![
+            MacroAction::Type("John".to_string()),
+            MacroAction::Press(SpecialKey::Tab),
+            MacroAction::Wait(1),
+            MacroAction::Type("Doe".to_string()),
+            MacroAction::Press(SpecialKey::Enter),
+        ];
+        backend.output_expansion(&result).await.unwrap();
+
+        assert_eq!(
+            backend.ops(),
+            vec![
+                OutputOp::Backspaces(5),
+                OutputOp::Type("John".to_string()),
+                OutputOp::Key(SpecialKey::Tab),
+                OutputOp::Type("Doe".to_string()),
+                OutputOp::Key(SpecialKey::Enter),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_backspace_strategies() {
+        let backend = RecordingBackend::new();
+        backend.send_backspaces(3).await.unwrap();
+        assert_eq!(backend.ops(), vec![OutputOp::Backspaces(3)]);
+
+        let backend = RecordingBackend::new().with_backspace_strategy(BackspaceStrategy::Individual);
+        backend.send_backspaces(3).await.unwrap();
+        assert_eq!(backend.ops(), vec![OutputOp::Backspaces(1); 3]);
+
+        let backend = RecordingBackend::new().with_backspace_strategy(BackspaceStrategy::SelectDelete);
+        backend.send_backspaces(3).await.unwrap();
+        assert_eq!(backend.ops(), vec![OutputOp::SelectLeft(3), OutputOp::Backspaces(1)]);
+
+        backend.send_backspaces(0).await.unwrap();
+        assert_eq!(backend.ops().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_post_action_skipped_after_cursor_move() {
+        let backend = RecordingBackend::new();
+        let mut result = expansion("()", 2);
+        result.cursor_offset = Some(1);
+        result.post_action = Some(PostAction::PressTab);
+        backend.output_expansion(&result).await.unwrap();
+
+        assert_eq!(backend.ops().last(), Some(&OutputOp::CursorLeft(1)));
+    }
+
+    #[tokio::test]
+    async fn test_selection_after_cursor_move() {
+        let backend = RecordingBackend::new();
+        let mut result = expansion("Dear name,", 5);
+        result.cursor_offset = Some(1);
+        result.selection_len = 4;
+        result.post_action = Some(PostAction::PressEnter);
+        backend.output_expansion(&result).await.unwrap();
+
+        assert_eq!(
+            backend.ops(),
+            vec![
+                OutputOp::Backspaces(5),
+                OutputOp::Type("Dear name,".to_string()),
+                OutputOp::CursorLeft(1),
+                OutputOp::SelectLeft(4),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_error_kinds() {
+        let err = check_installed("xpander-nonexistent-tool", "hint").await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<OutputError>(),
+            Some(OutputError::BackendMissing { program, .. }) if program == "xpander-nonexistent-tool"
+        ));
+
+        let err = run_command("false", &[]).await.unwrap_err();
+        assert!(err.downcast_ref::<OutputError>().is_some_and(OutputError::is_transient));
+    }
+
+    #[tokio::test]
+    async fn test_virtual_keyboard_fallback() {
+        // Without the protocol everything goes to the fallback, whose settings apply
+        let fallback = RecordingBackend::new();
+        let mut backend = VirtualKeyboardBackend::new(5, Box::new(fallback.clone()))
+            .with_backspace_strategy(BackspaceStrategy::SelectDelete);
+        backend.connect = || anyhow::bail!("The compositor doesn't offer the virtual keyboard protocol");
+
+        assert_eq!(backend.backspace_strategy(), BackspaceStrategy::SelectDelete);
+        assert!(backend.check_availability().await.is_ok());
+        assert!(backend.use_fallback.load(Ordering::Relaxed));
+        assert_eq!(backend.backspace_strategy(), BackspaceStrategy::Repeat);
+        assert_eq!(backend.keystroke_delay(), fallback.keystroke_delay());
+
+        backend.output_expansion(&expansion("hi", 2)).await.unwrap();
+        assert_eq!(
+            fallback.ops(),
+            vec![OutputOp::Backspaces(2), OutputOp::Type("hi".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_typing_is_reported() {
+        let backend = RecordingBackend::failing();
+        assert!(backend.output_expansion(&expansion("hello", 2)).await.is_err());
+        assert_eq!(backend.ops(), vec![OutputOp::Backspaces(2)]);
+    }
+
+    #[tokio::test]
+    async fn test_transliterate_fallback() {
+        let mut result = expansion("Grüße … x", 2);
+        result.cursor_offset = Some(2);
+        result.selection_len = 1;
+
+        // Opt-in: without it the trigger is restored as usual
+        let backend = RecordingBackend::ascii_only();
+        assert!(backend.output_expansion(&result).await.is_err());
+        assert_eq!(
+            backend.ops(),
+            vec![
+                OutputOp::Backspaces(2),
+                OutputOp::Type("Gr".to_string()),
+                OutputOp::Type(";t".to_string())
+            ]
+        );
+
+        // Only the characters that fail are respelled, and nothing is typed twice
+        result.transliterate_fallback = true;
+        let backend = RecordingBackend::ascii_only();
+        backend.output_expansion(&result).await.unwrap();
+        let typed: String = backend
+            .ops()
+            .iter()
+            .filter_map(|op| match op {
+                OutputOp::Type(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(typed, "Grusse ... x");
+        assert_eq!(
+            backend.ops()[backend.ops().len() - 2..],
+            [OutputOp::CursorLeft(2), OutputOp::SelectLeft(3)]
+        );
+
+        // A backend that can type them types the text unchanged
+        let backend = RecordingBackend::default();
+        backend.output_expansion(&result).await.unwrap();
+        assert_eq!(
+            backend.ops()[backend.ops().len() - 2..],
+            [OutputOp::CursorLeft(2), OutputOp::SelectLeft(1)]
+        );
+    }
+}
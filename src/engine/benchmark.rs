@@ -0,0 +1,136 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::config::Snippet;
+
+use super::matcher::Matcher;
+
+/// One in this many synthetic snippets has a regex trigger
+const REGEX_EVERY: usize = 20;
+
+/// Prose the synthetic keystroke stream is made of
+const SAMPLE_TEXT: &str = "The quick brown fox jumps over the lazy dog, then \
+    writes a short reply: thanks for the update, I will look into it tomorrow.\n";
+
+/// A trigger is typed after this many words of prose
+const WORDS_BETWEEN_TRIGGERS: usize = 12;
+
+/// Per-keystroke matcher latency over a synthetic workload
+#[derive(Debug)]
+pub struct BenchmarkReport {
+    pub snippets: usize,
+    pub regex_snippets: usize,
+    pub keystrokes: usize,
+    /// Keystrokes that completed a trigger
+    pub matches: usize,
+    pub average: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+/// `count` snippets with literal triggers like `;w42`, every `REGEX_EVERY`th one
+/// a regex trigger like `;r40_(\d+)`
+pub fn synthetic_snippets(count: usize) -> Vec<Snippet> {
+    (0..count)
+        .map(|i| {
+            if i % REGEX_EVERY == 0 {
+                let mut snippet = Snippet::new(format!(r";r{}_(\d+)", i), "Number $1");
+                snippet.regex = true;
+                snippet
+            } else {
+                Snippet::new(format!(";w{}", i), format!("Replacement {}", i))
+            }
+        })
+        .collect()
+}
+
+/// About `keystrokes` characters of prose with a trigger typed every few words,
+/// cycling through the snippets
+fn typed_stream(snippets: &[Snippet], keystrokes: usize) -> String {
+    let mut stream = String::with_capacity(keystrokes + 32);
+    let mut triggers = snippets.iter().cycle();
+    let mut words = SAMPLE_TEXT.split_inclusive(' ').cycle().enumerate();
+
+    while stream.len() < keystrokes {
+        let (i, word) = words.next().unwrap_or((0, ""));
+        stream.push_str(word);
+        if i % WORDS_BETWEEN_TRIGGERS == WORDS_BETWEEN_TRIGGERS - 1 {
+            match triggers.next() {
+                Some(s) if s.regex => stream.push_str(&format!("{}7 ", s.trigger.replace(r"(\d+)", ""))),
+                Some(s) => stream.push_str(&format!("{} ", s.trigger)),
+                None => {}
+            }
+        }
+    }
+    stream
+}
+
+/// Time `push_char` + `check_match` for each of `keystrokes` typed characters,
+/// with `snippet_count` synthetic snippets loaded
+pub fn run_benchmark(snippet_count: usize, keystrokes: usize) -> BenchmarkReport {
+    let snippets = synthetic_snippets(snippet_count);
+    let regex_snippets = snippets.iter().filter(|s| s.regex).count();
+    let stream = typed_stream(&snippets, keystrokes);
+
+    let mut matcher = Matcher::new();
+    matcher.reload(snippets);
+
+    let mut timings = Vec::with_capacity(stream.len());
+    let mut matches = 0;
+    for ch in stream.chars() {
+        let start = Instant::now();
+        matcher.push_char(ch);
+        let result = matcher.check_match();
+        timings.push(start.elapsed());
+
+        // Like the engine, drop the expanded trigger from the buffer
+        if let Some(result) = result {
+            matcher.remove_last(result.chars_to_delete);
+            matches += 1;
+        }
+    }
+
+    timings.sort_unstable();
+    let total: Duration = timings.iter().sum();
+    let percentile = |p: usize| timings[(timings.len() * p).div_ceil(100).saturating_sub(1)];
+
+    BenchmarkReport {
+        snippets: snippet_count,
+        regex_snippets,
+        keystrokes: timings.len(),
+        matches,
+        average: total / timings.len().max(1) as u32,
+        p99: percentile(99),
+        max: timings.last().copied().unwrap_or_default(),
+    }
+}
+
+impl fmt::Display for BenchmarkReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Matcher benchmark: {} snippets ({} regex), {} keystrokes, {} matches",
+            self.snippets, self.regex_snippets, self.keystrokes, self.matches
+        )?;
+        writeln!(f, "  average: {:.1?} per keystroke", self.average)?;
+        writeln!(f, "  p99:     {:.1?}", self.p99)?;
+        writeln!(f, "  max:     {:.1?}", self.max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_benchmark_matches_triggers() {
+        let report = run_benchmark(100, 2_000);
+
+        assert_eq!(report.regex_snippets, 5);
+        assert!(report.keystrokes >= 2_000);
+        // Every typed trigger fires, literal and regex alike
+        assert!(report.matches >= 2_000 / 100);
+        assert!(report.p99 <= report.max);
+        assert!(report.to_string().contains("100 snippets (5 regex)"));
+    }
+}
@@ -0,0 +1,336 @@
+use std::fmt;
+
+use crate::config::loader::ConfigManager;
+use crate::config::{Config, Snippet, SnippetNode};
+
+use super::expander::{expand_match, expand_snippet};
+use super::matcher::{compile_regex_trigger, MatchResult};
+use super::trie::Trie;
+
+/// Everything we found out about one snippet that matches the diagnosed trigger
+#[derive(Debug)]
+pub struct SnippetReport {
+    pub snippet: Snippet,
+    /// Names of the folders containing the snippet, outermost first
+    pub folder_path: Vec<String>,
+    /// Enclosing folders that are disabled
+    pub disabled_folders: Vec<String>,
+    /// None of the enclosing folders belongs to the active profile
+    pub outside_profile: bool,
+    /// Another enabled snippet with the same trigger takes precedence
+    pub shadowed: bool,
+    /// Compile error, if this is a regex snippet with an invalid pattern
+    pub regex_error: Option<String>,
+    /// What the snippet would type, or why expansion failed
+    pub expansion: Result<String, String>,
+}
+
+impl SnippetReport {
+    /// Whether the snippet is loaded into the matcher at all
+    pub fn is_active(&self) -> bool {
+        self.snippet.enabled && self.disabled_folders.is_empty() && !self.outside_profile
+    }
+}
+
+/// Result of diagnosing why a trigger does or doesn't fire
+#[derive(Debug)]
+pub struct Diagnosis {
+    pub trigger: String,
+    /// Snippets whose trigger is (or whose regex matches) the diagnosed text
+    pub reports: Vec<SnippetReport>,
+    /// A shorter trigger that fires before this one can be typed in full
+    pub fires_early: Option<String>,
+    /// Triggers that differ only in case, shown when nothing matched
+    pub similar: Vec<String>,
+}
+
+/// Work out whether `trigger` would expand with `config`, and if not, why
+pub fn diagnose(config: &Config, trigger: &str) -> Diagnosis {
+    let active = ConfigManager::flatten_active_snippets(config);
+
+    // The trie keeps the last definition of a trigger, like the matcher
+    let mut trie = Trie::new();
+    for snippet in active.iter().filter(|s| !s.regex) {
+        trie.insert(snippet.clone());
+    }
+
+    let mut reports = Vec::new();
+    let mut similar = Vec::new();
+    collect_reports(&config.snippets, &mut Vec::new(), &mut Vec::new(), trigger, &mut reports, &mut similar);
+
+    let profile_folders = config.settings.active_profile_folders();
+    for report in &mut reports {
+        report.outside_profile = profile_folders.is_some_and(|folders| {
+            !report.folder_path.is_empty() && !report.folder_path.iter().any(|f| folders.contains(f))
+        });
+        report.shadowed = report.is_active()
+            && !report.snippet.regex
+            && trie
+                .find_match(trigger)
+                .is_some_and(|(winner, _)| winner.trigger == trigger && !same_snippet(winner, &report.snippet));
+        report.expansion = preview_expansion(&report.snippet, trigger, &config.variables);
+    }
+
+    // Matching happens on every keystroke, so a trigger that ends a prefix of
+    // this one fires first (e.g. `;addr` never completes if `;add` exists)
+    let chars: Vec<char> = trigger.chars().collect();
+    let fires_early = (1..chars.len()).find_map(|len| {
+        let prefix: String = chars[..len].iter().collect();
+        trie.find_match(&prefix).map(|(s, _)| s.trigger.clone())
+    });
+
+    Diagnosis {
+        trigger: trigger.to_string(),
+        reports,
+        fires_early,
+        similar,
+    }
+}
+
+fn collect_reports(
+    nodes: &[SnippetNode],
+    folder_path: &mut Vec<String>,
+    disabled_folders: &mut Vec<String>,
+    trigger: &str,
+    reports: &mut Vec<SnippetReport>,
+    similar: &mut Vec<String>,
+) {
+    for node in nodes {
+        match node {
+            SnippetNode::Snippet(s) => {
+                let (matches, regex_error) = trigger_matches(s, trigger);
+                if matches {
+                    reports.push(SnippetReport {
+                        snippet: s.clone(),
+                        folder_path: folder_path.clone(),
+                        disabled_folders: disabled_folders.clone(),
+                        outside_profile: false,
+                        shadowed: false,
+                        regex_error,
+                        expansion: Ok(String::new()),
+                    });
+                } else if !s.regex && s.trigger.to_lowercase() == trigger.to_lowercase() {
+                    similar.push(s.trigger.clone());
+                }
+            }
+            SnippetNode::Folder(f) => {
+                folder_path.push(f.folder.clone());
+                if !f.enabled {
+                    disabled_folders.push(f.folder.clone());
+                }
+                let start = reports.len();
+                collect_reports(&f.items, folder_path, disabled_folders, trigger, reports, similar);
+                for report in &mut reports[start..] {
+                    f.apply_defaults(&mut report.snippet);
+                }
+                if !f.enabled {
+                    disabled_folders.pop();
+                }
+                folder_path.pop();
+            }
+        }
+    }
+}
+
+/// Whether `snippet` would match `trigger`, plus the regex compile error if any.
+/// A regex snippet whose pattern fails to compile matches if its source equals the text.
+fn trigger_matches(snippet: &Snippet, trigger: &str) -> (bool, Option<String>) {
+    if !snippet.regex {
+        return (snippet.trigger == trigger, None);
+    }
+
+    match compile_regex_trigger(&snippet.trigger) {
+        Ok(regex) => (snippet.trigger == trigger || regex.is_match(trigger), None),
+        Err(e) => (snippet.trigger == trigger, Some(e.to_string())),
+    }
+}
+
+fn same_snippet(a: &Snippet, b: &Snippet) -> bool {
+    a.trigger == b.trigger && a.replace == b.replace && a.cycle == b.cycle && a.label == b.label
+}
+
+/// Expand a snippet as if `trigger` had just been typed
+fn preview_expansion(snippet: &Snippet, trigger: &str, variables: &serde_yaml::Value) -> Result<String, String> {
+    let result = if snippet.regex {
+        let regex = compile_regex_trigger(&snippet.trigger).map_err(|e| e.to_string())?;
+        let captures = regex.captures(trigger).map(|caps| {
+            caps.iter()
+                .skip(1)
+                .map(|m| m.map_or(String::new(), |m| m.as_str().to_string()))
+                .collect()
+        });
+        let match_result = MatchResult {
+            snippet: snippet.clone(),
+            typed_trigger: trigger.to_string(),
+            chars_to_delete: trigger.chars().count(),
+            captures,
+            committed_by: None,
+            mid_word: false,
+        };
+        expand_match(&match_result, variables)
+    } else {
+        expand_snippet(snippet, variables)
+    };
+
+    result.map(|r| r.text).map_err(|e| e.to_string())
+}
+
+impl fmt::Display for Diagnosis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Diagnosis for trigger '{}':", self.trigger)?;
+
+        if self.reports.is_empty() {
+            writeln!(f, "  ✗ No snippet has this trigger")?;
+            for trigger in &self.similar {
+                writeln!(f, "    Did you mean '{}'? (triggers are case-sensitive)", trigger)?;
+            }
+            return Ok(());
+        }
+
+        for (i, report) in self.reports.iter().enumerate() {
+            let s = &report.snippet;
+            writeln!(f)?;
+            write!(f, "  Snippet #{}", i + 1)?;
+            if let Some(label) = &s.label {
+                write!(f, " \"{}\"", label)?;
+            }
+            if !report.folder_path.is_empty() {
+                write!(f, " in {}", report.folder_path.join(" / "))?;
+            }
+            writeln!(f)?;
+
+            if s.enabled {
+                writeln!(f, "    ✓ Enabled")?;
+            } else {
+                writeln!(f, "    ✗ Snippet is disabled")?;
+            }
+            for folder in &report.disabled_folders {
+                writeln!(f, "    ✗ Folder '{}' is disabled", folder)?;
+            }
+            if report.outside_profile {
+                writeln!(f, "    ✗ Not in a folder of the active profile")?;
+            }
+
+            if s.regex {
+                match &report.regex_error {
+                    Some(e) => writeln!(f, "    ✗ Regex does not compile: {}", e)?,
+                    None => writeln!(f, "    ✓ Regex trigger compiles")?,
+                }
+            }
+
+            if report.shadowed {
+                writeln!(f, "    ✗ Shadowed by a later snippet with the same trigger")?;
+            }
+
+            if s.needs_word_boundary() {
+                writeln!(f, "    • Only fires after a space, punctuation or the start of input")?;
+            }
+            if s.whole_word {
+                writeln!(f, "    • Only fires once a space, punctuation or Enter follows it")?;
+            }
+            if s.line_start {
+                writeln!(f, "    • Only fires at the start of a line")?;
+            }
+            if let Some(schedule) = &s.active_schedule {
+                if schedule.is_active_at(chrono::Local::now().naive_local()) {
+                    writeln!(f, "    ✓ Within its active schedule")?;
+                } else {
+                    writeln!(f, "    ✗ Outside its active schedule right now")?;
+                }
+            }
+
+            match &report.expansion {
+                Ok(text) => writeln!(f, "    → Expands to: {:?}", text)?,
+                Err(e) => writeln!(f, "    ✗ Expansion fails: {}", e)?,
+            }
+        }
+
+        if let Some(early) = &self.fires_early {
+            writeln!(f)?;
+            writeln!(
+                f,
+                "  ✗ '{}' fires before '{}' can be typed in full",
+                early, self.trigger
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Folder;
+
+    fn config(snippets: Vec<SnippetNode>) -> Config {
+        Config {
+            snippets,
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn test_diagnose_missing_trigger() {
+        let config = config(vec![SnippetNode::Snippet(Snippet::new(";Email", "a@b.c"))]);
+        let diagnosis = diagnose(&config, ";email");
+
+        assert!(diagnosis.reports.is_empty());
+        assert_eq!(diagnosis.similar, vec![";Email".to_string()]);
+    }
+
+    #[test]
+    fn test_diagnose_disabled_folder_and_shadowing() {
+        let mut disabled = Snippet::new(";sig", "old");
+        disabled.enabled = false;
+        let config = config(vec![
+            SnippetNode::Snippet(Snippet::new(";sig", "first")),
+            SnippetNode::Folder(Folder {
+                items: vec![SnippetNode::Snippet(Snippet::new(";sig", "second"))],
+                ..Folder::new("Work")
+            }),
+            SnippetNode::Folder(Folder {
+                items: vec![SnippetNode::Snippet(disabled)],
+                enabled: false,
+                ..Folder::new("Old")
+            }),
+        ]);
+        let diagnosis = diagnose(&config, ";sig");
+
+        assert_eq!(diagnosis.reports.len(), 3);
+        assert!(diagnosis.reports[0].shadowed);
+        assert!(!diagnosis.reports[1].shadowed);
+        assert_eq!(diagnosis.reports[1].folder_path, vec!["Work".to_string()]);
+        assert_eq!(diagnosis.reports[1].expansion.as_deref(), Ok("second"));
+        assert!(!diagnosis.reports[2].is_active());
+        assert_eq!(diagnosis.reports[2].disabled_folders, vec!["Old".to_string()]);
+    }
+
+    #[test]
+    fn test_diagnose_regex() {
+        let mut good = Snippet::new(r";n(\d+)", "Number $1");
+        good.regex = true;
+        let mut bad = Snippet::new(";bad(", "x");
+        bad.regex = true;
+        let config = config(vec![SnippetNode::Snippet(good), SnippetNode::Snippet(bad)]);
+
+        let diagnosis = diagnose(&config, ";n42");
+        assert_eq!(diagnosis.reports.len(), 1);
+        assert_eq!(diagnosis.reports[0].expansion.as_deref(), Ok("Number 42"));
+
+        let diagnosis = diagnose(&config, ";bad(");
+        assert!(diagnosis.reports[0].regex_error.is_some());
+        assert!(diagnosis.to_string().contains("does not compile"));
+    }
+
+    #[test]
+    fn test_diagnose_prefix_fires_first() {
+        let config = config(vec![
+            SnippetNode::Snippet(Snippet::new(";add", "add")),
+            SnippetNode::Snippet(Snippet::new(";addr", "123 Main St")),
+        ]);
+        let diagnosis = diagnose(&config, ";addr");
+
+        assert_eq!(diagnosis.fires_early.as_deref(), Some(";add"));
+    }
+}
@@ -1,11 +1,16 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use regex::Regex;
+use std::borrow::Cow;
+use std::io::{BufRead, Write};
 use std::sync::LazyLock;
 
-use crate::config::Snippet;
-use crate::variables::{expand_variables, find_cursor_position, propagate_case};
+use crate::config::{MacroStep, OutputMode, PostAction, Snippet};
+use crate::variables::{
+    expand_variables, extract_clipboard_set, find_cursor_position, propagate_case, unescape_unicode,
+};
 
 use super::matcher::MatchResult;
+use super::output::{MacroAction, SpecialKey};
 
 /// Result of expanding a snippet
 #[derive(Debug, Clone)]
@@ -16,6 +21,23 @@ pub struct ExpansionResult {
     pub delete_count: usize,
     /// Cursor offset from end of text (how many chars to move back)
     pub cursor_offset: Option<usize>,
+    /// Number of characters to select leftwards from the cursor (`$|$placeholder$|$`)
+    pub selection_len: usize,
+    /// The trigger text that was typed (used to restore it if output fails)
+    pub typed_trigger: String,
+    /// Key to press once the text has been typed
+    pub post_action: Option<PostAction>,
+    /// Text to load into the clipboard after typing (`{{clipboard_set:...}}`)
+    pub clipboard: Option<String>,
+    /// The snippet's own keystroke delay, if it overrides the global one
+    pub keystroke_delay: Option<u64>,
+    /// Type or paste regardless of the global output method (`Snippet::output_mode`)
+    pub output_mode: Option<OutputMode>,
+    /// A macro snippet's steps, output instead of `text` when there are any
+    pub macro_actions: Vec<MacroAction>,
+    /// Retry with the text transliterated to ASCII if typing it fails
+    /// (`settings.transliterate_fallback`)
+    pub transliterate_fallback: bool,
 }
 
 /// Regex for replacing capture group references ($1, $2, etc.)
@@ -26,38 +48,155 @@ static CAPTURE_REGEX: LazyLock<Regex> = LazyLock::new(|| {
 /// Process a match result and produce the final expansion
 pub fn expand_match(match_result: &MatchResult, variables: &serde_yaml::Value) -> Result<ExpansionResult> {
     let snippet = &match_result.snippet;
-    let mut text = snippet.replace.clone();
+    if let Some(steps) = &snippet.macro_steps {
+        return expand_macro(match_result, steps, variables);
+    }
+
+    // Step 1: Resolve \u{...} escapes in the replacement, before anything typed is substituted in
+    let mut text = unescape_unicode(snippet.replacement());
 
-    // Step 1: Replace regex capture groups if present
+    // Step 2: Replace regex capture groups if present
     if let Some(captures) = &match_result.captures {
         text = replace_captures(&text, captures);
     }
 
-    // Step 2: Expand variables ({{date}}, {{clipboard}}, etc.)
-    text = expand_variables(&text, variables)?;
+    // Step 3: Pull out {{clipboard_set:...}} directives. Their value is used as written
+    // (after capture groups), and {{clipboard}} elsewhere still reads the old contents.
+    // Raw snippets keep all `{{...}}` text as it is.
+    let mut clipboard = None;
+    if !snippet.raw {
+        let (remaining, value) = extract_clipboard_set(&text);
+        text = remaining;
+        clipboard = value;
+
+        // Step 4: Expand variables ({{date}}, {{clipboard}}, etc.), including the
+        // snippet's own `vars`, each of which is evaluated once
+        let variables = resolve_snippet_vars(snippet, variables)?;
+        text = expand_variables(&text, &variables)?;
+    }
 
-    // Step 3: Apply case propagation if enabled
-    if snippet.propagate_case {
+    // Step 5: Apply case propagation if enabled
+    if snippet.propagates_case() {
         text = propagate_case(&match_result.typed_trigger, &text);
     }
 
-    // Step 4: Find and process cursor position marker
-    let (final_text, cursor_pos) = find_cursor_position(&text);
+    // Step 6: Work out how much of the trigger to delete, keeping any prefix
+    let (delete_count, deleted_trigger) = trigger_to_delete(match_result)?;
+
+    // Step 7: Find and process cursor position marker
+    let (final_text, cursor_pos, selection_len) = find_cursor_position(&text);
 
     // Calculate cursor offset from end
-    let cursor_offset = if snippet.cursor_position {
-        cursor_pos.map(|pos| final_text.len() - pos)
+    let (cursor_offset, selection_len) = if snippet.cursor_position {
+        (cursor_pos.map(|pos| final_text.chars().count() - pos), selection_len)
     } else {
-        None
+        (None, 0)
     };
 
     Ok(ExpansionResult {
         text: final_text,
-        delete_count: match_result.chars_to_delete,
+        delete_count,
         cursor_offset,
+        selection_len,
+        typed_trigger: deleted_trigger,
+        post_action: snippet.post_action,
+        keystroke_delay: snippet.keystroke_delay_ms,
+        output_mode: snippet.output_mode,
+        clipboard,
+        macro_actions: Vec::new(),
+        transliterate_fallback: false,
+    })
+}
+
+/// Expand a macro snippet. Text steps get capture groups and variables like a
+/// replacement (no cursor markers or case propagation); key names are checked.
+fn expand_macro(match_result: &MatchResult, steps: &[MacroStep], variables: &serde_yaml::Value) -> Result<ExpansionResult> {
+    let snippet = &match_result.snippet;
+    let variables = resolve_snippet_vars(snippet, variables)?;
+
+    let mut macro_actions = Vec::with_capacity(steps.len());
+    for step in steps {
+        let action = match step {
+            MacroStep::Text(text) => {
+                let mut text = unescape_unicode(text);
+                if let Some(captures) = &match_result.captures {
+                    text = replace_captures(&text, captures);
+                }
+                if !snippet.raw {
+                    text = expand_variables(&text, &variables)?;
+                }
+                MacroAction::Type(text)
+            }
+            MacroStep::Key(name) => MacroAction::Press(
+                SpecialKey::from_name(name).with_context(|| format!("Unknown key '{}' in macro", name))?,
+            ),
+            MacroStep::Delay(ms) => MacroAction::Wait(*ms),
+        };
+        macro_actions.push(action);
+    }
+
+    let (delete_count, deleted_trigger) = trigger_to_delete(match_result)?;
+    let text = macro_actions
+        .iter()
+        .filter_map(|action| match action {
+            MacroAction::Type(text) => Some(text.as_str()),
+            _ => None,
+        })
+        .collect();
+
+    Ok(ExpansionResult {
+        text,
+        delete_count,
+        cursor_offset: None,
+        selection_len: 0,
+        typed_trigger: deleted_trigger,
+        post_action: snippet.post_action,
+        keystroke_delay: snippet.keystroke_delay_ms,
+        output_mode: snippet.output_mode,
+        clipboard: None,
+        macro_actions,
+        transliterate_fallback: false,
     })
 }
 
+/// How many characters of the typed trigger to delete and what they were, keeping
+/// the snippet's `keep_prefix_len`
+fn trigger_to_delete(match_result: &MatchResult) -> Result<(usize, String)> {
+    let keep = match_result.snippet.keep_prefix_len;
+    if keep > match_result.chars_to_delete {
+        anyhow::bail!(
+            "keep_prefix_len ({}) is longer than the trigger '{}'",
+            keep,
+            match_result.typed_trigger
+        );
+    }
+    let deleted_trigger = match_result.typed_trigger.chars().skip(keep).collect();
+    Ok((match_result.chars_to_delete - keep, deleted_trigger))
+}
+
+/// Add the snippet's `vars` to the global variables. Each one is expanded once
+/// (against the global variables) and shadows a global variable of the same name.
+fn resolve_snippet_vars<'a>(
+    snippet: &Snippet,
+    variables: &'a serde_yaml::Value,
+) -> Result<Cow<'a, serde_yaml::Value>> {
+    if snippet.vars.is_empty() {
+        return Ok(Cow::Borrowed(variables));
+    }
+
+    let mut merged = match variables {
+        serde_yaml::Value::Mapping(map) => map.clone(),
+        _ => serde_yaml::Mapping::new(),
+    };
+    for (name, value) in &snippet.vars {
+        let value = expand_variables(value, variables)
+            .with_context(|| format!("Failed to evaluate snippet variable '{}'", name))?;
+        merged.insert(serde_yaml::Value::String(name.clone()), serde_yaml::Value::String(value));
+    }
+
+    Ok(Cow::Owned(serde_yaml::Value::Mapping(merged)))
+}
+
 /// Replace capture group references ($1, $2, etc.) with actual captured values
 fn replace_captures(text: &str, captures: &[String]) -> String {
     let mut result = text.to_string();
@@ -74,22 +213,150 @@ fn replace_captures(text: &str, captures: &[String]) -> String {
     result
 }
 
+/// Capture group numbers referenced in `text` (`$1` → 1)
+pub fn capture_refs(text: &str) -> impl Iterator<Item = usize> + '_ {
+    CAPTURE_REGEX.captures_iter(text).filter_map(|cap| cap[1].parse().ok())
+}
+
 /// Expand a snippet directly (without a match result)
 pub fn expand_snippet(snippet: &Snippet, variables: &serde_yaml::Value) -> Result<ExpansionResult> {
     let match_result = MatchResult {
         snippet: snippet.clone(),
         typed_trigger: snippet.trigger.clone(),
-        chars_to_delete: snippet.trigger.len(),
+        chars_to_delete: snippet.trigger.chars().count(),
         captures: None,
+        committed_by: None,
+        mid_word: false,
     };
     expand_match(&match_result, variables)
 }
 
+/// Expand each line of `input` as a trigger, writing the expansions to `output`.
+/// Lines that aren't a (literal) trigger, or fail to expand, are written unchanged
+/// with a warning to `warnings`. The last definition of a trigger wins, like when typing.
+pub fn expand_lines(
+    snippets: &[Snippet],
+    variables: &serde_yaml::Value,
+    input: impl BufRead,
+    mut output: impl Write,
+    mut warnings: impl Write,
+) -> Result<()> {
+    for line in input.lines() {
+        let line = line.context("Failed to read input")?;
+        let snippet = snippets.iter().rev().find(|s| !s.regex && s.trigger == line);
+
+        let text = match snippet.map(|s| expand_snippet(s, variables)) {
+            Some(Ok(expansion)) => expansion.text,
+            Some(Err(e)) => {
+                writeln!(warnings, "Warning: failed to expand '{}': {:#}", line, e)?;
+                line
+            }
+            None => {
+                writeln!(warnings, "Warning: no snippet with trigger '{}'", line)?;
+                line
+            }
+        };
+        writeln!(output, "{}", text)?;
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::config::Snippet;
 
+    #[test]
+    fn test_expand_lines() {
+        let snippets = vec![
+            Snippet::new(";sig", "old"),
+            Snippet::new(";sig", "Best,\nRafa"),
+            Snippet::new(";hi", "Hello {{name}}"),
+        ];
+        let variables: serde_yaml::Value = serde_yaml::from_str("name: World").unwrap();
+        let mut output = Vec::new();
+        let mut warnings = Vec::new();
+
+        expand_lines(&snippets, &variables, "; sig\n;sig\n;hi\n".as_bytes(), &mut output, &mut warnings).unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), "; sig\nBest,\nRafa\nHello World\n");
+        assert_eq!(String::from_utf8(warnings).unwrap(), "Warning: no snippet with trigger '; sig'\n");
+    }
+
+    #[test]
+    fn test_snippet_vars() {
+        let mut snippet = Snippet::new(";sig", "{{name}} <{{email}}>");
+        snippet.vars.insert("name".to_string(), "{{user}}, PhD".to_string());
+        let variables: serde_yaml::Value =
+            serde_yaml::from_str("user: Rafa\nemail: rafa@example.com\nname: shadowed").unwrap();
+
+        let result = expand_snippet(&snippet, &variables).unwrap();
+        assert_eq!(result.text, "Rafa, PhD <rafa@example.com>");
+    }
+
+    #[test]
+    fn test_macro_snippet() {
+        let snippet: Snippet = serde_yaml::from_str(
+            r#"
+trigger: ";form"
+replace: "ignored"
+macro_steps:
+  - text: "{{first}}"
+  - key: tab
+  - delay: 200
+  - text: "Doe"
+  - key: Enter
+"#,
+        )
+        .unwrap();
+        let variables: serde_yaml::Value = serde_yaml::from_str("first: John").unwrap();
+
+        let result = expand_snippet(&snippet, &variables).unwrap();
+        assert_eq!(
+            result.macro_actions,
+            vec![
+                MacroAction::Type("John".to_string()),
+                MacroAction::Press(SpecialKey::Tab),
+                MacroAction::Wait(200),
+                MacroAction::Type("Doe".to_string()),
+                MacroAction::Press(SpecialKey::Enter),
+            ]
+        );
+        assert_eq!(result.text, "JohnDoe");
+        assert_eq!(result.delete_count, 5);
+
+        let mut unknown = snippet.clone();
+        unknown.macro_steps = Some(vec![MacroStep::Key("hyper".to_string())]);
+        assert!(expand_snippet(&unknown, &variables).is_err());
+    }
+
+    #[test]
+    fn test_raw_snippet() {
+        let mut snippet = Snippet::new(";tpl", "Use {{date}} or {{clipboard_set:x}} in replacements");
+        snippet.raw = true;
+
+        let result = expand_snippet(&snippet, &serde_yaml::Value::Null).unwrap();
+        assert_eq!(result.text, "Use {{date}} or {{clipboard_set:x}} in replacements");
+        assert_eq!(result.clipboard, None);
+        assert_eq!(result.delete_count, 4);
+    }
+
+    #[test]
+    fn test_snippet_shell_var_evaluated_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("runs");
+        let mut snippet = Snippet::new(";n", "Start {{n}} End {{n}}");
+        snippet.vars.insert(
+            "n".to_string(),
+            format!("{{{{shell:echo run >> {0}; wc -l < {0}}}}}", log.display()),
+        );
+
+        let result = expand_snippet(&snippet, &serde_yaml::Value::Null).unwrap();
+        assert_eq!(result.text, "Start 1 End 1");
+        assert_eq!(std::fs::read_to_string(&log).unwrap(), "run\n");
+    }
+
     #[test]
     fn test_basic_expansion() {
         let snippet = Snippet::new(";test", "hello world");
@@ -98,6 +365,8 @@ mod tests {
             typed_trigger: ";test".to_string(),
             chars_to_delete: 5,
             captures: None,
+            committed_by: None,
+            mid_word: false,
         };
 
         let result = expand_match(&match_result, &serde_yaml::Value::Null).unwrap();
@@ -125,17 +394,100 @@ mod tests {
             typed_trigger: ";sig".to_string(),
             chars_to_delete: 4,
             captures: None,
+            committed_by: None,
+            mid_word: false,
         };
 
         let result = expand_match(&match_result, &serde_yaml::Value::Null).unwrap();
         assert_eq!(result.text, "Hello  World");
         assert_eq!(result.cursor_offset, Some(6)); // 6 chars from end to cursor
+        assert_eq!(result.selection_len, 0);
+    }
+
+    #[test]
+    fn test_cursor_position_multibyte() {
+        // The offset is a count of Left key presses, so it must be in characters
+        let snippet = Snippet::new(";cv", "Café: $|$ — résumé").with_cursor_position();
+        let result = expand_snippet(&snippet, &serde_yaml::Value::Null).unwrap();
+        assert_eq!(result.text, "Café:  — résumé");
+        assert_eq!(result.cursor_offset, Some(9));
+
+        let snippet = Snippet::new(";sel", "¡Hola $|$señor$|$ Müller!").with_cursor_position();
+        let result = expand_snippet(&snippet, &serde_yaml::Value::Null).unwrap();
+        assert_eq!(result.cursor_offset, Some(8));
+        assert_eq!(result.selection_len, 5);
+    }
+
+    #[test]
+    fn test_cursor_selection() {
+        let mut snippet = Snippet::new(";dear", "Dear $|$name$|$,");
+        snippet.cursor_position = true;
+
+        let result = expand_snippet(&snippet, &serde_yaml::Value::Null).unwrap();
+        assert_eq!(result.text, "Dear name,");
+        assert_eq!(result.cursor_offset, Some(1));
+        assert_eq!(result.selection_len, 4);
+
+        // Without cursor_position the markers are stripped but nothing is selected
+        snippet.cursor_position = false;
+        let result = expand_snippet(&snippet, &serde_yaml::Value::Null).unwrap();
+        assert_eq!(result.selection_len, 0);
+    }
+
+    #[test]
+    fn test_clipboard_set() {
+        let mut snippet = Snippet::new(r";key(\w+)", "Copied $1! {{clipboard_set:key-$1}}");
+        snippet.regex = true;
+        let match_result = MatchResult {
+            snippet,
+            typed_trigger: ";keyprod".to_string(),
+            chars_to_delete: 8,
+            captures: Some(vec!["prod".to_string()]),
+            committed_by: None,
+            mid_word: false,
+        };
+
+        let result = expand_match(&match_result, &serde_yaml::Value::Null).unwrap();
+        assert_eq!(result.text, "Copied prod! ");
+        assert_eq!(result.clipboard.as_deref(), Some("key-prod"));
+    }
+
+    #[test]
+    fn test_unicode_escapes() {
+        let mut snippet = Snippet::new(r";smile(\d)", r"\u{1F600} x$1 \\u{41}");
+        snippet.regex = true;
+        let match_result = MatchResult {
+            snippet,
+            typed_trigger: ";smile2".to_string(),
+            chars_to_delete: 7,
+            captures: Some(vec!["2".to_string()]),
+            committed_by: None,
+            mid_word: false,
+        };
+
+        let result = expand_match(&match_result, &serde_yaml::Value::Null).unwrap();
+        assert_eq!(result.text, r"😀 x2 \u{41}");
+    }
+
+    #[test]
+    fn test_keep_prefix() {
+        let mut snippet = Snippet::new("#todo", "TODO(rafa)");
+        snippet.keep_prefix_len = 1;
+
+        let result = expand_snippet(&snippet, &serde_yaml::Value::Null).unwrap();
+        assert_eq!(result.delete_count, 4);
+        assert_eq!(result.text, "TODO(rafa)");
+        // Only the deleted part is restored if typing fails
+        assert_eq!(result.typed_trigger, "todo");
+
+        snippet.keep_prefix_len = 6;
+        assert!(expand_snippet(&snippet, &serde_yaml::Value::Null).is_err());
     }
 
     #[test]
     fn test_case_propagation() {
         let mut snippet = Snippet::new(";email", "test@example.com");
-        snippet.propagate_case = true;
+        snippet.propagate_case = Some(true);
 
         // Test uppercase trigger
         let match_result = MatchResult {
@@ -143,6 +495,8 @@ mod tests {
             typed_trigger: ";EMAIL".to_string(),
             chars_to_delete: 6,
             captures: None,
+            committed_by: None,
+            mid_word: false,
         };
 
         let result = expand_match(&match_result, &serde_yaml::Value::Null).unwrap();
@@ -159,6 +513,8 @@ mod tests {
             typed_trigger: ";test".to_string(),
             chars_to_delete: 5,
             captures: None,
+            committed_by: None,
+            mid_word: false,
         };
 
         let result = expand_match(&match_result, &serde_yaml::Value::Null).unwrap();
@@ -175,6 +531,8 @@ mod tests {
             typed_trigger: ";d456".to_string(),
             chars_to_delete: 5,
             captures: Some(vec!["456".to_string()]),
+            committed_by: None,
+            mid_word: false,
         };
 
         let result = expand_match(&match_result, &serde_yaml::Value::Null).unwrap();
@@ -0,0 +1,62 @@
+use anyhow::{Context, Result};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::Command;
+
+/// How long an `on_expand_command` may run before it is killed
+const ON_EXPAND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Run a snippet's `on_expand_command` in the background. Typing never waits for
+/// it; failures are only logged.
+pub fn spawn_on_expand(command: String, trigger: String, text: String) {
+    log::info!("Running on_expand_command for '{}'", trigger);
+    tokio::spawn(async move {
+        match run_on_expand(&command, &trigger, &text).await {
+            Ok(stdout) if !stdout.is_empty() => log::debug!("on_expand_command output: {}", stdout),
+            Ok(_) => {}
+            Err(e) => log::warn!("on_expand_command for '{}' failed: {:#}", trigger, e),
+        }
+    });
+}
+
+/// Run `command` through the shell with the expansion in `XPANDER_TRIGGER` and
+/// `XPANDER_TEXT`, returning its stdout
+async fn run_on_expand(command: &str, trigger: &str, text: &str) -> Result<String> {
+    let child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("XPANDER_TRIGGER", trigger)
+        .env("XPANDER_TEXT", text)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| format!("Failed to run: {}", command))?;
+
+    let output = tokio::time::timeout(ON_EXPAND_TIMEOUT, child.wait_with_output())
+        .await
+        .with_context(|| format!("Timed out after {:?}, killed", ON_EXPAND_TIMEOUT))??;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("exited with {}: {}", output.status, stderr.trim());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim_end().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_on_expand_env() {
+        let stdout = run_on_expand(r#"printf '%s|%s' "$XPANDER_TRIGGER" "$XPANDER_TEXT""#, ";sig", "Best,\nRafa")
+            .await
+            .unwrap();
+        assert_eq!(stdout, ";sig|Best,\nRafa");
+
+        assert!(run_on_expand("exit 3", ";sig", "").await.is_err());
+    }
+}
@@ -0,0 +1,81 @@
+/// Modifier keys held down while a key is pressed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Modifiers {
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+    pub super_key: bool,
+}
+
+/// A global hotkey such as "ctrl+alt+s"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hotkey {
+    pub modifiers: Modifiers,
+    /// The (unshifted, lowercase) character of the non-modifier key
+    pub key: char,
+}
+
+impl Hotkey {
+    /// Parse a hotkey string like "ctrl+alt+s" or "Super+Shift+X"
+    pub fn parse(spec: &str) -> Option<Self> {
+        let mut modifiers = Modifiers::default();
+        let mut key = None;
+
+        for part in spec.split('+').map(|p| p.trim().to_lowercase()) {
+            match part.as_str() {
+                "ctrl" | "control" => modifiers.ctrl = true,
+                "alt" => modifiers.alt = true,
+                "shift" => modifiers.shift = true,
+                "super" | "meta" | "win" => modifiers.super_key = true,
+                other => {
+                    let mut chars = other.chars();
+                    match (chars.next(), chars.next(), key) {
+                        (Some(ch), None, None) => key = Some(ch),
+                        _ => return None,
+                    }
+                }
+            }
+        }
+
+        // Require at least one modifier so the hotkey can't swallow normal typing
+        if modifiers == Modifiers::default() {
+            return None;
+        }
+
+        key.map(|key| Self { modifiers, key })
+    }
+
+    /// Check whether a key press (mapped to its unshifted character) triggers this hotkey
+    pub fn matches(&self, key: char, modifiers: Modifiers) -> bool {
+        self.key == key.to_ascii_lowercase() && self.modifiers == modifiers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hotkey() {
+        let hotkey = Hotkey::parse("Ctrl+Alt+S").unwrap();
+        assert!(hotkey.modifiers.ctrl);
+        assert!(hotkey.modifiers.alt);
+        assert!(!hotkey.modifiers.shift);
+        assert_eq!(hotkey.key, 's');
+
+        // No modifier or no key is invalid
+        assert!(Hotkey::parse("s").is_none());
+        assert!(Hotkey::parse("ctrl+alt").is_none());
+        assert!(Hotkey::parse("ctrl+ab").is_none());
+    }
+
+    #[test]
+    fn test_hotkey_matches() {
+        let hotkey = Hotkey::parse("ctrl+alt+s").unwrap();
+        let mods = Modifiers { ctrl: true, alt: true, ..Default::default() };
+
+        assert!(hotkey.matches('s', mods));
+        assert!(!hotkey.matches('s', Modifiers { ctrl: true, ..Default::default() }));
+        assert!(!hotkey.matches('d', mods));
+    }
+}
@@ -1,9 +1,29 @@
 use evdev::Key;
 use std::collections::HashMap;
+use unicode_normalization::UnicodeNormalization;
+
+/// Numpad keys that type only while NumLock is on (otherwise they move the cursor)
+const NUMLOCK_KEYS: [(Key, char); 11] = [
+    (Key::KEY_KP0, '0'), (Key::KEY_KP1, '1'), (Key::KEY_KP2, '2'), (Key::KEY_KP3, '3'),
+    (Key::KEY_KP4, '4'), (Key::KEY_KP5, '5'), (Key::KEY_KP6, '6'), (Key::KEY_KP7, '7'),
+    (Key::KEY_KP8, '8'), (Key::KEY_KP9, '9'), (Key::KEY_KPDOT, '.'),
+];
+
+/// Numpad operators, which type the same regardless of NumLock
+const NUMPAD_OPERATORS: [(Key, char); 4] = [
+    (Key::KEY_KPSLASH, '/'), (Key::KEY_KPASTERISK, '*'), (Key::KEY_KPMINUS, '-'), (Key::KEY_KPPLUS, '+'),
+];
+
+/// Accents typed by dead keys, with the combining mark they add to the next letter
+const DEAD_KEY_ACCENTS: [(char, char); 5] = [
+    ('^', '\u{302}'), ('¨', '\u{308}'), ('`', '\u{300}'), ('´', '\u{301}'), ('~', '\u{303}'),
+];
 
 pub struct KeyMap {
     pub normal: HashMap<Key, char>,
     pub shifted: HashMap<Key, char>,
+    /// Dead keys of the layout, by key and Shift state, with the accent they add
+    pub dead_keys: HashMap<(Key, bool), char>,
 }
 
 impl KeyMap {
@@ -73,7 +93,12 @@ impl KeyMap {
             _ => {} // Default to QWERTY
         }
 
-        Self { normal, shifted }
+        Self { normal, shifted, dead_keys: layout_dead_keys(layout) }
+    }
+
+    /// The accent if `key` is a dead key on this layout
+    pub fn map_dead_key(&self, key: Key, shift: bool) -> Option<char> {
+        self.dead_keys.get(&(key, shift)).copied()
     }
 
     pub fn map_key(&self, key: Key, shift: bool, caps_lock: bool) -> Option<char> {
@@ -96,6 +121,20 @@ impl KeyMap {
             }
         })
     }
+
+    /// Map a numpad key, which is the same on every layout. Digits and the decimal
+    /// point type only while NumLock is on; Shift inverts NumLock, as in X11.
+    pub fn map_numpad(&self, key: Key, shift: bool, num_lock: bool) -> Option<char> {
+        if let Some(&(_, ch)) = NUMPAD_OPERATORS.iter().find(|(k, _)| *k == key) {
+            return Some(ch);
+        }
+
+        NUMLOCK_KEYS
+            .iter()
+            .find(|(k, _)| *k == key)
+            .filter(|_| num_lock != shift)
+            .map(|&(_, ch)| ch)
+    }
 }
 
 fn apply_azerty(normal: &mut HashMap<Key, char>, shifted: &mut HashMap<Key, char>) {
@@ -155,6 +194,73 @@ fn apply_dvorak(_normal: &mut HashMap<Key, char>, _shifted: &mut HashMap<Key, ch
     // Placeholder
 }
 
+/// Dead keys of the layouts that have them. US QWERTY has none, so nothing changes there.
+fn layout_dead_keys(layout: &str) -> HashMap<(Key, bool), char> {
+    let keys: &[(Key, bool, char)] = match layout.to_lowercase().as_str() {
+        // French: circumflex and diaeresis right of P
+        "azerty" => &[(Key::KEY_LEFTBRACE, false, '^'), (Key::KEY_LEFTBRACE, true, '¨')],
+        // German: circumflex left of 1, acute and grave right of ß
+        "qwertz" => &[
+            (Key::KEY_GRAVE, false, '^'),
+            (Key::KEY_EQUAL, false, '´'),
+            (Key::KEY_EQUAL, true, '`'),
+        ],
+        _ => &[],
+    };
+    keys.iter().map(|&(key, shift, accent)| ((key, shift), accent)).collect()
+}
+
+/// Combines a dead key with the character typed after it, like the compositor does,
+/// so `^` then `e` is seen as `ê`
+#[derive(Debug, Default)]
+pub struct DeadKeys {
+    pending: Option<char>,
+}
+
+impl DeadKeys {
+    /// A dead key was pressed. Returns what it types right away: its accent if it was
+    /// pressed twice, or the previous accent if another dead key was pending.
+    pub fn press(&mut self, accent: char) -> Option<char> {
+        match self.pending.replace(accent) {
+            Some(previous) if previous == accent => {
+                self.pending = None;
+                Some(accent)
+            }
+            previous => previous,
+        }
+    }
+
+    /// A character was typed. Returns what is typed: the composed character, or the
+    /// pending accent followed by `ch` if they don't compose (a space types the accent alone).
+    pub fn compose(&mut self, ch: char) -> Vec<char> {
+        let Some(accent) = self.pending.take() else {
+            return vec![ch];
+        };
+        if ch == ' ' {
+            return vec![accent];
+        }
+        match compose_accent(accent, ch) {
+            Some(composed) => vec![composed],
+            None => vec![accent, ch],
+        }
+    }
+
+    /// A key that doesn't type (Backspace, Escape, arrows) drops the pending dead key
+    pub fn cancel(&mut self) {
+        self.pending = None;
+    }
+}
+
+/// `ch` with `accent` on it, if Unicode has a precomposed character for it
+fn compose_accent(accent: char, ch: char) -> Option<char> {
+    let (_, mark) = DEAD_KEY_ACCENTS.iter().find(|(a, _)| *a == accent)?;
+    let mut composed = [ch, *mark].into_iter().nfc();
+    match (composed.next(), composed.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
 fn swap_keys(normal: &mut HashMap<Key, char>, shifted: &mut HashMap<Key, char>, k1: Key, k2: Key) {
     if let (Some(n1), Some(n2)) = (normal.get(&k1).copied(), normal.get(&k2).copied()) {
         normal.insert(k1, n2);
@@ -170,3 +276,55 @@ fn setup_key(normal: &mut HashMap<Key, char>, shifted: &mut HashMap<Key, char>,
     normal.insert(k, n);
     shifted.insert(k, s);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_numpad_keys() {
+        let map = KeyMap::new("azerty");
+
+        assert_eq!(map.map_numpad(Key::KEY_KP1, false, true), Some('1'));
+        assert_eq!(map.map_numpad(Key::KEY_KPDOT, false, true), Some('.'));
+        // NumLock off: digits are navigation keys, operators still type
+        assert_eq!(map.map_numpad(Key::KEY_KP1, false, false), None);
+        assert_eq!(map.map_numpad(Key::KEY_KPPLUS, false, false), Some('+'));
+        // Shift inverts NumLock
+        assert_eq!(map.map_numpad(Key::KEY_KP7, true, true), None);
+        assert_eq!(map.map_numpad(Key::KEY_KP7, true, false), Some('7'));
+
+        assert_eq!(map.map_numpad(Key::KEY_A, false, true), None);
+    }
+
+    #[test]
+    fn test_dead_keys() {
+        let azerty = KeyMap::new("azerty");
+        assert_eq!(azerty.map_dead_key(Key::KEY_LEFTBRACE, false), Some('^'));
+        assert_eq!(azerty.map_dead_key(Key::KEY_LEFTBRACE, true), Some('¨'));
+        assert_eq!(KeyMap::new("qwertz").map_dead_key(Key::KEY_EQUAL, false), Some('´'));
+        assert_eq!(KeyMap::new("qwerty").map_dead_key(Key::KEY_LEFTBRACE, false), None);
+
+        let mut dead = DeadKeys::default();
+        assert_eq!(dead.press('^'), None);
+        assert_eq!(dead.compose('e'), vec!['ê']);
+        assert_eq!(dead.press('¨'), None);
+        assert_eq!(dead.compose('U'), vec!['Ü']);
+        assert_eq!(dead.press('´'), None);
+        assert_eq!(dead.compose('a'), vec!['á']);
+        // Nothing to compose: the accent is typed before the character
+        assert_eq!(dead.press('^'), None);
+        assert_eq!(dead.compose('x'), vec!['^', 'x']);
+        assert_eq!(dead.compose('e'), vec!['e']);
+        // Space or the dead key again types the accent itself
+        dead.press('^');
+        assert_eq!(dead.compose(' '), vec!['^']);
+        dead.press('^');
+        assert_eq!(dead.press('^'), Some('^'));
+        assert_eq!(dead.compose('e'), vec!['e']);
+
+        dead.press('^');
+        dead.cancel();
+        assert_eq!(dead.compose('e'), vec!['e']);
+    }
+}
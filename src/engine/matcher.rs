@@ -1,9 +1,14 @@
 use regex::Regex;
-use std::collections::HashMap;
+use std::collections::HashSet;
+use unicode_normalization::UnicodeNormalization;
 
 use crate::config::Snippet;
 use crate::engine::trie::Trie;
 
+/// Snippet edits touching more than this many snippets rebuild the matcher
+/// instead of patching it
+const MAX_INCREMENTAL_CHANGES: usize = 32;
+
 /// Result of a trigger match
 #[derive(Debug, Clone)]
 pub struct MatchResult {
@@ -15,6 +20,58 @@ pub struct MatchResult {
     pub chars_to_delete: usize,
     /// Regex capture groups (if regex trigger)
     pub captures: Option<Vec<String>>,
+    /// The space or punctuation typed after a `whole_word` trigger, which made it
+    /// fire. It is not part of `chars_to_delete`.
+    pub committed_by: Option<char>,
+    /// The match starts inside a word, right after a letter or digit
+    pub mid_word: bool,
+}
+
+/// A trigger that also matches at the end of a longer trigger
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SuffixConflict {
+    /// The shorter trigger, which has `word_boundary` off
+    pub trigger: String,
+    /// The longer trigger that ends with it
+    pub longer: String,
+}
+
+/// Outcome of trying a regex trigger on sample text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegexTest {
+    /// The pattern doesn't compile
+    Invalid(String),
+    /// The sample doesn't end with a match
+    NoMatch,
+    /// The sample ends with `text`; `captures` are the groups `$1`, `$2`, ...
+    /// (empty for groups that didn't take part in the match)
+    Matched { text: String, captures: Vec<String> },
+}
+
+/// Compile a regex trigger. It is anchored at the end, since the trigger must be
+/// what was just typed.
+pub fn compile_regex_trigger(pattern: &str) -> Result<Regex, regex::Error> {
+    Regex::new(&format!("(?:{})$", pattern))
+}
+
+/// Try a regex trigger against sample text, as if the sample had just been typed
+pub fn try_regex_trigger(pattern: &str, sample: &str) -> RegexTest {
+    let regex = match compile_regex_trigger(pattern) {
+        Ok(regex) => regex,
+        Err(e) => return RegexTest::Invalid(e.to_string()),
+    };
+
+    match regex.captures(sample) {
+        Some(caps) => RegexTest::Matched {
+            text: caps[0].to_string(),
+            captures: caps
+                .iter()
+                .skip(1)
+                .map(|m| m.map_or(String::new(), |m| m.as_str().to_string()))
+                .collect(),
+        },
+        None => RegexTest::NoMatch,
+    }
 }
 
 /// Maintains a buffer of typed text and matches against triggers
@@ -25,12 +82,28 @@ pub struct Matcher {
     max_buffer_size: usize,
     /// Trie for efficient literal matching
     trie: Trie,
-    /// List of regex snippets (checked linearly)
-    regex_snippets: Vec<Snippet>,
-    /// Cache for compiled regex patterns
-    regex_cache: HashMap<String, Regex>,
+    /// Lowercased triggers of propagate_case snippets, so ";EMAIL" matches ";email"
+    case_insensitive_trie: Trie,
+    /// Triggers of ignore_diacritics snippets with accents removed, so "cafe" matches "café"
+    folded_trie: Trie,
+    /// Triggers of snippets with both options, lowercased with accents removed
+    folded_case_insensitive_trie: Trie,
+    /// Regex snippets with their patterns compiled at reload (checked linearly)
+    regex_snippets: Vec<(Snippet, Regex)>,
     /// Whether we're at a word boundary (for word_boundary triggers)
     at_word_boundary: bool,
+    /// Whether the start of the buffer is the start of a line (for line_start triggers).
+    /// False once older text has been trimmed off the front.
+    buffer_at_line_start: bool,
+    /// Whether the start of the buffer is the start of a word (for word_boundary
+    /// triggers and `MatchResult::mid_word`)
+    buffer_at_word_start: bool,
+    /// Triggers defined by more than one enabled snippet (only the last one fires)
+    duplicate_triggers: Vec<String>,
+    /// Triggers that are a suffix of another trigger and could fire inside other words
+    suffix_conflicts: Vec<SuffixConflict>,
+    /// Triggers whose replacement has a `$|$` marker while `cursor_position` is off
+    unused_cursor_markers: Vec<String>,
 }
 
 impl Matcher {
@@ -40,9 +113,16 @@ impl Matcher {
             buffer: String::with_capacity(256),
             max_buffer_size: 256,
             trie: Trie::new(),
+            case_insensitive_trie: Trie::new(),
+            folded_trie: Trie::new(),
+            folded_case_insensitive_trie: Trie::new(),
             regex_snippets: Vec::new(),
-            regex_cache: HashMap::new(),
             at_word_boundary: true, // Start of input is a word boundary
+            buffer_at_line_start: true,
+            buffer_at_word_start: true,
+            duplicate_triggers: Vec::new(),
+            suffix_conflicts: Vec::new(),
+            unused_cursor_markers: Vec::new(),
         }
     }
 
@@ -51,12 +131,17 @@ impl Matcher {
         self.buffer.push(ch);
 
         // Update word boundary status
-        self.at_word_boundary = ch.is_whitespace() || ch.is_ascii_punctuation();
+        self.at_word_boundary = is_word_boundary(ch);
 
-        // Trim buffer if too long
+        // Trim buffer if too long (on a char boundary)
         if self.buffer.len() > self.max_buffer_size {
-            let drain_to = self.buffer.len() - self.max_buffer_size / 2;
-            self.buffer.drain(..drain_to);
+            let mut drain_to = self.buffer.len() - self.max_buffer_size / 2;
+            while !self.buffer.is_char_boundary(drain_to) {
+                drain_to += 1;
+            }
+            let trimmed: String = self.buffer.drain(..drain_to).collect();
+            self.buffer_at_line_start = false;
+            self.buffer_at_word_start = trimmed.chars().next_back().is_some_and(is_word_boundary);
         }
     }
 
@@ -69,117 +154,311 @@ impl Matcher {
     pub fn clear(&mut self) {
         self.buffer.clear();
         self.at_word_boundary = true;
+        self.buffer_at_line_start = true;
+        self.buffer_at_word_start = true;
+    }
+
+    /// Clear the buffer after `text` was typed just before the cursor (an
+    /// expansion), so the next trigger starts a word or a line only if `text`
+    /// ends one
+    pub fn clear_after(&mut self, text: &str) {
+        self.clear();
+        if let Some(last) = text.chars().next_back() {
+            self.at_word_boundary = is_word_boundary(last);
+            self.buffer_at_word_start = is_word_boundary(last);
+            self.buffer_at_line_start = last == '\n';
+        }
     }
 
     /// Remove the last N characters from the buffer (after a match)
     pub fn remove_last(&mut self, n: usize) {
-        let new_len = self.buffer.len().saturating_sub(n);
-        self.buffer.truncate(new_len);
+        for _ in 0..n {
+            if self.buffer.pop().is_none() {
+                break;
+            }
+        }
+    }
+
+    /// Whether a trigger preceded by `char_before` starts a word
+    fn is_word_start(&self, char_before: Option<char>) -> bool {
+        char_before.map_or(self.buffer_at_word_start, is_word_boundary)
+    }
+
+    /// Whether a trigger preceded by `char_before` starts at the beginning of a line
+    fn is_line_start(&self, char_before: Option<char>) -> bool {
+        match char_before {
+            Some(ch) => ch == '\n',
+            None => self.buffer_at_line_start,
+        }
     }
 
     /// Reload snippets into the Trie and regex list
     pub fn reload(&mut self, snippets: Vec<Snippet>) {
         self.trie = Trie::new();
+        self.case_insensitive_trie = Trie::new();
+        self.folded_trie = Trie::new();
+        self.folded_case_insensitive_trie = Trie::new();
         self.regex_snippets.clear();
-        self.regex_cache.clear();
 
-        for snippet in snippets {
-            if !snippet.enabled {
+        for snippet in snippets.iter().filter(|s| s.enabled) {
+            if snippet.regex {
+                match compile_regex_trigger(&snippet.trigger) {
+                    Ok(regex) => self.regex_snippets.push((snippet.clone(), regex)),
+                    Err(e) => log::error!("Invalid regex pattern '{}': {}", snippet.trigger, e),
+                }
                 continue;
             }
 
-            if snippet.regex {
-                self.regex_snippets.push(snippet);
-            } else {
-                self.trie.insert(snippet);
+            for (trie, key) in self.tries_mut().into_iter().zip(trie_keys(snippet)) {
+                if let Some(key) = key {
+                    trie.insert_as(&key, snippet.clone());
+                }
             }
         }
+
+        self.find_conflicts(&snippets);
     }
 
-    /// Check if any snippet matches the current buffer
-    pub fn check_match(&mut self) -> Option<MatchResult> {
-        // 1. Check Trie (O(L))
-        if let Some((snippet, len)) = self.trie.find_match(&self.buffer) {
-            // Verify word boundary if required
-            let valid = if snippet.word_boundary {
-                let buffer_len = self.buffer.len();
-                if buffer_len > len {
-                    let char_before_start = self.buffer.chars().nth(buffer_len - len - 1);
-                    if let Some(ch) = char_before_start {
-                        ch.is_whitespace() || ch.is_ascii_punctuation()
-                    } else {
-                        true
+    /// Load `new` in place of the `old` snippets loaded before. A small edit, like
+    /// one snippet changed in the GUI, patches the tries instead of rebuilding them;
+    /// larger edits and edits to regex snippets fall back to `reload`.
+    pub fn update(&mut self, old: &[Snippet], new: Vec<Snippet>) {
+        // The snippets between the unchanged head and tail are the edit
+        let head = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+        let tail = old[head..]
+            .iter()
+            .rev()
+            .zip(new[head..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let removed = &old[head..old.len() - tail];
+        let added = &new[head..new.len() - tail];
+
+        if removed.len() + added.len() > MAX_INCREMENTAL_CHANGES || removed.iter().chain(added).any(|s| s.regex) {
+            self.reload(new);
+            return;
+        }
+
+        match (removed, added) {
+            // One snippet edited without touching its keys: swap it where it is stored,
+            // unless it was shadowed by another definition there
+            ([before], [after])
+                if before.enabled
+                    && after.enabled
+                    && trie_keys(before) == trie_keys(after)
+                    && !self.duplicate_triggers.contains(&before.trigger) =>
+            {
+                for (trie, key) in self.tries_mut().into_iter().zip(trie_keys(after)) {
+                    if let Some(key) = key.filter(|key| trie.get(key) == Some(before)) {
+                        trie.update(&key, after.clone());
                     }
-                } else {
-                    true // Start of buffer
                 }
-            } else {
-                true
-            };
+            }
+            _ => {
+                // Drop every key a changed snippet is or was stored under, then insert
+                // again, in order, all snippets stored under those keys, so the last
+                // definition of a trigger still wins
+                let mut affected: [HashSet<String>; 4] = Default::default();
+                for snippet in removed.iter().chain(added).filter(|s| s.enabled) {
+                    for (keys, key) in affected.iter_mut().zip(trie_keys(snippet)) {
+                        keys.extend(key);
+                    }
+                }
+                for (trie, keys) in self.tries_mut().into_iter().zip(&affected) {
+                    for key in keys {
+                        trie.remove(key);
+                    }
+                }
+                for snippet in new.iter().filter(|s| s.enabled && !s.regex) {
+                    for ((trie, keys), key) in self.tries_mut().into_iter().zip(&affected).zip(trie_keys(snippet)) {
+                        if let Some(key) = key.filter(|key| keys.contains(key)) {
+                            trie.insert_as(&key, snippet.clone());
+                        }
+                    }
+                }
+            }
+        }
+
+        self.find_conflicts(&new);
+    }
+
+    /// The literal tries, in the order of `trie_keys`
+    fn tries_mut(&mut self) -> [&mut Trie; 4] {
+        [
+            &mut self.trie,
+            &mut self.case_insensitive_trie,
+            &mut self.folded_trie,
+            &mut self.folded_case_insensitive_trie,
+        ]
+    }
+
+    /// Record triggers defined more than once, triggers that end another one and
+    /// snippets with a cursor marker that does nothing
+    fn find_conflicts(&mut self, snippets: &[Snippet]) {
+        let literal_triggers: Vec<String> = snippets
+            .iter()
+            .filter(|s| s.enabled && !s.regex)
+            .map(|s| s.trigger.clone())
+            .collect();
+
+        self.duplicate_triggers.clear();
+        let mut seen = HashSet::new();
+        for trigger in &literal_triggers {
+            if !seen.insert(trigger) && !self.duplicate_triggers.contains(trigger) {
+                self.duplicate_triggers.push(trigger.clone());
+            }
+        }
+        if !self.duplicate_triggers.is_empty() {
+            log::warn!(
+                "Duplicate triggers (only the last definition will fire): {}",
+                self.duplicate_triggers.join(", ")
+            );
+        }
+
+        self.suffix_conflicts.clear();
+        self.find_suffix_conflicts(&literal_triggers);
 
-            if valid {
+        self.unused_cursor_markers = snippets
+            .iter()
+            .filter(|s| s.enabled && s.has_unused_cursor_marker())
+            .map(|s| s.trigger.clone())
+            .collect();
+        if !self.unused_cursor_markers.is_empty() {
+            log::warn!(
+                "Replacements contain a $|$ cursor marker but cursor_position is off, so the cursor stays at the end: {}",
+                self.unused_cursor_markers.join(", ")
+            );
+        }
+    }
+
+    /// Record triggers that end another trigger. Matching is suffix-based, so such a
+    /// trigger also fires at the end of longer words unless `word_boundary` is set.
+    fn find_suffix_conflicts(&mut self, triggers: &[String]) {
+        for longer in triggers {
+            for snippet in self.trie.suffixes_of(longer) {
+                let conflict = SuffixConflict {
+                    trigger: snippet.trigger.clone(),
+                    longer: longer.clone(),
+                };
+                if !snippet.needs_word_boundary() && !self.suffix_conflicts.contains(&conflict) {
+                    self.suffix_conflicts.push(conflict);
+                }
+            }
+        }
+
+        for conflict in &self.suffix_conflicts {
+            log::warn!(
+                "Trigger '{}' also matches the end of '{}'; enable word_boundary on it so it only fires as a whole word",
+                conflict.trigger,
+                conflict.longer
+            );
+        }
+    }
+
+    /// Triggers that were defined more than once in the last reload
+    pub fn duplicate_triggers(&self) -> &[String] {
+        &self.duplicate_triggers
+    }
+
+    /// Triggers found in the last reload that end another trigger
+    pub fn suffix_conflicts(&self) -> &[SuffixConflict] {
+        &self.suffix_conflicts
+    }
+
+    /// Triggers found in the last reload whose `$|$` marker is ignored because
+    /// `cursor_position` is off
+    pub fn unused_cursor_markers(&self) -> &[String] {
+        &self.unused_cursor_markers
+    }
+
+    /// Check if any snippet matches the current buffer
+    pub fn check_match(&self) -> Option<MatchResult> {
+        if let Some(result) = self.match_at_end(&self.buffer, false) {
+            return Some(result);
+        }
+
+        // A whole_word trigger fires once a boundary is typed after it
+        let mut before = self.buffer.chars();
+        let committed_by = before.next_back().filter(|&ch| is_word_boundary(ch))?;
+        let mut result = self.match_at_end(before.as_str(), true)?;
+        result.committed_by = Some(committed_by);
+        Some(result)
+    }
+
+    /// Find a snippet whose trigger ends `buffer`, considering only `whole_word`
+    /// snippets or only the others
+    fn match_at_end(&self, buffer: &str, whole_word: bool) -> Option<MatchResult> {
+        // 1. Check Trie (O(L)), then case-insensitively for propagate_case snippets
+        if let Some(result) = self.check_trie_match(&self.trie, buffer, buffer, whole_word) {
+            return Some(result);
+        }
+        let lowered = lowercase_chars(buffer);
+        if let Some(result) = self.check_trie_match(&self.case_insensitive_trie, &lowered, buffer, whole_word) {
+            return Some(result);
+        }
+        // Most configs have no ignore_diacritics snippets, so skip folding the buffer
+        if !self.folded_trie.is_empty() {
+            let folded = strip_diacritics(buffer);
+            if let Some(result) = self.check_trie_match(&self.folded_trie, &folded, buffer, whole_word) {
+                return Some(result);
+            }
+            if !self.folded_case_insensitive_trie.is_empty() {
+                let folded_lowered = lowercase_chars(&folded);
+                if let Some(result) =
+                    self.check_trie_match(&self.folded_case_insensitive_trie, &folded_lowered, buffer, whole_word)
+                {
+                    return Some(result);
+                }
+            }
+        }
+
+        // 2. Check Regex snippets (O(N) but only for regex ones)
+        self.regex_snippets
+            .iter()
+            .filter(|(snippet, _)| snippet.whole_word == whole_word)
+            .find_map(|(snippet, regex)| self.check_regex_match(snippet, regex, buffer))
+    }
+
+    /// Check a trie against `text` (`buffer`, possibly lowercased or with accents
+    /// removed - same char count)
+    fn check_trie_match(&self, trie: &Trie, text: &str, buffer: &str, whole_word: bool) -> Option<MatchResult> {
+        if let Some((snippet, len)) = trie.find_match(text) {
+            let char_before = char_before(buffer, len);
+            // Verify word boundary if required
+            let valid = !snippet.needs_word_boundary() || self.is_word_start(char_before);
+            let valid = valid && (!snippet.line_start || self.is_line_start(char_before));
+
+            if valid && snippet.whole_word == whole_word {
+                // Report what was actually typed, so case can be propagated
+                let skip = buffer.chars().count() - len;
                 return Some(MatchResult {
                     snippet: snippet.clone(),
-                    typed_trigger: snippet.trigger.clone(),
+                    typed_trigger: buffer.chars().skip(skip).collect(),
                     chars_to_delete: len,
                     captures: None,
+                    committed_by: None,
+                    mid_word: !self.is_word_start(char_before),
                 });
             }
         }
 
-        // 2. Check Regex snippets (O(N) but only for regex ones)
-        // We need to clone the snippets to iterate because check_regex_match borrows self mutably
-        // This is a bit annoying. Alternatively, we can inline check_regex_match logic or use RefCell.
-        // Or, we iterate indices.
-        // Actually, check_regex_match only needs &self for buffer and &mut self for cache.
-        // If we split the cache out, it would be easier.
-        // Let's just clone the regex snippets for now, or use a loop with manual indexing?
-        // Cloning Vec<Snippet> is expensive? No, we just need to iterate.
-        // Let's copy the needed logic here or refactor check_regex_match to split borrows.
-        
-        let regex_snippets = self.regex_snippets.clone();
-        for snippet in &regex_snippets {
-             if let Some(result) = self.check_regex_match(snippet) {
-                 return Some(result);
-             }
-        }
-        
         None
     }
 
-    /// Check for a regex trigger match
-    fn check_regex_match(&mut self, snippet: &Snippet) -> Option<MatchResult> {
-        // Get or compile the regex
-        let regex = if let Some(regex) = self.regex_cache.get(&snippet.trigger) {
-            regex
-        } else {
-            // Compile and cache the regex
-            let pattern = format!("(?:{})$", snippet.trigger);
-
-            match Regex::new(&pattern) {
-                Ok(regex) => {
-                    self.regex_cache.insert(snippet.trigger.clone(), regex);
-                    self.regex_cache.get(&snippet.trigger).unwrap()
-                }
-                Err(e) => {
-                    log::error!("Invalid regex pattern '{}': {}", snippet.trigger, e);
-                    return None;
-                }
-            }
-        };
-
+    /// Check for a regex trigger match at the end of `buffer`
+    fn check_regex_match(&self, snippet: &Snippet, regex: &Regex, buffer: &str) -> Option<MatchResult> {
         // Check for match at end of buffer
-        if let Some(caps) = regex.captures(&self.buffer) {
+        if let Some(caps) = regex.captures(buffer) {
             let full_match = caps.get(0)?;
+            let char_before = buffer[..full_match.start()].chars().next_back();
 
             // If word boundary required, check position
-            if snippet.word_boundary && full_match.start() > 0 {
-                let char_before = self.buffer.chars().nth(full_match.start() - 1);
-                if let Some(ch) = char_before {
-                    if !ch.is_whitespace() && !ch.is_ascii_punctuation() {
-                        return None;
-                    }
-                }
+            if snippet.needs_word_boundary() && !self.is_word_start(char_before) {
+                return None;
+            }
+
+            if snippet.line_start && !self.is_line_start(char_before) {
+                return None;
             }
 
             // Collect capture groups
@@ -192,8 +471,10 @@ impl Matcher {
             Some(MatchResult {
                 snippet: snippet.clone(),
                 typed_trigger: full_match.as_str().to_string(),
-                chars_to_delete: full_match.len(),
+                chars_to_delete: full_match.as_str().chars().count(),
                 captures: if captures.is_empty() { None } else { Some(captures) },
+                committed_by: None,
+                mid_word: !self.is_word_start(char_before),
             })
         } else {
             None
@@ -206,6 +487,47 @@ impl Matcher {
     }
 }
 
+/// Whether `ch` separates words (for word_boundary and whole_word triggers)
+fn is_word_boundary(ch: char) -> bool {
+    ch.is_whitespace() || ch.is_ascii_punctuation()
+}
+
+/// The character just before the last `trigger_chars` characters of `buffer`.
+/// Triggers may contain spaces or newlines, so this counts chars rather than words.
+fn char_before(buffer: &str, trigger_chars: usize) -> Option<char> {
+    buffer.chars().rev().nth(trigger_chars)
+}
+
+/// The keys a literal snippet is stored under in the matcher's tries (trigger,
+/// case-insensitive, diacritic-insensitive, both), `None` where it isn't stored
+fn trie_keys(snippet: &Snippet) -> [Option<String>; 4] {
+    let folded = snippet
+        .ignore_diacritics
+        .then(|| strip_diacritics(&snippet.trigger.nfc().collect::<String>()));
+    let case_insensitive = snippet.propagates_case();
+    [
+        Some(snippet.trigger.clone()),
+        case_insensitive.then(|| lowercase_chars(&snippet.trigger)),
+        folded.clone(),
+        folded.filter(|_| case_insensitive).map(|key| lowercase_chars(&key)),
+    ]
+}
+
+/// Lowercase each char individually, keeping the char count unchanged
+fn lowercase_chars(text: &str) -> String {
+    text.chars()
+        .map(|c| c.to_lowercase().next().unwrap_or(c))
+        .collect()
+}
+
+/// Replace each char with its base letter ("é" becomes "e"), keeping the char count
+/// unchanged. Chars without a canonical decomposition (like "ø") are kept.
+fn strip_diacritics(text: &str) -> String {
+    text.chars()
+        .map(|c| std::iter::once(c).nfd().next().unwrap_or(c))
+        .collect()
+}
+
 impl Default for Matcher {
     fn default() -> Self {
         Self::new()
@@ -220,6 +542,20 @@ mod tests {
         Snippet::new(trigger, replace)
     }
 
+    #[test]
+    fn test_try_regex_trigger() {
+        assert_eq!(
+            try_regex_trigger(r";n(\d+)(x)?", "total ;n42"),
+            RegexTest::Matched {
+                text: ";n42".to_string(),
+                captures: vec!["42".to_string(), String::new()],
+            }
+        );
+        // Only a match at the end of the sample counts
+        assert_eq!(try_regex_trigger(r";n(\d+)", ";n42 later"), RegexTest::NoMatch);
+        assert!(matches!(try_regex_trigger(";n(", ";n"), RegexTest::Invalid(_)));
+    }
+
     #[test]
     fn test_basic_match() {
         let mut matcher = Matcher::new();
@@ -258,7 +594,7 @@ mod tests {
     fn test_word_boundary() {
         let mut matcher = Matcher::new();
         let mut snippet = make_snippet("btw", "by the way");
-        snippet.word_boundary = true;
+        snippet.word_boundary = Some(true);
         let snippets = vec![snippet];
         matcher.reload(snippets);
 
@@ -276,6 +612,44 @@ mod tests {
         assert!(matcher.check_match().is_some());
     }
 
+    #[test]
+    fn test_whole_word() {
+        let mut matcher = Matcher::new();
+        let mut snippet = make_snippet("ok", "OK");
+        snippet.whole_word = true;
+        matcher.reload(vec![snippet]);
+
+        let typed = |matcher: &mut Matcher, text: &str| {
+            matcher.clear();
+            text.chars().find_map(|ch| {
+                matcher.push_char(ch);
+                matcher.check_match()
+            })
+        };
+
+        // Waits for the word to end, then fires with the boundary that ended it
+        matcher.push_char('o');
+        matcher.push_char('k');
+        assert!(matcher.check_match().is_none());
+        matcher.push_char(' ');
+        let result = matcher.check_match().unwrap();
+        assert_eq!(result.typed_trigger, "ok");
+        assert_eq!(result.chars_to_delete, 2);
+        assert_eq!(result.committed_by, Some(' '));
+
+        // Not in the middle or at the end of a longer word
+        assert!(typed(&mut matcher, "okay ").is_none());
+        assert!(typed(&mut matcher, "book ").is_none());
+        let result = typed(&mut matcher, "book ok.").unwrap();
+        assert_eq!(result.committed_by, Some('.'));
+
+        // word_boundary alone fires as soon as the trigger is typed, even in "okay"
+        let mut snippet = make_snippet("ok", "OK");
+        snippet.word_boundary = Some(true);
+        matcher.reload(vec![snippet]);
+        assert!(typed(&mut matcher, "okay").is_some_and(|m| m.committed_by.is_none()));
+    }
+
     #[test]
     fn test_backspace() {
         let mut matcher = Matcher::new();
@@ -312,6 +686,44 @@ mod tests {
         assert_eq!(result.captures, Some(vec!["123".to_string()]));
     }
 
+    #[test]
+    fn test_regex_reload_uses_new_pattern() {
+        let mut matcher = Matcher::new();
+        let mut snippet = make_snippet(r";d(\d+)", "Number: $1");
+        snippet.regex = true;
+        matcher.reload(vec![snippet.clone()]);
+
+        // Edit the pattern in place, keeping everything else the same
+        snippet.trigger = r";d([a-z]+)".to_string();
+        matcher.reload(vec![snippet]);
+
+        for ch in ";d123".chars() {
+            matcher.push_char(ch);
+        }
+        assert!(matcher.check_match().is_none());
+
+        matcher.clear();
+        for ch in ";dabc".chars() {
+            matcher.push_char(ch);
+        }
+        assert_eq!(matcher.check_match().unwrap().captures, Some(vec!["abc".to_string()]));
+    }
+
+    #[test]
+    fn test_invalid_regex_skipped_at_reload() {
+        let mut matcher = Matcher::new();
+        let mut bad = make_snippet(";bad(", "never");
+        bad.regex = true;
+        let mut good = make_snippet(r";n(\d)", "$1");
+        good.regex = true;
+        matcher.reload(vec![bad, good]);
+
+        for ch in ";n1".chars() {
+            matcher.push_char(ch);
+        }
+        assert!(matcher.check_match().is_some());
+    }
+
     #[test]
     fn test_disabled_snippet() {
         let mut matcher = Matcher::new();
@@ -326,4 +738,317 @@ mod tests {
 
         assert!(matcher.check_match().is_none());
     }
+
+    #[test]
+    fn test_multi_word_trigger() {
+        let mut matcher = Matcher::new();
+        let mut snippet = make_snippet("thanks a lot", "Thank you very much!");
+        snippet.word_boundary = Some(true);
+        matcher.reload(vec![snippet]);
+
+        // Must not fire before the final character
+        for ch in "ok thanks a lo".chars() {
+            matcher.push_char(ch);
+            assert!(matcher.check_match().is_none());
+        }
+
+        matcher.push_char('t');
+        let result = matcher.check_match().unwrap();
+        assert_eq!(result.typed_trigger, "thanks a lot");
+        assert_eq!(result.chars_to_delete, 12);
+
+        matcher.remove_last(result.chars_to_delete);
+        assert_eq!(matcher.buffer(), "ok ");
+    }
+
+    #[test]
+    fn test_multi_line_trigger() {
+        let mut matcher = Matcher::new();
+        matcher.reload(vec![make_snippet("foo\nbar", "baz")]);
+
+        for ch in "foo\nbar".chars() {
+            matcher.push_char(ch);
+        }
+        assert!(matcher.check_match().is_some());
+    }
+
+    #[test]
+    fn test_word_boundary_multibyte() {
+        let mut matcher = Matcher::new();
+        let mut snippet = make_snippet("btw", "by the way");
+        snippet.word_boundary = Some(true);
+        matcher.reload(vec![snippet]);
+
+        // "é" is two bytes; the boundary check must count chars
+        for ch in "café btw".chars() {
+            matcher.push_char(ch);
+        }
+        assert!(matcher.check_match().is_some());
+
+        matcher.clear();
+        for ch in "cafébtw".chars() {
+            matcher.push_char(ch);
+        }
+        assert!(matcher.check_match().is_none());
+    }
+
+    #[test]
+    fn test_line_start() {
+        let mut matcher = Matcher::new();
+        let mut snippet = make_snippet("##", "<h2>");
+        snippet.line_start = true;
+        matcher.reload(vec![snippet]);
+
+        // Mid-line: no match
+        for ch in "text ##".chars() {
+            matcher.push_char(ch);
+        }
+        assert!(matcher.check_match().is_none());
+
+        // After a newline: match
+        for ch in "\n##".chars() {
+            matcher.push_char(ch);
+        }
+        assert!(matcher.check_match().is_some());
+
+        // At the start of an empty buffer: match
+        matcher.clear();
+        for ch in "##".chars() {
+            matcher.push_char(ch);
+        }
+        assert!(matcher.check_match().is_some());
+
+        // Right after text that doesn't end the line: no match
+        matcher.clear_after("see above");
+        for ch in "##".chars() {
+            matcher.push_char(ch);
+        }
+        assert!(matcher.check_match().is_none());
+        matcher.clear_after("Notes:\n");
+        for ch in "##".chars() {
+            matcher.push_char(ch);
+        }
+        assert!(matcher.check_match().is_some());
+    }
+
+    #[test]
+    fn test_line_start_regex() {
+        let mut matcher = Matcher::new();
+        let mut snippet = make_snippet(r"#(\d)", "<h$1>");
+        snippet.regex = true;
+        snippet.line_start = true;
+        matcher.reload(vec![snippet]);
+
+        for ch in "a #2".chars() {
+            matcher.push_char(ch);
+        }
+        assert!(matcher.check_match().is_none());
+
+        for ch in "\n#2".chars() {
+            matcher.push_char(ch);
+        }
+        assert!(matcher.check_match().is_some());
+    }
+
+    #[test]
+    fn test_propagate_case_matches_any_case() {
+        let mut matcher = Matcher::new();
+        let mut snippet = make_snippet(";email", "test@example.com");
+        snippet.propagate_case = Some(true);
+        matcher.reload(vec![snippet, make_snippet(";exact", "exact")]);
+
+        for ch in ";EMAIL".chars() {
+            matcher.push_char(ch);
+        }
+        let result = matcher.check_match().unwrap();
+        assert_eq!(result.typed_trigger, ";EMAIL");
+        assert_eq!(result.chars_to_delete, 6);
+
+        // Snippets without propagate_case stay case-sensitive
+        matcher.clear();
+        for ch in ";EXACT".chars() {
+            matcher.push_char(ch);
+        }
+        assert!(matcher.check_match().is_none());
+    }
+
+    #[test]
+    fn test_ignore_diacritics() {
+        let mut matcher = Matcher::new();
+        let mut cafe = make_snippet("café", "coffee");
+        cafe.ignore_diacritics = true;
+        let mut naive = make_snippet("naive", "naïve");
+        naive.ignore_diacritics = true;
+        naive.propagate_case = Some(true);
+        // Decomposed "e" + combining acute accent
+        let mut resume = make_snippet("re\u{301}sume\u{301}", "CV");
+        resume.ignore_diacritics = true;
+        matcher.reload(vec![cafe, naive, resume, make_snippet("über", "over")]);
+
+        let mut typed = |text: &str| {
+            matcher.clear();
+            for ch in text.chars() {
+                matcher.push_char(ch);
+            }
+            matcher.check_match().map(|m| (m.snippet.replace, m.typed_trigger, m.chars_to_delete))
+        };
+
+        assert_eq!(typed("cafe"), Some(("coffee".to_string(), "cafe".to_string(), 4)));
+        assert_eq!(typed("café"), Some(("coffee".to_string(), "café".to_string(), 4)));
+        assert_eq!(typed("NAÏVE"), Some(("naïve".to_string(), "NAÏVE".to_string(), 5)));
+        assert_eq!(typed("résumé").map(|m| m.0), Some("CV".to_string()));
+        assert_eq!(typed("resume").map(|m| m.0), Some("CV".to_string()));
+
+        // Snippets without the option still need the exact accents
+        assert!(typed("uber").is_none());
+    }
+
+    #[test]
+    fn test_suffix_conflicts_reported() {
+        let mut matcher = Matcher::new();
+        let mut bounded = make_snippet("lol", "laughing");
+        bounded.word_boundary = Some(true);
+        matcher.reload(vec![
+            make_snippet("btw", "by the way"),
+            make_snippet("mbtw", "my btw"),
+            bounded,
+            make_snippet(";lol", "x"),
+        ]);
+
+        // "lol" has word_boundary set, so only "btw" is reported
+        assert_eq!(
+            matcher.suffix_conflicts(),
+            &[SuffixConflict {
+                trigger: "btw".to_string(),
+                longer: "mbtw".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_duplicate_triggers_reported() {
+        let mut matcher = Matcher::new();
+        let snippets = vec![
+            make_snippet(";dup", "first"),
+            make_snippet(";dup", "second"),
+            make_snippet(";other", "other"),
+        ];
+        matcher.reload(snippets);
+
+        assert_eq!(matcher.duplicate_triggers(), &[";dup".to_string()]);
+
+        // The last definition wins
+        for ch in ";dup".chars() {
+            matcher.push_char(ch);
+        }
+        assert_eq!(matcher.check_match().unwrap().snippet.replace, "second");
+
+        // Reloading without the duplicate clears the report
+        matcher.reload(vec![make_snippet(";dup", "only")]);
+        assert!(matcher.duplicate_triggers().is_empty());
+    }
+
+    #[test]
+    fn test_unused_cursor_markers_reported() {
+        let mut matcher = Matcher::new();
+        let mut placed = make_snippet(";ok", "Hi $|$!");
+        placed.cursor_position = true;
+        let mut cycled = make_snippet(";cy", "plain");
+        cycled.cycle = vec!["plain".to_string(), "({{cursor}})".to_string()];
+        matcher.reload(vec![
+            make_snippet(";sig", "Dear $|$,"),
+            placed,
+            cycled,
+            make_snippet(";usd", "$5 | $6"),
+        ]);
+
+        assert_eq!(matcher.unused_cursor_markers(), &[";sig".to_string(), ";cy".to_string()]);
+
+        matcher.reload(vec![make_snippet(";usd", "$5 | $6")]);
+        assert!(matcher.unused_cursor_markers().is_empty());
+    }
+
+    /// `update` from `old` to `new` must leave the matcher exactly as `reload(new)` would
+    fn assert_update_matches_reload(old: &[Snippet], new: Vec<Snippet>) {
+        let mut patched = Matcher::new();
+        patched.reload(old.to_vec());
+        patched.update(old, new.clone());
+        let mut rebuilt = Matcher::new();
+        rebuilt.reload(new);
+
+        assert_eq!(patched.trie, rebuilt.trie);
+        assert_eq!(patched.case_insensitive_trie, rebuilt.case_insensitive_trie);
+        assert_eq!(patched.folded_trie, rebuilt.folded_trie);
+        assert_eq!(patched.folded_case_insensitive_trie, rebuilt.folded_case_insensitive_trie);
+        let patterns = |m: &Matcher| m.regex_snippets.iter().map(|(s, _)| s.trigger.clone()).collect::<Vec<_>>();
+        assert_eq!(patterns(&patched), patterns(&rebuilt));
+        assert_eq!(patched.duplicate_triggers, rebuilt.duplicate_triggers);
+        assert_eq!(patched.suffix_conflicts, rebuilt.suffix_conflicts);
+    }
+
+    #[test]
+    fn test_update_matches_full_reload() {
+        let mut email = make_snippet(";Email", "A@b.c");
+        email.propagate_case = Some(true);
+        let mut cafe = make_snippet("café", "coffee");
+        cafe.ignore_diacritics = true;
+        cafe.propagate_case = Some(true);
+        let mut regex = make_snippet(r";n(\d+)", "$1");
+        regex.regex = true;
+        let old = vec![
+            make_snippet("btw", "by the way"),
+            make_snippet("mbtw", "my btw"),
+            make_snippet(";dup", "first"),
+            make_snippet(";dup", "second"),
+            email,
+            cafe,
+            regex,
+        ];
+
+        let edit = |i: usize, change: fn(&mut Snippet)| {
+            let mut new = old.clone();
+            change(&mut new[i]);
+            new
+        };
+
+        // Replacement text edited in place, including the shadowed duplicate
+        assert_update_matches_reload(&old, edit(0, |s| s.replace = "by the way!".to_string()));
+        assert_update_matches_reload(&old, edit(2, |s| s.replace = "edited".to_string()));
+        assert_update_matches_reload(&old, edit(3, |s| s.replace = "edited".to_string()));
+        // Trigger renamed, options toggled, snippet disabled
+        assert_update_matches_reload(&old, edit(1, |s| s.trigger = ";mb".to_string()));
+        assert_update_matches_reload(&old, edit(0, |s| s.word_boundary = Some(true)));
+        assert_update_matches_reload(&old, edit(4, |s| s.propagate_case = None));
+        assert_update_matches_reload(&old, edit(5, |s| s.ignore_diacritics = false));
+        assert_update_matches_reload(&old, edit(0, |s| s.enabled = false));
+        // A snippet whose case-insensitive key collides with another
+        let mut lower = make_snippet(";email", "lower");
+        lower.propagate_case = Some(true);
+        let mut new = old.clone();
+        new.insert(2, lower);
+        assert_update_matches_reload(&old, new.clone());
+        assert_update_matches_reload(&new, old.clone());
+        // Removing a trigger that shares nodes with a longer one, reordering duplicates
+        assert_update_matches_reload(&old, old[1..].to_vec());
+        let mut new = old.clone();
+        new.swap(2, 3);
+        assert_update_matches_reload(&old, new);
+        // Regex edits and large edits rebuild
+        assert_update_matches_reload(&old, edit(6, |s| s.replace = "#$1".to_string()));
+        assert_update_matches_reload(&old, Vec::new());
+    }
+
+    #[test]
+    fn test_update_matches_edited_snippet() {
+        let old = vec![make_snippet(";a", "old"), make_snippet(";b", "b")];
+        let mut matcher = Matcher::new();
+        matcher.reload(old.clone());
+        matcher.update(&old, vec![make_snippet(";a", "new"), make_snippet(";c", "c")]);
+
+        for (typed, expected) in [(";a", Some("new")), (";b", None), (";c", Some("c"))] {
+            matcher.clear();
+            typed.chars().for_each(|ch| matcher.push_char(ch));
+            assert_eq!(matcher.check_match().map(|m| m.snippet.replace).as_deref(), expected);
+        }
+    }
 }
@@ -1,27 +1,156 @@
+pub mod backends;
+pub mod benchmark;
+pub mod diagnose;
 pub mod expander;
+mod hooks;
+pub mod hotkey;
 pub mod matcher;
 pub mod monitor;
 pub mod output;
+pub mod rate_limit;
+pub mod snippet_check;
 mod trie;
+mod typing_aids;
+pub mod usage;
+mod virtual_keyboard;
 pub mod keymaps;
+pub mod window;
 
 pub use expander::expand_match;
 pub use matcher::Matcher;
-pub use monitor::{KeyboardEvent, KeyboardMonitor};
-pub use output::OutputEngine;
+pub use monitor::{KeySettings, KeyboardEvent, KeyboardMonitor};
+pub use output::{create_backend, detect_ydotool_socket, resolve_ydotool_socket, OutputBackend, OutputEngine, OutputError, OutputGuard};
 
 use anyhow::Result;
+use chrono::NaiveDateTime;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
-use tokio::sync::{mpsc, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::{mpsc, watch, RwLock};
 
-use crate::config::Config;
+use crate::config::loader::ConfigManager;
+use crate::config::schema::Settings;
+use crate::config::{Config, OutputMethod, Snippet};
+use expander::{expand_snippet, ExpansionResult};
+use matcher::MatchResult;
+use rate_limit::{Cooldown, RateLimit, RateLimiter};
+use usage::UsageStats;
+
+/// Time for focus to return to the previous window after the palette closes
+const PALETTE_FOCUS_DELAY: Duration = Duration::from_millis(150);
+
+/// How soon a `cycle` snippet's trigger must be typed again to move on to the
+/// next alternative
+const CYCLE_WINDOW: Duration = Duration::from_secs(3);
+
+/// Longest replacement preview shown next to a trigger in the palette
+const PALETTE_PREVIEW_MAX_CHARS: usize = 40;
+
+/// What the engine is doing, shared with the tray's status line
+#[derive(Debug, Clone, Default)]
+pub struct EngineStatus {
+    /// Snippets loaded into the matcher
+    pub snippet_count: usize,
+    /// When the last expansion was typed, if any
+    pub last_expansion: Option<SystemTime>,
+}
+
+/// A match held back in explicit trigger mode until the activation key follows it
+struct PendingMatch {
+    match_result: MatchResult,
+    /// Characters typed since the match, all so far a prefix of the activation key
+    activation_typed: String,
+}
+
+/// The last expansion of a `cycle` snippet, which typing its trigger again
+/// replaces with the next alternative
+struct CycleState {
+    trigger: String,
+    /// Position in the snippet's `cycle` list that was typed
+    index: usize,
+    /// Characters of the expansion on screen, deleted when moving on
+    output_len: usize,
+    at: Instant,
+    /// Characters typed since the expansion
+    typed_since: usize,
+}
 
 /// The main expansion engine that ties together monitoring, matching, and output
 pub struct ExpansionEngine {
     config: Arc<RwLock<Config>>,
     matcher: Matcher,
-    output: OutputEngine,
+    output: Box<dyn OutputBackend>,
+    /// Builds the output backend from the settings (replaceable in tests)
+    create_backend: fn(&Settings) -> Box<dyn OutputBackend>,
+    /// Finds ydotoold's socket when the config doesn't set one (replaceable in tests)
+    detect_ydotool_socket: fn() -> Option<String>,
+    /// ydotoold's socket, configured or found when the config was last loaded
+    ydotool_socket: Option<String>,
+    /// Backends for snippets with their own keystroke delay or output method,
+    /// kept until the config is reloaded
+    override_backends: HashMap<(u64, OutputMethod), Box<dyn OutputBackend>>,
     enabled: Arc<RwLock<bool>>,
+    /// Secure input mode (toggled by hotkey) - suppresses all expansions
+    secure_input: bool,
+    /// Current local time, used for snippet schedules (replaceable in tests)
+    clock: fn() -> NaiveDateTime,
+    /// Stops expansions that fire faster than a person could trigger them
+    rate_limiter: RateLimiter,
+    /// Limits the sound and notification to one per `feedback_cooldown_ms`
+    feedback_cooldown: Cooldown,
+    /// Plays the sound and/or shows the notification for an expansion (replaceable in tests)
+    feedback: fn(&str, bool, bool),
+    /// Play a sound after each expansion (`settings.enable_sound`)
+    enable_sound: bool,
+    /// Show a notification after each expansion (`settings.notify_on_expand`)
+    notify_on_expand: bool,
+    /// The last expansion failed to output (e.g. ydotoold is down)
+    output_degraded: bool,
+    /// Notified with `true` when output starts failing and `false` once it recovers
+    health_tx: Option<mpsc::Sender<bool>>,
+    /// Tells the keyboard monitor to ignore keystrokes while we are typing
+    output_guard: OutputGuard,
+    /// Loads `{{clipboard_set:...}}` values into the clipboard (replaceable in tests)
+    set_clipboard: fn(&str),
+    /// Text that commits a match, if `explicit_trigger` is on
+    activation_key: Option<String>,
+    /// Match waiting for the activation key
+    pending_match: Option<PendingMatch>,
+    /// Wait for the key completing a trigger to be released before expanding
+    /// (`settings.fire_on_release`)
+    fire_on_release: bool,
+    /// Match waiting for its last key to be released, with its activation key length
+    held_match: Option<(MatchResult, usize)>,
+    /// Codes typed after the leader key, mapped to snippet triggers (`settings.leader_codes`)
+    leader_codes: BTreeMap<String, String>,
+    /// The code typed so far, while in leader mode
+    leader: Option<String>,
+    /// Last `cycle` snippet expansion, while it can still be cycled
+    last_cycle: Option<CycleState>,
+    /// How often each trigger was expanded, to list recent snippets first in the palette
+    usage: UsageStats,
+    /// Shows the palette and returns the chosen entry (replaceable in tests)
+    picker: fn(&[String]) -> Result<Option<String>>,
+    /// Queries the focused window for application filters (replaceable in tests)
+    active_window: fn() -> Option<window::ActiveWindow>,
+    /// Snippets currently loaded into the matcher, to skip rebuilding it when only
+    /// variables or settings changed
+    loaded_snippets: Vec<Snippet>,
+    /// Snippet count and last expansion time, read by the tray
+    status: Arc<std::sync::RwLock<EngineStatus>>,
+    /// Tells the keyboard monitor about layout and hotkey changes
+    key_settings: Option<watch::Sender<KeySettings>>,
+    /// Triggers shorter than this only fire at the start of a word (`settings.min_trigger_chars`)
+    min_trigger_chars: usize,
+    /// Retry failed output transliterated to ASCII (`settings.transliterate_fallback`)
+    transliterate_fallback: bool,
+    /// Capitalize the first letter of each sentence (`settings.auto_capitalize`)
+    auto_capitalize: bool,
+    /// Turn two spaces after a word into ". " (`settings.double_space_period`)
+    double_space_period: bool,
+    /// Log every keystroke, the matcher buffer and full matches (`--debug-keys`).
+    /// This records everything typed, passwords included, so it is off by default.
+    debug_keys: bool,
 }
 
 impl ExpansionEngine {
@@ -30,71 +159,792 @@ impl ExpansionEngine {
         Self {
             config,
             matcher: Matcher::new(),
-            output: OutputEngine::new(12, None),
+            output: Box::new(OutputEngine::new(12, None)),
+            create_backend,
+            detect_ydotool_socket,
+            ydotool_socket: None,
+            override_backends: HashMap::new(),
             enabled,
+            secure_input: false,
+            clock: || chrono::Local::now().naive_local(),
+            rate_limiter: RateLimiter::new(crate::config::schema::Settings::default().max_expansions_per_second),
+            feedback_cooldown: Cooldown::new(Duration::from_millis(Settings::default().feedback_cooldown_ms)),
+            feedback: expansion_feedback,
+            enable_sound: false,
+            notify_on_expand: false,
+            output_degraded: false,
+            health_tx: None,
+            output_guard: OutputGuard::default(),
+            set_clipboard: crate::variables::set_clipboard,
+            activation_key: None,
+            pending_match: None,
+            fire_on_release: false,
+            held_match: None,
+            leader_codes: BTreeMap::new(),
+            leader: None,
+            last_cycle: None,
+            usage: UsageStats::default(),
+            picker: crate::variables::run_picker,
+            active_window: window::active_window,
+            loaded_snippets: Vec::new(),
+            status: Arc::default(),
+            key_settings: None,
+            min_trigger_chars: 0,
+            transliterate_fallback: false,
+            auto_capitalize: false,
+            double_space_period: false,
+            debug_keys: false,
         }
     }
 
+    /// Builder method to log keystrokes and matches for debugging
+    pub fn with_debug_keys(mut self, debug_keys: bool) -> Self {
+        self.debug_keys = debug_keys;
+        self
+    }
 
+    /// Builder method to send layout and hotkey changes to the keyboard monitor
+    pub fn with_key_settings(mut self, key_settings: watch::Sender<KeySettings>) -> Self {
+        self.key_settings = Some(key_settings);
+        self
+    }
+
+    /// Builder method to publish the snippet count and last expansion (e.g. to the tray)
+    pub fn with_status(mut self, status: Arc<std::sync::RwLock<EngineStatus>>) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Builder method to load and persist snippet usage stats
+    pub fn with_usage_stats(mut self, usage: UsageStats) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    /// Builder method to share the output guard with the keyboard monitor
+    pub fn with_output_guard(mut self, output_guard: OutputGuard) -> Self {
+        self.output_guard = output_guard;
+        self
+    }
+
+    /// Builder method to report output health changes (e.g. to the tray)
+    pub fn with_health_channel(mut self, health_tx: mpsc::Sender<bool>) -> Self {
+        self.health_tx = Some(health_tx);
+        self
+    }
+
+    /// Type an expansion, through a backend with the snippet's own keystroke delay
+    /// if it sets one
+    async fn output_expansion(&mut self, expansion: &ExpansionResult) -> Result<()> {
+        let delay = expansion.keystroke_delay.filter(|&delay| delay != self.output.keystroke_delay());
+        if delay.is_none() && expansion.output_mode.is_none() {
+            return self.output.output_expansion(expansion).await;
+        }
+
+        let mut settings = self.config.read().await.settings.clone();
+        let method = expansion.output_mode.map_or(settings.output_method, |mode| mode.method(settings.output_method));
+        if delay.is_none() && method == settings.output_method {
+            return self.output.output_expansion(expansion).await;
+        }
+        settings.keystroke_delay_ms = delay.unwrap_or(settings.keystroke_delay_ms);
+        settings.output_method = method;
+        settings.ydotool_socket = self.ydotool_socket.clone();
+        let create_backend = self.create_backend;
+        self.override_backends
+            .entry((settings.keystroke_delay_ms, method))
+            .or_insert_with(|| create_backend(&settings))
+            .output_expansion(expansion)
+            .await
+    }
+
+    /// Track whether output is working, notifying the user when it breaks and
+    /// when it recovers
+    fn set_output_healthy(&mut self, result: &Result<()>) {
+        let healthy = result.is_ok();
+        if self.output_degraded != healthy {
+            return;
+        }
+        self.output_degraded = !healthy;
+
+        let missing = result.as_ref().err().and_then(|e| e.downcast_ref::<OutputError>()).is_some_and(|e| {
+            matches!(e, OutputError::BackendMissing { .. } | OutputError::Spawn { .. })
+        });
+        if healthy {
+            log::info!("Text output recovered");
+        } else if missing {
+            log::warn!("Text output is failing; the configured output tool could not be run");
+            crate::notify::send_notification(
+                "xpander can't type expansions",
+                "The keystroke tool is not installed. Check `output_backend` in the config.",
+            );
+        } else {
+            log::warn!("Text output is failing; is ydotoold (or the configured output tool) running?");
+            crate::notify::send_notification(
+                "xpander can't type expansions",
+                "The keystroke tool appears to be down. Check that ydotoold is running.",
+            );
+        }
+
+        if let Some(tx) = &self.health_tx {
+            let _ = tx.try_send(!healthy);
+        }
+    }
+
+    /// Note that `trigger` was just expanded
+    fn record_usage(&mut self, trigger: &str) {
+        let now = SystemTime::now();
+        let secs = now.duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        self.usage.record(trigger, secs);
+        if let Ok(mut status) = self.status.write() {
+            status.last_expansion = Some(now);
+        }
+    }
+
+    /// Play the sound and show the notification for an expansion of `trigger`, if
+    /// enabled and the feedback cooldown has passed
+    fn give_feedback(&mut self, trigger: &str) {
+        if (self.enable_sound || self.notify_on_expand) && self.feedback_cooldown.ready(Instant::now()) {
+            (self.feedback)(trigger, self.enable_sound, self.notify_on_expand);
+        }
+    }
 
     /// Process a keyboard event
     pub async fn process_event(&mut self, event: KeyboardEvent) -> Result<()> {
+        // The secure input hotkey works even when expansions are disabled
+        if let KeyboardEvent::ToggleSecureInput = event {
+            self.secure_input = !self.secure_input;
+            self.matcher.clear();
+            self.pending_match = None;
+            self.held_match = None;
+            self.leader = None;
+            log::info!("Secure input {}", if self.secure_input { "on" } else { "off" });
+            return Ok(());
+        }
+
         // Check if expansion is enabled
         if !*self.enabled.read().await {
             return Ok(());
         }
 
+        if self.debug_keys {
+            log::debug!("Key event: {:?}", event);
+        }
+
+        // A held match fires once its key is released. If a character is typed
+        // first, it is already on screen after the trigger, so it is deleted and
+        // typed again after the replacement, like the boundary ending a whole word.
+        if let Some((mut match_result, activation_len)) = self.held_match.take() {
+            match event {
+                KeyboardEvent::KeyReleased => return self.expand(match_result, activation_len).await,
+                KeyboardEvent::Character(ch) | KeyboardEvent::WordBoundary(ch)
+                    if match_result.committed_by.is_none() && activation_len == 0 =>
+                {
+                    if let Some(cycle) = &mut self.last_cycle {
+                        cycle.typed_since += 1;
+                    }
+                    self.matcher.push_char(ch);
+                    match_result.committed_by = Some(ch);
+                    return self.expand(match_result, activation_len).await;
+                }
+                _ => log::debug!("Held match '{}' cancelled", match_result.typed_trigger),
+            }
+        }
+        if let KeyboardEvent::KeyReleased = event {
+            return Ok(());
+        }
+
+        // In leader mode, the word typed is a code to look up, not text to match
+        if let Some(code) = &mut self.leader {
+            match event {
+                KeyboardEvent::Character(ch) => {
+                    code.push(ch);
+                    self.matcher.push_char(ch);
+                    return Ok(());
+                }
+                KeyboardEvent::Backspace if !code.is_empty() => {
+                    code.pop();
+                    self.matcher.handle_backspace();
+                    return Ok(());
+                }
+                KeyboardEvent::WordBoundary(ch) => {
+                    let code = std::mem::take(code);
+                    self.leader = None;
+                    self.matcher.push_char(ch);
+                    return match self.leader_match(&code, ch) {
+                        Some(match_result) => self.expand(match_result, 0).await,
+                        None => {
+                            log::debug!("Leader code '{}' is not in leader_codes", code);
+                            Ok(())
+                        }
+                    };
+                }
+                _ => {
+                    log::debug!("Leader mode cancelled");
+                    self.leader = None;
+                }
+            }
+        }
+        if let KeyboardEvent::Leader = event {
+            if !self.secure_input && !self.leader_codes.is_empty() {
+                log::debug!("Leader mode");
+                self.leader = Some(String::new());
+                self.pending_match = None;
+                self.last_cycle = None;
+            }
+            return Ok(());
+        }
+
+        // Only typing the trigger again continues a cycle
+        match (&event, &mut self.last_cycle) {
+            (KeyboardEvent::Character(_) | KeyboardEvent::WordBoundary(_), Some(cycle)) => cycle.typed_since += 1,
+            _ => self.last_cycle = None,
+        }
+
         match event {
             KeyboardEvent::Character(ch) => {
                 self.matcher.push_char(ch);
+                self.capitalize_sentence(ch).await?;
                 self.check_and_expand().await?;
             }
             KeyboardEvent::WordBoundary(ch) => {
                 self.matcher.push_char(ch);
-                self.check_and_expand().await?;
+                if !self.double_space_period(ch).await? {
+                    self.check_and_expand().await?;
+                }
             }
             KeyboardEvent::Backspace => {
                 self.matcher.handle_backspace();
+                self.pending_match = None;
+            }
+            KeyboardEvent::Enter => {
+                // Keep the newline in the buffer so multi-line triggers can match
+                self.matcher.push_char('\n');
+                self.check_and_expand().await?;
+            }
+            KeyboardEvent::Tab | KeyboardEvent::Escape | KeyboardEvent::Navigation => {
+                // These keys reset the buffer (word boundary). After cursor movement
+                // we can't tell what is next to the cursor anymore.
+                self.matcher.clear();
+                self.pending_match = None;
             }
-            KeyboardEvent::Enter | KeyboardEvent::Tab | KeyboardEvent::Escape => {
-                // These keys reset the buffer (word boundary)
+            KeyboardEvent::Palette => {
+                // Whatever was typed before is no longer next to the cursor
                 self.matcher.clear();
+                self.pending_match = None;
+                if !self.secure_input {
+                    self.insert_from_palette().await?;
+                }
             }
+            KeyboardEvent::ToggleSecureInput | KeyboardEvent::KeyReleased | KeyboardEvent::Leader => {}
         }
 
         Ok(())
     }
 
+    /// With `auto_capitalize`, replace a lowercase letter that starts a sentence with
+    /// its capital. The buffer keeps the letter as typed, so a trigger starting with
+    /// it still matches, and deleting the trigger also deletes the capital.
+    async fn capitalize_sentence(&mut self, ch: char) -> Result<()> {
+        if !self.auto_capitalize || self.secure_input {
+            return Ok(());
+        }
+        let Some(capital) = typing_aids::capital(ch) else {
+            return Ok(());
+        };
+        let buffer = self.matcher.buffer();
+        if !typing_aids::starts_sentence(&buffer[..buffer.len() - ch.len_utf8()]) {
+            return Ok(());
+        }
+        // At most once per sentence, so querying the window here is cheap enough
+        if self.in_password_app().await {
+            return Ok(());
+        }
+
+        let _in_flight = self.output_guard.begin();
+        let result = match self.output.send_backspaces(1).await {
+            Ok(()) => self.output.type_text(&capital.to_string()).await,
+            Err(e) => Err(e),
+        };
+        self.set_output_healthy(&result);
+        result
+    }
+
+    /// With `double_space_period`, turn a second space after a word into ". ".
+    /// Returns whether the text was rewritten, in which case it isn't matched
+    /// against triggers again.
+    async fn double_space_period(&mut self, ch: char) -> Result<bool> {
+        if !self.double_space_period || self.secure_input || ch != ' ' {
+            return Ok(false);
+        }
+        let buffer = self.matcher.buffer();
+        if !typing_aids::is_double_space(&buffer[..buffer.len() - 1]) || self.in_password_app().await {
+            return Ok(false);
+        }
+
+        let _in_flight = self.output_guard.begin();
+        let result = match self.output.send_backspaces(2).await {
+            Ok(()) => self.output.type_text(". ").await,
+            Err(e) => Err(e),
+        };
+        self.set_output_healthy(&result);
+        result?;
+
+        self.matcher.remove_last(2);
+        self.matcher.push_char('.');
+        self.matcher.push_char(' ');
+        Ok(true)
+    }
+
     /// Check for matches and expand if found
     async fn check_and_expand(&mut self) -> Result<()> {
-        if let Some(match_result) = self.matcher.check_match() {
+        if self.secure_input {
+            self.matcher.clear();
+            return Ok(());
+        }
+
+        if self.debug_keys {
+            log::debug!("Buffer: {:?}", self.matcher.buffer());
+        }
+
+        let Some((match_result, activation_len)) = self.take_match() else {
+            return Ok(());
+        };
+        if self.fire_on_release {
+            log::debug!("Match '{}' waiting for its key to be released", match_result.typed_trigger);
+            self.held_match = Some((match_result, activation_len));
+            return Ok(());
+        }
+        self.expand(match_result, activation_len).await
+    }
+
+    /// Replace a match's trigger with its expansion, if the snippet may fire here and now
+    async fn expand(&mut self, match_result: MatchResult, activation_len: usize) -> Result<()> {
+        if let Some(schedule) = &match_result.snippet.active_schedule {
+            if !schedule.is_active_at((self.clock)()) {
+                log::debug!("Skipping '{}': outside its active schedule", match_result.typed_trigger);
+                return Ok(());
+            }
+        }
+
+        if self.too_short_mid_word(&match_result) {
+            log::debug!(
+                "Skipping '{}': shorter than min_trigger_chars and typed inside a word",
+                match_result.typed_trigger
+            );
+            return Ok(());
+        }
+
+        let window = self.window_for(&match_result.snippet).await;
+        if !window::app_filter_allows(window.as_ref(), &match_result.snippet) {
+            log::debug!("Skipping '{}': not allowed in this application", match_result.typed_trigger);
+            return Ok(());
+        }
+
+        if self.in_password_app().await {
+            log::debug!("Suppressing expansion in password app");
+            self.matcher.clear();
+            return Ok(());
+        }
+
+        if self.in_fullscreen_window().await {
+            log::debug!("Suppressing expansion in fullscreen window");
+            self.matcher.clear();
+            return Ok(());
+        }
+
+        match self.rate_limiter.check(std::time::Instant::now()) {
+            RateLimit::Allowed => {}
+            RateLimit::Tripped => {
+                log::warn!("Too many expansions per second, pausing expansion briefly (possible feedback loop)");
+                crate::notify::send_notification(
+                    "xpander paused expansions",
+                    "Snippets were expanding too fast - a replacement may contain its own trigger.",
+                );
+                self.matcher.clear();
+                return Ok(());
+            }
+            RateLimit::Suppressed => {
+                self.matcher.clear();
+                return Ok(());
+            }
+        }
+
+        if self.debug_keys {
+            log::debug!(
+                "Match found: '{}' (trigger {:?}, label {:?}, chars_to_delete {}) -> {:?}",
+                match_result.typed_trigger,
+                match_result.snippet.trigger,
+                match_result.snippet.label,
+                match_result.chars_to_delete,
+                match_result.snippet.replacement()
+            );
+        } else {
             log::debug!(
                 "Match found: '{}' -> <redacted len={}>",
                 match_result.typed_trigger,
-                match_result.snippet.replace.len()
+                match_result.snippet.replacement().len()
             );
+        }
 
-            // Remove the matched text from the buffer
-            self.matcher.remove_last(match_result.chars_to_delete);
+        // Start matching afresh after the replacement: what was typed before the
+        // trigger no longer ends at the cursor, and leaving it in the buffer could
+        // let it and the next keys form a trigger that isn't on screen. Only the
+        // boundary ending a whole word is kept, so the next word can still fire.
+        let committed_len = usize::from(match_result.committed_by.is_some());
+        self.matcher.clear();
+        if let Some(ch) = match_result.committed_by {
+            self.matcher.push_char(ch);
+        }
 
-            // Get variables from config
-            let variables = {
-                let config = self.config.read().await;
-                config.variables.clone()
-            };
+        // Get variables from config
+        let variables = {
+            let config = self.config.read().await;
+            config.variables.clone()
+        };
 
-            // Expand the match
-            let expansion = expand_match(&match_result, &variables)?;
+        // Keystrokes seen from here on are our own output or typed into a
+        // {{pick:...}} or {{input:...}} window, not text to match against
+        let _in_flight = self.output_guard.begin();
 
-            // Output the expansion
-            self.output.output_expansion(&expansion).await?;
+        // A cycle snippet types its next alternative in place of the last one
+        let previous_cycle = self.last_cycle.take().filter(|cycle| {
+            cycle.trigger == match_result.snippet.trigger
+                && cycle.typed_since == match_result.chars_to_delete + committed_len + activation_len
+                && cycle.at.elapsed() <= CYCLE_WINDOW
+        });
+        let mut match_result = match_result;
+        if let Some(replace) = window::app_replacement(window.as_ref(), &match_result.snippet) {
+            match_result.snippet.replace = replace.to_string();
+            match_result.snippet.cycle.clear();
+        }
+        let cycle_index = (!match_result.snippet.cycle.is_empty()).then(|| {
+            let index = previous_cycle
+                .as_ref()
+                .map_or(0, |cycle| (cycle.index + 1) % match_result.snippet.cycle.len());
+            match_result.snippet.cycle.rotate_left(index);
+            index
+        });
 
-            log::debug!("Expansion complete");
+        // Expand the match, also deleting the activation key. Expanding can wait on
+        // the user ({{pick:...}}, {{input:...}}), a command or the clipboard, so it
+        // runs off the engine task.
+        let mut expansion = {
+            let match_result = match_result.clone();
+            let window = window.clone();
+            tokio::task::spawn_blocking(move || {
+                crate::variables::with_active_window(window, || expand_match(&match_result, &variables))
+            })
+            .await??
+        };
+        if let Some(cycle) = &previous_cycle {
+            expansion.delete_count += cycle.output_len;
+        }
+        // The space or punctuation after a whole word is typed again after the replacement
+        if let Some(ch) = match_result.committed_by {
+            expansion.delete_count += 1;
+            expansion.text.push(ch);
+            expansion.typed_trigger.push(ch);
+            if let Some(offset) = &mut expansion.cursor_offset {
+                *offset += 1;
+            }
+        }
+        if activation_len > 0 {
+            expansion.delete_count += activation_len;
+            if let Some(key) = &self.activation_key {
+                expansion.typed_trigger.push_str(key);
+            }
         }
 
+        // Output the expansion
+        expansion.transliterate_fallback = self.transliterate_fallback;
+        let result = self.output_expansion(&expansion).await;
+        self.set_output_healthy(&result);
+        result?;
+
+        // Without a whole word's boundary in the buffer, the next trigger follows
+        // the text left before the cursor
+        if match_result.committed_by.is_none() {
+            let before_cursor = expansion.text.chars().count().saturating_sub(expansion.cursor_offset.unwrap_or(0));
+            let end = expansion.text.char_indices().nth(before_cursor).map_or(expansion.text.len(), |(i, _)| i);
+            self.matcher.clear_after(&expansion.text[..end]);
+        }
+
+        // Only touch the clipboard once the text was typed
+        if let Some(text) = &expansion.clipboard {
+            (self.set_clipboard)(text);
+        }
+
+        // The output can only be deleted again if the cursor is still at its end
+        if let Some(index) = cycle_index {
+            if expansion.cursor_offset.is_none() && expansion.post_action.is_none() {
+                self.last_cycle = Some(CycleState {
+                    trigger: match_result.snippet.trigger.clone(),
+                    index,
+                    output_len: match_result.snippet.keep_prefix_len + expansion.text.chars().count(),
+                    at: Instant::now(),
+                    typed_since: 0,
+                });
+            }
+        }
+
+        self.record_usage(&match_result.snippet.trigger);
+        self.give_feedback(&match_result.snippet.trigger);
+        if let Some(command) = &match_result.snippet.on_expand_command {
+            hooks::spawn_on_expand(command.clone(), match_result.typed_trigger.clone(), expansion.text.clone());
+        }
+        log::debug!("Expansion complete");
+
         Ok(())
     }
 
+    /// Let the user search all snippets and type the chosen one at the cursor
+    async fn insert_from_palette(&mut self) -> Result<()> {
+        let now = (self.clock)();
+        let (mut snippets, variables) = {
+            let config = self.config.read().await;
+            let snippets: Vec<Snippet> = ConfigManager::flatten_active_snippets(&config)
+                .into_iter()
+                .filter(|s| !s.regex)
+                .filter(|s| s.active_schedule.as_ref().is_none_or(|schedule| schedule.is_active_at(now)))
+                .collect();
+            (snippets, config.variables.clone())
+        };
+        if snippets.is_empty() {
+            return Ok(());
+        }
+        self.usage.order_by_usage(&mut snippets);
+
+        // Keys typed into the palette aren't text to match against
+        let _in_flight = self.output_guard.begin();
+
+        let entries: Vec<String> = snippets.iter().map(palette_entry).collect();
+        let picker = self.picker;
+        let choice = {
+            let entries = entries.clone();
+            tokio::task::spawn_blocking(move || picker(&entries)).await??
+        };
+        let Some(snippet) = choice
+            .and_then(|choice| entries.iter().position(|e| *e == choice))
+            .map(|i| &snippets[i])
+        else {
+            return Ok(());
+        };
+
+        // Wait for focus to return before looking up the window for {{window:...}}
+        tokio::time::sleep(PALETTE_FOCUS_DELAY).await;
+        if self.in_password_app().await {
+            log::debug!("Not inserting '{}' into a password app", snippet.trigger);
+            return Ok(());
+        }
+        let window = self.window_for(snippet).await;
+
+        // Nothing was typed, so there is no trigger to delete
+        let mut expansion = {
+            let snippet = snippet.clone();
+            tokio::task::spawn_blocking(move || {
+                crate::variables::with_active_window(window, || expand_snippet(&snippet, &variables))
+            })
+            .await??
+        };
+        expansion.delete_count = 0;
+        expansion.typed_trigger.clear();
+        expansion.transliterate_fallback = self.transliterate_fallback;
+
+        let result = self.output_expansion(&expansion).await;
+        self.set_output_healthy(&result);
+        result?;
+
+        if let Some(text) = &expansion.clipboard {
+            (self.set_clipboard)(text);
+        }
+
+        self.record_usage(&snippet.trigger);
+        self.give_feedback(&snippet.trigger);
+        if let Some(command) = &snippet.on_expand_command {
+            hooks::spawn_on_expand(command.clone(), snippet.trigger.clone(), expansion.text.clone());
+        }
+        Ok(())
+    }
+
+    /// The match to expand now, with the number of activation key characters typed
+    /// after it. In explicit trigger mode a match is held until the activation key
+    /// follows it, and typing anything else cancels it.
+    fn take_match(&mut self) -> Option<(MatchResult, usize)> {
+        let Some(activation_key) = self.activation_key.as_deref() else {
+            return self.matcher.check_match().map(|m| (m, 0));
+        };
+
+        if let Some(mut pending) = self.pending_match.take() {
+            if let Some(ch) = self.matcher.buffer().chars().next_back() {
+                pending.activation_typed.push(ch);
+            }
+            if pending.activation_typed == activation_key {
+                return Some((pending.match_result, activation_key.chars().count()));
+            }
+            if activation_key.starts_with(&pending.activation_typed) {
+                self.pending_match = Some(pending);
+                return None;
+            }
+            log::debug!("Pending match '{}' cancelled", pending.match_result.typed_trigger);
+        }
+
+        if let Some(match_result) = self.matcher.check_match() {
+            log::debug!("Match '{}' waiting for activation key", match_result.typed_trigger);
+            self.pending_match = Some(PendingMatch {
+                match_result,
+                activation_typed: String::new(),
+            });
+        }
+        None
+    }
+
+    /// The match for a leader code ended by `boundary`, which is typed again after
+    /// the replacement like the boundary ending a whole word
+    fn leader_match(&self, code: &str, boundary: char) -> Option<MatchResult> {
+        let trigger = self.leader_codes.get(code)?;
+        // The last definition of a trigger wins, as in the matcher
+        let Some(snippet) = self.loaded_snippets.iter().rev().find(|s| !s.regex && s.trigger == *trigger) else {
+            log::warn!("Leader code '{}' refers to '{}', which is not an active snippet", code, trigger);
+            return None;
+        };
+        Some(MatchResult {
+            snippet: Snippet {
+                keep_prefix_len: 0,
+                ..snippet.clone()
+            },
+            typed_trigger: code.to_string(),
+            chars_to_delete: code.chars().count(),
+            captures: None,
+            committed_by: Some(boundary),
+            mid_word: false,
+        })
+    }
+
+    /// Whether `min_trigger_chars` stops this match: its trigger is short, it was typed
+    /// inside a word, and the snippet doesn't set `word_boundary` either way
+    fn too_short_mid_word(&self, match_result: &MatchResult) -> bool {
+        match_result.mid_word
+            && match_result.snippet.word_boundary.is_none()
+            && match_result.typed_trigger.chars().count() < self.min_trigger_chars
+    }
+
+    /// Check whether the active window is a password manager / sensitive app.
+    /// Only queried once a match is found, to avoid spawning processes per keystroke.
+    async fn in_password_app(&self) -> bool {
+        let password_apps = {
+            let config = self.config.read().await;
+            if !config.settings.disable_in_password_fields {
+                return false;
+            }
+            config.settings.password_apps.clone()
+        };
+
+        tokio::task::spawn_blocking(self.active_window)
+            .await
+            .ok()
+            .flatten()
+            .is_some_and(|w| w.class_matches(&password_apps))
+    }
+
+    /// Check whether expansion is suspended because the focused window is fullscreen
+    /// (`settings.suspend_when_fullscreen`). Queried once a match is found, like
+    /// `in_password_app`; if the compositor can't tell, expansion goes ahead.
+    async fn in_fullscreen_window(&self) -> bool {
+        if !self.config.read().await.settings.suspend_when_fullscreen {
+            return false;
+        }
+
+        let window = tokio::task::spawn_blocking(self.active_window).await.ok().flatten();
+        window::suspended_for_fullscreen(true, window.as_ref())
+    }
+
+    /// The active window, for checking the snippet's application filters and
+    /// `per_app_replace`, and for its `{{window:...}}` variables. Only queried for
+    /// snippets that use it.
+    async fn window_for(&self, snippet: &Snippet) -> Option<window::ActiveWindow> {
+        if snippet.applications.is_none()
+            && snippet.exclude_applications.is_none()
+            && snippet.per_app_replace.is_empty()
+            && !snippet.uses_window()
+        {
+            return None;
+        }
+
+        tokio::task::spawn_blocking(self.active_window).await.ok().flatten()
+    }
+
+    /// Reload snippets into the matcher and reconfigure output from the current settings,
+    /// so changes take effect without restarting the daemon. Returns the snippet count.
+    async fn reload_config(&mut self) -> usize {
+        let config = self.config.read().await;
+        let flattened_snippets = ConfigManager::flatten_active_snippets(&config);
+        let count = flattened_snippets.len();
+        // Variables are read from the config on each expansion, so a change that
+        // only touches them doesn't need the (potentially large) Trie rebuilt, and
+        // editing a few snippets only patches it
+        if flattened_snippets == self.loaded_snippets {
+            log::debug!("Snippets unchanged, keeping the matcher");
+        } else {
+            self.matcher.update(&self.loaded_snippets, flattened_snippets.clone());
+            self.loaded_snippets = flattened_snippets;
+        }
+        if let Ok(mut status) = self.status.write() {
+            status.snippet_count = count;
+        }
+
+        let settings = resolve_ydotool_socket(&config.settings, self.detect_ydotool_socket);
+        self.ydotool_socket = settings.ydotool_socket.clone();
+        self.output = (self.create_backend)(&settings);
+        self.override_backends.clear();
+        self.rate_limiter.set_limit(config.settings.max_expansions_per_second);
+        self.feedback_cooldown.set_interval(Duration::from_millis(config.settings.feedback_cooldown_ms));
+        self.enable_sound = config.settings.enable_sound;
+        self.notify_on_expand = config.settings.notify_on_expand;
+        self.activation_key = (config.settings.explicit_trigger && !config.settings.activation_key.is_empty())
+            .then(|| config.settings.activation_key.clone());
+        self.pending_match = None;
+        self.held_match = None;
+        self.fire_on_release = config.settings.fire_on_release;
+        self.leader = None;
+        self.leader_codes = config.settings.leader_codes.clone();
+        crate::variables::set_locale_override(config.settings.locale.clone());
+        crate::variables::set_clipboard_timeout(config.settings.clipboard_timeout_ms);
+        crate::variables::set_secrets_file(config.settings.secrets_file.clone());
+        self.transliterate_fallback = config.settings.transliterate_fallback;
+        self.min_trigger_chars = config.settings.min_trigger_chars;
+        let short: Vec<&str> = self
+            .loaded_snippets
+            .iter()
+            .filter(|s| !s.regex && s.word_boundary.is_none() && s.trigger.chars().count() < self.min_trigger_chars)
+            .map(|s| s.trigger.as_str())
+            .collect();
+        if !short.is_empty() {
+            log::warn!(
+                "Triggers shorter than min_trigger_chars ({}) only fire at the start of a word: {}",
+                self.min_trigger_chars,
+                short.join(", ")
+            );
+        }
+        self.auto_capitalize = config.settings.auto_capitalize;
+        self.double_space_period = config.settings.double_space_period;
+        if let Some(tx) = &self.key_settings {
+            let key_settings = KeySettings::from_settings(&config.settings);
+            tx.send_if_modified(|current| {
+                let changed = *current != key_settings;
+                *current = key_settings;
+                changed
+            });
+        }
+        log::debug!(
+            "Output backend {:?} (keystroke delay {}ms, socket {:?})",
+            config.settings.output_backend,
+            self.output.keystroke_delay(),
+            self.output.socket_path()
+        );
+
+        count
+    }
+
     /// Run the engine with a keyboard event receiver and reload receiver
     pub async fn run(
         mut self,
@@ -103,13 +953,9 @@ impl ExpansionEngine {
     ) -> Result<()> {
         log::info!("Expansion engine started");
 
-        // Initial load of snippets
-        {
-            let config = self.config.read().await;
-            let flattened_snippets = crate::config::loader::ConfigManager::flatten_snippets(&config.snippets);
-            self.matcher.reload(flattened_snippets.clone());
-            log::info!("Loaded {} snippets into matcher", flattened_snippets.len());
-        }
+        // Initial load of snippets and settings
+        let count = self.reload_config().await;
+        log::info!("Loaded {} snippets into matcher", count);
 
         loop {
             tokio::select! {
@@ -120,10 +966,8 @@ impl ExpansionEngine {
                 }
                 Some(_) = reload_rx.recv() => {
                     log::info!("Reloading engine configuration...");
-                    let config = self.config.read().await;
-                    let flattened_snippets = crate::config::loader::ConfigManager::flatten_snippets(&config.snippets);
-                    self.matcher.reload(flattened_snippets.clone());
-                    log::info!("Reloaded {} snippets", flattened_snippets.len());
+                    let count = self.reload_config().await;
+                    log::info!("Reloaded {} snippets", count);
                 }
                 else => break,
             }
@@ -135,23 +979,70 @@ impl ExpansionEngine {
     }
 }
 
+/// Play the sound and/or show the notification for an expansion of `trigger`
+fn expansion_feedback(trigger: &str, sound: bool, notify: bool) {
+    if sound {
+        crate::notify::play_sound();
+    }
+    if notify {
+        crate::notify::send_notification("xpander", &format!("Expanded {}", trigger));
+    }
+}
+
+/// How a snippet is listed in the palette: its trigger, then its label or the
+/// start of its replacement
+fn palette_entry(snippet: &Snippet) -> String {
+    let description = match &snippet.label {
+        Some(label) => label.clone(),
+        None => {
+            let replace = snippet.replacement().replace('\n', " ");
+            match replace.char_indices().nth(PALETTE_PREVIEW_MAX_CHARS) {
+                Some((end, _)) => format!("{}…", &replace[..end]),
+                None => replace,
+            }
+        }
+    };
+    format!("{}  {}", snippet.trigger, description)
+}
+
 /// Start the full expansion pipeline
 pub async fn start_expansion_pipeline(
     config: Arc<RwLock<Config>>,
     enabled: Arc<RwLock<bool>>,
     reload_rx: mpsc::Receiver<()>,
+    health_tx: mpsc::Sender<bool>,
+    status: Arc<std::sync::RwLock<EngineStatus>>,
+    debug_keys: bool,
 ) -> Result<()> {
     // Check prerequisites
-    OutputEngine::check_availability().await?;
+    create_backend(&resolve_ydotool_socket(&config.read().await.settings, detect_ydotool_socket))
+        .check_availability()
+        .await?;
 
     // Create the keyboard event channel
     let (event_tx, event_rx) = mpsc::channel::<KeyboardEvent>(256);
 
+    // Shared so the monitor ignores the keystrokes the engine types
+    let output_guard = OutputGuard::default();
+
+    // Layout and hotkey changes go to the monitor on reload
+    let (key_settings_tx, key_settings_rx) =
+        watch::channel(KeySettings::from_settings(&config.read().await.settings));
+
     // Create and start the keyboard monitor
-    let monitor = KeyboardMonitor::new(event_tx, config.clone())?;
+    let monitor = KeyboardMonitor::new(event_tx, config.clone())
+        .await?
+        .with_output_guard(output_guard.clone())
+        .with_key_settings(key_settings_rx);
 
     // Create the expansion engine
-    let engine = ExpansionEngine::new(config, enabled);
+    let engine = ExpansionEngine::new(config, enabled)
+        .with_health_channel(health_tx)
+        .with_status(status)
+        .with_debug_keys(debug_keys)
+        .with_output_guard(output_guard)
+        .with_key_settings(key_settings_tx)
+        .with_usage_stats(UsageStats::load_default());
 
     // Run both in parallel
     tokio::select! {
@@ -173,6 +1064,681 @@ pub async fn start_expansion_pipeline(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::{OutputMethod, OutputMode, Snippet};
+    use crate::engine::backends::{OutputOp, RecordingBackend};
+
+    /// Build an engine loaded with `snippets` whose output is recorded instead of typed
+    fn recording_engine(snippets: Vec<Snippet>) -> (ExpansionEngine, RecordingBackend) {
+        let config = Config::default();
+        let enabled = Arc::new(RwLock::new(true));
+        let mut engine = ExpansionEngine::new(Arc::new(RwLock::new(config)), enabled);
+        engine.matcher.reload(snippets);
+
+        let backend = RecordingBackend::new();
+        engine.output = Box::new(backend.clone());
+        (engine, backend)
+    }
+
+    /// Feed text to the engine the way the keyboard monitor would
+    async fn type_text(engine: &mut ExpansionEngine, text: &str) {
+        for ch in text.chars() {
+            let event = if ch == ' ' || ch.is_ascii_punctuation() {
+                KeyboardEvent::WordBoundary(ch)
+            } else {
+                KeyboardEvent::Character(ch)
+            };
+            engine.process_event(event).await.unwrap();
+        }
+    }
+
+    fn typed(text: &str) -> OutputOp {
+        OutputOp::Type(text.to_string())
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_basic_expansion() {
+        let (mut engine, log) = recording_engine(vec![Snippet::new(";email", "test@example.com")]);
+
+        type_text(&mut engine, "hi ;emai").await;
+        assert!(log.ops().is_empty());
+
+        type_text(&mut engine, "l").await;
+        assert_eq!(
+            log.ops(),
+            vec![OutputOp::Backspaces(6), typed("test@example.com")]
+        );
+
+        // The buffer starts afresh after the replacement
+        assert_eq!(engine.matcher.buffer(), "");
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_backspace() {
+        let (mut engine, log) = recording_engine(vec![Snippet::new(";ok", "okay")]);
+
+        type_text(&mut engine, ";oj").await;
+        engine.process_event(KeyboardEvent::Backspace).await.unwrap();
+        engine.process_event(KeyboardEvent::Backspace).await.unwrap();
+        type_text(&mut engine, "ok").await;
+
+        assert_eq!(log.ops()[1], typed("okay"));
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_navigation_clears_buffer() {
+        let (mut engine, log) = recording_engine(vec![Snippet::new(";ok", "okay")]);
+
+        // `;o`, Left, `k` leaves `;ko` on screen, which must not expand
+        type_text(&mut engine, ";o").await;
+        engine.process_event(KeyboardEvent::Navigation).await.unwrap();
+        assert_eq!(engine.matcher.buffer(), "");
+
+        type_text(&mut engine, "k").await;
+        assert!(log.ops().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_case_propagation() {
+        let snippet = Snippet::new(";btw", "by the way").with_case_propagation();
+        let (mut engine, log) = recording_engine(vec![snippet]);
+
+        type_text(&mut engine, ";BTW").await;
+        assert_eq!(
+            log.ops(),
+            vec![OutputOp::Backspaces(4), typed("BY THE WAY")]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_cursor_position() {
+        let snippet = Snippet::new(";link", "[$|$](url)").with_cursor_position();
+        let (mut engine, log) = recording_engine(vec![snippet]);
+
+        type_text(&mut engine, ";link").await;
+        assert_eq!(
+            log.ops(),
+            vec![OutputOp::Backspaces(5), typed("[](url)"), OutputOp::CursorLeft(6)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_regex_snippet() {
+        let mut snippet = Snippet::new(r";n(\d+)x", "Number $1");
+        snippet.regex = true;
+        let (mut engine, log) = recording_engine(vec![snippet]);
+
+        type_text(&mut engine, ";n42x").await;
+        assert_eq!(
+            log.ops(),
+            vec![OutputOp::Backspaces(5), typed("Number 42")]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_keep_prefix() {
+        let mut snippet = Snippet::new("#todo", "TODO:");
+        snippet.keep_prefix_len = 1;
+        let (mut engine, log) = recording_engine(vec![snippet]);
+
+        type_text(&mut engine, "#todo").await;
+        assert_eq!(log.ops(), vec![OutputOp::Backspaces(4), typed("TODO:")]);
+        assert_eq!(engine.matcher.buffer(), "");
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_replacement_fires_once() {
+        // The kept `#` and the replacement's tail would spell the trigger again
+        // with the next keys
+        let mut snippet = Snippet::new("#todo", "todo #");
+        snippet.keep_prefix_len = 1;
+        let (mut engine, log) = recording_engine(vec![snippet, Snippet::new(";re", "re;re")]);
+
+        type_text(&mut engine, "#todo").await;
+        type_text(&mut engine, "todo").await;
+        assert_eq!(log.ops(), vec![OutputOp::Backspaces(4), typed("todo #")]);
+
+        type_text(&mut engine, " ;re").await;
+        type_text(&mut engine, "re").await;
+        assert_eq!(
+            log.ops()[2..],
+            [OutputOp::Backspaces(3), typed("re;re")]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_whole_word() {
+        let mut snippet = Snippet::new("ok", "OK");
+        snippet.whole_word = true;
+        let (mut engine, log) = recording_engine(vec![snippet]);
+
+        type_text(&mut engine, "okay book ").await;
+        assert!(log.ops().is_empty());
+
+        // The trigger and the space after it are deleted, then both typed again
+        type_text(&mut engine, "ok ").await;
+        assert_eq!(log.ops(), vec![OutputOp::Backspaces(3), typed("OK ")]);
+        // Only the space is kept, so the next word can fire
+        assert_eq!(engine.matcher.buffer(), " ");
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_auto_capitalize() {
+        let (mut engine, log) = recording_engine(vec![Snippet::new("btw", "by the way")]);
+        engine.auto_capitalize = true;
+
+        type_text(&mut engine, "it works. so does this! e.g. that").await;
+        assert_eq!(
+            log.ops(),
+            vec![
+                OutputOp::Backspaces(1),
+                typed("S"),
+                OutputOp::Backspaces(1),
+                typed("E"),
+            ]
+        );
+
+        // A trigger starting a sentence still expands, deleting the capital too
+        type_text(&mut engine, ". btw").await;
+        assert_eq!(
+            log.ops()[4..],
+            [OutputOp::Backspaces(1), typed("B"), OutputOp::Backspaces(3), typed("by the way")]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_double_space_period() {
+        let (mut engine, log) = recording_engine(vec![]);
+        engine.double_space_period = true;
+
+        // A single space is left alone
+        type_text(&mut engine, "it works ").await;
+        assert!(log.ops().is_empty());
+
+        type_text(&mut engine, " ").await;
+        assert_eq!(log.ops(), vec![OutputOp::Backspaces(2), typed(". ")]);
+        assert_eq!(engine.matcher.buffer(), "it works. ");
+
+        // A third space is just a space
+        type_text(&mut engine, " ").await;
+        assert_eq!(log.ops().len(), 2);
+
+        // Together with auto_capitalize, the next sentence starts with a capital
+        engine.auto_capitalize = true;
+        engine.process_event(KeyboardEvent::Escape).await.unwrap();
+        type_text(&mut engine, "done  so").await;
+        assert_eq!(
+            log.ops()[2..],
+            [OutputOp::Backspaces(2), typed(". "), OutputOp::Backspaces(1), typed("S")]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_clipboard_set() {
+        static CLIPBOARD: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+        let snippet = Snippet::new(";api", "Copied! {{clipboard_set:secret}}");
+        let (mut engine, log) = recording_engine(vec![snippet]);
+        engine.set_clipboard = |text| CLIPBOARD.lock().unwrap().push(text.to_string());
+
+        type_text(&mut engine, ";api").await;
+        assert_eq!(log.ops(), vec![OutputOp::Backspaces(4), typed("Copied! ")]);
+        assert_eq!(*CLIPBOARD.lock().unwrap(), vec!["secret".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_snippet_keystroke_delay() {
+        static DELAYS: std::sync::Mutex<Vec<u64>> = std::sync::Mutex::new(Vec::new());
+        static SLOW: std::sync::LazyLock<RecordingBackend> = std::sync::LazyLock::new(RecordingBackend::new);
+
+        let mut slow = Snippet::new(";slow", "careful");
+        slow.keystroke_delay_ms = Some(50);
+        let (mut engine, log) = recording_engine(vec![Snippet::new(";fast", "quick"), slow]);
+        engine.create_backend = |settings| {
+            DELAYS.lock().unwrap().push(settings.keystroke_delay_ms);
+            Box::new(SLOW.clone())
+        };
+
+        // Without an override the global backend types it
+        type_text(&mut engine, ";fast").await;
+        assert_eq!(log.ops(), vec![OutputOp::Backspaces(5), typed("quick")]);
+        assert!(DELAYS.lock().unwrap().is_empty());
+
+        type_text(&mut engine, " ;slow").await;
+        assert_eq!(log.ops().len(), 2);
+        assert_eq!(*DELAYS.lock().unwrap(), vec![50]);
+        assert_eq!(SLOW.ops(), vec![OutputOp::Backspaces(5), typed("careful")]);
+
+        // The backend is kept for the next expansion with the same delay
+        type_text(&mut engine, " ;slow").await;
+        assert_eq!(*DELAYS.lock().unwrap(), vec![50]);
+        assert_eq!(SLOW.ops().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_snippet_output_mode() {
+        static METHODS: std::sync::Mutex<Vec<OutputMethod>> = std::sync::Mutex::new(Vec::new());
+        static OVERRIDE: std::sync::LazyLock<RecordingBackend> = std::sync::LazyLock::new(RecordingBackend::new);
+
+        let mut pasted = Snippet::new(";long", "a long paragraph");
+        pasted.output_mode = Some(OutputMode::Paste);
+        let mut typed_snippet = Snippet::new(";pin", "1234");
+        typed_snippet.output_mode = Some(OutputMode::Type);
+        let (mut engine, log) = recording_engine(vec![pasted, typed_snippet]);
+        engine.create_backend = |settings| {
+            METHODS.lock().unwrap().push(settings.output_method);
+            Box::new(OVERRIDE.clone())
+        };
+
+        // Typing is already the global method, so the global backend is kept
+        type_text(&mut engine, ";pin").await;
+        assert_eq!(log.ops(), vec![OutputOp::Backspaces(4), typed("1234")]);
+        assert!(METHODS.lock().unwrap().is_empty());
+
+        type_text(&mut engine, " ;long").await;
+        assert_eq!(*METHODS.lock().unwrap(), vec![OutputMethod::Paste]);
+        assert_eq!(OVERRIDE.ops(), vec![OutputOp::Backspaces(5), typed("a long paragraph")]);
+
+        // With paste as the global method, the snippet that must be typed gets a typing backend
+        engine.config.write().await.settings.output_method = OutputMethod::Paste;
+        type_text(&mut engine, " ;pin").await;
+        assert_eq!(*METHODS.lock().unwrap(), vec![OutputMethod::Paste, OutputMethod::Stdin]);
+        assert_eq!(log.ops().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_explicit_trigger() {
+        let (mut engine, log) = recording_engine(vec![Snippet::new(";sig", "Best, Rafa")]);
+        engine.activation_key = Some(";;".to_string());
+
+        // The match waits for the activation key
+        type_text(&mut engine, ";sig").await;
+        assert!(log.ops().is_empty());
+        type_text(&mut engine, ";").await;
+        assert!(log.ops().is_empty());
+
+        // ...which is deleted along with the trigger
+        type_text(&mut engine, ";").await;
+        assert_eq!(log.ops(), vec![OutputOp::Backspaces(6), typed("Best, Rafa")]);
+        assert_eq!(engine.matcher.buffer(), "");
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_explicit_trigger_cancelled() {
+        let (mut engine, log) = recording_engine(vec![Snippet::new(";sig", "Best, Rafa")]);
+        engine.activation_key = Some(";;".to_string());
+
+        // Typing on cancels the pending match
+        type_text(&mut engine, ";signal;;").await;
+        assert!(log.ops().is_empty());
+
+        // So does a backspace, even if the activation key follows
+        type_text(&mut engine, " ;sig").await;
+        engine.process_event(KeyboardEvent::Backspace).await.unwrap();
+        type_text(&mut engine, "g;;").await;
+        assert_eq!(log.ops(), vec![OutputOp::Backspaces(6), typed("Best, Rafa")]);
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_fire_on_release() {
+        let (mut engine, log) = recording_engine(vec![Snippet::new(";sig", "Best, Rafa")]);
+        engine.fire_on_release = true;
+
+        // The match waits for the last key to come up, and fires only once
+        type_text(&mut engine, ";sig").await;
+        assert!(log.ops().is_empty());
+        engine.process_event(KeyboardEvent::KeyReleased).await.unwrap();
+        assert_eq!(log.ops(), vec![OutputOp::Backspaces(4), typed("Best, Rafa")]);
+        engine.process_event(KeyboardEvent::KeyReleased).await.unwrap();
+        assert_eq!(log.ops().len(), 2);
+
+        // A key pressed before the release is already on screen, so it is typed again
+        type_text(&mut engine, " ;sig.").await;
+        assert_eq!(log.ops()[2..], [OutputOp::Backspaces(5), typed("Best, Rafa.")]);
+        engine.process_event(KeyboardEvent::KeyReleased).await.unwrap();
+        assert_eq!(log.ops().len(), 4);
+
+        // Anything else cancels the held match
+        type_text(&mut engine, " ;sig").await;
+        engine.process_event(KeyboardEvent::Backspace).await.unwrap();
+        engine.process_event(KeyboardEvent::KeyReleased).await.unwrap();
+        assert_eq!(log.ops().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_leader_key() {
+        let snippets = vec![Snippet::new(";email", "a@b.c"), Snippet::new("brb", "be right back")];
+        let (mut engine, log) = recording_engine(snippets.clone());
+        engine.loaded_snippets = snippets;
+        engine.leader_codes = BTreeMap::from([("e".to_string(), ";email".to_string())]);
+
+        // Without the leader key a code is just text
+        type_text(&mut engine, "e ").await;
+        assert!(log.ops().is_empty());
+
+        // The code and the space ending it are replaced, and the space typed again
+        engine.process_event(KeyboardEvent::Leader).await.unwrap();
+        type_text(&mut engine, "e ").await;
+        assert_eq!(log.ops(), vec![OutputOp::Backspaces(2), typed("a@b.c ")]);
+        assert!(engine.leader.is_none());
+
+        // Triggers don't fire in leader mode, and an unknown code just ends it
+        engine.process_event(KeyboardEvent::Leader).await.unwrap();
+        type_text(&mut engine, "brb ").await;
+        assert_eq!(log.ops().len(), 2);
+        assert!(engine.leader.is_none());
+        type_text(&mut engine, "brb").await;
+        assert_eq!(log.ops()[2..], [OutputOp::Backspaces(3), typed("be right back")]);
+
+        // Backspace edits the code; Escape leaves leader mode
+        engine.process_event(KeyboardEvent::Leader).await.unwrap();
+        type_text(&mut engine, "x").await;
+        engine.process_event(KeyboardEvent::Backspace).await.unwrap();
+        type_text(&mut engine, "e.").await;
+        assert_eq!(log.ops()[4..], [OutputOp::Backspaces(2), typed("a@b.c.")]);
+        engine.process_event(KeyboardEvent::Leader).await.unwrap();
+        engine.process_event(KeyboardEvent::Escape).await.unwrap();
+        type_text(&mut engine, "e ").await;
+        assert_eq!(log.ops().len(), 6);
+    }
+
+    #[tokio::test]
+    async fn test_palette_inserts_chosen_snippet() {
+        let mut sig = Snippet::new(";sig", "Best, Rafa");
+        sig.label = Some("Signature".to_string());
+        let mut config = Config::default();
+        config.snippets = vec![
+            crate::config::SnippetNode::Snippet(Snippet::new(";addr", "123 Main St")),
+            crate::config::SnippetNode::Snippet(sig),
+        ];
+        let (mut engine, log) = recording_engine(Vec::new());
+        engine.config = Arc::new(RwLock::new(config));
+        engine.picker = |entries| {
+            // The most recently used snippet is listed first
+            assert_eq!(entries, [";sig  Signature", ";addr  123 Main St"]);
+            Ok(Some(entries[0].clone()))
+        };
+        engine.usage.record(";sig", 1);
+
+        type_text(&mut engine, "hello").await;
+        engine.process_event(KeyboardEvent::Palette).await.unwrap();
+
+        assert_eq!(log.ops(), vec![typed("Best, Rafa")]);
+        assert_eq!(engine.usage.get(";sig").map(|e| e.count), Some(2));
+        assert!(engine.status.read().unwrap().last_expansion.is_some());
+        assert_eq!(engine.matcher.buffer(), "");
+
+        // Nothing is typed into a password manager
+        engine.config.write().await.settings.disable_in_password_fields = true;
+        engine.active_window = || {
+            Some(window::ActiveWindow {
+                class: "KeePassXC".to_string(),
+                ..Default::default()
+            })
+        };
+        engine.process_event(KeyboardEvent::Palette).await.unwrap();
+        assert_eq!(log.ops().len(), 1);
+        assert_eq!(engine.usage.get(";sig").map(|e| e.count), Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_per_app_replace() {
+        let mut sig = Snippet::new(";sig", "Cheers");
+        sig.per_app_replace = vec![crate::config::schema::AppReplace {
+            applications: vec!["thunderbird".to_string()],
+            replace: "Best regards".to_string(),
+        }];
+        let (mut engine, log) = recording_engine(vec![sig]);
+
+        engine.active_window = || {
+            Some(window::ActiveWindow {
+                class: "thunderbird".to_string(),
+                title: "Write: Re: Invoice".to_string(),
+                ..Default::default()
+            })
+        };
+        type_text(&mut engine, ";sig").await;
+        assert_eq!(log.ops(), vec![OutputOp::Backspaces(4), typed("Best regards")]);
+
+        engine.active_window = || {
+            Some(window::ActiveWindow {
+                class: "firefox".to_string(),
+                title: "Mozilla Firefox".to_string(),
+                ..Default::default()
+            })
+        };
+        type_text(&mut engine, " ;sig").await;
+        assert_eq!(log.ops()[2..], [OutputOp::Backspaces(4), typed("Cheers")]);
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_window_variable() {
+        let (mut engine, log) = recording_engine(vec![Snippet::new(";from", "(from {{window:class}})")]);
+
+        // The engine's own lookup is what the variable inserts
+        engine.active_window = || {
+            Some(window::ActiveWindow {
+                class: "thunderbird".to_string(),
+                ..Default::default()
+            })
+        };
+        type_text(&mut engine, ";from").await;
+        assert_eq!(log.ops(), vec![OutputOp::Backspaces(5), typed("(from thunderbird)")]);
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_cycle() {
+        let mut arrow = Snippet::new(";ar", "");
+        arrow.cycle = vec!["→".to_string(), "⇒".to_string(), "⟶".to_string()];
+        let (mut engine, log) = recording_engine(vec![arrow]);
+
+        type_text(&mut engine, ";ar").await;
+        assert_eq!(log.ops(), vec![OutputOp::Backspaces(3), typed("→")]);
+
+        // Typing the trigger again right away replaces the output, wrapping around
+        type_text(&mut engine, ";ar;ar;ar").await;
+        assert_eq!(
+            log.ops()[2..],
+            [
+                OutputOp::Backspaces(4),
+                typed("⇒"),
+                OutputOp::Backspaces(4),
+                typed("⟶"),
+                OutputOp::Backspaces(4),
+                typed("→"),
+            ]
+        );
+
+        // Anything else typed in between starts over
+        type_text(&mut engine, " ;ar").await;
+        assert_eq!(log.ops()[8..], [OutputOp::Backspaces(3), typed("→")]);
+
+        engine.process_event(KeyboardEvent::Backspace).await.unwrap();
+        type_text(&mut engine, ";ar").await;
+        assert_eq!(log.ops()[10..], [OutputOp::Backspaces(3), typed("→")]);
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_disabled() {
+        let (mut engine, log) = recording_engine(vec![Snippet::new(";x", "never")]);
+        *engine.enabled.write().await = false;
+
+        type_text(&mut engine, ";x").await;
+        assert!(log.ops().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_schedule() {
+        let mut snippet = Snippet::new(";sig", "Work signature");
+        snippet.active_schedule = Some(crate::config::schema::Schedule {
+            start: Some("09:00".to_string()),
+            end: Some("17:00".to_string()),
+            ..Default::default()
+        });
+        let (mut engine, log) = recording_engine(vec![snippet]);
+
+        engine.clock = || NaiveDateTime::parse_from_str("2024-01-03 20:00", "%Y-%m-%d %H:%M").unwrap();
+        type_text(&mut engine, ";sig").await;
+        assert!(log.ops().is_empty());
+
+        engine.matcher.clear();
+        engine.clock = || NaiveDateTime::parse_from_str("2024-01-03 10:00", "%Y-%m-%d %H:%M").unwrap();
+        type_text(&mut engine, ";sig").await;
+        assert_eq!(log.ops(), vec![OutputOp::Backspaces(4), typed("Work signature")]);
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_suspend_when_fullscreen() {
+        let (mut engine, log) = recording_engine(vec![Snippet::new(";sig", "Cheers")]);
+        engine.config.write().await.settings.suspend_when_fullscreen = true;
+
+        engine.active_window = || {
+            Some(window::ActiveWindow {
+                class: "mpv".to_string(),
+                fullscreen: Some(true),
+                ..Default::default()
+            })
+        };
+        type_text(&mut engine, ";sig").await;
+        assert!(log.ops().is_empty());
+
+        engine.active_window = || None;
+        type_text(&mut engine, ";sig").await;
+        assert_eq!(log.ops(), vec![OutputOp::Backspaces(4), typed("Cheers")]);
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_application_filter() {
+        let mut snippet = Snippet::new(";pr", "Fixes #");
+        snippet.applications = Some(vec!["title:/GitHub/".to_string()]);
+        let (mut engine, log) = recording_engine(vec![snippet]);
+
+        engine.active_window = || {
+            Some(window::ActiveWindow {
+                class: "firefox".to_string(),
+                title: "Inbox - Mail".to_string(),
+                ..Default::default()
+            })
+        };
+        type_text(&mut engine, ";pr").await;
+        assert!(log.ops().is_empty());
+
+        engine.matcher.clear();
+        engine.active_window = || {
+            Some(window::ActiveWindow {
+                class: "firefox".to_string(),
+                title: "Issues · GitHub".to_string(),
+                ..Default::default()
+            })
+        };
+        type_text(&mut engine, ";pr").await;
+        assert_eq!(log.ops(), vec![OutputOp::Backspaces(3), typed("Fixes #")]);
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_rate_limit() {
+        let (mut engine, log) = recording_engine(vec![Snippet::new(";x", "y")]);
+        engine.rate_limiter.set_limit(3);
+
+        for _ in 0..5 {
+            type_text(&mut engine, ";x").await;
+        }
+
+        // Three expansions went through, then the breaker tripped
+        let expansions = log.ops().iter().filter(|op| **op == typed("y")).count();
+        assert_eq!(expansions, 3);
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_feedback_cooldown() {
+        static FEEDBACK: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+        let (mut engine, log) = recording_engine(vec![Snippet::new(";x", "y")]);
+        engine.feedback = |trigger, sound, notify| FEEDBACK.lock().unwrap().push(format!("{} {} {}", trigger, sound, notify));
+        engine.enable_sound = true;
+        engine.feedback_cooldown.set_interval(Duration::from_secs(60));
+
+        for _ in 0..5 {
+            type_text(&mut engine, ";x").await;
+        }
+
+        // Every expansion was typed, with a single sound for the burst
+        assert_eq!(log.ops().iter().filter(|op| **op == typed("y")).count(), 5);
+        assert_eq!(*FEEDBACK.lock().unwrap(), vec![";x true false".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_min_trigger_chars() {
+        let mut opted_in = Snippet::new("ly", "LY");
+        opted_in.word_boundary = Some(false);
+        let (mut engine, log) = recording_engine(vec![Snippet::new("ty", "thank you"), opted_in]);
+        engine.min_trigger_chars = 3;
+
+        // Inside a word the short trigger is held back
+        type_text(&mut engine, "pretty").await;
+        assert!(log.ops().is_empty());
+
+        // At the start of a word it still fires
+        type_text(&mut engine, " ty").await;
+        assert_eq!(log.ops(), vec![OutputOp::Backspaces(2), typed("thank you")]);
+
+        // A snippet that turns word_boundary off itself keeps firing mid-word
+        type_text(&mut engine, " only").await;
+        assert_eq!(log.ops()[2..], [OutputOp::Backspaces(2), typed("LY")]);
+
+        // Without the policy, "ty" fires anywhere
+        engine.min_trigger_chars = 0;
+        type_text(&mut engine, " pretty").await;
+        assert_eq!(log.ops()[4..], [OutputOp::Backspaces(2), typed("thank you")]);
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_trigger_after_immediate_expansion() {
+        let mut heading = Snippet::new("##", "<h2>");
+        heading.line_start = true;
+        let snippets = vec![
+            Snippet::new(";co", "Acme Co"),
+            Snippet::new(";nl", "Notes:\n"),
+            Snippet::new("ty", "thank you"),
+            heading,
+        ];
+        let (mut engine, log) = recording_engine(snippets);
+        engine.min_trigger_chars = 3;
+
+        // The replacement ends mid-line and mid-word, so neither trigger may fire
+        // right after it
+        type_text(&mut engine, ";co##").await;
+        assert_eq!(log.ops(), vec![OutputOp::Backspaces(3), typed("Acme Co")]);
+        type_text(&mut engine, ";coty").await;
+        assert_eq!(log.ops().len(), 4);
+
+        // After a replacement ending in a newline, a line_start trigger fires
+        type_text(&mut engine, " ;nl##").await;
+        assert_eq!(log.ops()[4..], [OutputOp::Backspaces(3), typed("Notes:\n"), OutputOp::Backspaces(2), typed("<h2>")]);
+    }
+
+    #[tokio::test]
+    async fn test_output_failure_marks_degraded() {
+        let (mut engine, _log) = recording_engine(vec![Snippet::new(";x", "y")]);
+        let (health_tx, mut health_rx) = mpsc::channel(4);
+        engine.health_tx = Some(health_tx);
+        engine.output = Box::new(RecordingBackend::failing());
+
+        type_text(&mut engine, ";").await;
+        assert!(engine.process_event(KeyboardEvent::Character('x')).await.is_err());
+        assert!(engine.output_degraded);
+        assert_eq!(health_rx.try_recv().ok(), Some(true));
+
+        // A further failure doesn't notify again
+        type_text(&mut engine, ";").await;
+        let _ = engine.process_event(KeyboardEvent::Character('x')).await;
+        assert!(health_rx.try_recv().is_err());
+
+        // Recovers on the next successful expansion
+        engine.output = Box::new(RecordingBackend::new());
+        type_text(&mut engine, ";x").await;
+        assert!(!engine.output_degraded);
+        assert_eq!(health_rx.try_recv().ok(), Some(false));
+    }
 
     #[tokio::test]
     async fn test_expansion_engine_creation() {
@@ -180,4 +1746,95 @@ mod tests {
         let enabled = Arc::new(RwLock::new(true));
         let _engine = ExpansionEngine::new(config, enabled);
     }
+
+    #[tokio::test]
+    async fn test_reload_applies_output_settings() {
+        let config = Arc::new(RwLock::new(Config::default()));
+        let enabled = Arc::new(RwLock::new(true));
+        let mut engine = ExpansionEngine::new(config.clone(), enabled);
+        engine.detect_ydotool_socket = || Some("/run/detected.sock".to_string());
+
+        engine.reload_config().await;
+        assert_eq!(engine.output.keystroke_delay(), 12);
+        assert_eq!(engine.output.socket_path(), Some("/run/detected.sock"));
+
+        {
+            let mut cfg = config.write().await;
+            cfg.settings.keystroke_delay_ms = 40;
+            cfg.settings.ydotool_socket = Some("/tmp/custom.sock".to_string());
+        }
+        engine.reload_config().await;
+
+        assert_eq!(engine.output.keystroke_delay(), 40);
+        assert_eq!(engine.output.socket_path(), Some("/tmp/custom.sock"));
+
+        config.write().await.settings.output_backend = crate::config::OutputBackendKind::Wtype;
+        engine.reload_config().await;
+        assert_eq!(engine.output.keystroke_delay(), 40);
+        assert_eq!(engine.output.socket_path(), None);
+    }
+
+    #[tokio::test]
+    async fn test_reload_notifies_key_settings() {
+        let config = Arc::new(RwLock::new(Config::default()));
+        let (tx, mut rx) = watch::channel(KeySettings::from_settings(&config.read().await.settings));
+        let mut engine = ExpansionEngine::new(config.clone(), Arc::new(RwLock::new(true))).with_key_settings(tx);
+
+        // A reload that leaves layout and hotkeys alone doesn't wake the monitor
+        config.write().await.settings.keystroke_delay_ms = 40;
+        engine.reload_config().await;
+        assert!(!rx.has_changed().unwrap());
+
+        config.write().await.settings.layout = "dvorak".to_string();
+        engine.reload_config().await;
+        assert!(rx.has_changed().unwrap());
+        assert_eq!(rx.borrow_and_update().layout, "dvorak");
+
+        config.write().await.settings.palette_hotkey = Some("ctrl+alt+p".to_string());
+        engine.reload_config().await;
+        assert!(rx.has_changed().unwrap());
+        assert_eq!(rx.borrow_and_update().palette_hotkey.as_deref(), Some("ctrl+alt+p"));
+    }
+
+    #[tokio::test]
+    async fn test_variables_only_reload_keeps_matcher() {
+        let mut config = Config::default();
+        config.snippets = vec![crate::config::SnippetNode::Snippet(Snippet::new(";a", "{{name}}"))];
+        let config = Arc::new(RwLock::new(config));
+        let mut engine = ExpansionEngine::new(config.clone(), Arc::new(RwLock::new(true)));
+        engine.reload_config().await;
+
+        // Swap in a probe snippet: it survives only if the Trie isn't rebuilt
+        engine.matcher.reload(vec![Snippet::new(";probe", "x")]);
+        config.write().await.variables = serde_yaml::from_str("name: Rafa").unwrap();
+        engine.reload_config().await;
+        ";probe".chars().for_each(|ch| engine.matcher.push_char(ch));
+        assert!(engine.matcher.check_match().is_some());
+
+        // Adding a snippet loads it into the matcher
+        engine.matcher.clear();
+        config.write().await.snippets.push(crate::config::SnippetNode::Snippet(Snippet::new(";b", "b")));
+        engine.reload_config().await;
+        ";b".chars().for_each(|ch| engine.matcher.push_char(ch));
+        assert!(engine.matcher.check_match().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_secure_input_toggle() {
+        let config = Arc::new(RwLock::new(Config::default()));
+        let enabled = Arc::new(RwLock::new(true));
+        let mut engine = ExpansionEngine::new(config, enabled);
+        engine.matcher.reload(vec![crate::config::Snippet::new(";x", "never")]);
+
+        engine.process_event(KeyboardEvent::ToggleSecureInput).await.unwrap();
+        assert!(engine.secure_input);
+
+        // While secure, the trigger is swallowed instead of expanded
+        engine.process_event(KeyboardEvent::WordBoundary(';')).await.unwrap();
+        engine.process_event(KeyboardEvent::Character('x')).await.unwrap();
+        assert!(engine.matcher.buffer().is_empty());
+
+        engine.process_event(KeyboardEvent::ToggleSecureInput).await.unwrap();
+        assert!(!engine.secure_input);
+    }
 }
@@ -1,13 +1,19 @@
 use anyhow::{Context, Result};
-use evdev::{Device, EventType, InputEventKind, Key};
+use evdev::{Device, EventType, InputEventKind, Key, LedType};
 use notify::{Config as NotifyConfig, Event, RecommendedWatcher, RecursiveMode, Watcher};
 use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tokio::sync::{mpsc, RwLock};
+use tokio::sync::{mpsc, watch, RwLock};
 
+use crate::config::schema::Settings;
 use crate::config::Config;
-use crate::engine::keymaps::KeyMap;
+use crate::engine::hotkey::{Hotkey, Modifiers};
+use crate::engine::keymaps::{DeadKeys, KeyMap};
+use crate::engine::output::OutputGuard;
+
+/// Where the kernel lists input devices with their names
+const SYS_CLASS_INPUT: &str = "/sys/class/input";
 
 /// Events emitted by the keyboard monitor
 #[derive(Debug, Clone)]
@@ -24,6 +30,41 @@ pub enum KeyboardEvent {
     Tab,
     /// Escape was pressed
     Escape,
+    /// An arrow, Home, End, Page Up/Down or Delete key moved the cursor or changed
+    /// the text around it
+    Navigation,
+    /// The secure input hotkey was pressed
+    ToggleSecureInput,
+    /// The snippet palette hotkey was pressed
+    Palette,
+    /// The leader key was pressed
+    Leader,
+    /// The key that typed the last character was released (only sent with
+    /// `settings.fire_on_release`)
+    KeyReleased,
+}
+
+/// The settings the monitor needs while translating keys. Sent by the engine when
+/// they change, so the monitor doesn't lock the config on every keystroke.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeySettings {
+    pub layout: String,
+    pub secure_input_hotkey: Option<String>,
+    pub palette_hotkey: Option<String>,
+    pub leader_key: Option<String>,
+    pub fire_on_release: bool,
+}
+
+impl KeySettings {
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self {
+            layout: settings.layout.clone(),
+            secure_input_hotkey: settings.secure_input_hotkey.clone(),
+            palette_hotkey: settings.palette_hotkey.clone(),
+            leader_key: settings.leader_key.clone(),
+            fire_on_release: settings.fire_on_release,
+        }
+    }
 }
 
 /// Keyboard monitor that reads from evdev devices
@@ -31,12 +72,21 @@ pub struct KeyboardMonitor {
     devices: Vec<(Device, PathBuf)>,
     event_tx: mpsc::Sender<KeyboardEvent>,
     config: Arc<RwLock<Config>>,
+    /// Layout and hotkeys, updated by the engine on reload
+    key_settings: watch::Receiver<KeySettings>,
+    /// Set while the engine is typing; key presses seen then are our own output
+    output_guard: OutputGuard,
 }
 
 impl KeyboardMonitor {
     /// Create a new keyboard monitor
-    pub fn new(event_tx: mpsc::Sender<KeyboardEvent>, config: Arc<RwLock<Config>>) -> Result<Self> {
-        let devices = Self::find_keyboard_devices()?;
+    pub async fn new(event_tx: mpsc::Sender<KeyboardEvent>, config: Arc<RwLock<Config>>) -> Result<Self> {
+        let (allowed, key_settings) = {
+            let config = config.read().await;
+            let allowed = config.settings.keyboard_devices.clone().unwrap_or_default();
+            (allowed, KeySettings::from_settings(&config.settings))
+        };
+        let devices = Self::find_keyboard_devices(&allowed)?;
         
         // We don't error if no devices are found initially, as we now support hot-plugging
         if devices.is_empty() {
@@ -50,11 +100,31 @@ impl KeyboardMonitor {
             }
         }
 
-        Ok(Self { devices, event_tx, config })
+        Ok(Self {
+            devices,
+            event_tx,
+            config,
+            // Without an engine to send updates, the settings stay as loaded
+            key_settings: watch::channel(key_settings).1,
+            output_guard: OutputGuard::default(),
+        })
     }
 
-    /// Find all keyboard devices in /dev/input/
-    fn find_keyboard_devices() -> Result<Vec<(Device, PathBuf)>> {
+    /// Builder method to receive layout and hotkey changes from the engine
+    pub fn with_key_settings(mut self, key_settings: watch::Receiver<KeySettings>) -> Self {
+        self.key_settings = key_settings;
+        self
+    }
+
+    /// Builder method to share the engine's output guard
+    pub fn with_output_guard(mut self, output_guard: OutputGuard) -> Self {
+        self.output_guard = output_guard;
+        self
+    }
+
+    /// Find all keyboard devices in /dev/input/, only considering the `allowed`
+    /// devices if any are listed
+    fn find_keyboard_devices(allowed: &[String]) -> Result<Vec<(Device, PathBuf)>> {
         let mut keyboards = Vec::new();
 
         let input_dir = PathBuf::from("/dev/input");
@@ -77,7 +147,10 @@ impl KeyboardMonitor {
                 continue;
             }
 
-            // Try to open the device
+            // Only open devices the settings allow
+            if !Self::is_allowed(allowed, &path) {
+                continue;
+            }
             match Device::open(&path) {
                 Ok(device) => {
                     // Check if this device has keyboard capabilities
@@ -94,8 +167,27 @@ impl KeyboardMonitor {
         Ok(keyboards)
     }
 
+    /// Check the device against the `keyboard_devices` setting, by its path and
+    /// the name sysfs reports, without opening it
+    fn is_allowed(allowed: &[String], path: &Path) -> bool {
+        if allowed.is_empty() {
+            return true;
+        }
+        let name = sysfs_device_name(Path::new(SYS_CLASS_INPUT), path);
+        let is_allowed = device_allowed(allowed, path, name.as_deref());
+        if !is_allowed {
+            log::debug!("Skipping {:?} ({}): not in keyboard_devices", path, name.as_deref().unwrap_or("Unknown"));
+        }
+        is_allowed
+    }
+
     /// Check if a device is a keyboard (has key events for common keys)
     fn is_keyboard(device: &Device) -> bool {
+        if device.name().is_some_and(is_virtual_output_device) {
+            log::debug!("Ignoring virtual output device: {}", device.name().unwrap_or_default());
+            return false;
+        }
+
         let Some(supported_keys) = device.supported_keys() else {
             return false;
         };
@@ -112,14 +204,24 @@ impl KeyboardMonitor {
     }
 
     /// Start monitoring keyboard events
-    pub async fn run(self) -> Result<()> {
+    pub async fn run(mut self) -> Result<()> {
         let mut shift_pressed = false;
         let mut caps_lock = false;
-
-        // Dynamic layout handling
-        let mut current_layout = String::new();
-        // Initialize with default/empty, will be updated in loop
-        let mut key_mapper = KeyMap::new("qwerty");
+        // Start from the keyboard's NumLock LED, since NumLock is often on at login
+        let mut num_lock = self.devices.iter().any(|(device, _)| {
+            device.get_led_state().is_ok_and(|leds| leds.contains(LedType::LED_NUML))
+        });
+        let mut modifiers = Modifiers::default();
+        let mut dead_keys = DeadKeys::default();
+
+        // Layout and hotkeys, rebuilt when the engine sends new settings
+        let mut current_layout = self.key_settings.borrow_and_update().layout.clone();
+        let (mut key_mapper, mut secure_hotkey, mut palette_hotkey) =
+            key_config(&self.key_settings.borrow_and_update());
+        let mut leader_key = parse_leader_key(self.key_settings.borrow().leader_key.as_deref());
+        let mut fire_on_release = self.key_settings.borrow().fire_on_release;
+        // Key whose press was last passed on, reported when released with `fire_on_release`
+        let mut last_input_key = None;
 
         // Channel for internal key events from device reading threads
         let (internal_tx, mut internal_rx) = mpsc::channel::<(Key, i32)>(256);
@@ -146,67 +248,129 @@ impl KeyboardMonitor {
             tokio::select! {
                 // Handle key events
                 Some((key, value)) = internal_rx.recv() => {
-                    // Check for layout change
-                    {
-                        let config = self.config.read().await;
-                        if config.settings.layout != current_layout {
-                            current_layout = config.settings.layout.clone();
-                            key_mapper = KeyMap::new(&current_layout);
+                    // Check for a layout or hotkey change (cheap, no lock unless changed)
+                    if self.key_settings.has_changed().unwrap_or(false) {
+                        let settings = self.key_settings.borrow_and_update().clone();
+                        (key_mapper, secure_hotkey, palette_hotkey) = key_config(&settings);
+                        leader_key = parse_leader_key(settings.leader_key.as_deref());
+                        fire_on_release = settings.fire_on_release;
+                        dead_keys.cancel();
+                        if settings.layout != current_layout {
+                            current_layout = settings.layout;
                             log::info!("Keyboard layout switched to: {}", current_layout);
                         }
                     }
 
-                    // value: 0 = release, 1 = press, 2 = repeat
-                    let is_press = value == 1;
-                    let is_release = value == 0;
+                    let Some(action) = KeyAction::from_value(value) else {
+                        continue;
+                    };
+                    let is_press = action == KeyAction::Press;
 
-                    // Track modifier states
+                    // Track modifier states (a held modifier auto-repeats, and is still down)
                     match key {
                         Key::KEY_LEFTSHIFT | Key::KEY_RIGHTSHIFT => {
-                            shift_pressed = is_press;
+                            shift_pressed = action.is_down();
+                            modifiers.shift = action.is_down();
+                            continue;
+                        }
+                        Key::KEY_LEFTCTRL | Key::KEY_RIGHTCTRL => {
+                            modifiers.ctrl = action.is_down();
+                            continue;
+                        }
+                        Key::KEY_LEFTALT | Key::KEY_RIGHTALT => {
+                            modifiers.alt = action.is_down();
+                            continue;
+                        }
+                        Key::KEY_LEFTMETA | Key::KEY_RIGHTMETA => {
+                            modifiers.super_key = action.is_down();
                             continue;
                         }
                         Key::KEY_CAPSLOCK if is_press => {
                             caps_lock = !caps_lock;
                             continue;
                         }
+                        Key::KEY_NUMLOCK if is_press => {
+                            num_lock = !num_lock;
+                            continue;
+                        }
                         _ => {}
                     }
 
-                    // Only process key presses (not releases or repeats for most keys)
-                    if !is_press {
-                        // Allow backspace repeat
-                        if key != Key::KEY_BACKSPACE || is_release {
-                            continue;
+                    // Ignore keystrokes injected by our own output
+                    if self.output_guard.is_active() {
+                        continue;
+                    }
+
+                    if action == KeyAction::Release && fire_on_release && last_input_key == Some(key) {
+                        last_input_key = None;
+                        if self.event_tx.send(KeyboardEvent::KeyReleased).await.is_err() {
+                            break;
                         }
+                        continue;
+                    }
+
+                    if !emits_input(key, action) {
+                        continue;
                     }
 
                     // Removed debug log for privacy
 
-                    let event = match key {
-                        Key::KEY_BACKSPACE => Some(KeyboardEvent::Backspace),
-                        Key::KEY_ENTER | Key::KEY_KPENTER => Some(KeyboardEvent::Enter),
-                        Key::KEY_TAB => Some(KeyboardEvent::Tab),
-                        Key::KEY_ESC => Some(KeyboardEvent::Escape),
-                        _ => {
-                            if let Some(ch) = key_mapper.map_key(key, shift_pressed, caps_lock) {
-                                if ch == ' ' || ch.is_ascii_punctuation() {
-                                    Some(KeyboardEvent::WordBoundary(ch))
-                                } else {
-                                    Some(KeyboardEvent::Character(ch))
-                                }
+                    let hotkey_event = is_press
+                        .then(|| key_mapper.map_key(key, false, false))
+                        .flatten()
+                        .and_then(|ch| {
+                            if secure_hotkey.as_ref().is_some_and(|h| h.matches(ch, modifiers)) {
+                                Some(KeyboardEvent::ToggleSecureInput)
+                            } else if palette_hotkey.as_ref().is_some_and(|h| h.matches(ch, modifiers)) {
+                                Some(KeyboardEvent::Palette)
                             } else {
                                 None
                             }
+                        });
+                    if let Some(event) = hotkey_event {
+                        if self.event_tx.send(event).await.is_err() {
+                            break;
                         }
-                    };
+                        continue;
+                    }
 
-                    if let Some(event) = event {
-                        if self.event_tx.send(event).await.is_err() {
-                            log::debug!("Event receiver dropped, stopping monitor");
+                    if is_press && leader_key == Some(key) {
+                        dead_keys.cancel();
+                        if self.event_tx.send(KeyboardEvent::Leader).await.is_err() {
                             break;
                         }
+                        continue;
+                    }
+
+                    let event = match key {
+                        Key::KEY_BACKSPACE => Some(KeyboardEvent::Backspace),
+                        Key::KEY_ENTER | Key::KEY_KPENTER => Some(KeyboardEvent::Enter),
+                        Key::KEY_TAB => Some(KeyboardEvent::Tab),
+                        Key::KEY_ESC => Some(KeyboardEvent::Escape),
+                        _ if is_navigation_key(key) => Some(KeyboardEvent::Navigation),
+                        _ => None,
+                    };
+
+                    // A dead key types nothing until the next character, which it may compose with
+                    let chars = if event.is_some() {
+                        dead_keys.cancel();
+                        Vec::new()
+                    } else if let Some(accent) = key_mapper.map_dead_key(key, shift_pressed) {
+                        dead_keys.press(accent).into_iter().collect()
+                    } else {
+                        key_mapper
+                            .map_numpad(key, shift_pressed, num_lock)
+                            .or_else(|| key_mapper.map_key(key, shift_pressed, caps_lock))
+                            .map(|ch| dead_keys.compose(ch))
+                            .unwrap_or_default()
+                    };
+
+                    let events = event.into_iter().chain(chars.into_iter().map(char_event));
+                    if !send_events(&self.event_tx, events).await {
+                        log::debug!("Event receiver dropped, stopping monitor");
+                        break;
                     }
+                    last_input_key = Some(key);
                 }
 
                 // Handle hot-plug events
@@ -218,6 +382,10 @@ impl KeyboardMonitor {
                     // Try to wait a bit for the device to be ready
                     tokio::time::sleep(std::time::Duration::from_millis(500)).await;
 
+                    let allowed = self.config.read().await.settings.keyboard_devices.clone().unwrap_or_default();
+                    if !Self::is_allowed(&allowed, &path) {
+                        continue;
+                    }
                     match Device::open(&path) {
                         Ok(device) => {
                             if Self::is_keyboard(&device) {
@@ -301,10 +469,238 @@ impl KeyboardMonitor {
     }
 }
 
+/// What an evdev key event reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum KeyAction {
+    Press,
+    /// Auto-repeat while the key is held down
+    Repeat,
+    Release,
+}
+
+impl KeyAction {
+    /// Decode an evdev key event value (0 = release, 1 = press, 2 = repeat)
+    fn from_value(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(Self::Release),
+            1 => Some(Self::Press),
+            2 => Some(Self::Repeat),
+            _ => None,
+        }
+    }
+
+    /// Whether the key is held down after this event
+    fn is_down(self) -> bool {
+        self != Self::Release
+    }
+}
+
+/// Whether a (non-modifier) key event is passed on to the engine. Presses are;
+/// releases aren't (but see `KeyboardEvent::KeyReleased`). Auto-repeat only
+/// counts for Backspace, so holding it keeps the buffer in step with the
+/// deleted text, while a held letter is seen once.
+fn emits_input(key: Key, action: KeyAction) -> bool {
+    match action {
+        KeyAction::Press => true,
+        KeyAction::Repeat => key == Key::KEY_BACKSPACE,
+        KeyAction::Release => false,
+    }
+}
+
+/// Keys after which the typed buffer no longer matches the text before the cursor
+fn is_navigation_key(key: Key) -> bool {
+    matches!(
+        key,
+        Key::KEY_LEFT
+            | Key::KEY_RIGHT
+            | Key::KEY_UP
+            | Key::KEY_DOWN
+            | Key::KEY_HOME
+            | Key::KEY_END
+            | Key::KEY_PAGEUP
+            | Key::KEY_PAGEDOWN
+            | Key::KEY_DELETE
+    )
+}
+
+/// The key named by the `leader_key` setting. Only keys that type nothing can lead,
+/// so that the code typed after one is all that's on screen.
+fn leader_key_code(name: &str) -> Option<Key> {
+    let name = name.trim().to_lowercase();
+    if let Some(n) = name.strip_prefix('f').and_then(|n| n.parse::<u16>().ok()) {
+        return (13..=24).contains(&n).then(|| Key::new(Key::KEY_F13.code() + n - 13));
+    }
+    match name.as_str() {
+        "menu" | "compose" => Some(Key::KEY_COMPOSE),
+        "scrolllock" | "scroll_lock" => Some(Key::KEY_SCROLLLOCK),
+        "pause" | "break" => Some(Key::KEY_PAUSE),
+        "insert" => Some(Key::KEY_INSERT),
+        _ => None,
+    }
+}
+
+fn parse_leader_key(name: Option<&str>) -> Option<Key> {
+    let name = name?;
+    let key = leader_key_code(name);
+    if key.is_none() {
+        log::warn!("Invalid leader_key: '{}' (use F13-F24, Menu, ScrollLock, Pause or Insert)", name);
+    }
+    key
+}
+
+/// The event for a typed character
+fn char_event(ch: char) -> KeyboardEvent {
+    if ch == ' ' || ch.is_ascii_punctuation() {
+        KeyboardEvent::WordBoundary(ch)
+    } else {
+        KeyboardEvent::Character(ch)
+    }
+}
+
+/// Send events to the engine in order. Returns false once the receiver is gone.
+async fn send_events(tx: &mpsc::Sender<KeyboardEvent>, events: impl IntoIterator<Item = KeyboardEvent>) -> bool {
+    for event in events {
+        if tx.send(event).await.is_err() {
+            return false;
+        }
+    }
+    true
+}
+
+/// The key map and hotkeys for `settings`
+fn key_config(settings: &KeySettings) -> (KeyMap, Option<Hotkey>, Option<Hotkey>) {
+    (
+        KeyMap::new(&settings.layout),
+        parse_hotkey_setting(settings.secure_input_hotkey.as_deref(), "secure_input_hotkey"),
+        parse_hotkey_setting(settings.palette_hotkey.as_deref(), "palette_hotkey"),
+    )
+}
+
+/// Whether the device at `path` named `name` may be read, given the
+/// `keyboard_devices` setting. An entry matches the device name (ignoring case) or
+/// its path, also through a symlink such as /dev/input/by-id/...
+fn device_allowed(allowed: &[String], path: &Path, name: Option<&str>) -> bool {
+    allowed.is_empty()
+        || allowed.iter().any(|entry| {
+            name.is_some_and(|name| name.eq_ignore_ascii_case(entry))
+                || Path::new(entry) == path
+                || std::fs::canonicalize(entry).is_ok_and(|target| target == path)
+        })
+}
+
+/// The name of the input device at `path` (/dev/input/eventN), read from
+/// `<sys_class_input>/eventN/device/name`
+fn sysfs_device_name(sys_class_input: &Path, path: &Path) -> Option<String> {
+    let event = path.file_name()?;
+    let name = std::fs::read_to_string(sys_class_input.join(event).join("device").join("name")).ok()?;
+    Some(name.trim_end().to_string())
+}
+
+/// Whether a device is the virtual keyboard our output tool types through
+/// (ydotoold creates "ydotoold virtual device")
+fn is_virtual_output_device(name: &str) -> bool {
+    name.to_lowercase().contains("ydotool")
+}
+
+/// Parse an optional hotkey setting, warning (and ignoring it) if it's invalid
+fn parse_hotkey_setting(spec: Option<&str>, setting: &str) -> Option<Hotkey> {
+    let spec = spec?;
+    let hotkey = Hotkey::parse(spec);
+    if hotkey.is_none() {
+        log::warn!("Invalid {}: '{}'", setting, spec);
+    }
+    hotkey
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_virtual_output_device() {
+        assert!(is_virtual_output_device("ydotoold virtual device"));
+        assert!(!is_virtual_output_device("AT Translated Set 2 keyboard"));
+    }
+
+    #[test]
+    fn test_device_allowed() {
+        let path = Path::new("/dev/input/event3");
+        let name = Some("AT Translated Set 2 keyboard");
+        assert!(device_allowed(&[], path, name));
+
+        let allowed = vec!["at translated set 2 keyboard".to_string()];
+        assert!(device_allowed(&allowed, path, name));
+        assert!(!device_allowed(&allowed, Path::new("/dev/input/event7"), Some("Logitech USB Receiver")));
+
+        let allowed = vec!["/dev/input/event7".to_string()];
+        assert!(device_allowed(&allowed, Path::new("/dev/input/event7"), None));
+        assert!(!device_allowed(&allowed, path, name));
+
+        // A symlink to the device counts as its path
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("event9");
+        std::fs::write(&target, "").unwrap();
+        let link = dir.path().join("usb-keyboard-event-kbd");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+        let allowed = vec![link.to_string_lossy().into_owned()];
+        assert!(device_allowed(&allowed, &target.canonicalize().unwrap(), None));
+    }
+
+    #[test]
+    fn test_sysfs_device_name() {
+        let sys = tempfile::tempdir().unwrap();
+        let device = sys.path().join("event3").join("device");
+        std::fs::create_dir_all(&device).unwrap();
+        std::fs::write(device.join("name"), "AT Translated Set 2 keyboard\n").unwrap();
+
+        assert_eq!(
+            sysfs_device_name(sys.path(), Path::new("/dev/input/event3")).as_deref(),
+            Some("AT Translated Set 2 keyboard")
+        );
+        assert_eq!(sysfs_device_name(sys.path(), Path::new("/dev/input/event4")), None);
+    }
+
+    #[test]
+    fn test_key_actions() {
+        assert_eq!(KeyAction::from_value(1), Some(KeyAction::Press));
+        assert_eq!(KeyAction::from_value(2), Some(KeyAction::Repeat));
+        assert_eq!(KeyAction::from_value(0), Some(KeyAction::Release));
+        assert_eq!(KeyAction::from_value(7), None);
+
+        // A held Shift keeps repeating and must stay down
+        assert!(KeyAction::Repeat.is_down());
+        assert!(!KeyAction::Release.is_down());
+
+        // Held Backspace keeps deleting, a held letter is only seen once
+        let held_backspace = [KeyAction::Press, KeyAction::Repeat, KeyAction::Repeat, KeyAction::Release];
+        let emitted = held_backspace.iter().filter(|a| emits_input(Key::KEY_BACKSPACE, **a)).count();
+        assert_eq!(emitted, 3);
+        let emitted = held_backspace.iter().filter(|a| emits_input(Key::KEY_A, **a)).count();
+        assert_eq!(emitted, 1);
+        assert!(!emits_input(Key::KEY_ENTER, KeyAction::Release));
+    }
+
+    #[test]
+    fn test_navigation_keys() {
+        assert!(is_navigation_key(Key::KEY_LEFT));
+        assert!(is_navigation_key(Key::KEY_HOME));
+        assert!(is_navigation_key(Key::KEY_DELETE));
+        assert!(!is_navigation_key(Key::KEY_BACKSPACE));
+        assert!(!is_navigation_key(Key::KEY_A));
+    }
+
+    #[test]
+    fn test_leader_key_names() {
+        assert_eq!(leader_key_code("F13"), Some(Key::KEY_F13));
+        assert_eq!(leader_key_code("f24"), Some(Key::KEY_F24));
+        assert_eq!(leader_key_code(" Menu "), Some(Key::KEY_COMPOSE));
+        assert_eq!(leader_key_code("ScrollLock"), Some(Key::KEY_SCROLLLOCK));
+        // Keys that type text or are used every day can't lead
+        assert_eq!(leader_key_code("f1"), None);
+        assert_eq!(leader_key_code("a"), None);
+        assert_eq!(leader_key_code("space"), None);
+    }
+
     #[test]
     fn test_key_mapper() {
         let mapper = KeyMap::new("qwerty");
@@ -1,41 +1,603 @@
-use anyhow::{Context, Result};
+use anyhow::Result;
+use async_trait::async_trait;
+use std::future::Future;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 use tokio::io::AsyncWriteExt;
 use tokio::process::Command;
 use tokio::time::{sleep, Duration};
 
 use super::expander::ExpansionResult;
+use super::backends::{VirtualKeyboardBackend, WtypeBackend, XdotoolBackend};
+use crate::config::schema::Settings;
+use crate::config::{BackspaceStrategy, OutputBackendKind, OutputMethod, PostAction};
 
-/// Text output engine using ydotool
+/// Where ydotoold puts its socket by default, the last place detection looks
+const DEFAULT_YDOTOOL_SOCKET: &str = "/tmp/.ydotool_socket";
+
+/// File name of ydotoold's socket in the runtime directory
+const YDOTOOL_SOCKET_NAME: &str = ".ydotool_socket";
+
+/// Number of attempts for a ydotool invocation before giving up
+const YDOTOOL_MAX_ATTEMPTS: u32 = 3;
+
+/// Delay before the first retry; doubled after each failed attempt
+const YDOTOOL_RETRY_DELAY_MS: u64 = 50;
+
+/// Errors from the output tools (ydotool, wtype, xdotool)
+#[derive(Debug, thiserror::Error)]
+pub enum OutputError {
+    /// The tool isn't on the PATH
+    #[error("{program} not found. {hint}")]
+    BackendMissing { program: String, hint: String },
+    /// The tool is installed but can't type yet (e.g. ydotoold isn't running)
+    #[error("{0}")]
+    NotReady(String),
+    /// The binary could not be spawned or talked to - retrying won't help
+    #[error("failed to run {program}: {source}")]
+    Spawn {
+        program: String,
+        #[source]
+        source: std::io::Error,
+    },
+    /// The command ran but exited with an error (e.g. ydotoold busy, socket not ready)
+    #[error("{program} failed: {stderr}")]
+    CommandFailed { program: String, stderr: String },
+}
+
+impl OutputError {
+    /// Whether the failure may go away if we try again
+    pub fn is_transient(&self) -> bool {
+        matches!(self, OutputError::CommandFailed { .. })
+    }
+
+    pub(crate) fn spawn(program: &str, source: std::io::Error) -> Self {
+        OutputError::Spawn {
+            program: program.to_string(),
+            source,
+        }
+    }
+}
+
+/// Run an operation, retrying transient failures with exponential backoff
+async fn retry_with_backoff<F, Fut>(max_attempts: u32, initial_delay: Duration, mut op: F) -> Result<(), OutputError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<(), OutputError>>,
+{
+    let mut delay = initial_delay;
+    let mut attempt = 1;
+
+    loop {
+        match op().await {
+            Ok(()) => return Ok(()),
+            Err(e) if e.is_transient() && attempt < max_attempts => {
+                log::warn!("{} (attempt {}/{}), retrying in {:?}", e, attempt, max_attempts, delay);
+                sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Pause between putting text on the clipboard and pressing Ctrl+V, since the
+/// clipboard is set from a background thread
+const PASTE_SETTLE_DELAY: Duration = Duration::from_millis(30);
+
+/// Clipboard contents saved before pasting, to put back afterwards
+#[derive(Debug)]
+pub struct ClipboardSnapshot {
+    /// Text on the clipboard before the paste (`None` if empty or not text)
+    saved: Option<String>,
+    /// Text we put on the clipboard to paste
+    pasted: String,
+}
+
+/// What to do with the clipboard once a paste has finished
+#[derive(Debug, PartialEq, Eq)]
+pub enum ClipboardRestore {
+    /// Put the saved text back
+    Restore(String),
+    /// The clipboard held no text before, so there is nothing to put back
+    NothingSaved,
+    /// The clipboard changed since we pasted (the user copied something), keep it
+    ChangedSincePaste,
+}
+
+impl ClipboardSnapshot {
+    pub fn new(saved: Option<String>, pasted: &str) -> Self {
+        Self {
+            saved,
+            pasted: pasted.to_string(),
+        }
+    }
+
+    /// Decide how to restore, given what is on the clipboard now
+    pub fn restore(self, current: Option<&str>) -> ClipboardRestore {
+        if current != Some(self.pasted.as_str()) {
+            return ClipboardRestore::ChangedSincePaste;
+        }
+        match self.saved {
+            Some(text) => ClipboardRestore::Restore(text),
+            None => ClipboardRestore::NothingSaved,
+        }
+    }
+}
+
+/// Read text from the system clipboard, if it holds any
+fn read_clipboard() -> Option<String> {
+    arboard::Clipboard::new().and_then(|mut clipboard| clipboard.get_text()).ok()
+}
+
+/// Put `text` on the primary selection and wait for it to settle before the caller
+/// pastes it. Returns false (after logging) if the session has no primary selection.
+pub(crate) async fn offer_primary_selection(set_primary: fn(&str) -> bool, text: &str) -> bool {
+    if !set_primary(text) {
+        log::warn!("No primary selection available, typing the replacement instead");
+        return false;
+    }
+    sleep(PASTE_SETTLE_DELAY).await;
+    true
+}
+
+/// How long after output finishes its keystrokes may still be arriving at the monitor
+const OUTPUT_GRACE_PERIOD: Duration = Duration::from_millis(50);
+
+/// Shared flag that is set while xpander is typing, so the keyboard monitor can
+/// ignore the injected keystrokes instead of feeding them back into the matcher
+#[derive(Clone, Default)]
+pub struct OutputGuard {
+    in_flight: Arc<AtomicUsize>,
+    finished_at: Arc<Mutex<Option<Instant>>>,
+}
+
+/// Marks output as in flight until dropped
+pub struct OutputInFlight {
+    guard: OutputGuard,
+}
+
+impl OutputGuard {
+    /// Mark output as in flight until the returned value is dropped
+    pub fn begin(&self) -> OutputInFlight {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        OutputInFlight { guard: self.clone() }
+    }
+
+    /// Whether keystrokes seen now are likely our own output
+    pub fn is_active(&self) -> bool {
+        self.is_active_at(Instant::now())
+    }
+
+    fn is_active_at(&self, now: Instant) -> bool {
+        if self.in_flight.load(Ordering::SeqCst) > 0 {
+            return true;
+        }
+        self.finished_at
+            .lock()
+            .ok()
+            .and_then(|finished| *finished)
+            .is_some_and(|finished| now.duration_since(finished) < OUTPUT_GRACE_PERIOD)
+    }
+}
+
+impl Drop for OutputInFlight {
+    fn drop(&mut self) {
+        if let Ok(mut finished) = self.guard.finished_at.lock() {
+            *finished = Some(Instant::now());
+        }
+        self.guard.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Non-character keys an expansion may need to press
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpecialKey {
+    Enter,
+    Tab,
+    Escape,
+    Backspace,
+    Delete,
+    Space,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+}
+
+impl SpecialKey {
+    /// Parse a key name from a macro step, e.g. `tab` or `page_down`
+    pub fn from_name(name: &str) -> Option<Self> {
+        let key = match name.trim().to_lowercase().replace('-', "_").as_str() {
+            "enter" | "return" => SpecialKey::Enter,
+            "tab" => SpecialKey::Tab,
+            "escape" | "esc" => SpecialKey::Escape,
+            "backspace" => SpecialKey::Backspace,
+            "delete" | "del" => SpecialKey::Delete,
+            "space" => SpecialKey::Space,
+            "up" => SpecialKey::Up,
+            "down" => SpecialKey::Down,
+            "left" => SpecialKey::Left,
+            "right" => SpecialKey::Right,
+            "home" => SpecialKey::Home,
+            "end" => SpecialKey::End,
+            "page_up" | "pageup" => SpecialKey::PageUp,
+            "page_down" | "pagedown" => SpecialKey::PageDown,
+            _ => return None,
+        };
+        Some(key)
+    }
+
+    /// The key's X keysym name, as ydotool, wtype and xdotool take it
+    pub fn keysym(self) -> &'static str {
+        match self {
+            SpecialKey::Enter => "Return",
+            SpecialKey::Tab => "Tab",
+            SpecialKey::Escape => "Escape",
+            SpecialKey::Backspace => "BackSpace",
+            SpecialKey::Delete => "Delete",
+            SpecialKey::Space => "space",
+            SpecialKey::Up => "Up",
+            SpecialKey::Down => "Down",
+            SpecialKey::Left => "Left",
+            SpecialKey::Right => "Right",
+            SpecialKey::Home => "Home",
+            SpecialKey::End => "End",
+            SpecialKey::PageUp => "Page_Up",
+            SpecialKey::PageDown => "Page_Down",
+        }
+    }
+}
+
+/// A macro snippet's step, with its text expanded and its key name resolved
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MacroAction {
+    Type(String),
+    Press(SpecialKey),
+    /// Wait this many milliseconds
+    Wait(u64),
+}
+
+impl From<PostAction> for SpecialKey {
+    fn from(action: PostAction) -> Self {
+        match action {
+            PostAction::PressEnter => SpecialKey::Enter,
+            PostAction::PressTab => SpecialKey::Tab,
+        }
+    }
+}
+
+/// A tool that can send keystrokes to the focused window
+#[async_trait]
+pub trait OutputBackend: Send + Sync {
+    /// Type `text` as if entered on the keyboard
+    async fn type_text(&self, text: &str) -> Result<()>;
+
+    /// Press Backspace `count` times in one go
+    async fn press_backspaces(&self, count: usize) -> Result<()>;
+
+    /// Press Left `count` times
+    async fn move_cursor_left(&self, count: usize) -> Result<()>;
+
+    /// Press Shift+Left `count` times, selecting text before the cursor
+    async fn select_left(&self, count: usize) -> Result<()>;
+
+    /// Press a single special key
+    async fn press_key(&self, key: SpecialKey) -> Result<()>;
+
+    /// Check that the backend's tool is installed and usable
+    async fn check_availability(&self) -> Result<()>;
+
+    /// Delay between keystrokes in milliseconds
+    fn keystroke_delay(&self) -> u64;
+
+    /// How `send_backspaces` deletes characters
+    fn backspace_strategy(&self) -> BackspaceStrategy {
+        BackspaceStrategy::Repeat
+    }
+
+    /// Delete the `count` characters before the cursor, using the backend's
+    /// backspace strategy
+    async fn send_backspaces(&self, count: usize) -> Result<()> {
+        if count == 0 {
+            return Ok(());
+        }
+
+        match self.backspace_strategy() {
+            BackspaceStrategy::Repeat => self.press_backspaces(count).await,
+            BackspaceStrategy::Individual => {
+                for i in 0..count {
+                    if i > 0 {
+                        sleep(Duration::from_millis(self.keystroke_delay())).await;
+                    }
+                    self.press_backspaces(1).await?;
+                }
+                Ok(())
+            }
+            BackspaceStrategy::SelectDelete => {
+                self.select_left(count).await?;
+                self.press_backspaces(1).await
+            }
+        }
+    }
+
+    /// Custom ydotoold socket path, if the backend uses one
+    fn socket_path(&self) -> Option<&str> {
+        None
+    }
+
+    /// Type, press and wait through a macro snippet's steps in order
+    async fn run_macro(&self, actions: &[MacroAction]) -> Result<()> {
+        for action in actions {
+            match action {
+                MacroAction::Type(text) => self.type_text(text).await?,
+                MacroAction::Press(key) => self.press_key(*key).await?,
+                MacroAction::Wait(ms) => sleep(Duration::from_millis(*ms)).await,
+            }
+        }
+        Ok(())
+    }
+
+    /// Type `text` with each non-ASCII character on its own, so that one the
+    /// backend can't type on this layout is typed in its ASCII spelling instead
+    /// (`settings.transliterate_fallback`). A character that fails has typed
+    /// nothing, so nothing is typed twice. Returns how many characters each
+    /// character of `text` came out as.
+    async fn type_transliterating(&self, text: &str) -> Result<Vec<usize>> {
+        let mut lengths = Vec::with_capacity(text.len());
+        let mut ascii = String::new();
+        for c in text.chars() {
+            if c.is_ascii() {
+                ascii.push(c);
+                lengths.push(1);
+                continue;
+            }
+            if !ascii.is_empty() {
+                self.type_text(&ascii).await?;
+                ascii.clear();
+            }
+
+            let original = c.to_string();
+            if let Err(e) = self.type_text(&original).await {
+                let spelling = deunicode::deunicode(&original);
+                log::warn!("Failed to type '{}' ({}), typing '{}' instead", c, e, spelling);
+                self.type_text(&spelling).await?;
+                lengths.push(spelling.chars().count());
+            } else {
+                lengths.push(1);
+            }
+        }
+        if !ascii.is_empty() {
+            self.type_text(&ascii).await?;
+        }
+        Ok(lengths)
+    }
+
+    /// Output an expansion result
+    async fn output_expansion(&self, expansion: &ExpansionResult) -> Result<()> {
+        // Step 1: Delete the trigger characters
+        if expansion.delete_count > 0 {
+            self.send_backspaces(expansion.delete_count).await?;
+            // Small delay after backspaces
+            sleep(Duration::from_millis(10)).await;
+        }
+
+        // Step 2: Type the replacement text, or run the macro's steps
+        let mut cursor_offset = expansion.cursor_offset;
+        let mut selection_len = expansion.selection_len;
+        let typed = if !expansion.macro_actions.is_empty() {
+            self.run_macro(&expansion.macro_actions).await
+        } else if expansion.transliterate_fallback && !expansion.text.is_ascii() {
+            // Characters typed as their ASCII spelling may be longer, so the
+            // cursor moves over what was actually typed
+            self.type_transliterating(&expansion.text).await.map(|lengths| {
+                let offset = cursor_offset.unwrap_or(0);
+                let typed_offset = typed_len(&lengths, offset);
+                cursor_offset = cursor_offset.map(|_| typed_offset);
+                selection_len = typed_len(&lengths, offset + selection_len) - typed_offset;
+            })
+        } else {
+            self.type_text(&expansion.text).await
+        };
+        if let Err(e) = typed {
+            // The trigger is already gone - put it back so the user doesn't lose their input
+            if expansion.delete_count > 0 && !expansion.typed_trigger.is_empty() {
+                log::warn!("Typing replacement failed, restoring trigger text");
+                if let Err(restore_err) = self.type_text(&expansion.typed_trigger).await {
+                    log::error!("Failed to restore trigger text: {}", restore_err);
+                }
+            }
+            return Err(e);
+        }
+
+        // Step 3: Move cursor back if needed
+        let mut cursor_moved = match cursor_offset {
+            Some(offset) if offset > 0 => {
+                sleep(Duration::from_millis(10)).await;
+                self.move_cursor_left(offset).await?;
+                true
+            }
+            _ => false,
+        };
+
+        // Step 3b: Select the placeholder before the cursor so typing replaces it
+        if selection_len > 0 {
+            sleep(Duration::from_millis(10)).await;
+            self.select_left(selection_len).await?;
+            cursor_moved = true;
+        }
+
+        // Step 4: Press the post-expansion key. Skipped when the cursor was moved,
+        // since the user is expected to keep typing at the cursor first.
+        if let Some(action) = expansion.post_action {
+            if cursor_moved {
+                log::debug!("Skipping post action {:?}: cursor was repositioned", action);
+            } else {
+                sleep(Duration::from_millis(10)).await;
+                self.press_key(action.into()).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// How many characters the last `count` characters came out as, given each
+/// one's typed length
+fn typed_len(lengths: &[usize], count: usize) -> usize {
+    lengths.iter().rev().take(count).sum()
+}
+
+/// Create the output backend selected in the settings
+pub fn create_backend(settings: &Settings) -> Box<dyn OutputBackend> {
+    let delay = settings.keystroke_delay_ms;
+    let ydotool = || {
+        OutputEngine::new(delay, settings.ydotool_socket.clone())
+            .with_method(settings.output_method)
+            .with_clipboard_restore(settings.preserve_clipboard, settings.clipboard_restore_delay_ms)
+            .with_backspace_strategy(settings.backspace_strategy)
+    };
+    match settings.output_backend {
+        OutputBackendKind::Ydotool => Box::new(ydotool()),
+        OutputBackendKind::Wtype => {
+            Box::new(WtypeBackend::new(delay).with_backspace_strategy(settings.backspace_strategy))
+        }
+        OutputBackendKind::VirtualKeyboard => Box::new(
+            VirtualKeyboardBackend::new(delay, Box::new(ydotool()))
+                .with_backspace_strategy(settings.backspace_strategy),
+        ),
+        OutputBackendKind::Xdotool => Box::new(
+            XdotoolBackend::new(delay)
+                .with_primary_selection(settings.output_method == OutputMethod::PrimarySelection)
+                .with_backspace_strategy(settings.backspace_strategy),
+        ),
+    }
+}
+
+/// The settings with `ydotool_socket` filled in by `detect` when the config
+/// doesn't set one and ydotool is used. Called once per config load, so making
+/// a backend doesn't search for the socket.
+pub fn resolve_ydotool_socket(settings: &Settings, detect: fn() -> Option<String>) -> Settings {
+    let mut settings = settings.clone();
+    let uses_ydotool = matches!(
+        settings.output_backend,
+        OutputBackendKind::Ydotool | OutputBackendKind::VirtualKeyboard
+    );
+    if settings.ydotool_socket.is_none() && uses_ydotool {
+        settings.ydotool_socket = detect();
+    }
+    settings
+}
+
+/// Places ydotoold's socket is looked for when `ydotool_socket` isn't set, in order:
+/// `$YDOTOOL_SOCKET`, the user's runtime directory, then `/tmp`
+fn ydotool_socket_candidates(env_socket: Option<String>, runtime_dir: Option<PathBuf>) -> Vec<PathBuf> {
+    let mut candidates: Vec<PathBuf> = env_socket.filter(|s| !s.is_empty()).map(PathBuf::from).into_iter().collect();
+    candidates.extend(runtime_dir.map(|dir| dir.join(YDOTOOL_SOCKET_NAME)));
+    candidates.push(PathBuf::from(DEFAULT_YDOTOOL_SOCKET));
+    candidates
+}
+
+/// The first candidate socket that exists
+fn find_socket(candidates: &[PathBuf]) -> Option<&Path> {
+    candidates.iter().map(PathBuf::as_path).find(|path| path.exists())
+}
+
+/// Find ydotoold's socket, which distributions put in different places
+pub fn detect_ydotool_socket() -> Option<String> {
+    let candidates = ydotool_socket_candidates(
+        std::env::var("YDOTOOL_SOCKET").ok(),
+        std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from),
+    );
+    let socket = find_socket(&candidates).map(|path| path.to_string_lossy().into_owned());
+    if socket.is_none() {
+        log::debug!("No ydotool socket found, tried {:?}", candidates);
+    }
+    socket
+}
+
+/// Text output backend using ydotool
 pub struct OutputEngine {
     /// Delay between keystrokes in milliseconds
     keystroke_delay: u64,
-    /// Optional custom socket path for ydotoold
+    /// Socket path for ydotoold
     socket_path: Option<String>,
+    /// The ydotool binary to invoke
+    program: String,
+    /// How text is passed to `ydotool type`
+    method: OutputMethod,
+    /// Delay before restoring the clipboard after a paste; `None` leaves the pasted text
+    clipboard_restore_delay: Option<Duration>,
+    /// Clipboard access for `Paste` (replaceable in tests)
+    get_clipboard: fn() -> Option<String>,
+    set_clipboard: fn(&str),
+    /// Primary selection access for `PrimarySelection`; false if there is none
+    set_primary: fn(&str) -> bool,
+    backspace_strategy: BackspaceStrategy,
 }
 
 impl OutputEngine {
-    /// Create a new output engine
+    /// Create a new output engine. Without a socket, ydotool's default is used.
     pub fn new(keystroke_delay: u64, socket_path: Option<String>) -> Self {
         Self {
             keystroke_delay,
             socket_path,
+            program: "ydotool".to_string(),
+            method: OutputMethod::Args,
+            clipboard_restore_delay: None,
+            get_clipboard: read_clipboard,
+            set_clipboard: crate::variables::set_clipboard,
+            set_primary: crate::variables::set_primary_selection,
+            backspace_strategy: BackspaceStrategy::Repeat,
         }
     }
 
+    /// Builder method to set how the trigger is deleted
+    pub fn with_backspace_strategy(mut self, strategy: BackspaceStrategy) -> Self {
+        self.backspace_strategy = strategy;
+        self
+    }
+
+    /// Builder method to restore the clipboard `delay_ms` after pasting
+    pub fn with_clipboard_restore(mut self, preserve: bool, delay_ms: u64) -> Self {
+        self.clipboard_restore_delay = preserve.then(|| Duration::from_millis(delay_ms));
+        self
+    }
+
+    /// Builder method to set how text is passed to ydotool
+    pub fn with_method(mut self, method: OutputMethod) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// How text is passed to `ydotool type`
+    pub fn method(&self) -> OutputMethod {
+        self.method
+    }
+
     /// Check if ydotool is available
-    pub async fn check_availability() -> Result<()> {
+    pub async fn check_availability() -> Result<(), OutputError> {
         let output = Command::new("which")
             .arg("ydotool")
             .output()
             .await
-            .context("Failed to check for ydotool")?;
+            .map_err(|e| OutputError::spawn("which", e))?;
 
         if !output.status.success() {
-            anyhow::bail!(
-                "ydotool not found. Please install it with: sudo apt install ydotool\n\
-                 Then enable the daemon: sudo systemctl enable --now ydotool"
-            );
+            return Err(OutputError::BackendMissing {
+                program: "ydotool".to_string(),
+                hint: "Please install it with: sudo apt install ydotool\n\
+                       Then enable the daemon: sudo systemctl enable --now ydotool"
+                    .to_string(),
+            });
         }
 
         // Check if ydotoold binary exists - if not, we're on 0.1.x which doesn't need a daemon
@@ -51,14 +613,16 @@ impl OutputEngine {
             let output = Command::new("pgrep")
                 .arg("ydotoold")
                 .output()
-                .await?;
+                .await
+                .map_err(|e| OutputError::spawn("pgrep", e))?;
 
             if !output.status.success() {
-                anyhow::bail!(
+                return Err(OutputError::NotReady(
                     "ydotoold daemon is not running. Start it with:\n\
                      sudo systemctl start ydotool\n\
                      Or run: sudo ydotoold &"
-                );
+                        .to_string(),
+                ));
             }
 
             // Verify we can connect to the daemon
@@ -90,31 +654,107 @@ impl OutputEngine {
         Ok(())
     }
 
-    /// Output an expansion result
-    pub async fn output_expansion(&self, expansion: &ExpansionResult) -> Result<()> {
-        // Step 1: Delete the trigger characters
-        if expansion.delete_count > 0 {
-            self.send_backspaces(expansion.delete_count).await?;
-            // Small delay after backspaces
-            sleep(Duration::from_millis(10)).await;
+    /// Types text when `method` is `Stdin`, with the same ydotool, socket and delay
+    fn pipe(&self) -> PipeOutputEngine {
+        PipeOutputEngine::new(self.keystroke_delay, self.socket_path.clone()).with_program(&self.program)
+    }
+
+    /// Run ydotool with the given arguments, retrying transient failures
+    async fn run_ydotool(&self, args: &[String]) -> Result<(), OutputError> {
+        retry_with_backoff(
+            YDOTOOL_MAX_ATTEMPTS,
+            Duration::from_millis(YDOTOOL_RETRY_DELAY_MS),
+            || self.run_ydotool_once(args),
+        )
+        .await
+    }
+
+    /// Run ydotool once with the given arguments
+    async fn run_ydotool_once(&self, args: &[String]) -> Result<(), OutputError> {
+        let mut cmd = Command::new(&self.program);
+        cmd.args(args);
+
+        // Set socket path if configured
+        if let Some(path) = &self.socket_path {
+            cmd.env("YDOTOOL_SOCKET", path);
         }
 
-        // Step 2: Type the replacement text
-        self.type_text(&expansion.text).await?;
+        cmd.stdin(Stdio::null());
+        cmd.stdout(Stdio::null());
+        cmd.stderr(Stdio::piped());
 
-        // Step 3: Move cursor back if needed
-        if let Some(offset) = expansion.cursor_offset {
-            if offset > 0 {
-                sleep(Duration::from_millis(10)).await;
-                self.move_cursor_left(offset).await?;
+        let output = cmd.output().await.map_err(|e| OutputError::spawn(&self.program, e))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(OutputError::CommandFailed {
+                program: self.program.clone(),
+                stderr: stderr.trim().to_string(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Paste text through the clipboard, then put back what was there before
+    async fn paste_text(&self, text: &str) -> Result<(), OutputError> {
+        let snapshot = self
+            .clipboard_restore_delay
+            .map(|delay| (delay, ClipboardSnapshot::new((self.get_clipboard)(), text)));
+
+        (self.set_clipboard)(text);
+        sleep(PASTE_SETTLE_DELAY).await;
+        let result = self.run_ydotool(&["key".to_string(), "ctrl+v".to_string()]).await;
+
+        if let Some((delay, snapshot)) = snapshot {
+            // Restoring right away can race the application reading the clipboard
+            sleep(delay).await;
+            match snapshot.restore((self.get_clipboard)().as_deref()) {
+                ClipboardRestore::Restore(saved) => (self.set_clipboard)(&saved),
+                ClipboardRestore::NothingSaved => {
+                    log::debug!("Clipboard held no text before pasting, leaving the pasted text");
+                }
+                ClipboardRestore::ChangedSincePaste => {
+                    log::warn!("Clipboard changed while pasting; not restoring it to avoid overwriting a new copy");
+                }
             }
         }
 
+        result
+    }
+
+    /// Paste text through the primary selection. Returns false if the session has
+    /// none, leaving the caller to type the text instead.
+    async fn paste_primary(&self, text: &str) -> Result<bool, OutputError> {
+        if !offer_primary_selection(self.set_primary, text).await {
+            return Ok(false);
+        }
+        self.run_ydotool(&["key".to_string(), "shift+Insert".to_string()]).await?;
+        Ok(true)
+    }
+
+    /// Type text character by character with delay (alternative method)
+    #[allow(dead_code)]
+    async fn type_text_slow(&self, text: &str) -> Result<()> {
+        for ch in text.chars() {
+            let mut cmd = Command::new("ydotool");
+            cmd.args(["type", "--", &ch.to_string()]);
+
+            if let Some(socket) = &self.socket_path {
+                cmd.env("YDOTOOL_SOCKET", socket);
+            }
+
+            cmd.output().await?;
+            sleep(Duration::from_millis(self.keystroke_delay)).await;
+        }
         Ok(())
     }
+}
 
+#[async_trait]
+impl OutputBackend for OutputEngine {
     /// Send backspace keys to delete characters
-    async fn send_backspaces(&self, count: usize) -> Result<()> {
+    async fn press_backspaces(&self, count: usize) -> Result<()> {
         if count == 0 {
             return Ok(());
         }
@@ -132,12 +772,30 @@ impl OutputEngine {
         Ok(())
     }
 
-    /// Type text using ydotool
     async fn type_text(&self, text: &str) -> Result<()> {
         if text.is_empty() {
             return Ok(());
         }
 
+        match self.method {
+            OutputMethod::Stdin => {
+                let pipe = self.pipe();
+                return Ok(retry_with_backoff(
+                    YDOTOOL_MAX_ATTEMPTS,
+                    Duration::from_millis(YDOTOOL_RETRY_DELAY_MS),
+                    || pipe.type_text(text),
+                )
+                .await?)
+            }
+            OutputMethod::Paste => return Ok(self.paste_text(text).await?),
+            OutputMethod::PrimarySelection => {
+                if self.paste_primary(text).await? {
+                    return Ok(());
+                }
+            }
+            OutputMethod::Args => {}
+        }
+
         // Use ydotool type command with --key-delay for 0.1.x compatibility
         let args = vec![
             "type".to_string(),
@@ -169,48 +827,45 @@ impl OutputEngine {
         Ok(())
     }
 
-    /// Run ydotool with the given arguments
-    async fn run_ydotool(&self, args: &[String]) -> Result<()> {
-        let mut cmd = Command::new("ydotool");
-        cmd.args(args);
-
-        // Set socket path if configured
-        if let Some(socket) = &self.socket_path {
-            cmd.env("YDOTOOL_SOCKET", socket);
-        } else {
-            cmd.env("YDOTOOL_SOCKET", "/tmp/.ydotool_socket");
+    /// Select N characters to the left of the cursor
+    async fn select_left(&self, count: usize) -> Result<()> {
+        if count == 0 {
+            return Ok(());
         }
 
-        cmd.stdin(Stdio::null());
-        cmd.stdout(Stdio::null());
-        cmd.stderr(Stdio::piped());
+        let args = vec![
+            "key".to_string(),
+            "--repeat".to_string(),
+            count.to_string(),
+            "Shift+Left".to_string(),
+        ];
 
-        let output = cmd.output().await
-            .context("Failed to run ydotool")?;
+        Ok(self.run_ydotool(&args).await?)
+    }
 
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            anyhow::bail!("ydotool failed: {}", stderr);
-        }
+    async fn press_key(&self, key: SpecialKey) -> Result<()> {
+        let args = vec!["key".to_string(), key.keysym().to_string()];
+        Ok(self.run_ydotool(&args).await?)
+    }
 
-        Ok(())
+    async fn check_availability(&self) -> Result<()> {
+        match &self.socket_path {
+            Some(socket) => log::info!("Using ydotool socket {}", socket),
+            None => log::info!("No ydotool socket found, leaving it to ydotool"),
+        }
+        Ok(Self::check_availability().await?)
     }
 
-    /// Type text character by character with delay (alternative method)
-    #[allow(dead_code)]
-    async fn type_text_slow(&self, text: &str) -> Result<()> {
-        for ch in text.chars() {
-            let mut cmd = Command::new("ydotool");
-            cmd.args(["type", "--", &ch.to_string()]);
+    fn keystroke_delay(&self) -> u64 {
+        self.keystroke_delay
+    }
 
-            if let Some(socket) = &self.socket_path {
-                cmd.env("YDOTOOL_SOCKET", socket);
-            }
+    fn backspace_strategy(&self) -> BackspaceStrategy {
+        self.backspace_strategy
+    }
 
-            cmd.output().await?;
-            sleep(Duration::from_millis(self.keystroke_delay)).await;
-        }
-        Ok(())
+    fn socket_path(&self) -> Option<&str> {
+        self.socket_path.as_deref()
     }
 }
 
@@ -218,6 +873,8 @@ impl OutputEngine {
 pub struct PipeOutputEngine {
     keystroke_delay: u64,
     socket_path: Option<String>,
+    /// The ydotool binary to invoke
+    program: String,
 }
 
 impl PipeOutputEngine {
@@ -225,41 +882,61 @@ impl PipeOutputEngine {
         Self {
             keystroke_delay,
             socket_path,
+            program: "ydotool".to_string(),
         }
     }
 
-    /// Type text by piping to ydotool's stdin
-    pub async fn type_text(&self, text: &str) -> Result<()> {
-        let mut cmd = Command::new("ydotool");
+    /// Builder method to set the ydotool binary
+    pub fn with_program(mut self, program: &str) -> Self {
+        self.program = program.to_string();
+        self
+    }
+
+    /// The `ydotool type` command reading the text from stdin. `--key-delay` is
+    /// the delay between keys, as when passing the text as an argument.
+    fn command(&self) -> Command {
+        let mut cmd = Command::new(&self.program);
         cmd.args([
             "type",
-            "--delay",
+            "--key-delay",
             &self.keystroke_delay.to_string(),
             "--file",
             "-", // Read from stdin
         ]);
 
-        if let Some(socket) = &self.socket_path {
-            cmd.env("YDOTOOL_SOCKET", socket);
+        if let Some(path) = &self.socket_path {
+            cmd.env("YDOTOOL_SOCKET", path);
         }
 
         cmd.stdin(Stdio::piped());
         cmd.stdout(Stdio::null());
         cmd.stderr(Stdio::piped());
+        cmd
+    }
 
-        let mut child = cmd.spawn()
-            .context("Failed to spawn ydotool")?;
+    /// Type text by piping to ydotool's stdin
+    pub async fn type_text(&self, text: &str) -> Result<(), OutputError> {
+        let mut child = self.command().spawn().map_err(|e| OutputError::spawn(&self.program, e))?;
 
+        // Once ydotool is running, a broken pipe means it exited early (e.g. ydotoold
+        // wasn't ready), which may not happen on another try
+        let failed = |e: std::io::Error| OutputError::CommandFailed {
+            program: self.program.clone(),
+            stderr: e.to_string(),
+        };
         if let Some(mut stdin) = child.stdin.take() {
-            stdin.write_all(text.as_bytes()).await?;
-            stdin.shutdown().await?;
+            stdin.write_all(text.as_bytes()).await.map_err(failed)?;
+            stdin.shutdown().await.map_err(failed)?;
         }
 
-        let output = child.wait_with_output().await?;
+        let output = child.wait_with_output().await.map_err(failed)?;
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
-            anyhow::bail!("ydotool failed: {}", stderr);
+            return Err(OutputError::CommandFailed {
+                program: self.program.clone(),
+                stderr: stderr.trim().to_string(),
+            });
         }
 
         Ok(())
@@ -277,6 +954,41 @@ mod tests {
         OutputEngine::check_availability().await.unwrap();
     }
 
+    #[test]
+    fn test_output_guard() {
+        let guard = OutputGuard::default();
+        assert!(!guard.is_active());
+
+        let in_flight = guard.begin();
+        assert!(guard.is_active());
+        assert!(guard.clone().is_active());
+
+        drop(in_flight);
+        // Still active briefly while the last keystrokes arrive, then cleared
+        let now = Instant::now();
+        assert!(guard.is_active_at(now));
+        assert!(!guard.is_active_at(now + OUTPUT_GRACE_PERIOD * 2));
+    }
+
+    #[test]
+    fn test_typed_len() {
+        // "Straße" with ß typed as "ss"
+        let lengths = [1, 1, 1, 1, 2, 1];
+        assert_eq!(typed_len(&lengths, 2), 3);
+        assert_eq!(typed_len(&lengths, 0), 0);
+        assert_eq!(typed_len(&lengths, 10), 7);
+    }
+
+    #[test]
+    fn test_special_key_names() {
+        assert_eq!(SpecialKey::from_name("Enter"), Some(SpecialKey::Enter));
+        assert_eq!(SpecialKey::from_name("return"), Some(SpecialKey::Enter));
+        assert_eq!(SpecialKey::from_name(" page-down "), Some(SpecialKey::PageDown));
+        assert_eq!(SpecialKey::from_name("hyper"), None);
+        assert_eq!(SpecialKey::PageDown.keysym(), "Page_Down");
+        assert_eq!(SpecialKey::Escape.keysym(), "Escape");
+    }
+
     #[test]
     fn test_output_engine_creation() {
         let engine = OutputEngine::new(12, None);
@@ -287,4 +999,214 @@ mod tests {
         assert_eq!(engine.keystroke_delay, 20);
         assert_eq!(engine.socket_path, Some("/tmp/ydotool.sock".to_string()));
     }
+
+    #[test]
+    fn test_ydotool_socket_detection() {
+        let runtime = tempfile::tempdir().unwrap();
+        let env_dir = tempfile::tempdir().unwrap();
+        let env_socket = env_dir.path().join("ydotool.sock");
+
+        let candidates = ydotool_socket_candidates(
+            Some(env_socket.to_string_lossy().into_owned()),
+            Some(runtime.path().to_path_buf()),
+        );
+        assert_eq!(
+            candidates,
+            vec![
+                env_socket.clone(),
+                runtime.path().join(".ydotool_socket"),
+                PathBuf::from("/tmp/.ydotool_socket"),
+            ]
+        );
+        assert_eq!(ydotool_socket_candidates(Some(String::new()), None), vec![PathBuf::from("/tmp/.ydotool_socket")]);
+
+        // Nothing exists yet
+        assert_eq!(find_socket(&candidates[..2]), None);
+
+        std::fs::write(runtime.path().join(".ydotool_socket"), "").unwrap();
+        assert_eq!(find_socket(&candidates), Some(candidates[1].as_path()));
+
+        // The environment variable wins when it points at a socket
+        std::fs::write(&env_socket, "").unwrap();
+        assert_eq!(find_socket(&candidates), Some(env_socket.as_path()));
+    }
+
+    #[test]
+    fn test_resolve_ydotool_socket() {
+        let detect = || Some("/run/user/1000/.ydotool_socket".to_string());
+        let mut settings = Settings::default();
+        assert_eq!(
+            resolve_ydotool_socket(&settings, detect).ydotool_socket.as_deref(),
+            Some("/run/user/1000/.ydotool_socket")
+        );
+
+        // A configured socket is kept, and wtype doesn't need one
+        settings.ydotool_socket = Some("/tmp/custom.sock".to_string());
+        assert_eq!(resolve_ydotool_socket(&settings, detect).ydotool_socket.as_deref(), Some("/tmp/custom.sock"));
+        settings.ydotool_socket = None;
+        settings.output_backend = OutputBackendKind::Wtype;
+        assert_eq!(resolve_ydotool_socket(&settings, detect).ydotool_socket, None);
+    }
+
+    #[test]
+    fn test_output_engine_pipe_mode() {
+        let engine = OutputEngine::new(15, None).with_method(OutputMethod::Stdin);
+        assert_eq!(engine.method(), OutputMethod::Stdin);
+        assert_eq!(engine.pipe().keystroke_delay, 15);
+
+        // Argument mode stays the default for engines built without settings
+        assert_eq!(OutputEngine::new(15, None).method(), OutputMethod::Args);
+    }
+
+    #[test]
+    fn test_clipboard_snapshot_restore() {
+        let snapshot = ClipboardSnapshot::new(Some("copied".to_string()), "pasted");
+        assert_eq!(snapshot.restore(Some("pasted")), ClipboardRestore::Restore("copied".to_string()));
+
+        let snapshot = ClipboardSnapshot::new(None, "pasted");
+        assert_eq!(snapshot.restore(Some("pasted")), ClipboardRestore::NothingSaved);
+
+        // Something new was copied during the paste
+        let snapshot = ClipboardSnapshot::new(Some("copied".to_string()), "pasted");
+        assert_eq!(snapshot.restore(Some("newer")), ClipboardRestore::ChangedSincePaste);
+        let snapshot = ClipboardSnapshot::new(Some("copied".to_string()), "pasted");
+        assert_eq!(snapshot.restore(None), ClipboardRestore::ChangedSincePaste);
+    }
+
+    #[tokio::test]
+    async fn test_paste_restores_clipboard() {
+        static CLIPBOARD: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+        let mut engine = OutputEngine::new(0, None)
+            .with_method(OutputMethod::Paste)
+            .with_clipboard_restore(true, 1);
+        engine.program = "true".to_string();
+        engine.get_clipboard = || CLIPBOARD.lock().unwrap().last().cloned();
+        engine.set_clipboard = |text| CLIPBOARD.lock().unwrap().push(text.to_string());
+        CLIPBOARD.lock().unwrap().push("copied".to_string());
+
+        engine.type_text("snippet text").await.unwrap();
+        assert_eq!(*CLIPBOARD.lock().unwrap(), ["copied", "snippet text", "copied"]);
+
+        // Without preserve_clipboard the pasted text stays
+        let engine = OutputEngine { clipboard_restore_delay: None, ..engine };
+        engine.type_text("again").await.unwrap();
+        assert_eq!(CLIPBOARD.lock().unwrap().last().map(String::as_str), Some("again"));
+    }
+
+    #[tokio::test]
+    async fn test_primary_selection_falls_back_to_typing() {
+        static PRIMARY: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+        let mut engine = OutputEngine::new(0, None).with_method(OutputMethod::PrimarySelection);
+        engine.program = "true".to_string();
+        engine.set_primary = |text| {
+            PRIMARY.lock().unwrap().push(text.to_string());
+            true
+        };
+        engine.type_text("ls -la").await.unwrap();
+        assert_eq!(*PRIMARY.lock().unwrap(), ["ls -la"]);
+
+        // Without a primary selection the text is typed, which fails with `false`
+        engine.set_primary = |_| false;
+        engine.program = "false".to_string();
+        assert!(engine.type_text("ls -la").await.is_err());
+        assert_eq!(PRIMARY.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_pipe_mode_command() {
+        let mut engine = OutputEngine::new(15, Some("/tmp/custom.sock".to_string())).with_method(OutputMethod::Stdin);
+        engine.program = "/opt/ydotool/bin/ydotool".to_string();
+
+        let cmd = engine.pipe().command();
+        let cmd = cmd.as_std();
+        assert_eq!(cmd.get_program(), "/opt/ydotool/bin/ydotool");
+        let args: Vec<_> = cmd.get_args().collect();
+        assert_eq!(args, ["type", "--key-delay", "15", "--file", "-"]);
+        let socket = cmd.get_envs().find(|(key, _)| *key == "YDOTOOL_SOCKET").and_then(|(_, value)| value);
+        assert_eq!(socket, Some(std::ffi::OsStr::new("/tmp/custom.sock")));
+
+        // Without a socket, ydotool's own default or an exported YDOTOOL_SOCKET applies
+        let cmd = OutputEngine::new(15, None).pipe().command();
+        assert!(cmd.as_std().get_envs().all(|(key, _)| key != "YDOTOOL_SOCKET"));
+    }
+
+    #[tokio::test]
+    async fn test_pipe_mode_uses_program() {
+        let mut engine = OutputEngine::new(0, None).with_method(OutputMethod::Stdin);
+        engine.program = "xpander-nonexistent-ydotool".to_string();
+        let err = engine.pipe().type_text("x").await.unwrap_err();
+        assert!(matches!(err, OutputError::Spawn { .. }));
+        assert!(err.to_string().contains("xpander-nonexistent-ydotool"));
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires ydotool to be installed and ydotoold running
+    async fn test_pipe_mode_types_text() {
+        let engine = OutputEngine::new(12, None).with_method(OutputMethod::Stdin);
+        engine.type_text("xpander pipe test").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_retry_transient_failures() {
+        let attempts = std::cell::Cell::new(0);
+        let result = retry_with_backoff(3, Duration::from_millis(1), || {
+            attempts.set(attempts.get() + 1);
+            async { Err(OutputError::CommandFailed { program: "ydotool".to_string(), stderr: "socket busy".to_string() }) }
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(attempts.get(), 3);
+
+        // Succeeds on the second attempt
+        let attempts = std::cell::Cell::new(0);
+        let result = retry_with_backoff(3, Duration::from_millis(1), || {
+            attempts.set(attempts.get() + 1);
+            let n = attempts.get();
+            async move {
+                if n < 2 {
+                    Err(OutputError::CommandFailed { program: "ydotool".to_string(), stderr: "socket busy".to_string() })
+                } else {
+                    Ok(())
+                }
+            }
+        })
+        .await;
+
+        assert!(result.is_ok());
+        assert_eq!(attempts.get(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_failing_command_is_retried() {
+        // `false` always exits with status 1, like a busy ydotoold
+        let mut engine = OutputEngine::new(0, None);
+        engine.program = "false".to_string();
+
+        let err = engine.run_ydotool_once(&[]).await.unwrap_err();
+        assert!(matches!(err, OutputError::CommandFailed { .. }));
+        assert!(err.is_transient());
+        assert!(engine.run_ydotool(&[]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_missing_binary_not_retried() {
+        let mut engine = OutputEngine::new(0, None);
+        engine.program = "xpander-nonexistent-ydotool".to_string();
+
+        let err = engine.run_ydotool_once(&[]).await.unwrap_err();
+        assert!(matches!(err, OutputError::Spawn { .. }));
+        assert!(!err.is_transient());
+
+        let attempts = std::cell::Cell::new(0);
+        let result = retry_with_backoff(3, Duration::from_millis(1), || {
+            attempts.set(attempts.get() + 1);
+            engine.run_ydotool_once(&[])
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(attempts.get(), 1);
+    }
 }
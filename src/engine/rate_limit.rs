@@ -0,0 +1,159 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Window over which expansions are counted
+const WINDOW: Duration = Duration::from_secs(1);
+
+/// How long expansions stay suppressed once the limit is exceeded
+const COOLDOWN: Duration = Duration::from_secs(3);
+
+/// Outcome of asking the rate limiter whether an expansion may run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimit {
+    Allowed,
+    /// This expansion exceeded the limit; expansions are now suppressed
+    Tripped,
+    /// Still cooling down from an earlier trip
+    Suppressed,
+}
+
+/// Circuit breaker against runaway expansions, e.g. a replacement that contains
+/// its own trigger or an app that echoes typed text back
+pub struct RateLimiter {
+    /// Maximum expansions per rolling second (0 disables the limit)
+    max_per_second: u32,
+    recent: VecDeque<Instant>,
+    suppressed_until: Option<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(max_per_second: u32) -> Self {
+        Self {
+            max_per_second,
+            recent: VecDeque::new(),
+            suppressed_until: None,
+        }
+    }
+
+    /// Change the limit, e.g. after a config reload
+    pub fn set_limit(&mut self, max_per_second: u32) {
+        self.max_per_second = max_per_second;
+    }
+
+    /// Record an expansion attempt at `now` and decide whether it may run
+    pub fn check(&mut self, now: Instant) -> RateLimit {
+        if self.max_per_second == 0 {
+            return RateLimit::Allowed;
+        }
+
+        if let Some(until) = self.suppressed_until {
+            if now < until {
+                return RateLimit::Suppressed;
+            }
+            self.suppressed_until = None;
+        }
+
+        while self
+            .recent
+            .front()
+            .is_some_and(|&t| now.duration_since(t) >= WINDOW)
+        {
+            self.recent.pop_front();
+        }
+
+        if self.recent.len() >= self.max_per_second as usize {
+            self.recent.clear();
+            self.suppressed_until = Some(now + COOLDOWN);
+            return RateLimit::Tripped;
+        }
+
+        self.recent.push_back(now);
+        RateLimit::Allowed
+    }
+}
+
+/// Lets something happen at most once per interval, so a burst of expansions
+/// gets a single sound or notification
+pub struct Cooldown {
+    /// Zero lets everything through
+    interval: Duration,
+    last: Option<Instant>,
+}
+
+impl Cooldown {
+    pub fn new(interval: Duration) -> Self {
+        Self { interval, last: None }
+    }
+
+    /// Change the interval, e.g. after a config reload
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    /// Whether it may happen at `now`, starting a new interval if so
+    pub fn ready(&mut self, now: Instant) -> bool {
+        if self.last.is_some_and(|last| now.duration_since(last) < self.interval) {
+            return false;
+        }
+        self.last = Some(now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trips_and_resets() {
+        let mut limiter = RateLimiter::new(3);
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+
+        assert_eq!(limiter.check(at(0)), RateLimit::Allowed);
+        assert_eq!(limiter.check(at(100)), RateLimit::Allowed);
+        assert_eq!(limiter.check(at(200)), RateLimit::Allowed);
+        assert_eq!(limiter.check(at(300)), RateLimit::Tripped);
+        assert_eq!(limiter.check(at(2000)), RateLimit::Suppressed);
+
+        // Allowed again once the cooldown has passed
+        assert_eq!(limiter.check(at(300) + COOLDOWN), RateLimit::Allowed);
+    }
+
+    #[test]
+    fn test_rolling_window() {
+        let mut limiter = RateLimiter::new(2);
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+
+        // Steady typing below the limit never trips
+        for i in 0..10 {
+            assert_eq!(limiter.check(at(i * 600)), RateLimit::Allowed);
+        }
+    }
+
+    #[test]
+    fn test_cooldown() {
+        let mut cooldown = Cooldown::new(Duration::from_millis(500));
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+
+        assert!(cooldown.ready(at(0)));
+        assert!(!cooldown.ready(at(100)));
+        assert!(!cooldown.ready(at(499)));
+        assert!(cooldown.ready(at(500)));
+
+        cooldown.set_interval(Duration::ZERO);
+        assert!(cooldown.ready(at(500)));
+        assert!(cooldown.ready(at(500)));
+    }
+
+    #[test]
+    fn test_zero_disables_limit() {
+        let mut limiter = RateLimiter::new(0);
+        let now = Instant::now();
+        for _ in 0..100 {
+            assert_eq!(limiter.check(now), RateLimit::Allowed);
+        }
+    }
+}
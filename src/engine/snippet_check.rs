@@ -0,0 +1,166 @@
+use std::fmt;
+
+use crate::config::Snippet;
+use crate::variables::with_test_mode;
+
+use super::expander::{capture_refs, expand_snippet};
+use super::matcher::compile_regex_trigger;
+
+/// How one snippet fared in `check_snippets`
+#[derive(Debug, PartialEq, Eq)]
+pub enum CheckOutcome {
+    Passed,
+    /// Expanded, but these variables were left as they are (clipboard, shell, ...)
+    Skipped(Vec<String>),
+    Failed(String),
+}
+
+#[derive(Debug)]
+pub struct SnippetCheck {
+    pub trigger: String,
+    pub label: Option<String>,
+    pub outcome: CheckOutcome,
+}
+
+/// Result of expanding every snippet with `--test-snippets`
+#[derive(Debug)]
+pub struct CheckReport {
+    pub checks: Vec<SnippetCheck>,
+}
+
+impl CheckReport {
+    pub fn failures(&self) -> usize {
+        self.checks.iter().filter(|c| matches!(c.outcome, CheckOutcome::Failed(_))).count()
+    }
+}
+
+/// Expand every snippet, each `cycle` alternative and `per_app_replace` text included,
+/// without side effects: clipboard, pick, window and secret variables are skipped,
+/// and shell commands too unless `run_shell` is set. Unknown variables and capture
+/// groups a regex trigger doesn't have count as failures.
+pub fn check_snippets(snippets: &[Snippet], variables: &serde_yaml::Value, run_shell: bool) -> CheckReport {
+    let checks = snippets
+        .iter()
+        .map(|snippet| {
+            let (result, mut skipped) = with_test_mode(run_shell, || check_snippet(snippet, variables));
+            skipped.sort();
+            skipped.dedup();
+            let outcome = match result {
+                Err(e) => CheckOutcome::Failed(e),
+                Ok(()) if skipped.is_empty() => CheckOutcome::Passed,
+                Ok(()) => CheckOutcome::Skipped(skipped),
+            };
+            SnippetCheck {
+                trigger: snippet.trigger.clone(),
+                label: snippet.label.clone(),
+                outcome,
+            }
+        })
+        .collect();
+
+    CheckReport { checks }
+}
+
+fn check_snippet(snippet: &Snippet, variables: &serde_yaml::Value) -> Result<(), String> {
+    let groups = if snippet.regex {
+        let regex = compile_regex_trigger(&snippet.trigger).map_err(|e| format!("Regex does not compile: {}", e))?;
+        Some(regex.captures_len() - 1)
+    } else {
+        None
+    };
+
+    let mut texts: Vec<&str> = if snippet.cycle.is_empty() {
+        vec![&snippet.replace]
+    } else {
+        snippet.cycle.iter().map(String::as_str).collect()
+    };
+    texts.extend(snippet.per_app_replace.iter().map(|r| r.replace.as_str()));
+
+    for text in texts {
+        if let Some(groups) = groups {
+            if let Some(group) = capture_refs(text).find(|&n| n > groups) {
+                return Err(format!("${} refers to a capture group the trigger doesn't have ({})", group, groups));
+            }
+        }
+
+        let alternative = Snippet {
+            replace: text.to_string(),
+            cycle: Vec::new(),
+            per_app_replace: Vec::new(),
+            ..snippet.clone()
+        };
+        expand_snippet(&alternative, variables).map_err(|e| format!("{:#}", e))?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for CheckReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut skipped = 0;
+        for check in &self.checks {
+            let name = match &check.label {
+                Some(label) => format!("'{}' \"{}\"", check.trigger, label),
+                None => format!("'{}'", check.trigger),
+            };
+            match &check.outcome {
+                CheckOutcome::Passed => writeln!(f, "  ✓ {}", name)?,
+                CheckOutcome::Skipped(vars) => {
+                    skipped += 1;
+                    let vars: Vec<String> = vars.iter().map(|v| format!("{{{{{}}}}}", v)).collect();
+                    writeln!(f, "  • {} (skipped {})", name, vars.join(", "))?;
+                }
+                CheckOutcome::Failed(e) => writeln!(f, "  ✗ {}: {}", name, e)?,
+            }
+        }
+
+        let failed = self.failures();
+        writeln!(
+            f,
+            "{} snippets: {} passed, {} with skipped variables, {} failed",
+            self.checks.len(),
+            self.checks.len() - skipped - failed,
+            skipped,
+            failed
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::schema::AppReplace;
+
+    #[test]
+    fn test_check_snippets() {
+        let mut capture = Snippet::new(r";n(\d+)", "Number $1 of $2");
+        capture.regex = true;
+        let mut cycle = Snippet::new(";c", "");
+        cycle.cycle = vec!["fine".to_string(), "{{missing}}".to_string()];
+        let mut per_app = Snippet::new(";p", "plain");
+        per_app.per_app_replace = vec![AppReplace {
+            applications: vec!["code".to_string()],
+            replace: "{{clipboard}} {{shell:false}}".to_string(),
+        }];
+        let snippets = vec![
+            Snippet::new(";ok", "Today is {{date}}"),
+            capture,
+            cycle,
+            per_app,
+        ];
+
+        let report = check_snippets(&snippets, &serde_yaml::Value::Null, false);
+        assert_eq!(report.checks[0].outcome, CheckOutcome::Passed);
+        assert!(matches!(&report.checks[1].outcome, CheckOutcome::Failed(e) if e.contains("$2")));
+        assert!(matches!(&report.checks[2].outcome, CheckOutcome::Failed(e) if e.contains("missing")));
+        assert_eq!(
+            report.checks[3].outcome,
+            CheckOutcome::Skipped(vec!["clipboard".to_string(), "shell:false".to_string()])
+        );
+        assert_eq!(report.failures(), 2);
+        assert!(report.to_string().ends_with("4 snippets: 1 passed, 1 with skipped variables, 2 failed\n"));
+
+        // Running shell commands catches the failing one
+        let report = check_snippets(&snippets[3..], &serde_yaml::Value::Null, true);
+        assert!(matches!(&report.checks[0].outcome, CheckOutcome::Failed(_)));
+    }
+}
@@ -2,7 +2,7 @@ use std::collections::HashMap;
 use crate::config::Snippet;
 
 /// A Trie node for storing text snippets
-#[derive(Debug, Default)]
+#[derive(Debug, Default, PartialEq)]
 pub struct TrieNode {
     children: HashMap<char, TrieNode>,
     /// If this node marks the end of a trigger, store the snippet here
@@ -16,6 +16,7 @@ impl TrieNode {
 }
 
 /// A Trie for efficient prefix/suffix matching of triggers
+#[derive(Debug, PartialEq)]
 pub struct Trie {
     root: TrieNode,
 }
@@ -30,8 +31,15 @@ impl Trie {
     /// Insert a snippet into the trie
     /// We insert the trigger in REVERSE order to support efficient suffix matching
     /// (matching as the user types backward from the cursor)
-    pub fn insert(&mut self, snippet: Snippet) {
+    ///
+    /// Returns the previously stored snippet if one with the same trigger was replaced
+    pub fn insert(&mut self, snippet: Snippet) -> Option<Snippet> {
         let text = snippet.trigger.clone();
+        self.insert_as(&text, snippet)
+    }
+
+    /// Insert a snippet under a key other than its trigger (e.g. a lowercased trigger)
+    pub fn insert_as(&mut self, text: &str, snippet: Snippet) -> Option<Snippet> {
         // Since we match what the user *just typed*, we look at the end of the buffer
         // So a structure that supports searching from the end is better.
         // We realize this is a Suffix match on the buffer.
@@ -42,7 +50,50 @@ impl Trie {
         for ch in text.chars().rev() {
             node = node.children.entry(ch).or_insert_with(TrieNode::new);
         }
-        node.snippet = Some(snippet);
+        node.snippet.replace(snippet)
+    }
+
+    /// The snippet stored under `key` (its trigger, or the key given to `insert_as`)
+    pub fn get(&self, key: &str) -> Option<&Snippet> {
+        let mut node = &self.root;
+        for ch in key.chars().rev() {
+            node = node.children.get(&ch)?;
+        }
+        node.snippet.as_ref()
+    }
+
+    /// Replace the snippet stored under `key`, returning the old one. Unlike
+    /// `insert_as`, nothing is added (and `None` returned) if `key` isn't in the trie.
+    pub fn update(&mut self, key: &str, snippet: Snippet) -> Option<Snippet> {
+        let mut node = &mut self.root;
+        for ch in key.chars().rev() {
+            node = node.children.get_mut(&ch)?;
+        }
+        node.snippet.as_mut().map(|stored| std::mem::replace(stored, snippet))
+    }
+
+    /// Remove the snippet stored under `key`, returning it. Nodes left with neither a
+    /// snippet nor children are pruned; nodes shared with other triggers are kept.
+    pub fn remove(&mut self, key: &str) -> Option<Snippet> {
+        let path: Vec<char> = key.chars().rev().collect();
+        Self::remove_from(&mut self.root, &path)
+    }
+
+    fn remove_from(node: &mut TrieNode, path: &[char]) -> Option<Snippet> {
+        let Some((ch, rest)) = path.split_first() else {
+            return node.snippet.take();
+        };
+        let child = node.children.get_mut(ch)?;
+        let removed = Self::remove_from(child, rest);
+        if child.snippet.is_none() && child.children.is_empty() {
+            node.children.remove(ch);
+        }
+        removed
+    }
+
+    /// Whether no snippet is stored
+    pub fn is_empty(&self) -> bool {
+        self.root.children.is_empty() && self.root.snippet.is_none()
     }
 
     /// Find a matching snippet for the end of the given text
@@ -70,6 +121,26 @@ impl Trie {
         
         best_match
     }
+
+    /// Snippets whose trigger is a proper suffix of `text`, shortest first
+    pub fn suffixes_of(&self, text: &str) -> Vec<&Snippet> {
+        let mut node = &self.root;
+        let mut found = Vec::new();
+
+        // Stop one char short so `text` itself isn't reported
+        let len = text.chars().count();
+        for ch in text.chars().rev().take(len.saturating_sub(1)) {
+            match node.children.get(&ch) {
+                Some(next_node) => node = next_node,
+                None => break,
+            }
+            if let Some(snippet) = &node.snippet {
+                found.push(snippet);
+            }
+        }
+
+        found
+    }
 }
 
 #[cfg(test)]
@@ -80,13 +151,28 @@ mod tests {
         Snippet {
             trigger: trigger.to_string(),
             replace: "content".to_string(),
+            cycle: Vec::new(),
             label: None,
-            propagate_case: false,
+            notes: None,
+            propagate_case: None,
             cursor_position: false,
-            word_boundary: false,
+            word_boundary: None,
+            whole_word: false,
+            line_start: false,
+            ignore_diacritics: false,
             regex: false,
             applications: None,
             exclude_applications: None,
+            per_app_replace: Vec::new(),
+            keep_prefix_len: 0,
+            post_action: None,
+            keystroke_delay_ms: None,
+            output_mode: None,
+            macro_steps: None,
+            active_schedule: None,
+            vars: Default::default(),
+            raw: false,
+            on_expand_command: None,
             enabled: true,
         }
     }
@@ -124,4 +210,70 @@ mod tests {
         assert_eq!(s.trigger, ";test");
         assert_eq!(len, 5);
     }
+
+    #[test]
+    fn test_suffixes_of() {
+        let mut trie = Trie::new();
+        trie.insert(make_snippet("w"));
+        trie.insert(make_snippet("btw"));
+        trie.insert(make_snippet("mbtw"));
+
+        let suffixes: Vec<&str> = trie.suffixes_of("mbtw").iter().map(|s| s.trigger.as_str()).collect();
+        assert_eq!(suffixes, vec!["w", "btw"]);
+        assert!(trie.suffixes_of("w").is_empty());
+    }
+
+    #[test]
+    fn test_insert_reports_overwrite() {
+        let mut trie = Trie::new();
+        assert!(trie.insert(make_snippet(";dup")).is_none());
+
+        let previous = trie.insert(make_snippet(";dup")).unwrap();
+        assert_eq!(previous.trigger, ";dup");
+    }
+
+    #[test]
+    fn test_remove_prunes_unshared_nodes() {
+        let mut trie = Trie::new();
+        assert!(trie.is_empty());
+        trie.insert(make_snippet("btw"));
+        trie.insert(make_snippet("mbtw"));
+        trie.insert(make_snippet(";sig"));
+
+        // "btw" is a suffix of "mbtw": its node stays, only the snippet goes
+        assert_eq!(trie.remove("btw").unwrap().trigger, "btw");
+        assert!(trie.find_match("btw").is_none());
+        assert_eq!(trie.find_match("mbtw").unwrap().0.trigger, "mbtw");
+
+        // Removing the rest leaves the same trie as never inserting them
+        trie.remove("mbtw");
+        let mut expected = Trie::new();
+        expected.insert(make_snippet(";sig"));
+        assert_eq!(trie, expected);
+
+        assert!(trie.remove("mbtw").is_none());
+        assert!(trie.remove(";si").is_none());
+        assert!(trie.get(";sig").is_some());
+        trie.remove(";sig");
+        assert!(trie.is_empty());
+
+        // Re-inserting after removal works like a fresh insert
+        trie.insert(make_snippet("btw"));
+        assert!(!trie.is_empty());
+        assert_eq!(trie.find_match("hey btw").unwrap().1, 3);
+    }
+
+    #[test]
+    fn test_update_replaces_only_existing() {
+        let mut trie = Trie::new();
+        trie.insert(make_snippet(";a"));
+
+        let mut edited = make_snippet(";a");
+        edited.replace = "edited".to_string();
+        assert_eq!(trie.update(";a", edited).unwrap().replace, "content");
+        assert_eq!(trie.get(";a").unwrap().replace, "edited");
+
+        assert!(trie.update(";b", make_snippet(";b")).is_none());
+        assert!(trie.get(";b").is_none());
+    }
 }
@@ -0,0 +1,97 @@
+/// Words that end in a period without ending the sentence, lowercase
+const ABBREVIATIONS: &[&str] = &[
+    "approx", "cf", "co", "dept", "dr", "etc", "fig", "inc", "jr", "ltd", "mr", "mrs", "ms", "no", "prof", "sr",
+    "st", "vol", "vs",
+];
+
+/// Whether a letter typed after `before` starts a new sentence: `before` ends in
+/// `.`, `!` or `?` followed by whitespace. Errs on the side of leaving text alone,
+/// so abbreviations (`e.g.`, `Dr.`), initials (`J.`), list numbers (`1.`) and
+/// ellipses don't count, and neither does the start of input.
+pub fn starts_sentence(before: &str) -> bool {
+    let text = before.trim_end();
+    if text.len() == before.len() {
+        return false;
+    }
+
+    let mut chars = text.chars();
+    match chars.next_back() {
+        Some('!' | '?') => true,
+        Some('.') => {
+            let word = chars.as_str().rsplit(char::is_whitespace).next().unwrap_or("");
+            word.chars().count() > 1
+                && word.chars().all(char::is_alphabetic)
+                && !ABBREVIATIONS.contains(&word.to_lowercase().as_str())
+        }
+        _ => false,
+    }
+}
+
+/// Whether a space typed after `before` is the second of two spaces ending a word,
+/// which `double_space_period` turns into ". ". Only after a letter or digit, so
+/// spaces after punctuation and runs of indentation are left alone.
+pub fn is_double_space(before: &str) -> bool {
+    let mut chars = before.chars();
+    chars.next_back() == Some(' ') && chars.next_back().is_some_and(char::is_alphanumeric)
+}
+
+/// The capital of `ch`, if it is a lowercase letter with a single-character capital
+pub fn capital(ch: char) -> Option<char> {
+    if !ch.is_lowercase() {
+        return None;
+    }
+    let mut upper = ch.to_uppercase();
+    match (upper.next(), upper.next()) {
+        (Some(capital), None) => Some(capital),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_starts_sentence() {
+        assert!(starts_sentence("It works. "));
+        assert!(starts_sentence("Really?  "));
+        assert!(starts_sentence("Done!\n"));
+
+        // Nothing to go on, or still in the sentence
+        assert!(!starts_sentence(""));
+        assert!(!starts_sentence("It works."));
+        assert!(!starts_sentence("It works "));
+        assert!(!starts_sentence("It works, "));
+
+        // Periods that don't end a sentence
+        assert!(!starts_sentence("Ask Dr. "));
+        assert!(!starts_sentence("apples vs. "));
+        assert!(!starts_sentence("fruit, e.g. "));
+        assert!(!starts_sentence("written by J. "));
+        assert!(!starts_sentence("1. "));
+        assert!(!starts_sentence("well... "));
+        assert!(!starts_sentence("see example.com. "));
+    }
+
+    #[test]
+    fn test_is_double_space() {
+        assert!(is_double_space("It works "));
+        assert!(is_double_space("version 2 "));
+
+        assert!(!is_double_space("It works"));
+        assert!(!is_double_space(" "));
+        assert!(!is_double_space("It works.  "));
+        assert!(!is_double_space("Really? "));
+        assert!(!is_double_space("    "));
+    }
+
+    #[test]
+    fn test_capital() {
+        assert_eq!(capital('w'), Some('W'));
+        assert_eq!(capital('é'), Some('É'));
+        assert_eq!(capital('W'), None);
+        assert_eq!(capital('3'), None);
+        // "SS" would change the length of the text on screen
+        assert_eq!(capital('ß'), None);
+    }
+}
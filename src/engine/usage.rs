@@ -0,0 +1,179 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::config::Snippet;
+
+/// How often and how recently a trigger was expanded
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsageEntry {
+    pub count: u64,
+    /// Unix timestamp (seconds) of the last expansion
+    pub last_used: u64,
+}
+
+/// Per-trigger usage, persisted so the snippet palette can list recent snippets first
+#[derive(Debug, Default)]
+pub struct UsageStats {
+    /// Where to save; `None` keeps the stats in memory only
+    path: Option<PathBuf>,
+    entries: HashMap<String, UsageEntry>,
+    /// Number of the last snapshot taken for saving
+    generation: u64,
+    /// Number of the last snapshot written, so a slow write of an older one
+    /// doesn't overwrite a newer one
+    saved: Arc<Mutex<u64>>,
+}
+
+impl UsageStats {
+    /// Load stats from `path`, starting empty if the file is missing or unreadable
+    pub fn load(path: PathBuf) -> Self {
+        let entries = match std::fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                log::warn!("Ignoring invalid usage stats in {}: {}", path.display(), e);
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+
+        Self {
+            path: Some(path),
+            entries,
+            ..Self::default()
+        }
+    }
+
+    /// Load stats from the default location (`~/.local/share/xpander/usage.json`)
+    pub fn load_default() -> Self {
+        match dirs::data_dir() {
+            Some(dir) => Self::load(dir.join("xpander").join("usage.json")),
+            None => Self::default(),
+        }
+    }
+
+    /// Record an expansion of `trigger` at `now` (unix seconds) and save. Inside a
+    /// tokio runtime the file is written on the blocking pool, off the engine task.
+    pub fn record(&mut self, trigger: &str, now: u64) {
+        let entry = self.entries.entry(trigger.to_string()).or_default();
+        entry.count += 1;
+        entry.last_used = now;
+
+        let Some(path) = self.path.clone() else {
+            return;
+        };
+        let content = match serde_json::to_string(&self.entries) {
+            Ok(content) => content,
+            Err(e) => {
+                log::warn!("Failed to serialize usage stats: {}", e);
+                return;
+            }
+        };
+        self.generation += 1;
+        let (generation, saved) = (self.generation, self.saved.clone());
+        let write = move || {
+            if let Err(e) = save(&path, &content, generation, &saved) {
+                log::warn!("Failed to save usage stats: {}", e);
+            }
+        };
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => drop(runtime.spawn_blocking(write)),
+            Err(_) => write(),
+        }
+    }
+
+    pub fn get(&self, trigger: &str) -> Option<&UsageEntry> {
+        self.entries.get(trigger)
+    }
+
+    /// Sort snippets most recently used first, then most used. Snippets that were
+    /// never used keep their config order at the end.
+    pub fn order_by_usage(&self, snippets: &mut [Snippet]) {
+        snippets.sort_by_key(|s| {
+            let entry = self.get(&s.trigger);
+            (
+                entry.is_none(),
+                Reverse(entry.map_or(0, |e| e.last_used)),
+                Reverse(entry.map_or(0, |e| e.count)),
+            )
+        });
+    }
+}
+
+/// Write snapshot number `generation` of the stats, unless a newer one was
+/// already written
+fn save(path: &Path, content: &str, generation: u64, saved: &Mutex<u64>) -> Result<()> {
+    let mut saved = saved.lock().unwrap_or_else(|e| e.into_inner());
+    if *saved > generation {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+    }
+    std::fs::write(path, content)
+        .with_context(|| format!("Failed to write usage stats: {}", path.display()))?;
+    *saved = generation;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_order_by_usage() {
+        let mut stats = UsageStats::default();
+        stats.record(";old", 100);
+        stats.record(";old", 100);
+        stats.record(";recent", 200);
+
+        let mut snippets: Vec<Snippet> = [";unused1", ";old", ";unused2", ";recent"]
+            .iter()
+            .map(|t| Snippet::new(*t, "x"))
+            .collect();
+        stats.order_by_usage(&mut snippets);
+
+        let order: Vec<&str> = snippets.iter().map(|s| s.trigger.as_str()).collect();
+        assert_eq!(order, vec![";recent", ";old", ";unused1", ";unused2"]);
+    }
+
+    #[test]
+    fn test_usage_persisted() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("data").join("usage.json");
+
+        let mut stats = UsageStats::load(path.clone());
+        stats.record(";sig", 42);
+        stats.record(";sig", 43);
+
+        let loaded = UsageStats::load(path.clone());
+        assert_eq!(loaded.get(";sig"), Some(&UsageEntry { count: 2, last_used: 43 }));
+
+        // An older snapshot finishing late doesn't overwrite a newer one
+        let saved = Mutex::new(0);
+        save(&path, "{}", 2, &saved).unwrap();
+        save(&path, "{\"stale\":{}}", 1, &saved).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{}");
+    }
+
+    #[tokio::test]
+    async fn test_usage_saved_off_the_runtime() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("usage.json");
+
+        let mut stats = UsageStats::load(path.clone());
+        stats.record(";sig", 42);
+        let expected = Some(UsageEntry { count: 1, last_used: 42 });
+        for _ in 0..100 {
+            if UsageStats::load(path.clone()).get(";sig") == expected.as_ref() {
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        panic!("usage stats were not saved");
+    }
+}
@@ -0,0 +1,259 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::os::fd::AsFd;
+use std::time::{Duration, Instant};
+use wayland_client::globals::{registry_queue_init, GlobalListContents};
+use wayland_client::protocol::wl_keyboard::{KeyState, KeymapFormat};
+use wayland_client::protocol::wl_registry::WlRegistry;
+use wayland_client::protocol::wl_seat::WlSeat;
+use wayland_client::{delegate_noop, Connection, Dispatch, EventQueue, QueueHandle};
+use wayland_protocols_misc::zwp_virtual_keyboard_v1::client::zwp_virtual_keyboard_manager_v1::ZwpVirtualKeyboardManagerV1;
+use wayland_protocols_misc::zwp_virtual_keyboard_v1::client::zwp_virtual_keyboard_v1::ZwpVirtualKeyboardV1;
+
+/// X11 clients (XWayland) can't see keycodes above 255, which is xkb keycode
+/// 8 + this many keys
+const MAX_KEYS: usize = 247;
+
+/// Modifier mask of Shift in the standard xkb modifier layout
+const SHIFT_MASK: u32 = 1;
+
+/// Nothing we bind sends events we need
+struct State;
+
+impl Dispatch<WlRegistry, GlobalListContents> for State {
+    fn event(
+        _: &mut Self,
+        _: &WlRegistry,
+        _: <WlRegistry as wayland_client::Proxy>::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+delegate_noop!(State: ignore WlSeat);
+delegate_noop!(State: ZwpVirtualKeyboardManagerV1);
+delegate_noop!(State: ZwpVirtualKeyboardV1);
+
+/// A virtual keyboard on the compositor's Wayland connection
+/// (`zwp_virtual_keyboard_v1`). Like wtype, it gives every key it presses a
+/// keycode of its own in a keymap it uploads itself, so any character can be
+/// typed whatever the user's layout.
+pub struct VirtualKeyboard {
+    queue: EventQueue<State>,
+    keyboard: ZwpVirtualKeyboardV1,
+    keymap: Keymap,
+    start: Instant,
+}
+
+impl VirtualKeyboard {
+    /// Connect to the compositor and create a virtual keyboard on the first seat.
+    /// Fails if there is no Wayland session or the protocol isn't offered.
+    pub fn connect() -> Result<Self> {
+        let conn = Connection::connect_to_env().context("No Wayland session")?;
+        let (globals, mut queue) =
+            registry_queue_init::<State>(&conn).context("Failed to list the compositor's globals")?;
+        let qh = queue.handle();
+
+        let manager: ZwpVirtualKeyboardManagerV1 = globals
+            .bind(&qh, 1..=1, ())
+            .context("The compositor doesn't offer the virtual keyboard protocol")?;
+        let seat: WlSeat = globals.bind(&qh, 1..=1, ()).context("The compositor has no seat")?;
+        let keyboard = manager.create_virtual_keyboard(&seat, &qh, ());
+
+        // A compositor that doesn't let this client have one ends the connection
+        queue
+            .roundtrip(&mut State)
+            .context("The compositor refused to create a virtual keyboard")?;
+
+        Ok(Self {
+            queue,
+            keyboard,
+            keymap: Keymap::default(),
+            start: Instant::now(),
+        })
+    }
+
+    /// Press and release each of `keys` (xkb keysym names) in turn, holding
+    /// Shift throughout if `shift`, and waiting `delay` after each
+    pub fn press_keys(&mut self, keys: &[String], shift: bool, delay: Duration) -> Result<()> {
+        if keys.is_empty() {
+            return Ok(());
+        }
+
+        if shift {
+            self.keyboard.modifiers(SHIFT_MASK, 0, 0, 0);
+        }
+        let mut rest = keys;
+        while !rest.is_empty() {
+            // Start a fresh keymap once this one is full
+            if !self.keymap.fits(&rest[0]) {
+                self.keymap = Keymap::default();
+            }
+            let mut codes = Vec::new();
+            while let Some(key) = rest.first().filter(|key| self.keymap.fits(key)) {
+                codes.push(self.keymap.keycode(key));
+                rest = &rest[1..];
+            }
+            if self.keymap.changed {
+                self.upload_keymap()?;
+            }
+
+            for code in codes {
+                self.keyboard.key(self.time(), code, KeyState::Pressed.into());
+                self.keyboard.key(self.time(), code, KeyState::Released.into());
+                self.queue.flush().context("Lost the connection to the compositor")?;
+                if !delay.is_zero() {
+                    std::thread::sleep(delay);
+                }
+            }
+        }
+        if shift {
+            self.keyboard.modifiers(0, 0, 0, 0);
+        }
+
+        self.queue
+            .roundtrip(&mut State)
+            .context("Lost the connection to the compositor")?;
+        Ok(())
+    }
+
+    /// Hand the compositor the current keymap, through a file as the protocol wants
+    fn upload_keymap(&mut self) -> Result<()> {
+        let text = self.keymap.to_xkb();
+        let file = keymap_file(&text).context("Failed to write the virtual keyboard keymap")?;
+        self.keyboard
+            .keymap(KeymapFormat::XkbV1.into(), file.as_fd(), text.len() as u32 + 1);
+        self.queue
+            .roundtrip(&mut State)
+            .context("Lost the connection to the compositor")?;
+        self.keymap.changed = false;
+        Ok(())
+    }
+
+    /// Milliseconds since the keyboard was created, as key events are stamped
+    fn time(&self) -> u32 {
+        self.start.elapsed().as_millis() as u32
+    }
+}
+
+/// An unlinked file holding `text` and a terminating NUL
+fn keymap_file(text: &str) -> std::io::Result<File> {
+    let path = dirs::runtime_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join(format!("xpander-keymap-{}", std::process::id()));
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)?;
+    std::fs::remove_file(&path)?;
+    file.write_all(text.as_bytes())?;
+    file.write_all(b"\0")?;
+    file.flush()?;
+    Ok(file)
+}
+
+/// The keysym name typing `c` takes, or None for control characters that
+/// have no key
+pub fn keysym_name(c: char) -> Option<String> {
+    match c {
+        '\n' => Some("Return".to_string()),
+        '\t' => Some("Tab".to_string()),
+        c if c.is_control() => None,
+        c => Some(format!("U{:04X}", c as u32)),
+    }
+}
+
+/// The keys of the virtual keyboard, each with a keycode of its own
+#[derive(Default)]
+struct Keymap {
+    keys: Vec<String>,
+    codes: HashMap<String, u32>,
+    /// Keys were added since the compositor was last sent the keymap
+    changed: bool,
+}
+
+impl Keymap {
+    fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Whether `key` has a keycode or there is room to give it one
+    fn fits(&self, key: &str) -> bool {
+        self.codes.contains_key(key) || self.len() < MAX_KEYS
+    }
+
+    /// The evdev keycode pressing `key`, adding it to the keymap if needed
+    fn keycode(&mut self, key: &str) -> u32 {
+        if let Some(&code) = self.codes.get(key) {
+            return code;
+        }
+        self.keys.push(key.to_string());
+        self.changed = true;
+        let code = self.keys.len() as u32;
+        self.codes.insert(key.to_string(), code);
+        code
+    }
+
+    /// The keymap in xkb's text format. Keycode n is xkb keycode n + 8.
+    fn to_xkb(&self) -> String {
+        let mut keycodes = String::new();
+        let mut symbols = String::new();
+        for (i, key) in self.keys.iter().enumerate() {
+            let code = i + 1;
+            keycodes.push_str(&format!("    <K{}> = {};\n", code, code + 8));
+            symbols.push_str(&format!("    key <K{}> {{ [ {} ] }};\n", code, key));
+        }
+        format!(
+            "xkb_keymap {{\n\
+             xkb_keycodes \"xpander\" {{\n    minimum = 8;\n    maximum = {};\n{}}};\n\
+             xkb_types \"xpander\" {{ include \"complete\" }};\n\
+             xkb_compatibility \"xpander\" {{ include \"complete\" }};\n\
+             xkb_symbols \"xpander\" {{\n{}}};\n\
+             }};\n",
+            self.len() + 9,
+            keycodes,
+            symbols
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keysym_names() {
+        assert_eq!(keysym_name('a').as_deref(), Some("U0061"));
+        assert_eq!(keysym_name('€').as_deref(), Some("U20AC"));
+        assert_eq!(keysym_name('😀').as_deref(), Some("U1F600"));
+        assert_eq!(keysym_name('\n').as_deref(), Some("Return"));
+        assert_eq!(keysym_name('\u{7}'), None);
+    }
+
+    #[test]
+    fn test_keymap() {
+        let mut keymap = Keymap::default();
+        assert_eq!(keymap.keycode("U0061"), 1);
+        assert_eq!(keymap.keycode("BackSpace"), 2);
+        assert_eq!(keymap.keycode("U0061"), 1);
+
+        let xkb = keymap.to_xkb();
+        assert!(xkb.contains("maximum = 11;"));
+        assert!(xkb.contains("<K1> = 9;"));
+        assert!(xkb.contains("key <K2> { [ BackSpace ] };"));
+
+        // Keycodes stop at 255, where X11 clients stop seeing them
+        for i in 0..MAX_KEYS as u32 - 2 {
+            keymap.keycode(&format!("U{:04X}", 0x100 + i));
+        }
+        assert_eq!(keymap.len(), MAX_KEYS);
+        assert!(keymap.fits("BackSpace"));
+        assert!(!keymap.fits("U0062"));
+    }
+}
@@ -0,0 +1,287 @@
+use regex::Regex;
+use std::process::Command;
+
+use crate::config::Snippet;
+
+/// Information about the currently focused window
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ActiveWindow {
+    /// Window class / app id (e.g. "firefox", "org.keepassxc.KeePassXC")
+    pub class: String,
+    /// Window title
+    pub title: String,
+    /// Whether the window is fullscreen, if the compositor told us
+    pub fullscreen: Option<bool>,
+}
+
+impl ActiveWindow {
+    /// Check whether the window class matches any entry in the list (case-insensitive substring)
+    pub fn class_matches(&self, patterns: &[String]) -> bool {
+        patterns.iter().any(|p| self.class_contains(p))
+    }
+
+    fn class_contains(&self, pattern: &str) -> bool {
+        !self.class.is_empty()
+            && !pattern.is_empty()
+            && self.class.to_lowercase().contains(&pattern.to_lowercase())
+    }
+
+    /// Check one `applications` entry against the window. `title:/regex/` matches the
+    /// title against a regex, `title:text` is a case-insensitive title substring, and
+    /// `class:name` or a bare name is a case-insensitive class substring.
+    pub fn matches_app(&self, entry: &str) -> bool {
+        let Some(pattern) = entry.strip_prefix("title:") else {
+            return self.class_contains(entry.strip_prefix("class:").unwrap_or(entry));
+        };
+
+        match pattern.strip_prefix('/').and_then(|p| p.strip_suffix('/')) {
+            Some(regex) => match Regex::new(regex) {
+                Ok(regex) => regex.is_match(&self.title),
+                Err(e) => {
+                    log::warn!("Invalid title regex in '{}': {}", entry, e);
+                    false
+                }
+            },
+            None => !pattern.is_empty() && self.title.to_lowercase().contains(&pattern.to_lowercase()),
+        }
+    }
+}
+
+/// Whether a snippet's `applications` / `exclude_applications` allow it to expand in
+/// `window`. If the window is unknown, snippets limited to some applications don't
+/// expand and exclusions are ignored.
+pub fn app_filter_allows(window: Option<&ActiveWindow>, snippet: &Snippet) -> bool {
+    let Some(window) = window else {
+        return snippet.applications.is_none();
+    };
+
+    let included = snippet
+        .applications
+        .as_ref()
+        .is_none_or(|apps| apps.iter().any(|a| window.matches_app(a)));
+    let excluded = snippet
+        .exclude_applications
+        .as_ref()
+        .is_some_and(|apps| apps.iter().any(|a| window.matches_app(a)));
+    included && !excluded
+}
+
+/// Whether `settings.suspend_when_fullscreen` should suppress expansion in `window`.
+/// Only a window known to be fullscreen does; an unknown state expands as usual.
+pub fn suspended_for_fullscreen(suspend_when_fullscreen: bool, window: Option<&ActiveWindow>) -> bool {
+    suspend_when_fullscreen && window.and_then(|w| w.fullscreen) == Some(true)
+}
+
+/// The snippet's `per_app_replace` replacement for `window`, if one matches
+pub fn app_replacement<'a>(window: Option<&ActiveWindow>, snippet: &'a Snippet) -> Option<&'a str> {
+    let window = window?;
+    snippet
+        .per_app_replace
+        .iter()
+        .find(|entry| entry.applications.iter().any(|a| window.matches_app(a)))
+        .map(|entry| entry.replace.as_str())
+}
+
+/// Query the compositor for the active window.
+///
+/// There is no standard way to do this on Wayland, so we try the
+/// compositor-specific tools we know about (Hyprland, Sway) and fall back to
+/// xdotool for X11/XWayland. Returns None if nothing could be detected.
+pub fn active_window() -> Option<ActiveWindow> {
+    from_hyprland()
+        .or_else(from_sway)
+        .or_else(from_xdotool)
+}
+
+/// Hyprland: `hyprctl activewindow -j`
+fn from_hyprland() -> Option<ActiveWindow> {
+    let output = run("hyprctl", &["activewindow", "-j"])?;
+    // JSON is valid YAML, so we can reuse serde_yaml here
+    let value: serde_yaml::Value = serde_yaml::from_str(&output).ok()?;
+    let class = value.get("class")?.as_str()?.to_string();
+    let title = value
+        .get("title")
+        .and_then(|t| t.as_str())
+        .unwrap_or("")
+        .to_string();
+    // Older releases report a bool, newer ones the fullscreen mode (0 = none)
+    let fullscreen = value.get("fullscreen").and_then(|f| f.as_bool().or_else(|| f.as_u64().map(|m| m != 0)));
+    Some(ActiveWindow { class, title, fullscreen })
+}
+
+/// Sway: walk `swaymsg -t get_tree` for the focused node
+fn from_sway() -> Option<ActiveWindow> {
+    let output = run("swaymsg", &["-t", "get_tree"])?;
+    let tree: serde_yaml::Value = serde_yaml::from_str(&output).ok()?;
+    find_focused_sway_node(&tree)
+}
+
+fn find_focused_sway_node(node: &serde_yaml::Value) -> Option<ActiveWindow> {
+    if node.get("focused").and_then(|f| f.as_bool()) == Some(true) {
+        // Native Wayland clients report app_id, XWayland clients report window_properties.class
+        let class = node
+            .get("app_id")
+            .and_then(|a| a.as_str())
+            .or_else(|| {
+                node.get("window_properties")
+                    .and_then(|p| p.get("class"))
+                    .and_then(|c| c.as_str())
+            })
+            .unwrap_or("")
+            .to_string();
+        let title = node
+            .get("name")
+            .and_then(|n| n.as_str())
+            .unwrap_or("")
+            .to_string();
+        // 0 = not fullscreen, 1 = fullscreen on its output, 2 = global fullscreen
+        let fullscreen = node.get("fullscreen_mode").and_then(|m| m.as_u64()).map(|m| m != 0);
+        return Some(ActiveWindow { class, title, fullscreen });
+    }
+
+    for key in ["nodes", "floating_nodes"] {
+        if let Some(serde_yaml::Value::Sequence(children)) = node.get(key) {
+            for child in children {
+                if let Some(found) = find_focused_sway_node(child) {
+                    return Some(found);
+                }
+            }
+        }
+    }
+
+    None
+}
+
+/// X11 / XWayland: `xdotool getactivewindow getwindowclassname`
+fn from_xdotool() -> Option<ActiveWindow> {
+    let class = run("xdotool", &["getactivewindow", "getwindowclassname"])?;
+    let title = run("xdotool", &["getactivewindow", "getwindowname"]).unwrap_or_default();
+    Some(ActiveWindow {
+        class: class.trim().to_string(),
+        title: title.trim().to_string(),
+        fullscreen: x11_fullscreen(),
+    })
+}
+
+/// X11: look for `_NET_WM_STATE_FULLSCREEN` in the active window's state via xprop
+fn x11_fullscreen() -> Option<bool> {
+    let id = run("xdotool", &["getactivewindow"])?;
+    let state = run("xprop", &["-id", id.trim(), "_NET_WM_STATE"])?;
+    Some(state.contains("_NET_WM_STATE_FULLSCREEN"))
+}
+
+/// Run a command and return its stdout if it succeeded
+fn run(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::schema::AppReplace;
+
+    #[test]
+    fn test_class_matches() {
+        let window = ActiveWindow {
+            class: "org.keepassxc.KeePassXC".to_string(),
+            title: "Passwords.kdbx".to_string(),
+            ..Default::default()
+        };
+
+        assert!(window.class_matches(&["keepassxc".to_string()]));
+        assert!(!window.class_matches(&["bitwarden".to_string()]));
+        assert!(!window.class_matches(&["".to_string()]));
+    }
+
+    #[test]
+    fn test_app_filters() {
+        let window = ActiveWindow {
+            class: "firefox".to_string(),
+            title: "Pull requests · GitHub — Mozilla Firefox".to_string(),
+            ..Default::default()
+        };
+
+        assert!(window.matches_app("Firefox"));
+        assert!(window.matches_app("class:firefox"));
+        assert!(!window.matches_app("class:GitHub"));
+        assert!(window.matches_app("title:/GitHub/"));
+        assert!(!window.matches_app("title:/^GitHub/"));
+        assert!(window.matches_app("title:github"));
+        assert!(!window.matches_app("title:/(unclosed/"));
+
+        let mut snippet = Snippet::new(";pr", "Fixes #");
+        snippet.applications = Some(vec!["title:/GitHub/".to_string()]);
+        assert!(app_filter_allows(Some(&window), &snippet));
+        assert!(!app_filter_allows(None, &snippet));
+
+        snippet.exclude_applications = Some(vec!["firefox".to_string()]);
+        assert!(!app_filter_allows(Some(&window), &snippet));
+
+        snippet.applications = None;
+        assert!(app_filter_allows(None, &snippet));
+    }
+
+    #[test]
+    fn test_suspended_for_fullscreen() {
+        let window = |fullscreen| ActiveWindow {
+            class: "mpv".to_string(),
+            fullscreen,
+            ..Default::default()
+        };
+
+        assert!(suspended_for_fullscreen(true, Some(&window(Some(true)))));
+        assert!(!suspended_for_fullscreen(true, Some(&window(Some(false)))));
+        assert!(!suspended_for_fullscreen(false, Some(&window(Some(true)))));
+        // Fail open when the state or the window is unknown
+        assert!(!suspended_for_fullscreen(true, Some(&window(None))));
+        assert!(!suspended_for_fullscreen(true, None));
+    }
+
+    #[test]
+    fn test_app_replacement() {
+        let window = |class: &str| ActiveWindow {
+            class: class.to_string(),
+            title: String::new(),
+            ..Default::default()
+        };
+        let mut snippet = Snippet::new(";sig", "Cheers, Rafa");
+        snippet.per_app_replace = vec![
+            AppReplace {
+                applications: vec!["thunderbird".to_string(), "evolution".to_string()],
+                replace: "Best regards,\nRafael (ACME Corp)".to_string(),
+            },
+            AppReplace {
+                applications: vec!["Slack".to_string()],
+                replace: "- R".to_string(),
+            },
+        ];
+
+        assert_eq!(
+            app_replacement(Some(&window("org.mozilla.Thunderbird")), &snippet),
+            Some("Best regards,\nRafael (ACME Corp)")
+        );
+        assert_eq!(app_replacement(Some(&window("Slack")), &snippet), Some("- R"));
+        assert_eq!(app_replacement(Some(&window("firefox")), &snippet), None);
+        assert_eq!(app_replacement(None, &snippet), None);
+    }
+
+    #[test]
+    fn test_find_focused_sway_node() {
+        let tree = r#"
+{"nodes": [
+  {"focused": false, "nodes": [
+    {"focused": false, "app_id": "firefox", "name": "Mozilla Firefox", "nodes": []},
+    {"focused": true, "app_id": "Bitwarden", "name": "Vault", "nodes": []}
+  ]}
+]}
+"#;
+        let value: serde_yaml::Value = serde_yaml::from_str(tree).unwrap();
+        let window = find_focused_sway_node(&value).unwrap();
+        assert_eq!(window.class, "Bitwarden");
+        assert_eq!(window.title, "Vault");
+    }
+}
@@ -1,12 +1,35 @@
 use gtk4::prelude::*;
 use gtk4::{
-    Box as GtkBox, CheckButton, Dialog, DialogFlags, Entry, Frame, Label,
-    Orientation, ResponseType, ScrolledWindow, TextBuffer, TextView, Window,
+    Box as GtkBox, CheckButton, Dialog, DialogFlags, DropDown, Entry, Frame, Grid, Label,
+    Orientation, ResponseType, ScrolledWindow, SpinButton, Switch, TextBuffer, TextView, Window,
 };
 use std::cell::RefCell;
+use std::path::PathBuf;
 use std::rc::Rc;
 
-use crate::config::Snippet;
+use crate::config::schema::{contains_cursor_marker, Settings};
+use crate::config::{OutputMode, PostAction, Snippet};
+use crate::engine::matcher::{try_regex_trigger, RegexTest};
+
+/// Entries in the "After expansion" dropdown, in display order
+const POST_ACTIONS: [(Option<PostAction>, &str); 3] = [
+    (None, "Do nothing"),
+    (Some(PostAction::PressEnter), "Press Enter"),
+    (Some(PostAction::PressTab), "Press Tab"),
+];
+
+/// Output modes offered in the snippet editor, with their labels
+const OUTPUT_MODES: [(Option<OutputMode>, &str); 3] = [
+    (None, "Default"),
+    (Some(OutputMode::Type), "Type keystrokes"),
+    (Some(OutputMode::Paste), "Paste"),
+];
+
+/// Keyboard layouts offered in the settings dialog (see `KeyMap`)
+const LAYOUTS: [&str; 3] = ["qwerty", "azerty", "qwertz"];
+
+/// Largest keystroke delay the settings dialog allows, in milliseconds
+const MAX_KEYSTROKE_DELAY_MS: f64 = 500.0;
 
 /// Dialog for creating or editing a snippet
 pub struct SnippetEditor {
@@ -14,11 +37,22 @@ pub struct SnippetEditor {
     trigger_entry: Entry,
     replace_buffer: TextBuffer,
     label_entry: Entry,
+    notes_buffer: TextBuffer,
     propagate_case: CheckButton,
     cursor_position: CheckButton,
     word_boundary: CheckButton,
+    whole_word: CheckButton,
+    line_start: CheckButton,
     regex_check: CheckButton,
+    raw_check: CheckButton,
     enabled_check: CheckButton,
+    post_action_dropdown: DropDown,
+    output_mode_dropdown: DropDown,
+    keep_prefix_spin: SpinButton,
+    custom_delay_check: CheckButton,
+    delay_spin: SpinButton,
+    /// The snippet being edited, so saving keeps the options the dialog doesn't show
+    existing: Option<Snippet>,
     on_save: Rc<RefCell<Option<Box<dyn Fn(Snippet)>>>>,
 }
 
@@ -105,6 +139,22 @@ impl SnippetEditor {
         label_box.append(&label_entry);
         content.append(&label_box);
 
+        // Notes field (optional, never typed)
+        let notes_frame = Frame::new(Some("Notes (optional)"));
+        let notes_scrolled = ScrolledWindow::builder()
+            .min_content_height(48)
+            .build();
+        let notes_view = TextView::new();
+        notes_view.set_wrap_mode(gtk4::WrapMode::Word);
+        notes_view.set_left_margin(8);
+        notes_view.set_right_margin(8);
+        notes_view.set_top_margin(4);
+        notes_view.set_bottom_margin(4);
+        let notes_buffer = notes_view.buffer();
+        notes_scrolled.set_child(Some(&notes_view));
+        notes_frame.set_child(Some(&notes_scrolled));
+        content.append(&notes_frame);
+
         // Options
         let options_frame = Frame::new(Some("Options"));
         let options_box = GtkBox::new(Orientation::Vertical, 8);
@@ -116,19 +166,158 @@ impl SnippetEditor {
         let propagate_case = CheckButton::with_label("Propagate case from trigger");
         let cursor_position = CheckButton::with_label("Position cursor at $|$ marker");
         let word_boundary = CheckButton::with_label("Only match at word boundaries");
+        let whole_word = CheckButton::with_label("Only match whole words (expands on the next space)");
+        let line_start = CheckButton::with_label("Only match at the start of a line");
         let regex_check = CheckButton::with_label("Use regex matching");
+        let raw_check = CheckButton::with_label("Type as written (don't expand {{variables}})");
         let enabled_check = CheckButton::with_label("Enabled");
         enabled_check.set_active(true);
 
         options_box.append(&propagate_case);
         options_box.append(&cursor_position);
         options_box.append(&word_boundary);
+        options_box.append(&whole_word);
+        options_box.append(&line_start);
         options_box.append(&regex_check);
+        options_box.append(&raw_check);
         options_box.append(&enabled_check);
 
+        let post_action_box = GtkBox::new(Orientation::Horizontal, 8);
+        let post_action_label = Label::new(Some("After expansion"));
+        let post_action_names: Vec<&str> = POST_ACTIONS.iter().map(|(_, name)| *name).collect();
+        let post_action_dropdown = DropDown::from_strings(&post_action_names);
+        post_action_box.append(&post_action_label);
+        post_action_box.append(&post_action_dropdown);
+        options_box.append(&post_action_box);
+
+        let output_mode_box = GtkBox::new(Orientation::Horizontal, 8);
+        let output_mode_label = Label::new(Some("Output"));
+        let output_mode_names: Vec<&str> = OUTPUT_MODES.iter().map(|(_, name)| *name).collect();
+        let output_mode_dropdown = DropDown::from_strings(&output_mode_names);
+        output_mode_dropdown.set_tooltip_text(Some("Type or paste this snippet regardless of the global output method"));
+        output_mode_box.append(&output_mode_label);
+        output_mode_box.append(&output_mode_dropdown);
+        options_box.append(&output_mode_box);
+
+        let keep_prefix_box = GtkBox::new(Orientation::Horizontal, 8);
+        let keep_prefix_label = Label::new(Some("Keep first N trigger characters"));
+        let keep_prefix_spin = SpinButton::with_range(0.0, 32.0, 1.0);
+        keep_prefix_box.append(&keep_prefix_label);
+        keep_prefix_box.append(&keep_prefix_spin);
+        options_box.append(&keep_prefix_box);
+
+        // Unchecked, the snippet types at the global keystroke delay
+        let delay_box = GtkBox::new(Orientation::Horizontal, 8);
+        let custom_delay_check = CheckButton::with_label("Own keystroke delay (ms)");
+        let delay_spin = SpinButton::with_range(0.0, MAX_KEYSTROKE_DELAY_MS, 1.0);
+        delay_spin.set_sensitive(false);
+        {
+            let delay_spin = delay_spin.clone();
+            custom_delay_check.connect_toggled(move |check| delay_spin.set_sensitive(check.is_active()));
+        }
+        delay_box.append(&custom_delay_check);
+        delay_box.append(&delay_spin);
+        options_box.append(&delay_box);
+
         options_frame.set_child(Some(&options_box));
         content.append(&options_frame);
 
+        // Users often add $|$ but forget the checkbox, offer to tick it
+        let marker_warning_box = GtkBox::new(Orientation::Horizontal, 8);
+        let marker_warning_label = Label::new(Some("The replacement has a $|$ marker, but the cursor won't be moved to it"));
+        marker_warning_label.set_xalign(0.0);
+        marker_warning_label.set_wrap(true);
+        marker_warning_label.set_hexpand(true);
+        marker_warning_label.add_css_class("warning");
+        let marker_fix_button = gtk4::Button::with_label("Position Cursor");
+        {
+            let cursor_position = cursor_position.clone();
+            marker_fix_button.connect_clicked(move |_| cursor_position.set_active(true));
+        }
+        marker_warning_box.append(&marker_warning_label);
+        marker_warning_box.append(&marker_fix_button);
+        marker_warning_box.set_visible(false);
+        content.append(&marker_warning_box);
+
+        let update_marker_warning = {
+            let replace_buffer = replace_buffer.clone();
+            let cursor_position = cursor_position.clone();
+            let marker_warning_box = marker_warning_box.clone();
+            Rc::new(move || {
+                let (start, end) = replace_buffer.bounds();
+                let replace = replace_buffer.text(&start, &end, true);
+                marker_warning_box.set_visible(!cursor_position.is_active() && contains_cursor_marker(&replace));
+            })
+        };
+        {
+            let update = update_marker_warning.clone();
+            replace_buffer.connect_changed(move |_| update());
+        }
+        {
+            let update = update_marker_warning.clone();
+            cursor_position.connect_toggled(move |_| update());
+        }
+
+        // Regex tester, shown while regex matching is on
+        let regex_test_box = GtkBox::new(Orientation::Vertical, 4);
+        let regex_test_label = Label::new(Some("Test regex"));
+        regex_test_label.set_xalign(0.0);
+        let regex_sample_entry = Entry::new();
+        regex_sample_entry.set_placeholder_text(Some("Sample text, e.g. ;n42"));
+        let regex_result_label = Label::new(None);
+        regex_result_label.set_xalign(0.0);
+        regex_result_label.set_wrap(true);
+        regex_test_box.append(&regex_test_label);
+        regex_test_box.append(&regex_sample_entry);
+        regex_test_box.append(&regex_result_label);
+        regex_test_box.set_visible(false);
+        content.append(&regex_test_box);
+
+        let update_regex_test = {
+            let trigger_entry = trigger_entry.clone();
+            let regex_check = regex_check.clone();
+            let regex_sample_entry = regex_sample_entry.clone();
+            let regex_test_box = regex_test_box.clone();
+            let regex_result_label = regex_result_label.clone();
+            Rc::new(move || {
+                regex_test_box.set_visible(regex_check.is_active());
+                if !regex_check.is_active() {
+                    return;
+                }
+                let result = try_regex_trigger(&trigger_entry.text(), &regex_sample_entry.text());
+                let (text, is_error) = match result {
+                    RegexTest::Invalid(e) => (format!("Invalid pattern: {}", e), true),
+                    _ if regex_sample_entry.text().is_empty() => ("Pattern compiles".to_string(), false),
+                    RegexTest::NoMatch => ("No match at the end of the sample".to_string(), false),
+                    RegexTest::Matched { text, captures } => {
+                        let mut summary = format!("Matches \"{}\"", text);
+                        for (i, capture) in captures.iter().enumerate() {
+                            summary.push_str(&format!("\n${} = \"{}\"", i + 1, capture));
+                        }
+                        (summary, false)
+                    }
+                };
+                regex_result_label.set_text(&text);
+                if is_error {
+                    regex_result_label.add_css_class("error");
+                } else {
+                    regex_result_label.remove_css_class("error");
+                }
+            })
+        };
+        {
+            let update = update_regex_test.clone();
+            trigger_entry.connect_changed(move |_| update());
+        }
+        {
+            let update = update_regex_test.clone();
+            regex_sample_entry.connect_changed(move |_| update());
+        }
+        {
+            let update = update_regex_test.clone();
+            regex_check.connect_toggled(move |_| update());
+        }
+
         // Fill in existing values if editing
         if let Some(snippet) = &existing {
             trigger_entry.set_text(&snippet.trigger);
@@ -136,11 +325,32 @@ impl SnippetEditor {
             if let Some(label) = &snippet.label {
                 label_entry.set_text(label);
             }
-            propagate_case.set_active(snippet.propagate_case);
+            if let Some(notes) = &snippet.notes {
+                notes_buffer.set_text(notes);
+            }
+            propagate_case.set_active(snippet.propagates_case());
             cursor_position.set_active(snippet.cursor_position);
-            word_boundary.set_active(snippet.word_boundary);
+            word_boundary.set_active(snippet.word_boundary.unwrap_or(false));
+            whole_word.set_active(snippet.whole_word);
+            line_start.set_active(snippet.line_start);
             regex_check.set_active(snippet.regex);
+            raw_check.set_active(snippet.raw);
             enabled_check.set_active(snippet.enabled);
+            let selected = POST_ACTIONS
+                .iter()
+                .position(|(action, _)| *action == snippet.post_action)
+                .unwrap_or(0);
+            post_action_dropdown.set_selected(selected as u32);
+            let output_mode = OUTPUT_MODES
+                .iter()
+                .position(|(mode, _)| *mode == snippet.output_mode)
+                .unwrap_or(0);
+            output_mode_dropdown.set_selected(output_mode as u32);
+            keep_prefix_spin.set_value(snippet.keep_prefix_len as f64);
+            if let Some(delay) = snippet.keystroke_delay_ms {
+                custom_delay_check.set_active(true);
+                delay_spin.set_value(delay as f64);
+            }
         }
 
         let editor = Self {
@@ -148,11 +358,21 @@ impl SnippetEditor {
             trigger_entry,
             replace_buffer,
             label_entry,
+            notes_buffer,
             propagate_case,
             cursor_position,
             word_boundary,
+            whole_word,
+            line_start,
             regex_check,
+            raw_check,
             enabled_check,
+            post_action_dropdown,
+            output_mode_dropdown,
+            keep_prefix_spin,
+            custom_delay_check,
+            delay_spin,
+            existing,
             on_save: Rc::new(RefCell::new(None)),
         };
 
@@ -165,11 +385,21 @@ impl SnippetEditor {
         let trigger_entry = self.trigger_entry.clone();
         let replace_buffer = self.replace_buffer.clone();
         let label_entry = self.label_entry.clone();
+        let notes_buffer = self.notes_buffer.clone();
         let propagate_case = self.propagate_case.clone();
         let cursor_position = self.cursor_position.clone();
         let word_boundary = self.word_boundary.clone();
+        let whole_word = self.whole_word.clone();
+        let line_start = self.line_start.clone();
         let regex_check = self.regex_check.clone();
+        let raw_check = self.raw_check.clone();
         let enabled_check = self.enabled_check.clone();
+        let post_action_dropdown = self.post_action_dropdown.clone();
+        let output_mode_dropdown = self.output_mode_dropdown.clone();
+        let keep_prefix_spin = self.keep_prefix_spin.clone();
+        let custom_delay_check = self.custom_delay_check.clone();
+        let delay_spin = self.delay_spin.clone();
+        let existing = self.existing.clone();
         let on_save = self.on_save.clone();
 
         self.dialog.connect_response(move |dialog, response| {
@@ -183,6 +413,12 @@ impl SnippetEditor {
                     return;
                 }
 
+                let keep_prefix_len = keep_prefix_spin.value_as_int().max(0) as usize;
+                if !regex_check.is_active() && keep_prefix_len > trigger.chars().count() {
+                    log::warn!("Characters to keep cannot exceed the trigger length");
+                    return;
+                }
+
                 let (start, end) = replace_buffer.bounds();
                 let replace = replace_buffer.text(&start, &end, true).to_string();
 
@@ -195,16 +431,53 @@ impl SnippetEditor {
                     }
                 };
 
+                let notes = {
+                    let (start, end) = notes_buffer.bounds();
+                    let text = notes_buffer.text(&start, &end, true);
+                    if text.trim().is_empty() {
+                        None
+                    } else {
+                        Some(text.to_string())
+                    }
+                };
+
                 let snippet = Snippet {
                     trigger,
                     replace,
+                    cycle: existing.as_ref().map(|s| s.cycle.clone()).unwrap_or_default(),
                     label,
-                    propagate_case: propagate_case.is_active(),
+                    notes,
+                    propagate_case: checkbox_option(
+                        existing.as_ref().and_then(|s| s.propagate_case),
+                        propagate_case.is_active(),
+                    ),
                     cursor_position: cursor_position.is_active(),
-                    word_boundary: word_boundary.is_active(),
+                    word_boundary: checkbox_option(
+                        existing.as_ref().and_then(|s| s.word_boundary),
+                        word_boundary.is_active(),
+                    ),
+                    whole_word: whole_word.is_active(),
+                    line_start: line_start.is_active(),
+                    ignore_diacritics: existing.as_ref().is_some_and(|s| s.ignore_diacritics),
                     regex: regex_check.is_active(),
                     applications: None,
                     exclude_applications: None,
+                    per_app_replace: existing.as_ref().map(|s| s.per_app_replace.clone()).unwrap_or_default(),
+                    keep_prefix_len,
+                    post_action: POST_ACTIONS
+                        .get(post_action_dropdown.selected() as usize)
+                        .and_then(|(action, _)| *action),
+                    keystroke_delay_ms: custom_delay_check
+                        .is_active()
+                        .then(|| delay_spin.value_as_int().max(0) as u64),
+                    output_mode: OUTPUT_MODES
+                        .get(output_mode_dropdown.selected() as usize)
+                        .and_then(|(mode, _)| *mode),
+                    macro_steps: existing.as_ref().and_then(|s| s.macro_steps.clone()),
+                    active_schedule: existing.as_ref().and_then(|s| s.active_schedule.clone()),
+                    vars: existing.as_ref().map(|s| s.vars.clone()).unwrap_or_default(),
+                    raw: raw_check.is_active(),
+                    on_expand_command: existing.as_ref().and_then(|s| s.on_expand_command.clone()),
                     enabled: enabled_check.is_active(),
                 };
 
@@ -218,6 +491,28 @@ impl SnippetEditor {
         });
     }
 
+    /// Add a button that opens the config file at the definition of `trigger`,
+    /// or just opens the file if the definition can't be found
+    pub fn add_reveal_button(&self, config_path: PathBuf, trigger: String) {
+        let button = gtk4::Button::with_label("Show in Config File");
+        button.set_icon_name("document-open-symbolic");
+        button.set_tooltip_text(Some("Show in Config File"));
+        button.set_halign(gtk4::Align::Start);
+        self.dialog.content_area().append(&button);
+
+        button.connect_clicked(move |_| {
+            let line = std::fs::read_to_string(&config_path)
+                .ok()
+                .and_then(|text| crate::config::loader::find_snippet_line(&text, &trigger));
+            if line.is_none() {
+                log::info!("Could not find '{}' in {}, opening the file", trigger, config_path.display());
+            }
+            if let Err(e) = crate::open_file_in_editor(&config_path, line) {
+                log::error!("Failed to open config file: {}", e);
+            }
+        });
+    }
+
     /// Connect a callback for when the snippet is saved
     pub fn connect_save<F: Fn(Snippet) + 'static>(&self, callback: F) {
         *self.on_save.borrow_mut() = Some(Box::new(callback));
@@ -229,6 +524,16 @@ impl SnippetEditor {
     }
 }
 
+/// Value to save for an option shown as a checkbox. An option left unset (so it
+/// follows the folder's default) stays unset unless the checkbox was changed.
+fn checkbox_option(original: Option<bool>, active: bool) -> Option<bool> {
+    if active == original.unwrap_or(false) {
+        original
+    } else {
+        Some(active)
+    }
+}
+
 /// Simple dialog for importing snippets
 pub fn show_import_dialog<F>(parent: &impl IsA<Window>, on_selected: F)
 where
@@ -380,3 +685,243 @@ pub fn show_input_dialog<F>(
 
     dialog.present();
 }
+
+/// Dialog for editing the custom `variables` mapping as a key/value list.
+/// Nested variables use dotted keys (e.g. `user.email`).
+pub fn show_variables_dialog<F>(parent: &impl IsA<Window>, variables: &serde_yaml::Value, on_save: F)
+where
+    F: Fn(serde_yaml::Value) + 'static,
+{
+    let dialog = Dialog::with_buttons(
+        Some("Variables"),
+        Some(parent),
+        DialogFlags::MODAL | DialogFlags::DESTROY_WITH_PARENT,
+        &[
+            ("Cancel", ResponseType::Cancel),
+            ("Save", ResponseType::Accept),
+        ],
+    );
+    dialog.set_default_width(500);
+    dialog.set_default_height(400);
+
+    if let Some(button) = dialog.widget_for_response(ResponseType::Accept) {
+        button.add_css_class("suggested-action");
+    }
+
+    let content = dialog.content_area();
+    content.set_spacing(8);
+    content.set_margin_start(12);
+    content.set_margin_end(12);
+    content.set_margin_top(12);
+    content.set_margin_bottom(12);
+
+    let help_label = Label::new(Some(
+        "Use variables in replacements as {{name}}. Use dots for nesting, e.g. user.email → {{user.email}}"
+    ));
+    help_label.set_xalign(0.0);
+    help_label.add_css_class("dim-label");
+    help_label.set_wrap(true);
+    content.append(&help_label);
+
+    let rows_box = GtkBox::new(Orientation::Vertical, 4);
+    let scrolled = ScrolledWindow::builder()
+        .vexpand(true)
+        .min_content_height(200)
+        .child(&rows_box)
+        .build();
+    content.append(&scrolled);
+
+    let error_label = Label::new(None);
+    error_label.set_xalign(0.0);
+    error_label.add_css_class("error");
+    error_label.set_visible(false);
+    content.append(&error_label);
+
+    let add_button = gtk4::Button::with_label("Add Variable");
+    add_button.set_halign(gtk4::Align::Start);
+    content.append(&add_button);
+
+    // (key entry, value entry) for every row still in the dialog
+    type VariableRows = Rc<RefCell<Vec<(Entry, Entry)>>>;
+    let rows: VariableRows = Rc::new(RefCell::new(Vec::new()));
+
+    let add_row = {
+        let rows_box = rows_box.clone();
+        let rows = rows.clone();
+        move |key: &str, value: &str| {
+            let row = GtkBox::new(Orientation::Horizontal, 8);
+
+            let key_entry = Entry::new();
+            key_entry.set_text(key);
+            key_entry.set_placeholder_text(Some("name"));
+            key_entry.add_css_class("monospace");
+
+            let value_entry = Entry::new();
+            value_entry.set_text(value);
+            value_entry.set_placeholder_text(Some("value"));
+            value_entry.set_hexpand(true);
+
+            let delete_btn = gtk4::Button::from_icon_name("user-trash-symbolic");
+            delete_btn.add_css_class("flat");
+            delete_btn.set_tooltip_text(Some("Delete"));
+
+            row.append(&key_entry);
+            row.append(&value_entry);
+            row.append(&delete_btn);
+            rows_box.append(&row);
+
+            rows.borrow_mut().push((key_entry.clone(), value_entry.clone()));
+
+            let rows_box = rows_box.clone();
+            let rows = rows.clone();
+            delete_btn.connect_clicked(move |_| {
+                rows.borrow_mut().retain(|(k, _)| k != &key_entry);
+                rows_box.remove(&row);
+            });
+        }
+    };
+
+    for (key, value) in crate::config::variables::flatten_variables(variables) {
+        add_row(&key, &value);
+    }
+
+    add_button.connect_clicked(move |_| add_row("", ""));
+
+    let original = variables.clone();
+    dialog.connect_response(move |d, response| {
+        if response == ResponseType::Accept {
+            let entries: Vec<(String, String)> = rows
+                .borrow()
+                .iter()
+                .map(|(k, v)| (k.text().trim().to_string(), v.text().to_string()))
+                .collect();
+
+            match crate::config::variables::build_variables(&entries, &original) {
+                Ok(new_variables) => on_save(new_variables),
+                Err(e) => {
+                    // Keep the dialog open so the user can fix the key
+                    error_label.set_text(&e.to_string());
+                    error_label.set_visible(true);
+                    return;
+                }
+            }
+        }
+        d.close();
+    });
+
+    dialog.present();
+}
+
+/// Show a dialog for editing the general settings
+pub fn show_settings_dialog<F>(parent: &impl IsA<Window>, settings: &Settings, on_save: F)
+where
+    F: Fn(Settings) + 'static,
+{
+    let dialog = Dialog::with_buttons(
+        Some("Settings"),
+        Some(parent),
+        DialogFlags::MODAL | DialogFlags::DESTROY_WITH_PARENT,
+        &[
+            ("Cancel", ResponseType::Cancel),
+            ("Save", ResponseType::Accept),
+        ],
+    );
+    dialog.set_default_width(420);
+
+    if let Some(button) = dialog.widget_for_response(ResponseType::Accept) {
+        button.add_css_class("suggested-action");
+    }
+
+    let content = dialog.content_area();
+    content.set_spacing(8);
+    content.set_margin_start(12);
+    content.set_margin_end(12);
+    content.set_margin_top(12);
+    content.set_margin_bottom(12);
+
+    let grid = Grid::new();
+    grid.set_row_spacing(8);
+    grid.set_column_spacing(12);
+    content.append(&grid);
+
+    let add_row = |row: i32, label: &str, widget: &gtk4::Widget| {
+        let label = Label::new(Some(label));
+        label.set_xalign(0.0);
+        label.set_hexpand(true);
+        grid.attach(&label, 0, row, 1, 1);
+        widget.set_halign(gtk4::Align::End);
+        widget.set_valign(gtk4::Align::Center);
+        grid.attach(widget, 1, row, 1, 1);
+    };
+
+    let delay_spin = SpinButton::with_range(0.0, MAX_KEYSTROKE_DELAY_MS, 1.0);
+    delay_spin.set_value(settings.keystroke_delay_ms as f64);
+    delay_spin.set_tooltip_text(Some("Delay between typed keystrokes, in milliseconds"));
+    add_row(0, "Keystroke delay (ms)", delay_spin.upcast_ref());
+
+    // Keep a layout we don't know about selectable, so saving doesn't change it
+    let mut layouts: Vec<&str> = LAYOUTS.to_vec();
+    if !layouts.contains(&settings.layout.as_str()) {
+        layouts.push(&settings.layout);
+    }
+    let layout_dropdown = DropDown::from_strings(&layouts);
+    let selected = layouts.iter().position(|l| *l == settings.layout).unwrap_or(0);
+    layout_dropdown.set_selected(selected as u32);
+    add_row(1, "Keyboard layout", layout_dropdown.upcast_ref());
+
+    let socket_entry = Entry::new();
+    socket_entry.set_text(settings.ydotool_socket.as_deref().unwrap_or(""));
+    socket_entry.set_placeholder_text(Some("Default"));
+    socket_entry.set_width_chars(24);
+    add_row(2, "ydotool socket", socket_entry.upcast_ref());
+
+    let delete_trigger_switch = Switch::new();
+    delete_trigger_switch.set_active(settings.delete_trigger);
+    add_row(3, "Delete trigger before expanding", delete_trigger_switch.upcast_ref());
+
+    let sound_switch = Switch::new();
+    sound_switch.set_active(settings.enable_sound);
+    add_row(4, "Play sound on expansion", sound_switch.upcast_ref());
+
+    let notify_switch = Switch::new();
+    notify_switch.set_active(settings.notify_on_expand);
+    add_row(5, "Notify on expansion", notify_switch.upcast_ref());
+
+    let error_label = Label::new(None);
+    error_label.set_xalign(0.0);
+    error_label.add_css_class("error");
+    error_label.set_visible(false);
+    content.append(&error_label);
+
+    let layouts: Vec<String> = layouts.iter().map(|l| l.to_string()).collect();
+    let original = settings.clone();
+    dialog.connect_response(move |d, response| {
+        if response == ResponseType::Accept {
+            let delay = delay_spin.value();
+            if !(0.0..=MAX_KEYSTROKE_DELAY_MS).contains(&delay) {
+                // Keep the dialog open so the user can fix the value
+                error_label.set_text(&format!(
+                    "Keystroke delay must be between 0 and {} ms",
+                    MAX_KEYSTROKE_DELAY_MS
+                ));
+                error_label.set_visible(true);
+                return;
+            }
+
+            let socket = socket_entry.text().trim().to_string();
+            let mut settings = original.clone();
+            settings.keystroke_delay_ms = delay as u64;
+            if let Some(layout) = layouts.get(layout_dropdown.selected() as usize) {
+                settings.layout = layout.clone();
+            }
+            settings.ydotool_socket = (!socket.is_empty()).then_some(socket);
+            settings.delete_trigger = delete_trigger_switch.is_active();
+            settings.enable_sound = sound_switch.is_active();
+            settings.notify_on_expand = notify_switch.is_active();
+            on_save(settings);
+        }
+        d.close();
+    });
+
+    dialog.present();
+}
@@ -1,7 +1,33 @@
 pub mod editor;
+pub mod picker;
 pub mod tray;
 pub mod window;
 
 
-pub use tray::{start_tray, TrayCommand};
+pub use tray::{start_tray, TrayCommand, TrayHandle};
+pub use picker::{run_input, run_picker};
 pub use window::create_config_app;
+
+use std::ffi::OsString;
+
+/// Whether there is a Wayland or X11 display to open windows on. `var` looks up an
+/// environment variable (replaceable in tests).
+pub fn display_available(var: fn(&str) -> Option<OsString>) -> bool {
+    ["WAYLAND_DISPLAY", "DISPLAY"]
+        .iter()
+        .any(|name| var(name).is_some_and(|value| !value.is_empty()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_available() {
+        assert!(display_available(|name| (name == "WAYLAND_DISPLAY").then(|| "wayland-0".into())));
+        assert!(display_available(|name| (name == "DISPLAY").then(|| ":0".into())));
+        // Over SSH without forwarding, or with the variables set but empty
+        assert!(!display_available(|_| None));
+        assert!(!display_available(|_| Some(OsString::new())));
+    }
+}
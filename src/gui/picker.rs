@@ -0,0 +1,152 @@
+use gtk4::prelude::*;
+use gtk4::{
+    Application, ApplicationWindow, Box as GtkBox, Label, ListBox, ListBoxRow, Orientation, ScrolledWindow,
+    SearchEntry, SelectionMode,
+};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use super::editor::show_input_dialog;
+
+/// Show a searchable list of `options` and return the one the user chooses,
+/// or `None` if the window is closed or Escape is pressed
+pub fn run_picker(options: Vec<String>) -> Option<String> {
+    let app = Application::builder()
+        .application_id("com.xpander.picker")
+        .build();
+
+    let choice = Rc::new(RefCell::new(None));
+    {
+        let choice = choice.clone();
+        app.connect_activate(move |app| build_picker_window(app, &options, choice.clone()));
+    }
+
+    // Pass empty args so GTK doesn't try to parse our --pick flag
+    let empty_args: &[&str] = &[];
+    app.run_with_args(empty_args);
+
+    choice.take()
+}
+
+fn build_picker_window(app: &Application, options: &[String], choice: Rc<RefCell<Option<String>>>) {
+    let window = ApplicationWindow::builder()
+        .application(app)
+        .title("Xpander - Pick an entry")
+        .default_width(420)
+        .default_height(360)
+        .build();
+
+    let main_box = GtkBox::new(Orientation::Vertical, 8);
+    main_box.set_margin_start(12);
+    main_box.set_margin_end(12);
+    main_box.set_margin_top(12);
+    main_box.set_margin_bottom(12);
+
+    let search_entry = SearchEntry::new();
+    search_entry.set_key_capture_widget(Some(&window));
+    main_box.append(&search_entry);
+
+    let list_box = ListBox::new();
+    list_box.set_selection_mode(SelectionMode::Browse);
+    list_box.set_activate_on_single_click(true);
+    list_box.add_css_class("boxed-list");
+
+    for option in options {
+        let label = Label::new(Some(option));
+        label.set_xalign(0.0);
+        label.set_wrap(true);
+        label.set_margin_start(8);
+        label.set_margin_end(8);
+        label.set_margin_top(6);
+        label.set_margin_bottom(6);
+
+        let row = ListBoxRow::new();
+        row.set_child(Some(&label));
+        list_box.append(&row);
+    }
+
+    let scrolled = ScrolledWindow::builder()
+        .vexpand(true)
+        .child(&list_box)
+        .build();
+    main_box.append(&scrolled);
+
+    // Case-insensitive substring filter on the search text
+    let lowered: Rc<Vec<String>> = Rc::new(options.iter().map(|o| o.to_lowercase()).collect());
+    {
+        let search_entry = search_entry.clone();
+        let lowered = lowered.clone();
+        list_box.set_filter_func(move |row| {
+            let query = search_entry.text().to_lowercase();
+            lowered
+                .get(row.index() as usize)
+                .is_some_and(|option| option.contains(&query))
+        });
+    }
+    {
+        let list_box = list_box.clone();
+        search_entry.connect_search_changed(move |_| list_box.invalidate_filter());
+    }
+
+    // Choosing a row ends the pick
+    let options: Rc<Vec<String>> = Rc::new(options.to_vec());
+    {
+        let window = window.clone();
+        list_box.connect_row_activated(move |_, row| {
+            *choice.borrow_mut() = options.get(row.index() as usize).cloned();
+            window.close();
+        });
+    }
+
+    // Enter in the search field picks the first entry still shown
+    {
+        let list_box = list_box.clone();
+        search_entry.connect_activate(move |_| {
+            let first_visible = (0..)
+                .map_while(|i| list_box.row_at_index(i))
+                .find(|row| row.is_child_visible());
+            if let Some(row) = first_visible {
+                row.emit_activate();
+            }
+        });
+    }
+
+    {
+        let window = window.clone();
+        search_entry.connect_stop_search(move |_| window.close());
+    }
+
+    window.set_child(Some(&main_box));
+    window.present();
+    search_entry.grab_focus();
+}
+
+/// Ask for a single line of text with `prompt` in the config window's input
+/// dialog and return it, or `None` if it is cancelled or left empty
+pub fn run_input(prompt: String) -> Option<String> {
+    let app = Application::builder()
+        .application_id("com.xpander.input")
+        .build();
+
+    let answer = Rc::new(RefCell::new(None));
+    {
+        let answer = answer.clone();
+        app.connect_activate(move |app| {
+            // Never shown: the dialog needs a parent, and this keeps the app
+            // running until the dialog is answered
+            let parent = ApplicationWindow::builder().application(app).build();
+            let answer = answer.clone();
+            let window = parent.clone();
+            show_input_dialog(&parent, &prompt, "", move |text| {
+                *answer.borrow_mut() = text;
+                window.close();
+            });
+        });
+    }
+
+    // Pass empty args so GTK doesn't try to parse our --input flag
+    let empty_args: &[&str] = &[];
+    app.run_with_args(empty_args);
+
+    answer.take()
+}
@@ -1,8 +1,11 @@
 use anyhow::Result;
-use ksni::{self, menu::StandardItem, Icon, MenuItem, Tray, TrayService};
+use ksni::{self, menu::{RadioGroup, RadioItem, StandardItem, SubMenu}, Icon, MenuItem, Tray, TrayService};
 use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
 use tokio::sync::mpsc;
 
+use crate::engine::EngineStatus;
+
 /// Commands that can be sent from the tray menu
 #[derive(Debug, Clone)]
 pub enum TrayCommand {
@@ -14,6 +17,8 @@ pub enum TrayCommand {
     EditConfigFile,
     /// Reload configuration
     ReloadConfig,
+    /// Switch to a snippet profile (`None` activates all folders)
+    SetProfile(Option<String>),
     /// Quit the application
     Quit,
 }
@@ -21,9 +26,38 @@ pub enum TrayCommand {
 /// State shared with the tray icon
 struct TrayState {
     enabled: bool,
+    /// Text output is failing (e.g. ydotoold stopped)
+    degraded: bool,
+    /// Profile names from the config, in display order
+    profiles: Vec<String>,
+    active_profile: Option<String>,
+    /// Snippet count and last expansion, written by the engine
+    status: Arc<RwLock<EngineStatus>>,
+    /// Status line currently shown at the top of the menu
+    status_label: String,
     command_tx: mpsc::Sender<TrayCommand>,
 }
 
+/// Status line such as "12 snippets active, last expansion 5 min ago"
+fn status_label(status: &EngineStatus, now: SystemTime) -> String {
+    let snippets = match status.snippet_count {
+        1 => "1 snippet active".to_string(),
+        n => format!("{} snippets active", n),
+    };
+    let last = match status.last_expansion {
+        None => "no expansions yet".to_string(),
+        Some(at) => {
+            let minutes = now.duration_since(at).map_or(0, |d| d.as_secs() / 60);
+            match minutes {
+                0 => "last expansion just now".to_string(),
+                1..=59 => format!("last expansion {} min ago", minutes),
+                _ => format!("last expansion {} h ago", minutes / 60),
+            }
+        }
+    };
+    format!("{}, {}", snippets, last)
+}
+
 /// The system tray implementation
 struct XpanderTray {
     state: Arc<RwLock<TrayState>>,
@@ -39,6 +73,9 @@ impl Tray for XpanderTray {
     }
 
     fn icon_name(&self) -> String {
+        if self.state.read().map(|s| s.degraded).unwrap_or(false) {
+            return "dialog-warning".to_string();
+        }
         // Use a standard icon that's likely to be available
         "input-keyboard".to_string()
     }
@@ -76,11 +113,40 @@ impl Tray for XpanderTray {
         }]
     }
 
+    fn watcher_online(&self) {
+        log::info!("System tray host found, showing the tray icon");
+    }
+
+    fn watcher_offine(&self) -> bool {
+        // Keep the service running so the icon shows up if a host starts later
+        log::warn!("No system tray host (StatusNotifierWatcher) is running; xpander keeps working without a tray icon");
+        true
+    }
+
     fn menu(&self) -> Vec<MenuItem<Self>> {
         // Get current enabled state using std RwLock (non-async)
         let enabled = self.state.read().map(|s| s.enabled).unwrap_or(true);
+        let degraded = self.state.read().map(|s| s.degraded).unwrap_or(false);
+        let status_label = self.state.read().map(|s| s.status_label.clone()).unwrap_or_default();
+
+        let mut items = vec![
+            MenuItem::Standard(StandardItem {
+                label: status_label,
+                enabled: false,
+                ..Default::default()
+            }),
+            MenuItem::Separator,
+        ];
+        if degraded {
+            items.push(MenuItem::Standard(StandardItem {
+                label: "⚠ Text output unavailable - is ydotoold running?".to_string(),
+                enabled: false,
+                ..Default::default()
+            }));
+            items.push(MenuItem::Separator);
+        }
 
-        vec![
+        items.extend([
             MenuItem::Standard(StandardItem {
                 label: if enabled {
                     "Disable Expansions".to_string()
@@ -94,6 +160,13 @@ impl Tray for XpanderTray {
                 }),
                 ..Default::default()
             }),
+        ]);
+
+        if let Some(profile_menu) = self.profile_menu() {
+            items.push(profile_menu);
+        }
+
+        items.extend([
             MenuItem::Separator,
             MenuItem::Standard(StandardItem {
                 label: "Open Configuration...".to_string(),
@@ -132,13 +205,59 @@ impl Tray for XpanderTray {
                 }),
                 ..Default::default()
             }),
-        ]
+        ]);
+        items
+    }
+}
+
+impl XpanderTray {
+    /// Submenu for switching profiles, if any are configured
+    fn profile_menu(&self) -> Option<MenuItem<Self>> {
+        let (profiles, active) = self
+            .state
+            .read()
+            .map(|s| (s.profiles.clone(), s.active_profile.clone()))
+            .ok()?;
+        if profiles.is_empty() {
+            return None;
+        }
+
+        // Option 0 is "all folders", then one entry per profile
+        let selected = active
+            .as_ref()
+            .and_then(|name| profiles.iter().position(|p| p == name))
+            .map_or(0, |i| i + 1);
+
+        let mut options = vec![RadioItem {
+            label: "All Snippets".to_string(),
+            ..Default::default()
+        }];
+        options.extend(profiles.iter().map(|name| RadioItem {
+            label: name.clone(),
+            ..Default::default()
+        }));
+
+        Some(MenuItem::SubMenu(SubMenu {
+            label: "Profile".to_string(),
+            submenu: vec![MenuItem::RadioGroup(RadioGroup {
+                selected,
+                select: Box::new(move |tray: &mut Self, index| {
+                    let profile = index.checked_sub(1).and_then(|i| profiles.get(i).cloned());
+                    if let Ok(state) = tray.state.read() {
+                        let _ = state.command_tx.try_send(TrayCommand::SetProfile(profile));
+                    }
+                }),
+                options,
+            })],
+            ..Default::default()
+        }))
     }
 }
 
 /// Handle for controlling the system tray
 pub struct TrayHandle {
     state: Arc<RwLock<TrayState>>,
+    handle: ksni::Handle<XpanderTray>,
 }
 
 impl TrayHandle {
@@ -147,16 +266,57 @@ impl TrayHandle {
         if let Ok(mut state) = self.state.write() {
             state.enabled = enabled;
         }
+        self.handle.update(|_| {});
+    }
+
+    /// Update the profiles offered in the menu and the active one
+    pub fn set_profiles(&self, profiles: Vec<String>, active_profile: Option<String>) {
+        if let Ok(mut state) = self.state.write() {
+            state.profiles = profiles;
+            state.active_profile = active_profile;
+        }
+        self.handle.update(|_| {});
+    }
+
+    /// Show or clear the "output unavailable" warning
+    pub fn set_degraded(&self, degraded: bool) {
+        if let Ok(mut state) = self.state.write() {
+            state.degraded = degraded;
+        }
+        self.handle.update(|_| {});
+    }
+
+    /// Re-read the engine status and update the menu if the status line changed
+    pub fn refresh_status(&self) {
+        let changed = match self.state.write() {
+            Ok(mut state) => {
+                let label = state.status.read().map(|s| status_label(&s, SystemTime::now())).unwrap_or_default();
+                let changed = label != state.status_label;
+                state.status_label = label;
+                changed
+            }
+            Err(_) => false,
+        };
+        if changed {
+            self.handle.update(|_| {});
+        }
     }
 }
 
 /// Start the system tray icon
 pub fn start_tray(
     enabled: bool,
+    status: Arc<RwLock<EngineStatus>>,
     command_tx: mpsc::Sender<TrayCommand>,
 ) -> Result<TrayHandle> {
+    let status_label = status.read().map(|s| status_label(&s, SystemTime::now())).unwrap_or_default();
     let state = Arc::new(RwLock::new(TrayState {
         enabled,
+        degraded: false,
+        profiles: Vec::new(),
+        active_profile: None,
+        status,
+        status_label,
         command_tx,
     }));
 
@@ -165,12 +325,20 @@ pub fn start_tray(
     };
 
     let service = TrayService::new(tray);
-    service.spawn();
+    let handle = service.handle();
+    // Without a D-Bus session the service fails to start; expansion doesn't need the
+    // tray, so carry on without one instead of letting its thread panic
+    std::thread::spawn(move || {
+        if let Err(e) = service.run() {
+            log::warn!("System tray unavailable, running without it: {}", e);
+        }
+    });
 
     log::info!("System tray started");
 
     Ok(TrayHandle {
         state,
+        handle,
     })
 }
 
@@ -183,6 +351,11 @@ mod tests {
         let (tx, _rx) = mpsc::channel(10);
         let state = Arc::new(RwLock::new(TrayState {
             enabled: true,
+            degraded: false,
+            profiles: Vec::new(),
+            active_profile: None,
+            status: Arc::default(),
+            status_label: String::new(),
             command_tx: tx,
         }));
 
@@ -191,4 +364,24 @@ mod tests {
         state.write().unwrap().enabled = false;
         assert!(!state.read().unwrap().enabled);
     }
+
+    #[test]
+    fn test_status_label() {
+        let now = SystemTime::now();
+        let mut status = EngineStatus {
+            snippet_count: 12,
+            last_expansion: None,
+        };
+        assert_eq!(status_label(&status, now), "12 snippets active, no expansions yet");
+
+        status.last_expansion = Some(now - std::time::Duration::from_secs(30));
+        assert_eq!(status_label(&status, now), "12 snippets active, last expansion just now");
+
+        status.snippet_count = 1;
+        status.last_expansion = Some(now - std::time::Duration::from_secs(5 * 60 + 10));
+        assert_eq!(status_label(&status, now), "1 snippet active, last expansion 5 min ago");
+
+        status.last_expansion = Some(now - std::time::Duration::from_secs(3 * 3600));
+        assert_eq!(status_label(&status, now), "1 snippet active, last expansion 3 h ago");
+    }
 }
@@ -2,22 +2,129 @@ use anyhow::Result;
 use gtk4::glib;
 use gtk4::prelude::*;
 use gtk4::{
-    Application, ApplicationWindow, Box as GtkBox, Button, CenterBox, HeaderBar,
-    Label, ListBox, ListBoxRow, Orientation, ScrolledWindow, SelectionMode, Switch,
+    Application, ApplicationWindow, Box as GtkBox, Button, CallbackAction, CenterBox, HeaderBar,
+    Label, ListBox, ListBoxRow, MenuButton, Orientation, Popover, Revealer, RevealerTransitionType,
+    ScrolledWindow, SelectionMode, Shortcut, ShortcutController, ShortcutScope, ShortcutTrigger,
+    Switch, ToggleButton,
 };
 use std::cell::RefCell;
 use std::path::PathBuf;
 use std::rc::Rc;
+use std::time::Duration;
 
-use crate::config::{Config, ConfigManager, SnippetNode};
+use crate::config::loader::{apply_import, backup_path, is_hand_edited, ConfigError, ExportData, ImportMode, ImportSummary};
+use crate::config::{Config, ConfigManager, Snippet, SnippetNode};
+use crate::engine::Matcher;
 
-use super::editor::{SnippetEditor, show_import_dialog, show_export_dialog, show_confirm_dialog, show_input_dialog};
+use super::editor::{SnippetEditor, show_import_dialog, show_export_dialog, show_confirm_dialog, show_input_dialog, show_settings_dialog, show_variables_dialog};
+
+/// How long "Saved" stays visible
+const SAVED_MESSAGE_DURATION: Duration = Duration::from_secs(2);
+
+/// How long an error stays visible
+const ERROR_MESSAGE_DURATION: Duration = Duration::from_secs(6);
+
+/// Ways to order the items of a folder
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SortOrder {
+    /// Folders before snippets, otherwise keeping the current order
+    FoldersFirst,
+    /// Folders by name, then snippets by trigger
+    ByTrigger,
+    /// Folders by name, then snippets by label (or trigger, if they have none)
+    ByLabel,
+}
+
+/// Sort orders offered by the Sort menu
+const SORT_ORDERS: [(SortOrder, &str); 3] = [
+    (SortOrder::FoldersFirst, "Folders first"),
+    (SortOrder::ByTrigger, "Alphabetical by trigger"),
+    (SortOrder::ByLabel, "Alphabetical by label"),
+];
 
 /// Shared state for the config window
 struct WindowState {
     config: Config,
     config_path: PathBuf,
     current_path: Vec<usize>, // Path of indices to current folder
+    feedback: SaveFeedback,
+}
+
+impl WindowState {
+    /// Apply `change` to the config and save it, telling the user whether that
+    /// worked. `change` returns whether it changed anything. Returns false if the
+    /// save failed and the change was undone.
+    fn apply(&mut self, change: impl FnOnce(&mut Config) -> bool) -> bool {
+        let config_path = self.config_path.clone();
+        let Some(result) = apply_change(&mut self.config, change, |config| {
+            ConfigManager::save_config(&config_path, config)
+        }) else {
+            return true;
+        };
+
+        if let Err(e) = &result {
+            log::error!("Failed to save config, change undone: {}", e);
+        }
+        let (message, is_error) = save_message(&result);
+        self.feedback.show(&message, is_error);
+        result.is_ok()
+    }
+}
+
+/// A message that slides in under the toolbar and hides itself again, confirming
+/// a save or showing why it failed
+#[derive(Clone)]
+struct SaveFeedback {
+    revealer: Revealer,
+    label: Label,
+    /// Pending timeout hiding the current message
+    hide_timeout: Rc<RefCell<Option<glib::SourceId>>>,
+}
+
+impl SaveFeedback {
+    fn new() -> Self {
+        let label = Label::new(None);
+        label.set_wrap(true);
+        label.set_margin_start(12);
+        label.set_margin_end(12);
+        label.set_margin_bottom(8);
+
+        let revealer = Revealer::builder()
+            .transition_type(RevealerTransitionType::SlideDown)
+            .child(&label)
+            .build();
+
+        Self {
+            revealer,
+            label,
+            hide_timeout: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    /// Show `message`, replacing any message still visible
+    fn show(&self, message: &str, is_error: bool) {
+        self.label.set_text(message);
+        if is_error {
+            self.label.remove_css_class("success");
+            self.label.add_css_class("error");
+        } else {
+            self.label.remove_css_class("error");
+            self.label.add_css_class("success");
+        }
+        self.revealer.set_reveal_child(true);
+
+        if let Some(timeout) = self.hide_timeout.borrow_mut().take() {
+            timeout.remove();
+        }
+        let duration = if is_error { ERROR_MESSAGE_DURATION } else { SAVED_MESSAGE_DURATION };
+        let revealer = self.revealer.clone();
+        let hide_timeout = self.hide_timeout.clone();
+        let timeout = glib::timeout_add_local_once(duration, move || {
+            hide_timeout.borrow_mut().take();
+            revealer.set_reveal_child(false);
+        });
+        *self.hide_timeout.borrow_mut() = Some(timeout);
+    }
 }
 
 /// The main configuration window
@@ -32,12 +139,14 @@ impl ConfigWindow {
     /// Create a new configuration window
     pub fn new(app: &Application, config_path: PathBuf) -> Result<Self> {
         // Load config synchronously
-        let config = ConfigManager::load_config(&config_path)?;
+        let config = ConfigManager::load_and_upgrade_config(&config_path)?;
 
+        let feedback = SaveFeedback::new();
         let state = Rc::new(RefCell::new(WindowState {
             config,
             config_path,
             current_path: Vec::new(),
+            feedback: feedback.clone(),
         }));
 
         let window = ApplicationWindow::builder()
@@ -65,9 +174,29 @@ impl ConfigWindow {
         let import_button = Button::with_label("Import");
         header.pack_start(&import_button);
 
+        let import_url_button = Button::from_icon_name("web-browser-symbolic");
+        import_url_button.set_tooltip_text(Some("Import from URL"));
+        header.pack_start(&import_url_button);
+
+        let import_string_button = Button::from_icon_name("edit-paste-symbolic");
+        import_string_button.set_tooltip_text(Some("Import from string"));
+        header.pack_start(&import_string_button);
+
         let export_button = Button::with_label("Export");
         header.pack_start(&export_button);
 
+        let settings_button = Button::from_icon_name("preferences-system-symbolic");
+        settings_button.set_tooltip_text(Some("Settings"));
+        header.pack_end(&settings_button);
+
+        let variables_button = Button::with_label("Variables");
+        header.pack_end(&variables_button);
+
+        let sort_button = MenuButton::new();
+        sort_button.set_icon_name("view-sort-ascending-symbolic");
+        sort_button.set_tooltip_text(Some("Sort this folder"));
+        header.pack_end(&sort_button);
+
         window.set_titlebar(Some(&header));
 
         // Main content
@@ -88,12 +217,29 @@ impl ConfigWindow {
         enable_box.append(&enable_switch);
         toolbar.set_start_widget(Some(&enable_box));
 
+        // Select mode, for enabling, disabling or deleting several items at once
+        let bulk_box = GtkBox::new(Orientation::Horizontal, 4);
+        let select_button = ToggleButton::with_label("Select");
+        select_button.set_tooltip_text(Some("Select several items"));
+        bulk_box.append(&select_button);
+        let enable_selected_button = Button::with_label("Enable");
+        let disable_selected_button = Button::with_label("Disable");
+        let delete_selected_button = Button::with_label("Delete");
+        delete_selected_button.add_css_class("destructive-action");
+        for button in [&enable_selected_button, &disable_selected_button, &delete_selected_button] {
+            button.set_visible(false);
+            button.set_sensitive(false);
+            bulk_box.append(button);
+        }
+        toolbar.set_center_widget(Some(&bulk_box));
+
         let snippet_count = state.borrow().config.snippets.len();
         let stats_label = Label::new(Some(&format!("{} snippets", snippet_count)));
         stats_label.add_css_class("dim-label");
         toolbar.set_end_widget(Some(&stats_label));
 
         main_box.append(&toolbar);
+        main_box.append(&feedback.revealer);
 
         // Scrolled list of snippets
         let scrolled = ScrolledWindow::builder()
@@ -123,7 +269,15 @@ impl ConfigWindow {
         };
 
         // Connect signals and get refresh function
-        let refresh = config_window.setup_signals(&back_button, &add_button, &add_folder_button, &import_button, &export_button, &enable_switch);
+        let refresh = config_window.setup_signals(&back_button, &add_button, &add_folder_button, &import_button, &import_url_button, &import_string_button, &export_button, &variables_button, &settings_button, &enable_switch);
+        config_window.setup_bulk_actions(
+            &select_button,
+            &enable_selected_button,
+            &disable_selected_button,
+            &delete_selected_button,
+            refresh.clone(),
+        );
+        config_window.setup_sort(&sort_button, refresh.clone());
         
         // Initial refresh
         refresh();
@@ -138,7 +292,11 @@ impl ConfigWindow {
         add_button: &Button,
         add_folder_button: &Button,
         import_button: &Button,
+        import_url_button: &Button,
+        import_string_button: &Button,
         export_button: &Button,
+        variables_button: &Button,
+        settings_button: &Button,
         enable_switch: &Switch,
     ) -> Rc<dyn Fn()> {
         // Shared state refs
@@ -148,6 +306,13 @@ impl ConfigWindow {
         let stats_label = self.stats_label.clone();
         let window = self.window.clone();
 
+        // Delete buttons of the rows currently shown, indexed like the list (for the Delete shortcut)
+        let delete_buttons: Rc<RefCell<Vec<Button>>> = Rc::new(RefCell::new(Vec::new()));
+
+        // Matcher over the snippets last shown, for the stats. Only changed snippets
+        // are run through it again, so invalid regexes aren't logged on every refresh.
+        let stats_matcher: Rc<RefCell<(Vec<Snippet>, Matcher)>> = Rc::new(RefCell::new((Vec::new(), Matcher::new())));
+
         // Refresh function
         type RefreshFn = Box<dyn Fn()>;
         let refresh_cell: Rc<RefCell<Option<RefreshFn>>> = Rc::new(RefCell::new(None));
@@ -160,12 +325,15 @@ impl ConfigWindow {
             let stats_label = stats_label.clone();
             let refresh_weak_inner = refresh_weak.clone();
             let window = window.clone();
+            let delete_buttons = delete_buttons.clone();
+            let stats_matcher = stats_matcher.clone();
 
             move || {
                 // Clear existing rows
                 while let Some(row) = list_box.row_at_index(0) {
                     list_box.remove(&row);
                 }
+                delete_buttons.borrow_mut().clear();
 
                 let state_borrow = state.borrow();
                 
@@ -204,12 +372,13 @@ impl ConfigWindow {
                                         {
                                             let mut s = state.borrow_mut();
                                             let path = s.current_path.clone();
-                                            if let Some(list) = get_list_at_path_mut(&mut s.config.snippets, &path) {
-                                                if index < list.len() {
+                                            s.apply(|config| match get_list_at_path_mut(&mut config.snippets, &path) {
+                                                Some(list) if index < list.len() => {
                                                     list.remove(index);
-                                                    let _ = ConfigManager::save_config(&s.config_path, &s.config);
+                                                    true
                                                 }
-                                            }
+                                                _ => false,
+                                            });
                                         }
                                         if let Some(cell) = refresh_weak.upgrade() {
                                             if let Some(refresh) = cell.borrow().as_ref() {
@@ -237,12 +406,16 @@ impl ConfigWindow {
                                              {
                                                 let mut s = state.borrow_mut();
                                                 let path = s.current_path.clone();
-                                                if let Some(list) = get_list_at_path_mut(&mut s.config.snippets, &path) {
-                                                    if let Some(SnippetNode::Folder(f)) = list.get_mut(index) {
-                                                        f.folder = new_name;
-                                                        let _ = ConfigManager::save_config(&s.config_path, &s.config);
+                                                s.apply(|config| {
+                                                    let list = get_list_at_path_mut(&mut config.snippets, &path);
+                                                    match list.and_then(|list| list.get_mut(index)) {
+                                                        Some(SnippetNode::Folder(f)) => {
+                                                            f.folder = new_name;
+                                                            true
+                                                        }
+                                                        _ => false,
                                                     }
-                                                }
+                                                });
                                             }
                                             if let Some(cell) = refresh_weak.upgrade() {
                                                 if let Some(refresh) = cell.borrow().as_ref() {
@@ -255,12 +428,71 @@ impl ConfigWindow {
                             }
                         };
                         
-                        ConfigWindow::add_snippet_node_row(&list_box, node, index, on_delete, on_edit);
+                        let delete_btn = ConfigWindow::add_snippet_node_row(&list_box, node, index, on_delete, on_edit);
+                        delete_buttons.borrow_mut().push(delete_btn);
                     }
                 }
                 
-                let total = ConfigManager::flatten_snippets(&state_borrow.config.snippets).len();
-                stats_label.set_text(&format!("{} snippets (total)", total));
+                let flattened = ConfigManager::flatten_snippets(&state_borrow.config.snippets);
+                let total = flattened.len();
+
+                // Run the snippets through a matcher to find shadowed triggers
+                let mut stats_matcher = stats_matcher.borrow_mut();
+                let (loaded, matcher) = &mut *stats_matcher;
+                if *loaded != flattened {
+                    matcher.update(loaded, flattened.clone());
+                    *loaded = flattened;
+                }
+                let duplicates = matcher.duplicate_triggers();
+                let conflicts = matcher.suffix_conflicts();
+                let unused_markers = matcher.unused_cursor_markers();
+
+                let mut stats = format!("{} snippets (total)", total);
+                let mut tooltip = Vec::new();
+                if !duplicates.is_empty() {
+                    stats.push_str(&format!(
+                        ", {} duplicate trigger{}",
+                        duplicates.len(),
+                        if duplicates.len() == 1 { "" } else { "s" }
+                    ));
+                    tooltip.push(format!(
+                        "Only the last definition of these triggers will fire:\n{}",
+                        duplicates.join("\n")
+                    ));
+                }
+                if !conflicts.is_empty() {
+                    stats.push_str(&format!(
+                        ", {} overlapping trigger{}",
+                        conflicts.len(),
+                        if conflicts.len() == 1 { "" } else { "s" }
+                    ));
+                    let lines: Vec<String> = conflicts
+                        .iter()
+                        .map(|c| format!("{} (end of {})", c.trigger, c.longer))
+                        .collect();
+                    tooltip.push(format!(
+                        "These triggers also fire at the end of longer words; consider enabling word boundary:\n{}",
+                        lines.join("\n")
+                    ));
+                }
+                if !unused_markers.is_empty() {
+                    stats.push_str(&format!(
+                        ", {} unused cursor marker{}",
+                        unused_markers.len(),
+                        if unused_markers.len() == 1 { "" } else { "s" }
+                    ));
+                    tooltip.push(format!(
+                        "These replacements contain $|$ but \"Position cursor\" is off, so the cursor stays at the end:\n{}",
+                        unused_markers.join("\n")
+                    ));
+                }
+
+                stats_label.set_text(&stats);
+                if tooltip.is_empty() {
+                    stats_label.set_tooltip_text(None);
+                } else {
+                    stats_label.set_tooltip_text(Some(&tooltip.join("\n\n")));
+                }
             }
         };
 
@@ -277,6 +509,8 @@ impl ConfigWindow {
              })
         };
 
+        self.setup_shortcuts(back_button, add_button, add_folder_button, delete_buttons);
+
         // Back button
         let state_clone = state.clone();
         let refresh_clone = refresh.clone();
@@ -298,14 +532,13 @@ impl ConfigWindow {
                     {
                         let mut s = state.borrow_mut();
                         let path = s.current_path.clone();
-                        if let Some(list) = get_list_at_path_mut(&mut s.config.snippets, &path) {
-                            list.push(SnippetNode::Folder(crate::config::Folder {
-                                folder: name,
-                                items: Vec::new(),
-                                enabled: true,
-                            }));
-                            let _ = ConfigManager::save_config(&s.config_path, &s.config);
-                        }
+                        s.apply(|config| match get_list_at_path_mut(&mut config.snippets, &path) {
+                            Some(list) => {
+                                list.push(SnippetNode::Folder(crate::config::Folder::new(name)));
+                                true
+                            }
+                            None => false,
+                        });
                     }
                     refresh();
                 }
@@ -326,12 +559,13 @@ impl ConfigWindow {
                 {
                     let mut s = state.borrow_mut();
                     let path = s.current_path.clone();
-                    let current_list_opt = get_list_at_path_mut(&mut s.config.snippets, &path);
-                    
-                    if let Some(current_list) = current_list_opt {
-                         current_list.push(SnippetNode::Snippet(snippet.clone()));
-                        let _ = ConfigManager::save_config(&s.config_path, &s.config);
-                    }
+                    s.apply(|config| match get_list_at_path_mut(&mut config.snippets, &path) {
+                        Some(current_list) => {
+                            current_list.push(SnippetNode::Snippet(snippet.clone()));
+                            true
+                        }
+                        None => false,
+                    });
                 }
                 refresh();
             });
@@ -350,28 +584,142 @@ impl ConfigWindow {
             show_import_dialog(&window, move |path| {
                 match crate::config::loader::import_custom_entries(&path) {
                     Ok(data) => {
-                        let new_snippets = data.snippets;
                         {
                             let mut s = state.borrow_mut();
-                            s.config.snippets.extend(new_snippets);
-                             // Merge variables logic skipped for brevity, check previous
-                            match (&mut s.config.variables, data.variables) {
-                                (serde_yaml::Value::Mapping(map), serde_yaml::Value::Mapping(new_map)) => {
-                                    for (k, v) in new_map { map.insert(k, v); }
-                                },
-                                (current, new) => { *current = new; }
-                            }
-                            let _ = ConfigManager::save_config(&s.config_path, &s.config);
+                            s.apply(|config| {
+                                let summary = crate::config::loader::apply_import(
+                                    config,
+                                    data,
+                                    crate::config::loader::ImportMode::Merge,
+                                );
+                                log::info!("Imported {} snippets and {} variables", summary.snippets, summary.variables);
+                                if !summary.overwritten_variables.is_empty() {
+                                    log::info!(
+                                        "Replaced variables with imported values: {}",
+                                        summary.overwritten_variables.join(", ")
+                                    );
+                                }
+                                true
+                            });
                         }
                         refresh();
                     }
                     Err(e) => {
                         log::error!("Failed to import: {}", e);
+                        state.borrow().feedback.show(&format!("Import failed: {:#}", e), true);
                     }
                 }
             });
         });
         
+        // Import from URL: download in the background, then confirm what it adds
+        let window = self.window.clone();
+        let state = self.state.clone();
+        let refresh_clone = refresh.clone();
+
+        import_url_button.connect_clicked(move |_| {
+            let parent = window.clone();
+            let state = state.clone();
+            let refresh = refresh_clone.clone();
+
+            show_input_dialog(&window, "Import from URL", "https://", move |url| {
+                let Some(url) = url else {
+                    return;
+                };
+                let window = parent.clone();
+                let state = state.clone();
+                let refresh = refresh.clone();
+
+                glib::spawn_future_local(async move {
+                    let fetch_url = url.clone();
+                    let result = gtk4::gio::spawn_blocking(move || {
+                        crate::config::remote::fetch_snippet_pack(&fetch_url)
+                    })
+                    .await;
+                    let data = match result {
+                        Ok(Ok(data)) => data,
+                        Ok(Err(e)) => {
+                            log::error!("Failed to import from {}: {:#}", url, e);
+                            state.borrow().feedback.show(&format!("Import failed: {:#}", e), true);
+                            return;
+                        }
+                        Err(_) => {
+                            state.borrow().feedback.show("Import failed: download crashed", true);
+                            return;
+                        }
+                    };
+
+                    // Work out what the pack adds on a copy, nothing changes until confirmed
+                    let mut preview = state.borrow().config.clone();
+                    let summary = apply_import(&mut preview, data.clone(), ImportMode::Merge);
+                    let message = import_preview_message(&url, &summary);
+
+                    show_confirm_dialog(&window, "Import from URL", &message, move |confirmed| {
+                        if !confirmed {
+                            return;
+                        }
+                        state.borrow_mut().apply(|config| {
+                            apply_import(config, data.clone(), ImportMode::Merge);
+                            true
+                        });
+                        refresh();
+                    });
+                });
+            });
+        });
+
+        // Import from string: add a snippet shared with `xpander --share`
+        let window = self.window.clone();
+        let state = self.state.clone();
+        let refresh_clone = refresh.clone();
+
+        import_string_button.connect_clicked(move |_| {
+            let parent = window.clone();
+            let state = state.clone();
+            let refresh = refresh_clone.clone();
+
+            show_input_dialog(&window, "Import from string", "", move |encoded| {
+                let Some(encoded) = encoded else {
+                    return;
+                };
+                let snippet = match crate::config::share::unshare_snippet(&encoded) {
+                    Ok(snippet) => snippet,
+                    Err(e) => {
+                        state.borrow().feedback.show(&format!("Import failed: {:#}", e), true);
+                        return;
+                    }
+                };
+                let runs_commands = snippet.runs_commands();
+                let message = format!(
+                    "'{}' runs commands on your machine when it expands. Add it?",
+                    snippet.trigger
+                );
+                let data = ExportData {
+                    snippets: vec![SnippetNode::Snippet(snippet)],
+                    variables: serde_yaml::Value::Null,
+                };
+                let state = state.clone();
+                let refresh = refresh.clone();
+                let import = move || {
+                    state.borrow_mut().apply(|config| {
+                        apply_import(config, data.clone(), ImportMode::Merge);
+                        true
+                    });
+                    refresh();
+                };
+
+                if runs_commands {
+                    show_confirm_dialog(&parent, "Import from string", &message, move |confirmed| {
+                        if confirmed {
+                            import();
+                        }
+                    });
+                } else {
+                    import();
+                }
+            });
+        });
+
         // Export
         let window = self.window.clone();
         let state = self.state.clone();
@@ -382,19 +730,58 @@ impl ConfigWindow {
                 let s = state.borrow();
                 if let Err(e) = crate::config::loader::export_custom_entries(&s.config.snippets, &s.config.variables, &path) {
                     log::error!("Failed to export entries: {}", e);
+                    s.feedback.show(&format!("Export failed: {:#}", e), true);
                 }
             });
         });
 
+        // Variables
+        let window = self.window.clone();
+        let state = self.state.clone();
+
+        variables_button.connect_clicked(move |_| {
+            let variables = state.borrow().config.variables.clone();
+            let state = state.clone();
+            show_variables_dialog(&window, &variables, move |new_variables| {
+                state.borrow_mut().apply(|config| {
+                    config.variables = new_variables;
+                    true
+                });
+            });
+        });
+
+        // Settings
+        let window = self.window.clone();
+        let state = self.state.clone();
+
+        settings_button.connect_clicked(move |_| {
+            let settings = state.borrow().config.settings.clone();
+            let state = state.clone();
+            show_settings_dialog(&window, &settings, move |new_settings| {
+                state.borrow_mut().apply(|config| {
+                    config.settings = new_settings;
+                    true
+                });
+            });
+        });
+
         // Enable Switch
         let state = self.state.clone();
-        enable_switch.connect_state_set(move |_, active| {
-            {
-                let mut s = state.borrow_mut();
-                s.config.settings.enabled = active;
-                let _ = ConfigManager::save_config(&s.config_path, &s.config);
+        enable_switch.connect_state_set(move |switch, active| {
+            let saved = state.borrow_mut().apply(|config| {
+                let changed = config.settings.enabled != active;
+                config.settings.enabled = active;
+                changed
+            });
+            if saved {
+                return glib::Propagation::Proceed;
             }
-            glib::Propagation::Proceed
+
+            // Flip the switch back once this signal is done; the config already
+            // matches then, so nothing is saved again
+            let switch = switch.clone();
+            glib::idle_add_local_once(move || switch.set_active(!active));
+            glib::Propagation::Stop
         });
 
         // Row interaction
@@ -419,7 +806,9 @@ impl ConfigWindow {
             if let Some(node) = node {
                 match node {
                     crate::config::SnippetNode::Snippet(snippet) => {
+                        let trigger = snippet.trigger.clone();
                         let editor = SnippetEditor::new(&window, Some(snippet));
+                        editor.add_reveal_button(state.borrow().config_path.clone(), trigger);
                         let state = state.clone();
                         let refresh = refresh_clone.clone();
                         let row_index = index;
@@ -428,12 +817,16 @@ impl ConfigWindow {
                             {
                                 let mut s = state.borrow_mut();
                                 let path = s.current_path.clone();
-                                if let Some(list) = get_list_at_path_mut(&mut s.config.snippets, &path) {
-                                     if let Some(SnippetNode::Snippet(_)) = list.get(row_index) {
-                                         list[row_index] = SnippetNode::Snippet(updated_snippet.clone());
-                                         let _ = ConfigManager::save_config(&s.config_path, &s.config);
-                                     }
-                                }
+                                s.apply(|config| {
+                                    let list = get_list_at_path_mut(&mut config.snippets, &path);
+                                    match list.and_then(|list| list.get_mut(row_index)) {
+                                        Some(node @ SnippetNode::Snippet(_)) => {
+                                            *node = SnippetNode::Snippet(updated_snippet.clone());
+                                            true
+                                        }
+                                        _ => false,
+                                    }
+                                });
                             }
                             refresh();
                         });
@@ -450,14 +843,172 @@ impl ConfigWindow {
         refresh
     }
 
-    /// Add a snippet node row to the list
+    /// Select mode: clicking rows selects them, and the selected items of the current
+    /// folder can be enabled, disabled or deleted together with a single save
+    fn setup_bulk_actions(
+        &self,
+        select_button: &ToggleButton,
+        enable_button: &Button,
+        disable_button: &Button,
+        delete_button: &Button,
+        refresh: Rc<dyn Fn()>,
+    ) {
+        let actions = [enable_button.clone(), disable_button.clone(), delete_button.clone()];
+
+        let list_box = self.list_box.clone();
+        let toggled_actions = actions.clone();
+        select_button.connect_toggled(move |button| {
+            let selecting = button.is_active();
+            list_box.unselect_all();
+            list_box.set_selection_mode(if selecting { SelectionMode::Multiple } else { SelectionMode::Single });
+            // A single click selects instead of opening the item
+            list_box.set_activate_on_single_click(!selecting);
+            for action in &toggled_actions {
+                action.set_visible(selecting);
+            }
+        });
+
+        self.list_box.connect_selected_rows_changed(move |list_box| {
+            let any_selected = !list_box.selected_rows().is_empty();
+            for action in &actions {
+                action.set_sensitive(any_selected);
+            }
+        });
+
+        for (button, enabled) in [(enable_button, true), (disable_button, false)] {
+            let state = self.state.clone();
+            let list_box = self.list_box.clone();
+            let refresh = refresh.clone();
+            button.connect_clicked(move |_| {
+                let indices = selected_indices(&list_box);
+                {
+                    let mut s = state.borrow_mut();
+                    let path = s.current_path.clone();
+                    s.apply(|config| {
+                        get_list_at_path_mut(&mut config.snippets, &path)
+                            .is_some_and(|list| set_enabled_at(list, &indices, enabled) > 0)
+                    });
+                }
+                refresh();
+            });
+        }
+
+        let window = self.window.clone();
+        let state = self.state.clone();
+        let list_box = self.list_box.clone();
+        delete_button.connect_clicked(move |_| {
+            let indices = selected_indices(&list_box);
+            let state = state.clone();
+            let refresh = refresh.clone();
+            let message = format!(
+                "Are you sure you want to delete {} item{}?",
+                indices.len(),
+                if indices.len() == 1 { "" } else { "s" }
+            );
+            show_confirm_dialog(&window, "Delete Items", &message, move |confirmed| {
+                if !confirmed {
+                    return;
+                }
+                {
+                    let mut s = state.borrow_mut();
+                    let path = s.current_path.clone();
+                    s.apply(|config| {
+                        get_list_at_path_mut(&mut config.snippets, &path)
+                            .is_some_and(|list| remove_at(list, &indices) > 0)
+                    });
+                }
+                refresh();
+            });
+        });
+    }
+
+    /// Sort menu: reorders the items of the current folder and saves
+    fn setup_sort(&self, sort_button: &MenuButton, refresh: Rc<dyn Fn()>) {
+        let popover = Popover::new();
+        let menu = GtkBox::new(Orientation::Vertical, 0);
+        for (order, name) in SORT_ORDERS {
+            let button = Button::with_label(name);
+            button.add_css_class("flat");
+            let state = self.state.clone();
+            let refresh = refresh.clone();
+            let popover = popover.clone();
+            button.connect_clicked(move |_| {
+                popover.popdown();
+                {
+                    let mut s = state.borrow_mut();
+                    let path = s.current_path.clone();
+                    s.apply(|config| {
+                        get_list_at_path_mut(&mut config.snippets, &path).is_some_and(|list| sort_items(list, order))
+                    });
+                }
+                refresh();
+            });
+            menu.append(&button);
+        }
+        popover.set_child(Some(&menu));
+        sort_button.set_popover(Some(&popover));
+    }
+
+    /// Set up keyboard shortcuts. Each one activates the matching button,
+    /// so behavior is identical to clicking it.
+    fn setup_shortcuts(
+        &self,
+        back_button: &Button,
+        add_button: &Button,
+        add_folder_button: &Button,
+        delete_buttons: Rc<RefCell<Vec<Button>>>,
+    ) {
+        let controller = ShortcutController::new();
+        controller.set_scope(ShortcutScope::Global);
+
+        let add_shortcut = |accelerator: &str, callback: Box<dyn Fn()>| {
+            controller.add_shortcut(Shortcut::new(
+                ShortcutTrigger::parse_string(accelerator),
+                Some(CallbackAction::new(move |_, _| {
+                    callback();
+                    true
+                })),
+            ));
+        };
+
+        let button = add_button.clone();
+        add_shortcut("<Control>n", Box::new(move || button.emit_clicked()));
+
+        let button = add_folder_button.clone();
+        add_shortcut("<Control><Shift>n", Box::new(move || button.emit_clicked()));
+
+        let button = back_button.clone();
+        add_shortcut("Escape", Box::new(move || {
+            if button.is_visible() {
+                button.emit_clicked();
+            }
+        }));
+
+        let list_box = self.list_box.clone();
+        add_shortcut("Delete", Box::new(move || {
+            // In select mode the toolbar's Delete button handles the selection
+            if list_box.selection_mode() == SelectionMode::Multiple {
+                return;
+            }
+            let button = list_box
+                .selected_row()
+                .and_then(|row| delete_buttons.borrow().get(row.index() as usize).cloned());
+            if let Some(button) = button {
+                button.emit_clicked();
+            }
+        }));
+
+        self.window.add_controller(controller);
+    }
+
+    /// Add a snippet node row to the list, returning the row's delete button
     fn add_snippet_node_row(
         list_box: &ListBox,
         node: &crate::config::SnippetNode,
         _index: usize,
         on_delete: impl Fn() + 'static,
         on_edit: impl Fn() + 'static,
-    ) {
+    ) -> Button {
         let row = ListBoxRow::new();
         let (child, delete_btn, edit_btn) = Self::create_node_widget(node);
         
@@ -469,6 +1020,7 @@ impl ConfigWindow {
         
         row.set_child(Some(&child));
         list_box.append(&row);
+        delete_btn
     }
     
     /// Helper to create widget content for a node
@@ -486,6 +1038,9 @@ impl ConfigWindow {
                 // Trigger
                 let trigger_label = Label::new(Some(&snippet.trigger));
                 trigger_label.add_css_class("monospace");
+                if let Some(tooltip) = label_tooltip(snippet) {
+                    trigger_label.set_tooltip_text(Some(&tooltip));
+                }
                 trigger_label.set_xalign(0.0);
                 trigger_label.set_width_chars(15);
                 hbox.append(&trigger_label);
@@ -496,17 +1051,16 @@ impl ConfigWindow {
                 hbox.append(&arrow);
 
                 // Replacement (truncated)
-                let replace_text = snippet.replace.lines().next().unwrap_or("");
-                let display_text = if replace_text.len() > 30 {
-                    format!("{}...", &replace_text[..30])
-                } else if snippet.replace.contains('\n') {
-                    format!("{}...", replace_text)
-                } else {
-                    replace_text.to_string()
+                let replace_text = snippet.replacement().lines().next().unwrap_or("");
+                let display_text = match truncate_chars(replace_text, ROW_PREVIEW_MAX_CHARS) {
+                    Some(prefix) => format!("{}...", prefix),
+                    None if snippet.replacement().contains('\n') => format!("{}...", replace_text),
+                    None => replace_text.to_string(),
                 };
 
                 let replace_label = Label::new(Some(&display_text));
                 replace_label.set_xalign(0.0);
+                replace_label.set_tooltip_text(Some(&replacement_tooltip(snippet.replacement())));
                 replace_label.set_hexpand(true);
                 replace_label.set_ellipsize(gtk4::pango::EllipsizeMode::End);
                 hbox.append(&replace_label);
@@ -558,6 +1112,32 @@ impl ConfigWindow {
     /// Show the window
     pub fn show(&self) {
         self.window.present();
+
+        // Saving from the window rewrites the whole file, so say so up front
+        let (hand_edited, config_path) = {
+            let state = self.state.borrow();
+            let hand_edited = state.config.settings.preserve_formatting
+                && std::fs::read_to_string(&state.config_path).is_ok_and(|content| is_hand_edited(&content));
+            (hand_edited, state.config_path.clone())
+        };
+        if hand_edited {
+            let window = self.window.clone();
+            show_confirm_dialog(
+                &self.window,
+                "Hand-edited Config",
+                &format!(
+                    "{} has comments or custom formatting. Saving changes here rewrites it \
+                     without them; the current version will be kept in {}.\n\nContinue?",
+                    config_path.display(),
+                    backup_path(&config_path).display()
+                ),
+                move |confirmed| {
+                    if !confirmed {
+                        window.close();
+                    }
+                },
+            );
+        }
     }
 }
 
@@ -588,6 +1168,158 @@ pub fn create_config_app() -> Application {
     app
 }
 
+/// Maximum number of replacement characters shown in a row
+const ROW_PREVIEW_MAX_CHARS: usize = 30;
+
+/// The first `max_chars` characters of `text`, or `None` if it is no longer than that.
+/// Cuts on a char boundary, so emoji and accented text don't panic.
+fn truncate_chars(text: &str, max_chars: usize) -> Option<&str> {
+    text.char_indices()
+        .nth(max_chars)
+        .map(|(end, _)| &text[..end])
+}
+
+/// Maximum number of replacement characters shown in a row tooltip
+const TOOLTIP_MAX_CHARS: usize = 500;
+
+/// Build the hover tooltip for a snippet row: the full replacement, capped at
+/// `TOOLTIP_MAX_CHARS`. Plain-text tooltips keep newlines, so multi-line
+/// replacements render as they will be typed.
+fn replacement_tooltip(replace: &str) -> String {
+    let mut text: String = replace.chars().take(TOOLTIP_MAX_CHARS).collect();
+    if replace.chars().count() > TOOLTIP_MAX_CHARS {
+        text.push('…');
+    }
+    if text.trim().is_empty() {
+        text = "(empty replacement)".to_string();
+    }
+    text
+}
+
+/// Hover tooltip for a snippet's trigger: its label, then its notes
+fn label_tooltip(snippet: &crate::config::Snippet) -> Option<String> {
+    let parts: Vec<&str> = [snippet.label.as_deref(), snippet.notes.as_deref()]
+        .into_iter()
+        .flatten()
+        .filter(|text| !text.trim().is_empty())
+        .collect();
+    (!parts.is_empty()).then(|| parts.join("\n\n"))
+}
+
+/// Apply `change` to `config` and `save` it, putting the old config back if saving
+/// fails so the window keeps showing what is on disk. `None` if nothing changed.
+fn apply_change<E>(
+    config: &mut Config,
+    change: impl FnOnce(&mut Config) -> bool,
+    save: impl FnOnce(&Config) -> Result<(), E>,
+) -> Option<Result<(), E>> {
+    let previous = config.clone();
+    if !change(config) {
+        return None;
+    }
+
+    let result = save(config);
+    if result.is_err() {
+        *config = previous;
+    }
+    Some(result)
+}
+
+/// Text shown after saving, and whether it reports an error
+fn save_message(result: &Result<(), ConfigError>) -> (String, bool) {
+    match result {
+        Ok(()) => ("Saved".to_string(), false),
+        Err(e) => (format!("Save failed: {}", e), true),
+    }
+}
+
+/// Question asked before importing a downloaded snippet pack
+fn import_preview_message(url: &str, summary: &ImportSummary) -> String {
+    let mut message = format!(
+        "{} has {} snippets and {} variables. Import them?",
+        url, summary.snippets, summary.variables
+    );
+    if !summary.duplicates.is_empty() {
+        message.push_str(&format!(
+            "\n\nThese triggers are already defined and will be overridden: {}",
+            summary.duplicates.join(", ")
+        ));
+    }
+    if !summary.overwritten_variables.is_empty() {
+        message.push_str(&format!(
+            "\n\nThese variables will get the imported values: {}",
+            summary.overwritten_variables.join(", ")
+        ));
+    }
+    if !summary.commands.is_empty() {
+        message.push_str(&format!(
+            "\n\nThese snippets run commands on your machine when they expand: {}",
+            summary.commands.join(", ")
+        ));
+    }
+    message
+}
+
+/// Indices of the selected rows, which are those of the items in the current folder
+fn selected_indices(list_box: &ListBox) -> Vec<usize> {
+    list_box.selected_rows().iter().map(|row| row.index() as usize).collect()
+}
+
+/// Enable or disable the items of `list` at `indices`, returning how many changed
+fn set_enabled_at(list: &mut [SnippetNode], indices: &[usize], enabled: bool) -> usize {
+    let mut changed = 0;
+    for &index in indices {
+        let flag = match list.get_mut(index) {
+            Some(SnippetNode::Snippet(snippet)) => &mut snippet.enabled,
+            Some(SnippetNode::Folder(folder)) => &mut folder.enabled,
+            None => continue,
+        };
+        if *flag != enabled {
+            *flag = enabled;
+            changed += 1;
+        }
+    }
+    changed
+}
+
+/// Remove the items of `list` at `indices`, returning how many were removed.
+/// Removes from the highest index down, so the other indices stay valid.
+fn remove_at(list: &mut Vec<SnippetNode>, indices: &[usize]) -> usize {
+    let mut indices = indices.to_vec();
+    indices.sort_unstable_by(|a, b| b.cmp(a));
+    indices.dedup();
+    indices.retain(|&index| index < list.len());
+    for &index in &indices {
+        list.remove(index);
+    }
+    indices.len()
+}
+
+/// Reorder `list` by `order`, keeping the current order of items that compare
+/// equal. Returns whether anything moved.
+fn sort_items(list: &mut Vec<SnippetNode>, order: SortOrder) -> bool {
+    let mut indices: Vec<usize> = (0..list.len()).collect();
+    // Stable, so equal keys keep their order
+    indices.sort_by_cached_key(|&i| match &list[i] {
+        SnippetNode::Folder(f) => (0, if order == SortOrder::FoldersFirst { String::new() } else { f.folder.to_lowercase() }),
+        SnippetNode::Snippet(s) => (
+            1,
+            match order {
+                SortOrder::FoldersFirst => String::new(),
+                SortOrder::ByTrigger => s.trigger.to_lowercase(),
+                SortOrder::ByLabel => s.label.as_deref().unwrap_or(&s.trigger).to_lowercase(),
+            },
+        ),
+    });
+    if indices.iter().enumerate().all(|(position, &i)| position == i) {
+        return false;
+    }
+
+    let mut items: Vec<Option<SnippetNode>> = std::mem::take(list).into_iter().map(Some).collect();
+    *list = indices.into_iter().filter_map(|i| items[i].take()).collect();
+    true
+}
+
 /// Helper to get mutable reference to the list at a specific path
 fn get_list_at_path_mut<'a>(
     root: &'a mut Vec<crate::config::SnippetNode>,
@@ -606,3 +1338,157 @@ fn get_list_at_path_mut<'a>(
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate_chars() {
+        assert_eq!(truncate_chars("short", 30), None);
+        assert_eq!(truncate_chars("abcdef", 3), Some("abc"));
+
+        // Byte 30 falls inside a multi-byte char here
+        let text = format!("{}é and more text", "a".repeat(29));
+        assert_eq!(truncate_chars(&text, 30), Some(format!("{}é", "a".repeat(29)).as_str()));
+        assert_eq!(truncate_chars("😀😀😀😀", 2), Some("😀😀"));
+    }
+
+    fn triggers(list: &[SnippetNode]) -> Vec<String> {
+        list.iter()
+            .map(|node| match node {
+                SnippetNode::Snippet(s) => s.trigger.clone(),
+                SnippetNode::Folder(f) => f.folder.clone(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_bulk_enable_and_delete() {
+        let mut list: Vec<SnippetNode> = [";a", ";b", ";c", ";d"]
+            .iter()
+            .map(|t| SnippetNode::Snippet(crate::config::Snippet::new(*t, "x")))
+            .collect();
+        list.push(SnippetNode::Folder(crate::config::Folder::new("Work")));
+
+        assert_eq!(set_enabled_at(&mut list, &[1, 4, 9], false), 2);
+        let enabled: Vec<bool> = list
+            .iter()
+            .map(|node| match node {
+                SnippetNode::Snippet(s) => s.enabled,
+                SnippetNode::Folder(f) => f.enabled,
+            })
+            .collect();
+        assert_eq!(enabled, vec![true, false, true, true, false]);
+        // Already disabled: nothing changes, so nothing would be saved
+        assert_eq!(set_enabled_at(&mut list, &[1], false), 0);
+
+        // Indices in any order, repeated or out of range
+        assert_eq!(remove_at(&mut list, &[0, 3, 3, 2, 7]), 3);
+        assert_eq!(triggers(&list), vec![";b", "Work"]);
+    }
+
+    #[test]
+    fn test_sort_items() {
+        let snippet = |trigger: &str, label: Option<&str>| {
+            let mut snippet = crate::config::Snippet::new(trigger, "x");
+            snippet.label = label.map(str::to_string);
+            SnippetNode::Snippet(snippet)
+        };
+        let original = vec![
+            snippet(";zip", Some("Archive")),
+            SnippetNode::Folder(crate::config::Folder::new("work")),
+            snippet(";Addr", None),
+            snippet(";b", Some("zebra")),
+            SnippetNode::Folder(crate::config::Folder::new("Personal")),
+            snippet(";addr", Some("Address")),
+        ];
+
+        let mut list = original.clone();
+        assert!(sort_items(&mut list, SortOrder::FoldersFirst));
+        assert_eq!(triggers(&list), vec!["work", "Personal", ";zip", ";Addr", ";b", ";addr"]);
+        // Already in order: nothing to save
+        assert!(!sort_items(&mut list, SortOrder::FoldersFirst));
+
+        let mut list = original.clone();
+        assert!(sort_items(&mut list, SortOrder::ByTrigger));
+        // Case-insensitive, and equal triggers keep their order
+        assert_eq!(triggers(&list), vec!["Personal", "work", ";Addr", ";addr", ";b", ";zip"]);
+
+        let mut list = original.clone();
+        assert!(sort_items(&mut list, SortOrder::ByLabel));
+        // ";Addr" has no label and sorts by its trigger, which starts with ';'
+        assert_eq!(triggers(&list), vec!["Personal", "work", ";Addr", ";addr", ";zip", ";b"]);
+        assert_eq!(list.len(), original.len());
+    }
+
+    #[test]
+    fn test_apply_change_rolls_back_failed_save() {
+        let mut config = Config::default();
+        let add = |config: &mut Config| {
+            config.snippets.push(SnippetNode::Snippet(crate::config::Snippet::new(";a", "a")));
+            true
+        };
+
+        assert!(apply_change(&mut config, add, |_| Ok::<(), ()>(())).unwrap().is_ok());
+        assert_eq!(config.snippets.len(), 1);
+
+        // A failed save leaves the config as it was
+        assert!(apply_change(&mut config, add, |_| Err(())).unwrap().is_err());
+        assert_eq!(config.snippets.len(), 1);
+
+        // Nothing changed, nothing saved
+        let result = apply_change(&mut config, |_| false, |_| -> Result<(), ()> { panic!("saved") });
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn test_save_message() {
+        assert_eq!(save_message(&Ok(())), ("Saved".to_string(), false));
+
+        let err = ConfigError::Write {
+            path: PathBuf::from("/etc/xpander/config.yaml"),
+            source: std::io::Error::new(std::io::ErrorKind::PermissionDenied, "Permission denied"),
+        };
+        assert_eq!(
+            save_message(&Err(err)),
+            ("Save failed: failed to write /etc/xpander/config.yaml: Permission denied".to_string(), true)
+        );
+    }
+
+    #[test]
+    fn test_import_preview_message() {
+        let summary = ImportSummary {
+            snippets: 3,
+            variables: 1,
+            duplicates: vec![";sig".to_string()],
+            commands: vec![";ip".to_string()],
+            overwritten_variables: vec!["name".to_string()],
+            ..Default::default()
+        };
+        let message = import_preview_message("https://example.com/pack.yaml", &summary);
+        assert!(message.starts_with("https://example.com/pack.yaml has 3 snippets and 1 variables"));
+        assert!(message.contains("overridden: ;sig"));
+        assert!(message.contains("imported values: name"));
+        assert!(message.ends_with("when they expand: ;ip"));
+
+        let message = import_preview_message("https://example.com/pack.yaml", &ImportSummary::default());
+        assert!(!message.contains("overridden"));
+        assert!(!message.contains("commands"));
+    }
+
+    #[test]
+    fn test_label_tooltip() {
+        let mut snippet = crate::config::Snippet::new(";sig", "Best");
+        assert_eq!(label_tooltip(&snippet), None);
+
+        snippet.notes = Some("Use at the end of emails".to_string());
+        assert_eq!(label_tooltip(&snippet).as_deref(), Some("Use at the end of emails"));
+
+        snippet.label = Some("Signature".to_string());
+        assert_eq!(
+            label_tooltip(&snippet).as_deref(),
+            Some("Signature\n\nUse at the end of emails")
+        );
+    }
+}
@@ -1,6 +1,8 @@
 mod config;
+mod daemon;
 mod engine;
 mod gui;
+mod notify;
 mod variables;
 
 use anyhow::{Context, Result};
@@ -13,6 +15,15 @@ use config::{Config, ConfigManager};
 use engine::start_expansion_pipeline;
 use gui::{start_tray, TrayCommand, create_config_app};
 
+/// Snippets loaded by `--benchmark` unless a count is given
+const BENCHMARK_SNIPPETS: usize = 10_000;
+
+/// Characters typed by `--benchmark`
+const BENCHMARK_KEYSTROKES: usize = 50_000;
+
+/// How often the tray's status line is brought up to date
+const TRAY_STATUS_REFRESH: std::time::Duration = std::time::Duration::from_secs(15);
+
 /// Application state shared across components
 struct AppState {
     config: Arc<RwLock<Config>>,
@@ -22,23 +33,101 @@ struct AppState {
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Initialize logging
+    // Check for --gui flag to open config window
+    // Check for --gui flag, or --export/--import
+    let args: Vec<String> = env::args().collect();
+    let debug_keys = args.iter().any(|a| a == "--debug-keys");
+
+    // Initialize logging (--debug-keys needs debug output to be useful)
     env_logger::Builder::from_env(
-        env_logger::Env::default().default_filter_or("info")
+        env_logger::Env::default().default_filter_or(if debug_keys { "info,xpander=debug" } else { "info" })
     )
     .format_timestamp_secs()
     .init();
 
-    // Check for --gui flag to open config window
-    // Check for --gui flag, or --export/--import
-    let args: Vec<String> = env::args().collect();
+    if debug_keys {
+        log::warn!("**************************************************************");
+        log::warn!("--debug-keys is on: EVERY KEY YOU TYPE IS WRITTEN TO THE LOG,");
+        log::warn!("including passwords and private messages. Use it only to debug");
+        log::warn!("a trigger, restart without it afterwards, and check the log for");
+        log::warn!("sensitive text before sharing it.");
+        log::warn!("**************************************************************");
+    }
     
     // Simple argument parsing
     if args.len() > 1 {
         if args.iter().any(|a| a == "--gui" || a == "-g") {
             return run_config_gui().await;
         }
+
+        if args.iter().any(|a| a == "--pick") {
+            return run_pick();
+        }
+
+        if args.iter().any(|a| a == "--input") {
+            return run_input();
+        }
+
+        if args.iter().any(|a| a == "--emit-schema") {
+            return run_emit_schema();
+        }
+
+        if args.iter().any(|a| a == "--expand-stdin") {
+            return run_expand_stdin();
+        }
+
+        if args.iter().any(|a| a == "--status") {
+            return run_status().await;
+        }
+
+        if let Some(pos) = args.iter().position(|a| a == "--benchmark") {
+            let snippets = match args.get(pos + 1).filter(|a| !a.starts_with("--")) {
+                Some(n) => n.parse().context("--benchmark takes a snippet count")?,
+                None => BENCHMARK_SNIPPETS,
+            };
+            let max_p99_us = match args.iter().position(|a| a == "--max-p99-us") {
+                Some(pos) => Some(
+                    args.get(pos + 1)
+                        .and_then(|us| us.parse().ok())
+                        .context("--max-p99-us takes a number of microseconds")?,
+                ),
+                None => None,
+            };
+            return run_benchmark(snippets, max_p99_us);
+        }
+
+        if args.iter().any(|a| a == "--test-snippets") {
+            return run_test_snippets(args.iter().any(|a| a == "--run-shell"));
+        }
+
+        if let Some(pos) = args.iter().position(|a| a == "--diagnose") {
+            if let Some(trigger) = args.get(pos + 1) {
+                return run_diagnose(trigger).await;
+            } else {
+                eprintln!("Error: --diagnose requires a trigger");
+                std::process::exit(1);
+            }
+        }
         
+        if let Some(pos) = args.iter().position(|a| a == "--share") {
+            if let Some(trigger) = args.get(pos + 1) {
+                return run_share(trigger);
+            } else {
+                eprintln!("Error: --share requires a trigger");
+                std::process::exit(1);
+            }
+        }
+
+        if let Some(pos) = args.iter().position(|a| a == "--unshare") {
+            if let Some(encoded) = args.get(pos + 1) {
+                let confirmed = args.iter().any(|a| a == "--yes" || a == "-y");
+                return run_unshare(encoded, confirmed).await;
+            } else {
+                eprintln!("Error: --unshare requires a shared string");
+                std::process::exit(1);
+            }
+        }
+
         // Handle export
         if let Some(pos) = args.iter().position(|a| a == "--export") {
             if let Some(path) = args.get(pos + 1) {
@@ -50,20 +139,58 @@ async fn main() -> Result<()> {
         }
         
         // Handle import
+        let import_mode = || match (args.iter().any(|a| a == "--merge"), args.iter().any(|a| a == "--replace")) {
+            (true, true) => {
+                eprintln!("Error: --merge and --replace can't be combined");
+                std::process::exit(1);
+            }
+            (_, true) => config::loader::ImportMode::Replace,
+            _ => config::loader::ImportMode::Merge,
+        };
         if let Some(pos) = args.iter().position(|a| a == "--import") {
             if let Some(path) = args.get(pos + 1) {
-                return run_import(path).await;
+                return run_import(path, import_mode()).await;
             } else {
                 eprintln!("Error: --import requires a file path");
                 std::process::exit(1);
             }
         }
+        if let Some(pos) = args.iter().position(|a| a == "--import-url") {
+            if let Some(url) = args.get(pos + 1) {
+                let confirmed = args.iter().any(|a| a == "--yes" || a == "-y");
+                return run_import_url(url, import_mode(), confirmed).await;
+            } else {
+                eprintln!("Error: --import-url requires a URL");
+                std::process::exit(1);
+            }
+        }
+
+        if args.iter().any(|a| a == "--stop") {
+            return run_stop();
+        }
+
+        if args.iter().any(|a| a == "--daemonize") {
+            return run_daemonize(&args);
+        }
     }
 
     log::info!("Starting xpander text expansion daemon");
 
-    // Check prerequisites
-    check_prerequisites().await?;
+    // Two daemons would both read the keyboard and expand every trigger twice
+    if let Err(e) = daemon::acquire_instance_lock() {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+
+    // Exit cleanly on SIGTERM (e.g. from `xpander --stop`)
+    let mut terminate = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+        .context("Failed to handle SIGTERM")?;
+    tokio::spawn(async move {
+        terminate.recv().await;
+        log::info!("Terminated, shutting down");
+        daemon::release_instance();
+        std::process::exit(0);
+    });
 
     // Load configuration
     let (config_manager, mut config_rx) = ConfigManager::new()
@@ -71,6 +198,9 @@ async fn main() -> Result<()> {
         .context("Failed to initialize configuration")?;
 
     let initial_config = config_manager.get_config().await.clone();
+
+    // Check prerequisites
+    check_prerequisites(&initial_config.settings).await?;
     let initial_enabled = initial_config.settings.enabled;
 
     log::info!(
@@ -93,9 +223,36 @@ async fn main() -> Result<()> {
     // Create channel for tray commands
     let (tray_tx, mut tray_rx) = mpsc::channel::<TrayCommand>(32);
 
+    // Reload on SIGHUP (`kill -HUP`) just like "Reload config" in the tray, e.g.
+    // with `config_watch_mode: off`
+    let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+        .context("Failed to handle SIGHUP")?;
+    let tray_tx_for_hangup = tray_tx.clone();
+    tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            log::info!("SIGHUP received, reloading configuration");
+            if tray_tx_for_hangup.send(TrayCommand::ReloadConfig).await.is_err() {
+                break;
+            }
+        }
+    });
+
     // Start system tray
-    let tray_handle = start_tray(initial_enabled, tray_tx)
+    let engine_status = Arc::new(std::sync::RwLock::new(engine::EngineStatus::default()));
+    let tray_handle = start_tray(initial_enabled, engine_status.clone(), tray_tx)
         .context("Failed to start system tray")?;
+    let tray_handle = Arc::new(tray_handle);
+    update_tray_profiles(&tray_handle, &*config.read().await);
+
+    // Keep the tray's status line ("N snippets active, last expansion ...") current
+    let tray_handle_for_status = tray_handle.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(TRAY_STATUS_REFRESH);
+        loop {
+            interval.tick().await;
+            tray_handle_for_status.refresh_status();
+        }
+    });
 
     // Create channel for reload notifications
     let (reload_tx, reload_rx) = mpsc::channel(1);
@@ -103,8 +260,10 @@ async fn main() -> Result<()> {
     // Handle config reload notifications
     let config_for_reload = config.clone();
     let reload_tx_for_watcher = reload_tx.clone();
+    let tray_handle_for_reload = tray_handle.clone();
     tokio::spawn(async move {
         while let Some(new_config) = config_rx.recv().await {
+            update_tray_profiles(&tray_handle_for_reload, &new_config);
             let mut cfg = config_for_reload.write().await;
             *cfg = new_config;
             log::info!("Configuration reloaded");
@@ -116,7 +275,6 @@ async fn main() -> Result<()> {
 
     // Handle tray commands
     let state_for_tray = Arc::new(state);
-    let tray_handle = Arc::new(tray_handle);
 
     let tray_handle_clone = tray_handle.clone();
     let state_clone = state_for_tray.clone();
@@ -152,13 +310,13 @@ async fn main() -> Result<()> {
                     log::info!("Opening config file: {}", config_path.display());
 
                     // Try to open with default editor
-                    if let Err(e) = open_file_in_editor(&config_path) {
+                    if let Err(e) = open_file_in_editor(&config_path, None) {
                         log::error!("Failed to open config file: {}", e);
                     }
                 }
                 TrayCommand::ReloadConfig => {
                     let manager = state_clone.config_manager.read().await;
-                    match ConfigManager::load_config(manager.path()) {
+                    match ConfigManager::load_and_upgrade_config(manager.path()) {
                         Ok(new_config) => {
                             let mut cfg = state_clone.config.write().await;
                             *cfg = new_config;
@@ -175,30 +333,69 @@ async fn main() -> Result<()> {
                         }
                     }
                 }
+                TrayCommand::SetProfile(profile) => {
+                    log::info!("Switching to profile: {}", profile.as_deref().unwrap_or("all snippets"));
+
+                    let new_config = {
+                        let mut cfg = state_clone.config.write().await;
+                        cfg.settings.active_profile = profile;
+                        cfg.clone()
+                    };
+                    update_tray_profiles(&tray_handle_clone, &new_config);
+
+                    // Persist the choice; the engine reloads to apply it, through the
+                    // config watcher when there is one
+                    let manager = state_clone.config_manager.read().await;
+                    let saved = manager.update_config(new_config).await;
+                    if let Err(e) = &saved {
+                        log::error!("Failed to save active profile: {}", e);
+                    }
+                    if saved.is_err() || !manager.watches_file() {
+                        if let Err(e) = reload_tx_for_tray.send(()).await {
+                            log::warn!("Failed to send reload notification: {}", e);
+                        }
+                    }
+                }
                 TrayCommand::Quit => {
                     log::info!("Quit requested, shutting down");
+                    daemon::release_instance();
                     std::process::exit(0);
                 }
             }
         }
     });
 
+    // Reflect text output failures in the tray
+    let (health_tx, mut health_rx) = mpsc::channel::<bool>(4);
+    let tray_handle_for_health = tray_handle.clone();
+    tokio::spawn(async move {
+        while let Some(degraded) = health_rx.recv().await {
+            tray_handle_for_health.set_degraded(degraded);
+        }
+    });
+
     // Start the expansion pipeline
     log::info!("Starting expansion engine");
-    start_expansion_pipeline(config, enabled, reload_rx).await?;
+    start_expansion_pipeline(config, enabled, reload_rx, health_tx, engine_status, debug_keys).await?;
 
     Ok(())
 }
 
+/// Show the configured profiles in the tray menu
+fn update_tray_profiles(tray_handle: &gui::TrayHandle, config: &Config) {
+    tray_handle.set_profiles(
+        config.settings.profiles.keys().cloned().collect(),
+        config.settings.active_profile.clone(),
+    );
+}
+
 /// Check that all prerequisites are met
-async fn check_prerequisites() -> Result<()> {
-    // Check for ydotool
-    engine::OutputEngine::check_availability().await
-        .context(
-            "ydotool is required for text expansion on Wayland.\n\
-             Install with: sudo apt install ydotool\n\
-             Then enable the daemon: sudo systemctl enable --now ydotool"
-        )?;
+async fn check_prerequisites(settings: &config::schema::Settings) -> Result<()> {
+    // Check for the configured output tool (ydotool by default)
+    engine::create_backend(&engine::resolve_ydotool_socket(settings, engine::detect_ydotool_socket))
+        .check_availability()
+        .await
+        .context("A keystroke output tool is required for text expansion (see `output_backend` in the config)")?;
 
     // Check for input group membership
     check_input_group()?;
@@ -208,13 +405,7 @@ async fn check_prerequisites() -> Result<()> {
 
 /// Check if user is in the input group
 fn check_input_group() -> Result<()> {
-    let groups_output = Command::new("groups")
-        .output()
-        .context("Failed to check user groups")?;
-
-    let groups = String::from_utf8_lossy(&groups_output.stdout);
-
-    if !groups.contains("input") {
+    if !in_input_group()? {
         log::warn!(
             "User may not be in 'input' group. If keyboard monitoring fails, run:\n\
              sudo usermod -aG input $USER\n\
@@ -225,12 +416,27 @@ fn check_input_group() -> Result<()> {
     Ok(())
 }
 
-/// Open a file in the default editor
-fn open_file_in_editor(path: &std::path::Path) -> Result<()> {
-    // Try common editors in order of preference
-    let editors = ["xdg-open", "gedit", "kate", "code", "vim"];
+/// Whether the user is in the input group, needed to read keyboard events
+fn in_input_group() -> Result<bool> {
+    let groups_output = Command::new("groups")
+        .output()
+        .context("Failed to check user groups")?;
+
+    let groups = String::from_utf8_lossy(&groups_output.stdout);
+    Ok(groups.contains("input"))
+}
+
+/// Open a file in the default editor, at `line` (1-based) if the editor supports it
+fn open_file_in_editor(path: &std::path::Path, line: Option<usize>) -> Result<()> {
+    // Try common editors in order of preference. xdg-open can't jump to a line,
+    // so editors that can are tried first when a line is given.
+    let editors: &[&str] = if line.is_some() {
+        &["gedit", "kate", "code", "xdg-open", "vim"]
+    } else {
+        &["xdg-open", "gedit", "kate", "code", "vim"]
+    };
 
-    for editor in editors {
+    for &editor in editors {
         if Command::new("which")
             .arg(editor)
             .output()
@@ -238,7 +444,7 @@ fn open_file_in_editor(path: &std::path::Path) -> Result<()> {
             .unwrap_or(false)
         {
             Command::new(editor)
-                .arg(path)
+                .args(editor_args(editor, path, line))
                 .spawn()
                 .context(format!("Failed to open with {}", editor))?;
             return Ok(());
@@ -248,6 +454,17 @@ fn open_file_in_editor(path: &std::path::Path) -> Result<()> {
     anyhow::bail!("No suitable editor found")
 }
 
+/// Command-line arguments that open `path` in `editor`, at `line` where supported
+fn editor_args(editor: &str, path: &std::path::Path, line: Option<usize>) -> Vec<String> {
+    let path = path.display().to_string();
+    match (editor, line) {
+        ("gedit" | "vim", Some(line)) => vec![format!("+{}", line), path],
+        ("kate", Some(line)) => vec!["--line".to_string(), line.to_string(), path],
+        ("code", Some(line)) => vec!["--goto".to_string(), format!("{}:{}", path, line)],
+        _ => vec![path],
+    }
+}
+
 /// Run export command
 async fn run_export(path_str: &str) -> Result<()> {
     let (config_manager, _) = ConfigManager::new().await?;
@@ -255,52 +472,350 @@ async fn run_export(path_str: &str) -> Result<()> {
     let path = std::path::Path::new(path_str);
     
     config::loader::export_custom_entries(&config.snippets, &config.variables, path)?;
-    println!("Successfully exported {} snippets and variables to {}", config.snippets.len(), path.display());
+    println!(
+        "Exported {} snippets and {} variables to {}",
+        config::loader::count_snippets(&config.snippets),
+        config.variables.as_mapping().map_or(0, |map| map.len()),
+        path.display()
+    );
     Ok(())
 }
 
-/// Run import command
-async fn run_import(path_str: &str) -> Result<()> {
+/// Run import command, merging into or replacing the current snippets and variables
+async fn run_import(path_str: &str, mode: config::loader::ImportMode) -> Result<()> {
     let path = std::path::Path::new(path_str);
     if !path.exists() {
         anyhow::bail!("Import file not found: {}", path.display());
     }
 
     let data = config::loader::import_custom_entries(path)?;
-    
+
     let (config_manager, _) = ConfigManager::new().await?;
     let mut config = config_manager.get_config().await.clone();
-    
-    // Merge logic: append new snippets, merge variables
-    // For simplicity, we'll append snippets and overwrite variables if they clash, 
-    // but ideally we'd merge deeply.
-    // Let's just append snippets.
-    let old_count = config.snippets.len();
-    config.snippets.extend(data.snippets);
-    
-    // For variables, if it's a mapping, we can try to merge.
-    // If it's something else, we overwrite.
-    match (&mut config.variables, data.variables) {
-        (serde_yaml::Value::Mapping(map), serde_yaml::Value::Mapping(new_map)) => {
-            for (k, v) in new_map {
-                map.insert(k, v);
+    let summary = config::loader::apply_import(&mut config, data, mode);
+    config_manager.update_config(config.clone()).await?;
+
+    let action = match mode {
+        config::loader::ImportMode::Merge => "Merged",
+        config::loader::ImportMode::Replace => "Replaced config with",
+    };
+    println!(
+        "{} {} snippets and {} variables from {}",
+        action,
+        summary.snippets,
+        summary.variables,
+        path.display()
+    );
+    if !summary.duplicates.is_empty() {
+        println!(
+            "{} imported triggers were already defined; the imported snippets take precedence: {}",
+            summary.duplicates.len(),
+            summary.duplicates.join(", ")
+        );
+    }
+    print_kept_files(&summary);
+    if !summary.overwritten_variables.is_empty() {
+        println!(
+            "{} variables were replaced with the imported values: {}",
+            summary.overwritten_variables.len(),
+            summary.overwritten_variables.join(", ")
+        );
+    }
+    println!("Total snippets: {}", config::loader::count_snippets(&config.snippets));
+
+    Ok(())
+}
+
+/// List the `snippets_dir` files a replacing import leaves in place
+fn print_kept_files(summary: &config::loader::ImportSummary) {
+    if summary.kept_files.is_empty() {
+        return;
+    }
+    println!("Snippet files in snippets_dir are kept; delete them to remove their snippets:");
+    for file in &summary.kept_files {
+        println!("  {}", file.display());
+    }
+}
+
+/// Download a snippet pack, show what it would add and import it once confirmed
+async fn run_import_url(url: &str, mode: config::loader::ImportMode, confirmed: bool) -> Result<()> {
+    let fetch_url = url.to_string();
+    let data = tokio::task::spawn_blocking(move || config::remote::fetch_snippet_pack(&fetch_url)).await??;
+
+    let (config_manager, _) = ConfigManager::new().await?;
+    let mut config = config_manager.get_config().await.clone();
+    let summary = config::loader::apply_import(&mut config, data, mode);
+
+    println!("{} has {} snippets and {} variables", url, summary.snippets, summary.variables);
+    if mode == config::loader::ImportMode::Replace {
+        println!("They will replace the snippets and variables in the config file");
+    }
+    print_kept_files(&summary);
+    if !summary.duplicates.is_empty() {
+        println!(
+            "{} of its triggers are already defined and will take precedence: {}",
+            summary.duplicates.len(),
+            summary.duplicates.join(", ")
+        );
+    }
+    if !summary.overwritten_variables.is_empty() {
+        println!(
+            "{} of its variables will replace ones you have: {}",
+            summary.overwritten_variables.len(),
+            summary.overwritten_variables.join(", ")
+        );
+    }
+    if !summary.commands.is_empty() {
+        println!(
+            "{} of its snippets run commands on your machine when they expand: {}",
+            summary.commands.len(),
+            summary.commands.join(", ")
+        );
+    }
+
+    if !confirmed && !ask("Import them?")? {
+        println!("Nothing imported");
+        return Ok(());
+    }
+
+    config_manager.update_config(config.clone()).await?;
+    println!("Imported. Total snippets: {}", config::loader::count_snippets(&config.snippets));
+
+    Ok(())
+}
+
+/// Print one snippet as a string that can be pasted in a chat and added with `--unshare`
+fn run_share(trigger: &str) -> Result<()> {
+    let path = ConfigManager::get_config_path()?;
+    let config = ConfigManager::load_config(&path)?;
+    let snippet = config::share::find_shared_snippet(&config.snippets, trigger)
+        .with_context(|| format!("No snippet has the trigger '{}'", trigger))?;
+    println!("{}", config::share::share_snippet(&snippet)?);
+    Ok(())
+}
+
+/// Ask a yes/no question on the terminal; anything but yes is no
+fn ask(question: &str) -> Result<bool> {
+    print!("{} [y/N] ", question);
+    std::io::Write::flush(&mut std::io::stdout())?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Add a snippet shared with `--share` to the config. One that runs commands
+/// is only added once confirmed.
+async fn run_unshare(encoded: &str, confirmed: bool) -> Result<()> {
+    let snippet = config::share::unshare_snippet(encoded)?;
+    let trigger = snippet.trigger.clone();
+    if snippet.runs_commands() {
+        println!("'{}' runs commands on your machine when it expands:", trigger);
+        println!("{}", serde_yaml::to_string(&snippet)?.trim_end());
+        if !confirmed && !ask("Add it?")? {
+            println!("Nothing added");
+            return Ok(());
+        }
+    }
+    let data = config::loader::ExportData {
+        snippets: vec![config::SnippetNode::Snippet(snippet)],
+        variables: serde_yaml::Value::Null,
+    };
+
+    let (config_manager, _) = ConfigManager::new().await?;
+    let mut config = config_manager.get_config().await.clone();
+    let summary = config::loader::apply_import(&mut config, data, config::loader::ImportMode::Merge);
+    config_manager.update_config(config).await?;
+
+    println!("Added snippet '{}'", trigger);
+    if !summary.duplicates.is_empty() {
+        println!("'{}' was already defined; the added snippet takes precedence", trigger);
+    }
+    Ok(())
+}
+
+/// Print the config JSON Schema to stdout
+fn run_emit_schema() -> Result<()> {
+    let schema = config::schema::config_json_schema();
+    let json = serde_json::to_string_pretty(&schema)
+        .context("Failed to serialize config schema")?;
+    println!("{}", json);
+    Ok(())
+}
+
+/// Print the config path, whether it loads, and whether the prerequisites for
+/// expansion are met. Exits with status 1 if something is missing or broken.
+async fn run_status() -> Result<()> {
+    let path = ConfigManager::get_config_path()?;
+    let mut healthy = true;
+
+    println!("Config: {}", path.display());
+    let config = if !path.exists() {
+        println!("  • Not created yet, defaults are used until xpander first starts");
+        Config::default()
+    } else {
+        match ConfigManager::load_config(&path) {
+            Ok(config) => {
+                println!("  ✓ Loads without errors");
+                config
             }
-        },
-        (current, new) => {
-            *current = new;
+            Err(e) => {
+                println!("  ✗ {:#}", e);
+                healthy = false;
+                Config::default()
+            }
+        }
+    };
+
+    let settings = &config.settings;
+    println!(
+        "Snippets: {} active of {}",
+        ConfigManager::flatten_active_snippets(&config).len(),
+        config::loader::count_snippets(&config.snippets)
+    );
+    println!("Expansion: {}", if settings.enabled { "enabled" } else { "disabled" });
+    println!("Layout: {}", settings.layout);
+    println!("Output backend: {:?}", settings.output_backend);
+
+    let settings = engine::resolve_ydotool_socket(settings, engine::detect_ydotool_socket);
+    match engine::create_backend(&settings).check_availability().await {
+        Ok(()) => println!("  ✓ Output tool available"),
+        Err(e) => {
+            println!("  ✗ {:#}", e);
+            healthy = false;
         }
     }
-    
-    config_manager.update_config(config.clone()).await?;
-    println!("Successfully imported. Snippets: {} -> {}. Variables updated.", old_count, config.snippets.len());
-    
+
+    match in_input_group() {
+        Ok(true) => println!("  ✓ User is in the 'input' group"),
+        Ok(false) => {
+            println!("  ✗ User is not in the 'input' group (sudo usermod -aG input $USER)");
+            healthy = false;
+        }
+        Err(e) => println!("  • {:#}", e),
+    }
+
+    if !healthy {
+        std::process::exit(1);
+    }
     Ok(())
 }
 
+/// Start the daemon in the background (`--daemonize`)
+fn run_daemonize(args: &[String]) -> Result<()> {
+    let pid = daemon::daemonize(args)?;
+    println!(
+        "xpander started in the background (PID {}), logging to {}",
+        pid,
+        daemon::log_file_path().display()
+    );
+    Ok(())
+}
+
+/// Stop a daemon started with `--daemonize` (`--stop`)
+fn run_stop() -> Result<()> {
+    let pid = daemon::stop_daemon()?;
+    println!("Stopped xpander (PID {})", pid);
+    Ok(())
+}
+
+/// Expand the triggers read from stdin, one per line, printing the results to stdout
+fn run_expand_stdin() -> Result<()> {
+    let path = ConfigManager::get_config_path()?;
+    let config = ConfigManager::load_config(&path)?;
+    let snippets = ConfigManager::flatten_active_snippets(&config);
+
+    engine::expander::expand_lines(
+        &snippets,
+        &config.variables,
+        std::io::stdin().lock(),
+        std::io::stdout().lock(),
+        std::io::stderr(),
+    )
+}
+
+/// Time the matcher against synthetic snippets and print per-keystroke latency.
+/// Exits with status 1 if the p99 latency is over `max_p99_us` microseconds.
+fn run_benchmark(snippets: usize, max_p99_us: Option<u64>) -> Result<()> {
+    let report = engine::benchmark::run_benchmark(snippets, BENCHMARK_KEYSTROKES);
+    print!("{}", report);
+
+    if let Some(max) = max_p99_us {
+        if report.p99 > std::time::Duration::from_micros(max) {
+            eprintln!("p99 latency {:.1?} is over the {}µs limit", report.p99, max);
+            std::process::exit(1);
+        }
+    }
+    Ok(())
+}
+
+/// Expand every snippet and print which fail. Exits with status 1 if any do.
+fn run_test_snippets(run_shell: bool) -> Result<()> {
+    let path = ConfigManager::get_config_path()?;
+    let config = ConfigManager::load_config(&path)?;
+    let snippets = ConfigManager::flatten_snippets(&config.snippets);
+
+    println!("Config: {}", path.display());
+    let report = engine::snippet_check::check_snippets(&snippets, &config.variables, run_shell);
+    print!("{}", report);
+
+    if report.failures() > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Explain why a trigger does or doesn't expand
+async fn run_diagnose(trigger: &str) -> Result<()> {
+    let path = ConfigManager::get_config_path()?;
+    let config = ConfigManager::load_config(&path)?;
+    println!("Config: {}", path.display());
+    print!("{}", engine::diagnose::diagnose(&config, trigger));
+    Ok(())
+}
+
+/// Show the `{{pick:...}}` chooser: read the options as a JSON array on stdin and
+/// print the chosen one as a JSON string. Exits with status 1 if the user cancels.
+fn run_pick() -> Result<()> {
+    let options: Vec<String> = serde_json::from_reader(std::io::stdin())
+        .context("Failed to read pick options")?;
+
+    match gui::run_picker(options) {
+        Some(choice) => {
+            println!("{}", serde_json::to_string(&choice)?);
+            Ok(())
+        }
+        None => std::process::exit(1),
+    }
+}
+
+/// Show the `{{input:...}}` prompt: read the prompt as a JSON string on stdin and
+/// print the text entered as a JSON string. Exits with status 1 if the user cancels.
+fn run_input() -> Result<()> {
+    let prompt: String = serde_json::from_reader(std::io::stdin())
+        .context("Failed to read the input prompt")?;
+
+    match gui::run_input(prompt) {
+        Some(text) => {
+            println!("{}", serde_json::to_string(&text)?);
+            Ok(())
+        }
+        None => std::process::exit(1),
+    }
+}
+
 /// Run the GTK configuration GUI
 async fn run_config_gui() -> Result<()> {
     use gtk4::prelude::*;
 
+    // GTK aborts when it can't open a display, e.g. over SSH
+    if !gui::display_available(|name| std::env::var_os(name)) {
+        eprintln!(
+            "No display available; edit the config file at {} instead",
+            ConfigManager::get_config_path()?.display()
+        );
+        std::process::exit(1);
+    }
+
     let app = create_config_app();
     // Pass empty args so GTK doesn't try to parse our --gui flag
     let empty_args: &[&str] = &[];
@@ -323,7 +838,39 @@ OPTIONS:
     -v, --version   Show version information
     -c, --config    Path to config file (default: ~/.config/xpander/config.yaml)
     --export PATH   Export snippets and variables to file
-    --import PATH   Import snippets and variables from file
+    --import PATH [--merge|--replace]
+                    Import snippets and variables from file, adding to the current
+                    ones (--merge, default) or replacing them (--replace)
+    --import-url URL [--merge|--replace] [--yes]
+                    Download a snippet pack (an exported file) over https and
+                    import it, after showing what it adds (--yes skips asking)
+    --share TRIGGER Print a snippet as a single line of text to paste in a chat
+    --unshare TEXT [--yes]
+                    Add a snippet printed by --share, asking first if it runs
+                    commands (--yes skips asking)
+    --emit-schema   Print a JSON Schema for config.yaml to stdout
+    --diagnose TRIGGER
+                    Explain why a trigger does or doesn't expand
+    --test-snippets [--run-shell]
+                    Expand every enabled snippet and report the ones that fail
+                    (unknown variables, bad capture groups, ...); exits with 1
+                    if any do. Clipboard, pick, window, secret and shell
+                    variables are skipped (--run-shell runs the commands)
+    --expand-stdin  Read triggers from stdin, one per line, and print their
+                    expansions to stdout instead of typing them
+    --status        Print the config path, snippet count and whether the
+                    prerequisites are met (exits with 1 if not)
+    --benchmark [N] [--max-p99-us US]
+                    Time the matcher with N synthetic snippets (default 10000)
+                    and print the per-keystroke latency; exits with 1 if the
+                    p99 is over US microseconds
+    --daemonize     Run the daemon in the background, without systemd. Its PID
+                    is kept in $XDG_RUNTIME_DIR/xpander.pid and its log in
+                    xpander.log next to it; refuses to start if it is running
+    --stop          Stop a daemon started with --daemonize
+    --debug-keys    Log every keystroke, the match buffer and matches, for
+                    debugging triggers. WARNING: this logs everything you type,
+                    passwords included
 
 PREREQUISITES:
     1. Install ydotool:
@@ -354,6 +901,9 @@ VARIABLES:
     {{{{env:VAR}}}}      - Environment variable
     {{{{shell:CMD}}}}    - Shell command output
     {{{{uuid}}}}         - Random UUID
+    {{{{file:PATH}}}}    - Contents of a file
+    {{{{pick:LIST}}}}    - Choose an entry of a list variable
+    {{{{input:PROMPT}}}} - Ask for a line of text
 
 For more information, see: https://github.com/example/xpander
 "#
@@ -0,0 +1,46 @@
+use std::process::{Command, ExitStatus, Stdio};
+
+/// Sound from the freedesktop theme, played with `paplay` if `canberra-gtk-play` is missing
+const FALLBACK_SOUND: &str = "/usr/share/sounds/freedesktop/stereo/message.oga";
+
+/// Show a desktop notification via `notify-send` without blocking the caller.
+/// Best effort: failures (e.g. no notification daemon) are only logged.
+pub fn send_notification(summary: &str, body: &str) {
+    // Don't pop up notifications while running the test suite
+    if cfg!(test) {
+        return;
+    }
+
+    let summary = summary.to_string();
+    let body = body.to_string();
+
+    std::thread::spawn(move || {
+        let result = run_quietly(Command::new("notify-send").args(["--app-name=xpander", &summary, &body]));
+
+        if let Err(e) = result {
+            log::debug!("Could not show notification: {}", e);
+        }
+    });
+}
+
+/// Play the desktop's "message" sound without blocking the caller.
+/// Best effort, like `send_notification`.
+pub fn play_sound() {
+    if cfg!(test) {
+        return;
+    }
+
+    std::thread::spawn(|| {
+        let played = run_quietly(Command::new("canberra-gtk-play").args(["--id=message", "--description=xpander"]))
+            .is_ok_and(|status| status.success())
+            || run_quietly(Command::new("paplay").arg(FALLBACK_SOUND)).is_ok_and(|status| status.success());
+
+        if !played {
+            log::debug!("Could not play sound (is canberra-gtk-play or paplay installed?)");
+        }
+    });
+}
+
+fn run_quietly(command: &mut Command) -> std::io::Result<ExitStatus> {
+    command.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null()).status()
+}
@@ -1,15 +1,168 @@
 use anyhow::{Context, Result};
-use chrono::Local;
+use chrono::{DateTime, Local, Locale, TimeZone};
 use rand::Rng;
 use regex::Regex;
-use std::process::Command;
-use std::sync::LazyLock;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, RwLock};
+use std::time::Duration;
+
+use crate::engine::window::ActiveWindow;
 
 /// Regex for matching variable patterns in text
 static VARIABLE_REGEX: LazyLock<Regex> = LazyLock::new(|| {
     Regex::new(r"\{\{([^}]+)\}\}").expect("Invalid variable regex")
 });
 
+/// Regex for `{{clipboard_set:...}}` directives
+static CLIPBOARD_SET_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\{\{\s*clipboard_set:([^}]*)\}\}").expect("Invalid clipboard_set regex")
+});
+
+/// Maximum size of a file inserted with `{{file:...}}`
+const MAX_FILE_VARIABLE_SIZE: u64 = 1024 * 1024;
+
+/// Words of the classic lorem ipsum passage, in order, for `{{lorem:...}}`
+const LOREM_WORDS: &[&str] = &[
+    "lorem", "ipsum", "dolor", "sit", "amet", "consectetur", "adipiscing", "elit", "sed", "do",
+    "eiusmod", "tempor", "incididunt", "ut", "labore", "et", "dolore", "magna", "aliqua", "ut",
+    "enim", "ad", "minim", "veniam", "quis", "nostrud", "exercitation", "ullamco", "laboris", "nisi",
+    "ut", "aliquip", "ex", "ea", "commodo", "consequat", "duis", "aute", "irure", "dolor", "in",
+    "reprehenderit", "in", "voluptate", "velit", "esse", "cillum", "dolore", "eu", "fugiat", "nulla",
+    "pariatur", "excepteur", "sint", "occaecat", "cupidatat", "non", "proident", "sunt", "in", "culpa",
+    "qui", "officia", "deserunt", "mollit", "anim", "id", "est", "laborum",
+];
+
+/// Everyday words for `{{random_word}}`
+const RANDOM_WORDS: &[&str] = &[
+    "apple", "river", "garden", "window", "silver", "rocket", "candle", "forest", "pencil", "harbor",
+    "meadow", "button", "orange", "planet", "marble", "castle", "bridge", "violet", "pepper", "anchor",
+    "cloud", "tiger", "lemon", "piano", "falcon", "copper", "island", "maple", "thunder", "velvet",
+    "summer", "winter", "basket", "mirror", "ocean", "pillow", "saddle", "tunnel", "walnut", "yellow",
+];
+
+/// Upper bound for `{{lorem:N}}`, to keep a typo from typing for minutes
+const MAX_LOREM_WORDS: usize = 1000;
+
+/// Words in each sentence of `{{lorem:sentences=N}}`
+const LOREM_SENTENCE_WORDS: std::ops::RangeInclusive<usize> = 6..=14;
+
+/// Locale from `settings.locale`, taking precedence over the environment
+static LOCALE_OVERRIDE: RwLock<Option<String>> = RwLock::new(None);
+
+/// Timeout for reading the clipboard, from `settings.clipboard_timeout_ms`
+static CLIPBOARD_TIMEOUT_MS: AtomicU64 = AtomicU64::new(1000);
+
+/// Secrets file from `settings.secrets_file` (`None` uses `secrets.yaml` in the config directory)
+static SECRETS_FILE: RwLock<Option<String>> = RwLock::new(None);
+
+/// Variables `with_test_mode` skips: they read the clipboard, ask the user, look at
+/// the desktop or read secrets, or (unless allowed) run commands
+const TEST_MODE_SKIPPED: &[&str] = &["clipboard:", "pick:", "input:", "window:", "secret:", "shell:"];
+
+/// Test mode state for this thread while `with_test_mode` runs
+struct TestMode {
+    run_shell: bool,
+    skipped: Vec<String>,
+}
+
+thread_local! {
+    static TEST_MODE: RefCell<Option<TestMode>> = const { RefCell::new(None) };
+
+    /// The window `with_active_window` gave the expansion running on this thread
+    static EXPANSION_WINDOW: RefCell<Option<Option<ActiveWindow>>> = const { RefCell::new(None) };
+}
+
+/// Run `f` with `window` as the focused window `{{window:...}}` inserts: the one the
+/// engine already looked up for the match, or `None` if it couldn't be detected.
+/// Outside of `f`, the variable detects the window itself.
+pub fn with_active_window<T>(window: Option<ActiveWindow>, f: impl FnOnce() -> T) -> T {
+    EXPANSION_WINDOW.with(|current| *current.borrow_mut() = Some(window));
+    let result = f();
+    EXPANSION_WINDOW.with(|current| current.borrow_mut().take());
+    result
+}
+
+/// The window given by `with_active_window`, or the one detected now
+fn expansion_window() -> Option<ActiveWindow> {
+    EXPANSION_WINDOW
+        .with(|current| current.borrow().clone())
+        .unwrap_or_else(crate::engine::window::active_window)
+}
+
+/// Run `f` in test mode: variables with side effects expand to themselves instead
+/// (`{{shell:...}}` only if `run_shell` is false) and unknown variables are errors.
+/// Returns what `f` returned and the variables that were skipped.
+pub fn with_test_mode<T>(run_shell: bool, f: impl FnOnce() -> T) -> (T, Vec<String>) {
+    TEST_MODE.with(|mode| *mode.borrow_mut() = Some(TestMode { run_shell, skipped: Vec::new() }));
+    let result = f();
+    let skipped = TEST_MODE.with(|mode| mode.borrow_mut().take()).map_or_else(Vec::new, |m| m.skipped);
+    (result, skipped)
+}
+
+/// In test mode, whether `var` is skipped (and record it); outside test mode, never
+fn skip_in_test_mode(var: &str) -> bool {
+    TEST_MODE.with(|mode| {
+        let mut mode = mode.borrow_mut();
+        let Some(mode) = mode.as_mut() else {
+            return false;
+        };
+        let skip = var == "clipboard"
+            || TEST_MODE_SKIPPED
+                .iter()
+                .any(|prefix| var.starts_with(prefix) && !(mode.run_shell && *prefix == "shell:"));
+        if skip {
+            mode.skipped.push(var.to_string());
+        }
+        skip
+    })
+}
+
+fn in_test_mode() -> bool {
+    TEST_MODE.with(|mode| mode.borrow().is_some())
+}
+
+/// Set how long `{{clipboard}}` waits for the clipboard
+pub fn set_clipboard_timeout(timeout_ms: u64) {
+    CLIPBOARD_TIMEOUT_MS.store(timeout_ms, Ordering::Relaxed);
+}
+
+/// Set the file `{{secret:name}}` reads from
+pub fn set_secrets_file(path: Option<String>) {
+    if let Ok(mut current) = SECRETS_FILE.write() {
+        *current = path;
+    }
+}
+
+/// Set the locale used for month and weekday names (`None` uses `$LANG`)
+pub fn set_locale_override(locale: Option<String>) {
+    if let Ok(mut current) = LOCALE_OVERRIDE.write() {
+        *current = locale;
+    }
+}
+
+/// Whether `text` has a variable starting with `prefix`
+fn has_variable(text: &str, prefix: &str) -> bool {
+    VARIABLE_REGEX
+        .captures_iter(text)
+        .any(|cap| cap[1].trim().starts_with(prefix))
+}
+
+/// Whether expanding `text` runs a command (`{{shell:...}}`)
+pub fn runs_shell(text: &str) -> bool {
+    has_variable(text, "shell:")
+}
+
+/// Whether `text` inserts the focused window's class or title (`{{window:...}}`)
+pub fn uses_window(text: &str) -> bool {
+    has_variable(text, "window:")
+}
+
 /// Expand all variables in the given text
 pub fn expand_variables(text: &str, custom_vars: &serde_yaml::Value) -> Result<String> {
     let mut result = text.to_string();
@@ -40,6 +193,10 @@ fn expand_single_variable(var: &str, custom_vars: &serde_yaml::Value) -> Result<
         return Ok(val);
     }
 
+    if skip_in_test_mode(var) {
+        return Ok(format!("{{{{{}}}}}", var));
+    }
+
     // Handle different variable types
     if var == "date" {
         Ok(expand_date(None))
@@ -54,18 +211,36 @@ fn expand_single_variable(var: &str, custom_vars: &serde_yaml::Value) -> Result<
     } else if let Some(format) = var.strip_prefix("datetime:") {
         Ok(expand_datetime(Some(format.trim())))
     } else if var == "clipboard" {
-        expand_clipboard()
+        expand_clipboard(ClipboardFlavor::Text)
+    } else if let Some(flavor) = var.strip_prefix("clipboard:") {
+        expand_clipboard(parse_clipboard_flavor(flavor.trim())?)
     } else if let Some(n) = var.strip_prefix("random:") {
         expand_random(n.trim())
+    } else if var == "random_word" {
+        Ok(expand_random_word())
+    } else if let Some(spec) = var.strip_prefix("lorem:") {
+        expand_lorem(spec.trim())
     } else if let Some(var_name) = var.strip_prefix("env:") {
         expand_env(var_name.trim())
     } else if let Some(cmd) = var.strip_prefix("shell:") {
         expand_shell(cmd.trim())
+    } else if let Some(path) = var.strip_prefix("file:") {
+        expand_file(path.trim())
+    } else if let Some(name) = var.strip_prefix("secret:") {
+        expand_secret(name.trim())
+    } else if let Some(name) = var.strip_prefix("pick:") {
+        expand_pick(name.trim(), custom_vars)
+    } else if let Some(prompt) = var.strip_prefix("input:") {
+        expand_input(prompt.trim(), run_input)
+    } else if let Some(field) = var.strip_prefix("window:") {
+        expand_window(field.trim(), expansion_window)
     } else if var == "uuid" {
         Ok(expand_uuid())
     } else if var == "cursor" || var == "|" {
         // Cursor position marker - keep it for later processing
         Ok("$|$".to_string())
+    } else if in_test_mode() {
+        anyhow::bail!("Unknown variable '{}'", var)
     } else {
         // Unknown variable - keep as-is
         log::warn!("Unknown variable: {}", var);
@@ -77,31 +252,223 @@ fn expand_single_variable(var: &str, custom_vars: &serde_yaml::Value) -> Result<
 fn expand_date(format: Option<&str>) -> String {
     let now = Local::now();
     let fmt = format.unwrap_or("%Y-%m-%d");
-    now.format(fmt).to_string()
+    format_localized(&now, fmt, date_locale())
 }
 
 /// Expand time variable with optional format
 fn expand_time(format: Option<&str>) -> String {
     let now = Local::now();
     let fmt = format.unwrap_or("%H:%M:%S");
-    now.format(fmt).to_string()
+    format_localized(&now, fmt, date_locale())
 }
 
 /// Expand datetime variable with optional format
 fn expand_datetime(format: Option<&str>) -> String {
     let now = Local::now();
     let fmt = format.unwrap_or("%Y-%m-%d %H:%M:%S");
-    now.format(fmt).to_string()
+    format_localized(&now, fmt, date_locale())
+}
+
+/// Format a date with month/weekday names (`%B`, `%A`, ...) in the given locale
+fn format_localized<Tz: TimeZone>(date: &DateTime<Tz>, fmt: &str, locale: Locale) -> String
+where
+    Tz::Offset: std::fmt::Display,
+{
+    date.format_localized(fmt, locale).to_string()
 }
 
-/// Expand clipboard variable
-fn expand_clipboard() -> Result<String> {
+/// Locale for dates: `settings.locale`, then `LC_ALL`, `LC_TIME` or `LANG`.
+/// Falls back to English if none is set or the locale is unknown.
+fn date_locale() -> Locale {
+    let configured = LOCALE_OVERRIDE.read().ok().and_then(|l| l.clone());
+    let name = configured.or_else(|| {
+        ["LC_ALL", "LC_TIME", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty())
+    });
+
+    name.and_then(|name| parse_locale(&name)).unwrap_or(Locale::POSIX)
+}
+
+/// Parse a locale name such as `de_DE.UTF-8` or `fr_FR@euro`
+fn parse_locale(name: &str) -> Option<Locale> {
+    let base = name.split(['.', '@']).next()?.trim();
+    if base.is_empty() || base == "C" || base == "POSIX" {
+        return None;
+    }
+
+    let locale = Locale::try_from(base).ok();
+    if locale.is_none() {
+        log::warn!("Unknown locale '{}', using English for dates", name);
+    }
+    locale
+}
+
+/// Which form of the clipboard contents `{{clipboard:...}}` inserts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ClipboardFlavor {
+    /// Plain text, the default
+    Text,
+    /// The `text/html` markup, e.g. when copying from a browser
+    Html,
+}
+
+fn parse_clipboard_flavor(name: &str) -> Result<ClipboardFlavor> {
+    match name.to_lowercase().as_str() {
+        "text" | "plain" => Ok(ClipboardFlavor::Text),
+        "html" => Ok(ClipboardFlavor::Html),
+        _ => anyhow::bail!("Unknown clipboard flavor '{}' (expected text or html)", name),
+    }
+}
+
+/// Expand clipboard variable. Reading can block on some Wayland setups (e.g. when
+/// the app that owns the clipboard hangs), so it gives up after the clipboard timeout.
+fn expand_clipboard(flavor: ClipboardFlavor) -> Result<String> {
+    let timeout = Duration::from_millis(CLIPBOARD_TIMEOUT_MS.load(Ordering::Relaxed));
+    with_timeout(timeout, move || read_clipboard(flavor))
+        .with_context(|| format!("Timed out reading the clipboard after {}ms", timeout.as_millis()))?
+}
+
+fn read_clipboard(flavor: ClipboardFlavor) -> Result<String> {
     let mut clipboard = arboard::Clipboard::new()
         .context("Failed to access clipboard")?;
 
-    clipboard
-        .get_text()
-        .context("Failed to get clipboard text")
+    match flavor {
+        ClipboardFlavor::Text => clipboard
+            .get_text()
+            .context("Failed to get clipboard text"),
+        // Plain text copies have no HTML flavor, which isn't worth failing the expansion
+        ClipboardFlavor::Html => match clipboard.get().html() {
+            Err(arboard::Error::ContentNotAvailable) => {
+                log::info!("Clipboard has no HTML content, inserting nothing");
+                Ok(String::new())
+            }
+            result => result.context("Failed to get clipboard HTML"),
+        },
+    }
+}
+
+/// Run `f` on another thread and wait at most `timeout` for the result. On timeout
+/// it is left to finish in the background. In the daemon this is a thread of
+/// tokio's blocking pool, and the wait happens on the expansion's own blocking
+/// thread, never on the engine task; elsewhere `f` gets a thread of its own.
+fn with_timeout<T: Send + 'static>(timeout: Duration, f: impl FnOnce() -> T + Send + 'static) -> Option<T> {
+    let (tx, rx) = std::sync::mpsc::channel();
+    let run = move || {
+        let _ = tx.send(f());
+    };
+    match tokio::runtime::Handle::try_current() {
+        Ok(runtime) => drop(runtime.spawn_blocking(run)),
+        Err(_) => drop(std::thread::spawn(run)),
+    }
+    rx.recv_timeout(timeout).ok()
+}
+
+/// Replace `\u{1F600}` and `\u00E9` escapes with the characters they name, and `\\`
+/// with a single backslash. Invalid escapes are left as written.
+pub fn unescape_unicode(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(pos) = rest.find('\\') {
+        result.push_str(&rest[..pos]);
+        let after = &rest[pos + 1..];
+
+        if let Some(tail) = after.strip_prefix('\\') {
+            result.push('\\');
+            rest = tail;
+        } else if let Some((ch, len)) = parse_unicode_escape(after) {
+            result.push(ch);
+            rest = &after[len..];
+        } else {
+            if after.starts_with('u') {
+                let escape: String = after.chars().take(10).collect();
+                log::warn!("Invalid unicode escape in replacement: \\{}", escape);
+            }
+            result.push('\\');
+            rest = after;
+        }
+    }
+
+    result.push_str(rest);
+    result
+}
+
+/// Parse the `u...` part of a unicode escape, returning the character and the bytes consumed
+fn parse_unicode_escape(text: &str) -> Option<(char, usize)> {
+    let text = text.strip_prefix('u')?;
+    let (hex, len) = match text.strip_prefix('{') {
+        Some(braced) => {
+            let end = braced.find('}')?;
+            (&braced[..end], end + 3)
+        }
+        None => (text.get(..4)?, 5),
+    };
+
+    if hex.is_empty() || hex.len() > 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let ch = char::from_u32(u32::from_str_radix(hex, 16).ok()?)?;
+    Some((ch, len))
+}
+
+/// Remove `{{clipboard_set:...}}` directives from `text`, returning the text left to
+/// type and the value to load into the clipboard. If there are several, the last one wins.
+pub fn extract_clipboard_set(text: &str) -> (String, Option<String>) {
+    let clipboard = CLIPBOARD_SET_REGEX
+        .captures_iter(text)
+        .last()
+        .map(|cap| cap[1].to_string());
+    let remaining = CLIPBOARD_SET_REGEX.replace_all(text, "").into_owned();
+    (remaining, clipboard)
+}
+
+/// Put `text` on the system clipboard. On Linux the clipboard is served by the
+/// process that set it, so a background thread keeps it alive until something else is copied.
+pub fn set_clipboard(text: &str) {
+    use arboard::SetExtLinux;
+
+    let text = text.to_string();
+    std::thread::spawn(move || {
+        let result = arboard::Clipboard::new().and_then(|mut clipboard| clipboard.set().wait().text(text));
+        if let Err(e) = result {
+            log::error!("Failed to set clipboard: {}", e);
+        }
+    });
+}
+
+/// Put text on the primary selection (what middle-click and Shift+Insert paste in
+/// most terminals). Returns false without setting anything if the session has no
+/// primary selection.
+pub fn set_primary_selection(text: &str) -> bool {
+    use arboard::{GetExtLinux, LinuxClipboardKind, SetExtLinux};
+
+    // Reading reports `ClipboardNotSupported` when there is no primary selection
+    match arboard::Clipboard::new() {
+        Ok(mut clipboard) => {
+            if let Err(arboard::Error::ClipboardNotSupported) =
+                clipboard.get().clipboard(LinuxClipboardKind::Primary).text()
+            {
+                return false;
+            }
+        }
+        Err(e) => {
+            log::warn!("Failed to open clipboard: {}", e);
+            return false;
+        }
+    }
+
+    let text = text.to_string();
+    std::thread::spawn(move || {
+        let result = arboard::Clipboard::new().and_then(|mut clipboard| {
+            clipboard.set().clipboard(LinuxClipboardKind::Primary).wait().text(text)
+        });
+        if let Err(e) = result {
+            log::error!("Failed to set primary selection: {}", e);
+        }
+    });
+    true
 }
 
 /// Expand random number variable
@@ -121,14 +488,108 @@ fn expand_random(n: &str) -> Result<String> {
     Ok(format!("{:0width$}", num, width = digits))
 }
 
+/// Pick a word from the bundled word list
+fn expand_random_word() -> String {
+    RANDOM_WORDS[rand::thread_rng().gen_range(0..RANDOM_WORDS.len())].to_string()
+}
+
+/// Expand lorem ipsum filler: `N` words of the classic passage, or
+/// `sentences=N` sentences of random lorem words
+fn expand_lorem(spec: &str) -> Result<String> {
+    let (count, sentences) = match spec.strip_prefix("sentences=") {
+        Some(n) => (n.trim(), true),
+        None => (spec, false),
+    };
+    let count: usize = count.parse().context("Invalid count for lorem")?;
+
+    if sentences {
+        if count.saturating_mul(*LOREM_SENTENCE_WORDS.end()) > MAX_LOREM_WORDS {
+            anyhow::bail!("Lorem is limited to {} sentences", MAX_LOREM_WORDS / LOREM_SENTENCE_WORDS.end());
+        }
+        let mut rng = rand::thread_rng();
+        let sentences: Vec<String> = (0..count)
+            .map(|_| {
+                let len = rng.gen_range(LOREM_SENTENCE_WORDS);
+                let words: Vec<&str> = (0..len)
+                    .map(|_| LOREM_WORDS[rng.gen_range(0..LOREM_WORDS.len())])
+                    .collect();
+                format!("{}.", capitalize_first(&words.join(" ")))
+            })
+            .collect();
+        return Ok(sentences.join(" "));
+    }
+
+    if count > MAX_LOREM_WORDS {
+        anyhow::bail!("Lorem is limited to {} words", MAX_LOREM_WORDS);
+    }
+    let words: Vec<&str> = LOREM_WORDS.iter().cycle().take(count).copied().collect();
+    Ok(capitalize_first(&words.join(" ")))
+}
+
+fn capitalize_first(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
 /// Expand environment variable
 fn expand_env(var_name: &str) -> Result<String> {
     std::env::var(var_name)
         .with_context(|| format!("Environment variable '{}' not found", var_name))
 }
 
+/// How `{{shell:...}}` output is cleaned up, chosen with a `|mode` suffix
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ShellOutput {
+    /// Drop trailing newlines (the default)
+    TrimNewlines,
+    /// `|trim`: drop all trailing whitespace
+    Trim,
+    /// `|raw`: keep the output exactly as printed
+    Raw,
+    /// `|oneline`: join the non-empty lines with spaces
+    OneLine,
+}
+
+impl ShellOutput {
+    fn apply(self, output: &str) -> String {
+        match self {
+            ShellOutput::TrimNewlines => output.trim_end_matches('\n').to_string(),
+            ShellOutput::Trim => output.trim_end().to_string(),
+            ShellOutput::Raw => output.to_string(),
+            ShellOutput::OneLine => output
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .collect::<Vec<_>>()
+                .join(" "),
+        }
+    }
+}
+
+/// Split a `|mode` suffix off a shell variable. Anything else after the last `|`
+/// is part of the command (a pipe), as is a mode after `||`.
+fn parse_shell_spec(spec: &str) -> (&str, ShellOutput) {
+    let mode = spec
+        .rsplit_once('|')
+        .filter(|(cmd, _)| !cmd.ends_with('|'))
+        .and_then(|(cmd, mode)| {
+            let mode = match mode.trim() {
+                "trim" => ShellOutput::Trim,
+                "raw" => ShellOutput::Raw,
+                "oneline" => ShellOutput::OneLine,
+                _ => return None,
+            };
+            Some((cmd.trim_end(), mode))
+        });
+    mode.unwrap_or((spec, ShellOutput::TrimNewlines))
+}
+
 /// Expand shell command variable
-fn expand_shell(cmd: &str) -> Result<String> {
+fn expand_shell(spec: &str) -> Result<String> {
+    let (cmd, mode) = parse_shell_spec(spec);
     let output = Command::new("sh")
         .arg("-c")
         .arg(cmd)
@@ -140,11 +601,192 @@ fn expand_shell(cmd: &str) -> Result<String> {
         anyhow::bail!("Shell command failed: {}", stderr);
     }
 
-    let stdout = String::from_utf8_lossy(&output.stdout)
-        .trim_end_matches('\n')
-        .to_string();
+    Ok(mode.apply(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Expand file variable, resolving relative paths against the config directory
+fn expand_file(path: &str) -> Result<String> {
+    let config_dir = dirs::config_dir()
+        .context("Could not determine config directory")?
+        .join("xpander");
+    read_file_variable(&resolve_file_path(path, &config_dir))
+}
+
+/// Resolve `~` and relative paths for the file variable
+fn resolve_file_path(path: &str, base_dir: &Path) -> PathBuf {
+    if let Some(rest) = path.strip_prefix("~/") {
+        if let Some(home) = dirs::home_dir() {
+            return home.join(rest);
+        }
+    }
+
+    let path = Path::new(path);
+    if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        base_dir.join(path)
+    }
+}
+
+/// Read a file for the file variable, trimming the trailing newline like `{{shell:...}}`
+fn read_file_variable(path: &Path) -> Result<String> {
+    let metadata = std::fs::metadata(path)
+        .with_context(|| format!("Failed to read file: {}", path.display()))?;
+
+    if metadata.len() > MAX_FILE_VARIABLE_SIZE {
+        anyhow::bail!(
+            "File too large to insert ({} bytes, max {}): {}",
+            metadata.len(),
+            MAX_FILE_VARIABLE_SIZE,
+            path.display()
+        );
+    }
+
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read file: {}", path.display()))?;
+
+    Ok(content.trim_end_matches('\n').to_string())
+}
+
+/// Expand secret variable from the secrets file
+fn expand_secret(name: &str) -> Result<String> {
+    let config_dir = dirs::config_dir()
+        .context("Could not determine config directory")?
+        .join("xpander");
+    let configured = SECRETS_FILE.read().ok().and_then(|path| path.clone());
+    let path = resolve_file_path(configured.as_deref().unwrap_or("secrets.yaml"), &config_dir);
+    read_secret(&path, name)
+}
+
+/// Look up `name` in a secrets file (a YAML mapping of names to values). The file
+/// must not be accessible to other users.
+fn read_secret(path: &Path, name: &str) -> Result<String> {
+    let metadata = std::fs::metadata(path)
+        .with_context(|| format!("Failed to read secrets file: {}", path.display()))?;
+
+    if metadata.permissions().mode() & 0o077 != 0 {
+        anyhow::bail!(
+            "Secrets file is accessible to other users, run: chmod 600 {}",
+            path.display()
+        );
+    }
+
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read secrets file: {}", path.display()))?;
+    let secrets: HashMap<String, String> = serde_yaml::from_str(&content)
+        .with_context(|| format!("Invalid secrets file: {}", path.display()))?;
+
+    secrets
+        .get(name)
+        .cloned()
+        .with_context(|| format!("Secret '{}' not found in {}", name, path.display()))
+}
+
+/// Expand pick variable by letting the user choose an entry of a list variable
+fn expand_pick(name: &str, custom_vars: &serde_yaml::Value) -> Result<String> {
+    let mut options = pick_options(name, custom_vars)?;
+    if options.len() == 1 {
+        return Ok(options.remove(0));
+    }
+
+    match run_picker(&options)? {
+        Some(choice) => Ok(choice),
+        None => anyhow::bail!("Selection from '{}' cancelled", name),
+    }
+}
+
+/// The entries of the list variable `name` (e.g. `responses: [...]` in `variables`)
+fn pick_options(name: &str, custom_vars: &serde_yaml::Value) -> Result<Vec<String>> {
+    let value = lookup_variable(name, custom_vars)
+        .with_context(|| format!("Variable '{}' not found for pick", name))?;
+
+    let serde_yaml::Value::Sequence(items) = value else {
+        anyhow::bail!("Variable '{}' must be a list to use with pick", name);
+    };
+
+    let options: Vec<String> = items
+        .iter()
+        .filter_map(|item| {
+            let text = scalar_to_string(item);
+            if text.is_none() {
+                log::warn!("Skipping non-text entry in pick list '{}'", name);
+            }
+            text
+        })
+        .collect();
+
+    if options.is_empty() {
+        anyhow::bail!("Pick list '{}' is empty", name);
+    }
+    Ok(options)
+}
+
+/// Show the picker window (`xpander --pick`) and return the chosen option,
+/// or `None` if the user cancelled. Options go in on stdin as a JSON array,
+/// the choice comes back on stdout as a JSON string.
+pub fn run_picker(options: &[String]) -> Result<Option<String>> {
+    run_prompt_window("--pick", options)
+}
+
+/// Expand `{{input:prompt}}` with the text entered into the window shown by `ask`.
+/// Cancelling fails the expansion, so nothing is typed.
+fn expand_input(prompt: &str, ask: fn(&str) -> Result<Option<String>>) -> Result<String> {
+    if prompt.is_empty() {
+        anyhow::bail!("{{{{input:...}}}} needs a prompt, e.g. {{{{input:Ticket number}}}}");
+    }
+    match ask(prompt)? {
+        Some(text) => Ok(text),
+        None => anyhow::bail!("Input '{}' cancelled", prompt),
+    }
+}
+
+/// Show a one-line input window (`xpander --input`) and return the text entered,
+/// or `None` if the user cancelled. The prompt goes in on stdin and the text comes
+/// back on stdout, both as JSON strings.
+fn run_input(prompt: &str) -> Result<Option<String>> {
+    run_prompt_window("--input", &prompt)
+}
+
+/// Run `xpander <flag>` with `input` as JSON on stdin, returning the JSON string it
+/// prints, or `None` if it exits unsuccessfully (the user cancelled)
+fn run_prompt_window(flag: &str, input: &(impl serde::Serialize + ?Sized)) -> Result<Option<String>> {
+    let exe = std::env::current_exe().context("Failed to locate the xpander executable")?;
+    let mut child = Command::new(exe)
+        .arg(flag)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("Failed to open the prompt window")?;
+
+    let input = serde_json::to_vec(input).context("Failed to encode the prompt")?;
+    child
+        .stdin
+        .take()
+        .context("Failed to open prompt window input")?
+        .write_all(&input)
+        .context("Failed to send the prompt window its input")?;
+
+    let output = child.wait_with_output().context("Prompt window failed")?;
+    if !output.status.success() || output.stdout.is_empty() {
+        return Ok(None);
+    }
+
+    let answer = serde_json::from_slice(&output.stdout).context("Invalid prompt window output")?;
+    Ok(Some(answer))
+}
 
-    Ok(stdout)
+/// Expand `{{window:class}}` or `{{window:title}}` with the focused window found by
+/// `detect`. Inserts nothing if it can't be detected (e.g. on an unsupported compositor).
+fn expand_window(field: &str, detect: impl FnOnce() -> Option<ActiveWindow>) -> Result<String> {
+    if !matches!(field, "class" | "title") {
+        anyhow::bail!("Unknown window field '{}' (expected class or title)", field);
+    }
+    let Some(window) = detect() else {
+        log::warn!("Could not detect the active window for {{{{window:{}}}}}", field);
+        return Ok(String::new());
+    };
+    Ok(if field == "class" { window.class } else { window.title })
 }
 
 /// Expand UUID variable
@@ -152,44 +794,61 @@ fn expand_uuid() -> String {
     uuid::Uuid::new_v4().to_string()
 }
 
-/// Find cursor position marker in text and return (text_without_marker, cursor_offset)
-pub fn find_cursor_position(text: &str) -> (String, Option<usize>) {
+/// Find cursor position marker in text and return (text_without_marker, cursor_pos, selection_len).
+/// `cursor_pos` counts characters (not bytes) from the start of the cleaned text.
+///
+/// A pair of markers (`Dear $|$name$|$,`) selects the text between them: the cursor
+/// is placed after the placeholder and `selection_len` is its length in characters.
+/// Markers after the first pair are removed and ignored.
+pub fn find_cursor_position(text: &str) -> (String, Option<usize>, usize) {
     const CURSOR_MARKER: &str = "$|$";
 
-    if let Some(pos) = text.find(CURSOR_MARKER) {
-        let cleaned = format!("{}{}", &text[..pos], &text[pos + CURSOR_MARKER.len()..]);
-        Some((cleaned, Some(pos)))
-    } else {
-        None
+    let parts: Vec<&str> = text.split(CURSOR_MARKER).collect();
+    match parts.as_slice() {
+        [_] => (text.to_string(), None, 0),
+        [before, after] => (format!("{}{}", before, after), Some(before.chars().count()), 0),
+        [before, placeholder, rest @ ..] => {
+            if rest.len() > 1 {
+                log::warn!("Only the first two $|$ markers are used, ignoring {} more", rest.len() - 1);
+            }
+            let selection_len = placeholder.chars().count();
+            (parts.concat(), Some(before.chars().count() + selection_len), selection_len)
+        }
+        [] => unreachable!("split always yields at least one part"),
     }
-    .unwrap_or_else(|| (text.to_string(), None))
 }
 
 /// Expand custom variable using dot notation (e.g. "user.email")
 fn expand_custom_variable(var_path: &str, custom_vars: &serde_yaml::Value) -> Option<String> {
-    let parts: Vec<&str> = var_path.split('.').collect();
+    match lookup_variable(var_path, custom_vars)? {
+        serde_yaml::Value::Null => Some("".to_string()),
+        // Complex types (arrays/objects) not supported as direct replacement
+        value => scalar_to_string(value),
+    }
+}
+
+/// Find a custom variable by its dotted path, e.g. `user.email`
+fn lookup_variable<'a>(var_path: &str, custom_vars: &'a serde_yaml::Value) -> Option<&'a serde_yaml::Value> {
     let mut current = custom_vars;
 
-    for part in parts {
+    for part in var_path.split('.') {
         match current {
             serde_yaml::Value::Mapping(map) => {
-                // Try exact match first
-                if let Some(val) = map.get(&serde_yaml::Value::String(part.to_string())) {
-                    current = val;
-                } else {
-                    return None;
-                }
+                current = map.get(&serde_yaml::Value::String(part.to_string()))?;
             }
             _ => return None,
         }
     }
 
-    match current {
+    Some(current)
+}
+
+fn scalar_to_string(value: &serde_yaml::Value) -> Option<String> {
+    match value {
         serde_yaml::Value::String(s) => Some(s.clone()),
         serde_yaml::Value::Number(n) => Some(n.to_string()),
         serde_yaml::Value::Bool(b) => Some(b.to_string()),
-        serde_yaml::Value::Null => Some("".to_string()),
-        _ => None, // Complex types (arrays/objects) not supported as direct replacement
+        _ => None,
     }
 }
 
@@ -229,6 +888,30 @@ pub fn propagate_case(trigger: &str, replacement: &str) -> String {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_with_timeout() {
+        assert_eq!(with_timeout(Duration::from_secs(5), || 42), Some(42));
+
+        let slow = || {
+            std::thread::sleep(Duration::from_millis(500));
+            42
+        };
+        let start = std::time::Instant::now();
+        assert_eq!(with_timeout(Duration::from_millis(20), slow), None);
+        assert!(start.elapsed() < Duration::from_millis(400));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_with_timeout_in_runtime() {
+        // As the engine runs an expansion, on a blocking thread
+        let start = std::time::Instant::now();
+        let expansion = tokio::task::spawn_blocking(|| {
+            with_timeout(Duration::from_millis(20), || std::thread::sleep(Duration::from_millis(500)))
+        });
+        assert_eq!(expansion.await.unwrap(), None);
+        assert!(start.elapsed() < Duration::from_millis(400));
+    }
+
     #[test]
     fn test_expand_date() {
         let result = expand_date(None);
@@ -256,6 +939,61 @@ mod tests {
         assert!(result.chars().all(|c| c.is_ascii_digit()));
     }
 
+    #[test]
+    fn test_expand_lorem() {
+        let words = expand_lorem("5").unwrap();
+        assert_eq!(words, "Lorem ipsum dolor sit amet");
+        // Longer than the passage: it wraps around
+        assert_eq!(expand_lorem("200").unwrap().split_whitespace().count(), 200);
+        assert_eq!(expand_lorem("0").unwrap(), "");
+        assert!(expand_lorem("many").is_err());
+        assert!(expand_lorem("100000").is_err());
+
+        let sentences = expand_lorem("sentences=3").unwrap();
+        assert_eq!(sentences.matches('.').count(), 3);
+        assert!(sentences.starts_with(char::is_uppercase));
+        assert_eq!(expand_lorem("sentences=0").unwrap(), "");
+    }
+
+    #[test]
+    fn test_expand_random_word() {
+        let word = expand_random_word();
+        assert!(RANDOM_WORDS.contains(&word.as_str()));
+    }
+
+    #[test]
+    fn test_parse_clipboard_flavor() {
+        assert_eq!(parse_clipboard_flavor("html").unwrap(), ClipboardFlavor::Html);
+        assert_eq!(parse_clipboard_flavor("HTML").unwrap(), ClipboardFlavor::Html);
+        assert_eq!(parse_clipboard_flavor("text").unwrap(), ClipboardFlavor::Text);
+        assert!(parse_clipboard_flavor("image").is_err());
+    }
+
+    #[test]
+    fn test_expand_window() {
+        let editor = || {
+            Some(ActiveWindow {
+                class: "org.gnome.TextEditor".to_string(),
+                title: "notes.txt".to_string(),
+                ..Default::default()
+            })
+        };
+        assert_eq!(expand_window("class", editor).unwrap(), "org.gnome.TextEditor");
+        assert_eq!(expand_window("title", editor).unwrap(), "notes.txt");
+        assert!(expand_window("pid", editor).is_err());
+
+        // Detection failing inserts nothing instead of failing the expansion
+        assert_eq!(expand_window("class", || None).unwrap(), "");
+
+        // The engine's window is used as given, even when it found none
+        let vars = serde_yaml::Value::Null;
+        let expanded = with_active_window(editor(), || expand_variables("{{window:title}}", &vars));
+        assert_eq!(expanded.unwrap(), "notes.txt");
+        assert_eq!(with_active_window(None, || expand_variables("[{{window:class}}]", &vars)).unwrap(), "[]");
+        assert!(uses_window("from {{ window:class }}"));
+        assert!(!uses_window("{{window}}"));
+    }
+
     #[test]
     fn test_expand_env() {
         std::env::set_var("TEST_VAR_XPANDER", "test_value");
@@ -269,6 +1007,72 @@ mod tests {
         assert_eq!(result, "hello");
     }
 
+    #[test]
+    fn test_expand_shell_output_modes() {
+        let cmd = r"printf 'one \n two  \n\n\n'";
+        assert_eq!(expand_shell(cmd).unwrap(), "one \n two  ");
+        assert_eq!(expand_shell(&format!("{} | trim", cmd)).unwrap(), "one \n two");
+        assert_eq!(expand_shell(&format!("{}|raw", cmd)).unwrap(), "one \n two  \n\n\n");
+        assert_eq!(expand_shell(&format!("{} |oneline", cmd)).unwrap(), "one two");
+
+        // A pipe into a real command is left alone
+        assert_eq!(parse_shell_spec("ls | wc -l"), ("ls | wc -l", ShellOutput::TrimNewlines));
+        assert_eq!(expand_shell("echo a b | tr ' ' '\\n' | oneline").unwrap(), "a b");
+
+        // `||` runs its right side as a command
+        assert_eq!(parse_shell_spec("false || trim"), ("false || trim", ShellOutput::TrimNewlines));
+        assert_eq!(parse_shell_spec("false || true | raw"), ("false || true", ShellOutput::Raw));
+    }
+
+    #[test]
+    fn test_expand_file() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("header.txt"), "## Changelog\n").unwrap();
+
+        // Relative paths resolve against the base (config) directory
+        let path = resolve_file_path("header.txt", dir.path());
+        assert_eq!(read_file_variable(&path).unwrap(), "## Changelog");
+
+        // Absolute paths are used as-is
+        let absolute = dir.path().join("header.txt");
+        let path = resolve_file_path(absolute.to_str().unwrap(), Path::new("/nonexistent"));
+        assert_eq!(read_file_variable(&path).unwrap(), "## Changelog");
+    }
+
+    #[test]
+    fn test_expand_file_missing_or_too_large() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(read_file_variable(&dir.path().join("missing.txt")).is_err());
+
+        let big = dir.path().join("big.txt");
+        std::fs::write(&big, vec![b'a'; MAX_FILE_VARIABLE_SIZE as usize + 1]).unwrap();
+        assert!(read_file_variable(&big).is_err());
+    }
+
+    #[test]
+    fn test_read_secret() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("secrets.yaml");
+        std::fs::write(&path, "github_token: ghp_abc123\n").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+
+        assert_eq!(read_secret(&path, "github_token").unwrap(), "ghp_abc123");
+        let err = read_secret(&path, "missing").unwrap_err();
+        assert!(err.to_string().contains("Secret 'missing' not found"));
+
+        // A secrets file others can read is refused
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        assert!(read_secret(&path, "github_token").unwrap_err().to_string().contains("chmod 600"));
+
+        assert!(read_secret(&dir.path().join("none.yaml"), "github_token").is_err());
+    }
+
+    #[test]
+    fn test_resolve_file_path_home() {
+        let home = dirs::home_dir().unwrap();
+        assert_eq!(resolve_file_path("~/notes.txt", Path::new("/base")), home.join("notes.txt"));
+    }
+
     #[test]
     fn test_expand_uuid() {
         let result = expand_uuid();
@@ -304,6 +1108,40 @@ mod tests {
         assert!(result.contains("age: 30"));
     }
 
+    #[test]
+    fn test_pick_options() {
+        let vars: serde_yaml::Value = serde_yaml::from_str(
+            r#"
+responses:
+  - "Thanks, I'll look into it."
+  - 42
+  - [nested]
+team:
+  greetings: ["Hi", "Hello"]
+name: Rafa
+empty: []
+"#,
+        )
+        .unwrap();
+
+        // Numbers become text, non-scalar entries are skipped
+        assert_eq!(
+            pick_options("responses", &vars).unwrap(),
+            vec!["Thanks, I'll look into it.".to_string(), "42".to_string()]
+        );
+        assert_eq!(pick_options("team.greetings", &vars).unwrap().len(), 2);
+
+        assert!(pick_options("missing", &vars).is_err());
+        assert!(pick_options("name", &vars).is_err());
+        assert!(pick_options("empty", &vars).is_err());
+    }
+
+    #[test]
+    fn test_pick_single_option() {
+        let vars: serde_yaml::Value = serde_yaml::from_str("only: [\"just this\"]").unwrap();
+        assert_eq!(expand_variables("{{pick:only}}", &vars).unwrap(), "just this");
+    }
+
     #[test]
     fn test_expand_variables_default() {
         // Test with empty custom variables (should behave like before)
@@ -315,15 +1153,121 @@ mod tests {
         assert!(!result.contains("{{"));
     }
 
+    #[test]
+    fn test_expand_input() {
+        assert_eq!(expand_input("Ticket number", |_| Ok(Some("1234".to_string()))).unwrap(), "1234");
+        // An empty answer is still an answer
+        assert_eq!(expand_input("Note", |_| Ok(Some(String::new()))).unwrap(), "");
+        assert!(expand_input("Ticket number", |_| Ok(None)).is_err());
+        assert!(expand_input("", |_| panic!("asked without a prompt")).is_err());
+    }
+
+    #[test]
+    fn test_input_prompts_in_order() {
+        let vars = serde_yaml::Value::Null;
+        let text = "Ticket {{input:Ticket number}}: {{ input: Summary }} ({{input:Ticket number}})";
+
+        // Test mode records each prompt instead of showing it, in the order asked
+        let (result, skipped) = with_test_mode(false, || expand_variables(text, &vars));
+        assert!(result.is_ok());
+        // and once per marker, even with the same prompt
+        assert_eq!(skipped, vec!["input:Ticket number", "input: Summary", "input:Ticket number"]);
+    }
+
+    #[test]
+    fn test_test_mode() {
+        let vars = serde_yaml::Value::Null;
+        let text = "{{shell:echo hi}} {{clipboard}} {{clipboard:html}} {{random:2}}";
+
+        let (result, skipped) = with_test_mode(false, || expand_variables(text, &vars));
+        assert!(result.unwrap().starts_with("{{shell:echo hi}} {{clipboard}} {{clipboard:html}} "));
+        assert_eq!(skipped, vec!["shell:echo hi", "clipboard", "clipboard:html"]);
+
+        let (result, skipped) = with_test_mode(true, || expand_variables("{{shell:echo hi}}", &vars));
+        assert_eq!(result.unwrap(), "hi");
+        assert!(skipped.is_empty());
+
+        let (result, _) = with_test_mode(false, || expand_variables("{{nope}}", &vars));
+        assert!(result.is_err());
+        // Outside test mode unknown variables are kept
+        assert_eq!(expand_variables("{{nope}}", &vars).unwrap(), "{{nope}}");
+    }
+
+    #[test]
+    fn test_localized_dates() {
+        let date = Local.with_ymd_and_hms(2024, 3, 15, 12, 0, 0).unwrap();
+
+        let german = parse_locale("de_DE.UTF-8").unwrap();
+        assert_eq!(format_localized(&date, "%B", german), "März");
+        assert_eq!(format_localized(&date, "%A", german), "Freitag");
+
+        let french = parse_locale("fr_FR@euro").unwrap();
+        assert_eq!(format_localized(&date, "%d %B", french), "15 mars");
+
+        // Unknown locales fall back to English
+        assert!(parse_locale("xx_YY").is_none());
+        assert!(parse_locale("C").is_none());
+        assert_eq!(format_localized(&date, "%B", Locale::POSIX), "March");
+    }
+
+    #[test]
+    fn test_unescape_unicode() {
+        // BMP characters, braced and 4-digit forms
+        assert_eq!(unescape_unicode(r"caf\u00e9 \u{2192}"), "café →");
+        // Astral plane
+        assert_eq!(unescape_unicode(r"\u{1F600}!"), "😀!");
+        // Escaped backslash stays literal
+        assert_eq!(unescape_unicode(r"\\u{41} C:\\path"), r"\u{41} C:\path");
+        // Invalid escapes are left as written
+        assert_eq!(unescape_unicode(r"\u{110000} \uZZZZ \u{} \n"), r"\u{110000} \uZZZZ \u{} \n");
+    }
+
+    #[test]
+    fn test_extract_clipboard_set() {
+        let (text, clipboard) = extract_clipboard_set("Copied! {{clipboard_set:secret}}");
+        assert_eq!(text, "Copied! ");
+        assert_eq!(clipboard.as_deref(), Some("secret"));
+
+        let (text, clipboard) = extract_clipboard_set("{{clipboard_set:a}}{{clipboard_set:b}}");
+        assert_eq!(text, "");
+        assert_eq!(clipboard.as_deref(), Some("b"));
+
+        let (text, clipboard) = extract_clipboard_set("plain {{date}}");
+        assert_eq!(text, "plain {{date}}");
+        assert!(clipboard.is_none());
+    }
+
     #[test]
     fn test_find_cursor_position() {
-        let (text, pos) = find_cursor_position("Hello $|$ World");
+        let (text, pos, selection) = find_cursor_position("Hello $|$ World");
         assert_eq!(text, "Hello  World");
         assert_eq!(pos, Some(6));
+        assert_eq!(selection, 0);
 
-        let (text, pos) = find_cursor_position("No cursor here");
+        let (text, pos, selection) = find_cursor_position("No cursor here");
         assert_eq!(text, "No cursor here");
         assert_eq!(pos, None);
+        assert_eq!(selection, 0);
+    }
+
+    #[test]
+    fn test_find_cursor_position_selection() {
+        let (text, pos, selection) = find_cursor_position("Dear $|$name$|$, hi");
+        assert_eq!(text, "Dear name, hi");
+        // Cursor sits after the placeholder, which is then selected leftwards
+        assert_eq!(pos, Some(9));
+        assert_eq!(selection, 4);
+
+        let (text, pos, selection) = find_cursor_position("$|$ñame$|$");
+        assert_eq!(text, "ñame");
+        assert_eq!(pos, Some(4));
+        assert_eq!(selection, 4);
+
+        // Extra markers are dropped
+        let (text, pos, selection) = find_cursor_position("a$|$b$|$c$|$d");
+        assert_eq!(text, "abcd");
+        assert_eq!(pos, Some(2));
+        assert_eq!(selection, 1);
     }
 
     #[test]
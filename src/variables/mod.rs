@@ -1,3 +1,7 @@
 pub mod builtins;
 
-pub use builtins::{expand_variables, find_cursor_position, propagate_case};
+pub use builtins::{
+    expand_variables, extract_clipboard_set, find_cursor_position, propagate_case, run_picker,
+    runs_shell, set_clipboard, set_clipboard_timeout, set_locale_override, set_primary_selection, set_secrets_file,
+    unescape_unicode, uses_window, with_active_window, with_test_mode,
+};